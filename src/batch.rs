@@ -0,0 +1,298 @@
+use crate::types::*;
+use crate::util::*;
+use crate::dedupe::*;
+
+use std::collections::BTreeMap;
+use std::io::Read;
+
+/// Read orders of a batch from `file`, or stdin if it is `-` or not specified.
+/// Nothing is placed if any of them is malformed.
+///
+/// # Arguments
+/// * `file` - path of file of orders
+/// * `format` - `BatchFormat` of orders, otherwise inferred from extension of `file`
+pub fn read_batch_orders(file: Option<&str>, format: Option<BatchFormat>) -> Result<Vec<BatchOrder>, StatusCode> {
+    let file = file.filter(|f| *f != "-");
+    let mut content = String::new();
+    let read = match file {
+        Some(path) => std::fs::File::open(path).and_then(|mut f| f.read_to_string(&mut content)),
+        None => std::io::stdin().read_to_string(&mut content),
+    };
+    if let Err(e) = read {
+        eprintln!("Error: cannot read orders from {}: {}", file.unwrap_or("stdin"), e);
+        return Err(StatusCode::InternalErrorGeneric);
+    }
+
+    let format = format.unwrap_or_else(|| get_batch_format_of_path(file.unwrap_or_default()));
+    let orders = parse_batch_orders(&content, format).map_err(|message| {
+        eprintln!("Error: {}", message);
+        StatusCode::ErrorIncorrectParameterValue
+    })?;
+    if orders.is_empty() {
+        eprintln!("Error: no orders to place");
+        return Err(StatusCode::ErrorIncorrectParameterValue);
+    }
+    Ok(orders)
+}
+
+/// `BatchFormat` as of extension of `path`, lines if it is neither csv, nor json.
+pub fn get_batch_format_of_path(path: &str) -> BatchFormat {
+    match std::path::Path::new(path).extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase()).as_deref() {
+        Some("csv") => BatchFormat::Csv,
+        Some("json") | Some("jsonl") => BatchFormat::Json,
+        _ => BatchFormat::Lines,
+    }
+}
+
+/// Parse orders of a batch from `content`. Blank lines, and lines starting
+/// with `#` are skipped. Return message of the first malformed order with its
+/// line if any.
+///
+/// # Arguments
+/// * `content` - orders as read
+/// * `format` - `BatchFormat` of `content`
+pub fn parse_batch_orders(content: &str, format: BatchFormat) -> Result<Vec<BatchOrder>, String> {
+    let lines = content.lines().enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+
+    let orders: Vec<BatchOrder> = match format {
+        BatchFormat::Json if content.trim_start().starts_with('[') => {
+            let orders: Vec<BatchOrder> = serde_json::from_str(content).map_err(|e| format!("malformed json: {}", e))?;
+            orders.into_iter().enumerate().map(|(i, order)| BatchOrder { line: i + 1, ..order }).collect()
+        },
+        BatchFormat::Json => lines
+            .map(|(i, line)| serde_json::from_str::<BatchOrder>(line)
+                .map(|order| BatchOrder { line: i, ..order })
+                .map_err(|e| format!("line {}: malformed json: {}", i, e)))
+            .collect::<Result<_, _>>()?,
+        BatchFormat::Lines => lines
+            .map(|(i, line)| parse_batch_fields(i, &line.split_whitespace().collect::<Vec<_>>()))
+            .collect::<Result<_, _>>()?,
+        BatchFormat::Csv => {
+            let mut lines = lines.peekable();
+            // header names columns in any order, otherwise they are as of lines
+            let header: Option<Vec<String>> = lines.peek()
+                .filter(|(_, line)| line.split(',').next().is_some_and(|f| f.trim().eq_ignore_ascii_case("symbol")))
+                .map(|(_, line)| line.split(',').map(|f| f.trim().to_lowercase()).collect());
+            if header.is_some() {
+                lines.next();
+            }
+            lines.map(|(i, line)| {
+                    let fields: Vec<&str> = line.split(',').map(|f| f.trim()).collect();
+                    match &header {
+                        Some(header) => {
+                            let field = |name: &str| header.iter().position(|h| h == name).and_then(|c| fields.get(c).copied()).unwrap_or_default();
+                            parse_batch_fields(i, &[field("symbol"), field("side"), field("qty"), field("sl_pcnt"), field("tp_pcnt")])
+                        },
+                        None => parse_batch_fields(i, &fields),
+                    }
+                })
+                .collect::<Result<_, _>>()?
+        },
+    };
+
+    // side is case insensitive as written by hand
+    orders.into_iter()
+        .map(|order| match order.side.to_lowercase().as_str() {
+            "buy" => Ok(BatchOrder { side: String::from("Buy"), ..order }),
+            "sell" => Ok(BatchOrder { side: String::from("Sell"), ..order }),
+            _ => Err(format!("line {}: unknown side '{}', expect Buy, or Sell", order.line, order.side)),
+        })
+        .collect()
+}
+
+/// Parse `fields` of `symbol side qty [sl_pcnt] [tp_pcnt]` read from `line`.
+/// Empty, or `-` stop-loss, and take-profit is not specified.
+fn parse_batch_fields(line: usize, fields: &[&str]) -> Result<BatchOrder, String> {
+    if fields.len() < 3 || fields.len() > 5 || fields[..3].iter().any(|f| f.is_empty()) {
+        return Err(format!("line {}: expect symbol side qty [sl] [tp]", line));
+    }
+    let qty = fields[2].parse::<u64>().map_err(|_| format!("line {}: malformed qty '{}'", line, fields[2]))?;
+    let pcnt = |i: usize| match fields.get(i).copied() {
+        None | Some("") | Some("-") => Ok(None),
+        Some(f) => f.parse::<f64>().map(Some).map_err(|_| format!("line {}: malformed percentage '{}'", line, f)),
+    };
+    Ok(BatchOrder {
+        line,
+        symbol: fields[0].to_owned(),
+        side: fields[1].to_owned(),
+        qty,
+        sl_pcnt: pcnt(3)?,
+        tp_pcnt: pcnt(4)?,
+    })
+}
+
+/// Compute an order of a batch as of a quick limit order with its own
+/// stop-loss, and take-profit if specified, without sending it.
+///
+/// # Arguments
+/// * `context` - `TradingContext` for context information used in trading
+/// * `order` - `BatchOrder`
+pub fn api_plan_batch_order(context: &TradingContext, order: &BatchOrder) -> Result<(TradingContext, OrderPlan), StatusCode> {
+    let qty = validate_order_fields(context, &order.symbol, &order.side, order.qty, order.sl_pcnt, order.tp_pcnt)?;
+    let order_context = get_order_context(context, order.sl_pcnt, order.tp_pcnt);
+    let plan = api_plan_quick_limit_order(&order_context, &order.symbol, qty)?;
+    Ok((order_context, plan))
+}
+
+/// Sum quantity of `orders` of a batch by symbol, and side. Return symbol, and
+/// signed quantity, positive for buy, negative for sell, of each.
+///
+/// # Arguments
+/// * `orders` - `BatchOrder` of each order
+pub fn sum_batch_orders_qty(orders: &[BatchOrder]) -> Vec<(&str, i64)> {
+    let mut totals: BTreeMap<(&str, &str), u64> = BTreeMap::new();
+    for order in orders.iter() {
+        *totals.entry((&order.symbol, &order.side)).or_default() += order.qty;
+    }
+    totals.into_iter()
+        .map(|((symbol, side), qty)| (symbol, if side == "Sell" { -(qty as i64) } else { qty as i64 }))
+        .collect()
+}
+
+/// Check planned orders of a batch against safety limits. Orders of the same
+/// symbol, and side are checked as a whole as of a ladder, so splitting an
+/// order across lines doesn't get around them. Return the result of the group
+/// of each line, lines not planned pass.
+///
+/// # Arguments
+/// * `context` - `TradingContext` for its limits
+/// * `plans` - `OrderPlan` of each line if it could be computed
+pub fn check_batch_orders_limits(context: &TradingContext, plans: &[Option<&OrderPlan>]) -> Vec<Result<(), StatusCode>> {
+    let mut groups: BTreeMap<(&str, &str), Vec<usize>> = BTreeMap::new();
+    for (i, plan) in plans.iter().enumerate() {
+        if let Some(plan) = plan {
+            groups.entry((&plan.symbol, &plan.side)).or_default().push(i);
+        }
+    }
+    let groups: Vec<Vec<usize>> = groups.into_values().collect();
+    let checked = run_concurrently(&groups, |lines| {
+        let group_plans: Vec<OrderPlan> = lines.iter().filter_map(|&i| plans[i].cloned()).collect();
+        check_order_plans_limits(context, &group_plans)
+    });
+
+    let mut results: Vec<Result<(), StatusCode>> = plans.iter().map(|_| Ok(())).collect();
+    for (lines, checked) in groups.iter().zip(checked) {
+        if let Err(e) = checked {
+            for &i in lines.iter() {
+                results[i] = Err(if matches!(e, StatusCode::ErrorLimitExceeded) { StatusCode::ErrorLimitExceeded } else { StatusCode::InternalErrorGeneric });
+            }
+        }
+    }
+    results
+}
+
+/// Place `orders` of a batch concurrently, each priced as of a quick limit
+/// order, and passing safety limits as of `check_batch_orders_limits`. The batch counts as a unit for
+/// duplicate protection, so identical orders of it don't collide, while the
+/// same batch sent again is refused as a whole. Return the plan if it could be
+/// computed, and the result of each order in order of `orders`.
+///
+/// # Arguments
+/// * `context` - `TradingContext` for context information used in trading
+/// * `orders` - `BatchOrder` of each order
+pub fn api_send_batch_orders(context: &TradingContext, orders: &[BatchOrder]) -> Vec<(Option<OrderPlan>, SentOrderPlan)> {
+    let start = std::time::Instant::now();
+    let planned = run_concurrently(orders, |order| api_plan_batch_order(context, order));
+    let checked = check_batch_orders_limits(context, &planned.iter().map(|p| p.as_ref().ok().map(|(_, plan)| plan)).collect::<Vec<_>>());
+    let planned: Vec<_> = planned.into_iter().zip(checked)
+        .map(|(planned, checked)| planned.map(|(order_context, plan)| (order_context, plan, checked)))
+        .collect();
+    let reserved = reserve_orders(context, &planned.iter().flatten().filter(|(_, _, checked)| checked.is_ok()).map(|(_, plan, _)| plan).collect::<Vec<_>>());
+    let sent = run_concurrently(&planned, |planned| match planned {
+        Ok((order_context, plan, Ok(()))) if reserved.is_ok() => Some(api_send_reserved_order_plan(order_context, plan)),
+        _ => None,
+    });
+    let not_reserved = || match reserved {
+        Err(StatusCode::ErrorDuplicateOrder) => StatusCode::ErrorDuplicateOrder,
+        _ => StatusCode::InternalErrorGeneric,
+    };
+
+    planned.into_iter().zip(sent)
+        .map(|(planned, sent)| match (planned, sent) {
+            (Ok((_, plan, _)), Some(sent)) => (Some(plan), sent),
+            (Ok((_, plan, Err(e))), None) => (Some(plan), (Err(e), measure_end(&start, false))),
+            (Ok((_, plan, Ok(()))), None) => (Some(plan), (Err(not_reserved()), measure_end(&start, false))),
+            (Err(e), _) => (None, (Err(e), measure_end(&start, false))),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fields of `order` to compare against.
+    fn fields(order: &BatchOrder) -> (usize, &str, &str, u64, Option<f64>, Option<f64>) {
+        (order.line, &order.symbol, &order.side, order.qty, order.sl_pcnt, order.tp_pcnt)
+    }
+
+    fn order(symbol: &str, side: &str, qty: u64) -> BatchOrder {
+        BatchOrder { line: 0, symbol: symbol.to_owned(), side: side.to_owned(), qty, sl_pcnt: None, tp_pcnt: None }
+    }
+
+    #[test]
+    fn fields_are_parsed_with_optional_percentages() {
+        let order = parse_batch_fields(3, &["XRPUSD", "Buy", "10"]).unwrap();
+        assert_eq!(fields(&order), (3, "XRPUSD", "Buy", 10, None, None));
+        let order = parse_batch_fields(4, &["XRPUSD", "Sell", "20", "1.5", "3"]).unwrap();
+        assert_eq!(fields(&order), (4, "XRPUSD", "Sell", 20, Some(1.5), Some(3.0)));
+        let order = parse_batch_fields(5, &["XRPUSD", "Sell", "20", "-", "3"]).unwrap();
+        assert_eq!(fields(&order), (5, "XRPUSD", "Sell", 20, None, Some(3.0)));
+        let order = parse_batch_fields(6, &["XRPUSD", "Sell", "20", "", ""]).unwrap();
+        assert_eq!(fields(&order), (6, "XRPUSD", "Sell", 20, None, None));
+    }
+
+    #[test]
+    fn malformed_fields_are_reported_with_their_line() {
+        assert_eq!(parse_batch_fields(7, &["XRPUSD", "Buy"]).err().as_deref(), Some("line 7: expect symbol side qty [sl] [tp]"));
+        assert_eq!(parse_batch_fields(7, &["XRPUSD", "Buy", "10", "1", "2", "3"]).err().as_deref(), Some("line 7: expect symbol side qty [sl] [tp]"));
+        assert_eq!(parse_batch_fields(7, &["XRPUSD", "", "10"]).err().as_deref(), Some("line 7: expect symbol side qty [sl] [tp]"));
+        assert_eq!(parse_batch_fields(7, &["XRPUSD", "Buy", "-10"]).err().as_deref(), Some("line 7: malformed qty '-10'"));
+        assert_eq!(parse_batch_fields(7, &["XRPUSD", "Buy", "ten"]).err().as_deref(), Some("line 7: malformed qty 'ten'"));
+        assert_eq!(parse_batch_fields(7, &["XRPUSD", "Buy", "10", "1%"]).err().as_deref(), Some("line 7: malformed percentage '1%'"));
+    }
+
+    #[test]
+    fn lines_skip_blanks_and_comments_and_normalize_side() {
+        let orders = parse_batch_orders("# symbol side qty\n\nXRPUSD buy 10\n  BTCUSD SELL 5 1 2  \n", BatchFormat::Lines).unwrap();
+        assert_eq!(orders.iter().map(fields).collect::<Vec<_>>(), vec![(3, "XRPUSD", "Buy", 10, None, None), (4, "BTCUSD", "Sell", 5, Some(1.0), Some(2.0))]);
+        assert_eq!(parse_batch_orders("XRPUSD buy 10\nXRPUSD hold 10\n", BatchFormat::Lines).err().as_deref(), Some("line 2: unknown side 'hold', expect Buy, or Sell"));
+        assert_eq!(parse_batch_orders("XRPUSD buy 10\nXRPUSD buy\n", BatchFormat::Lines).err().as_deref(), Some("line 2: expect symbol side qty [sl] [tp]"));
+    }
+
+    #[test]
+    fn csv_columns_follow_header_if_any() {
+        let orders = parse_batch_orders("XRPUSD,Buy,10,,2\n", BatchFormat::Csv).unwrap();
+        assert_eq!(orders.iter().map(fields).collect::<Vec<_>>(), vec![(1, "XRPUSD", "Buy", 10, None, Some(2.0))]);
+        let orders = parse_batch_orders("Symbol, qty, side, tp_pcnt\nXRPUSD, 10, Sell, 2\n", BatchFormat::Csv).unwrap();
+        assert_eq!(orders.iter().map(fields).collect::<Vec<_>>(), vec![(2, "XRPUSD", "Sell", 10, None, Some(2.0))]);
+        assert_eq!(parse_batch_orders("symbol,side,qty\nXRPUSD,Buy,x\n", BatchFormat::Csv).err().as_deref(), Some("line 2: malformed qty 'x'"));
+    }
+
+    #[test]
+    fn json_is_an_array_or_an_object_per_line() {
+        let orders = parse_batch_orders(r#"[{"symbol": "XRPUSD", "side": "Buy", "qty": 10}, {"symbol": "XRPUSD", "side": "sell", "qty": 5, "sl_pcnt": 1.0}]"#, BatchFormat::Json).unwrap();
+        assert_eq!(orders.iter().map(fields).collect::<Vec<_>>(), vec![(1, "XRPUSD", "Buy", 10, None, None), (2, "XRPUSD", "Sell", 5, Some(1.0), None)]);
+        let orders = parse_batch_orders("# orders\n{\"symbol\": \"XRPUSD\", \"side\": \"Buy\", \"qty\": 10}\n", BatchFormat::Json).unwrap();
+        assert_eq!(orders.iter().map(fields).collect::<Vec<_>>(), vec![(2, "XRPUSD", "Buy", 10, None, None)]);
+        assert!(parse_batch_orders("{\"symbol\": \"XRPUSD\", \"side\": \"Buy\"}\n", BatchFormat::Json).err().is_some_and(|e| e.starts_with("line 1: malformed json")));
+        assert!(parse_batch_orders("[{\"symbol\": \"XRPUSD\"", BatchFormat::Json).err().is_some_and(|e| e.starts_with("malformed json")));
+    }
+
+    #[test]
+    fn format_is_inferred_from_extension() {
+        assert!(matches!(get_batch_format_of_path("orders.CSV"), BatchFormat::Csv));
+        assert!(matches!(get_batch_format_of_path("orders.jsonl"), BatchFormat::Json));
+        assert!(matches!(get_batch_format_of_path("orders.txt"), BatchFormat::Lines));
+        assert!(matches!(get_batch_format_of_path("orders"), BatchFormat::Lines));
+    }
+
+    #[test]
+    fn qty_is_summed_by_symbol_and_side() {
+        let orders = vec![order("XRPUSD", "Buy", 10), order("BTCUSD", "Sell", 5), order("XRPUSD", "Buy", 20), order("XRPUSD", "Sell", 7)];
+        assert_eq!(sum_batch_orders_qty(&orders), vec![("BTCUSD", -5), ("XRPUSD", 30), ("XRPUSD", -7)]);
+        assert!(sum_batch_orders_qty(&[]).is_empty());
+    }
+}
@@ -0,0 +1,303 @@
+use crate::types::*;
+use crate::defines::*;
+use crate::util::*;
+use crate::output::*;
+use crate::ladder::*;
+use crate::ttl::*;
+use crate::journal::*;
+
+use std::time::{Duration, Instant};
+
+/// Compute a bracket of a quick limit entry of `qty` with its stop-loss, and
+/// take-profit `targets` each at its multiple of risk away from entry price,
+/// without sending it. Take-profit of `TradingContext` is not used as targets
+/// replace it.
+///
+/// # Arguments
+/// * `context` - `TradingContext` for context information used in trading
+/// * `symbol` - symbol to create orders for
+/// * `qty` - quantity of entry, positive for buy, negative for sell
+/// * `targets` - `BracketTarget` of each take-profit target
+pub fn api_plan_bracket(context: &TradingContext, symbol: &str, qty: i64, targets: &[BracketTarget]) -> Result<BracketPlan, StatusCode> {
+    let total_pcnt: f64 = targets.iter().map(|t| t.pcnt).sum();
+    if targets.is_empty() || total_pcnt > 100.0 + f64::EPSILON {
+        eprintln!("Error: percentages of targets add up to {}, expect at most 100", total_pcnt);
+        return Err(StatusCode::ErrorIncorrectParameterValue);
+    }
+
+    let mut entry_context = context.clone();
+    entry_context.take_profit_pcnt = None;
+    let entry = api_plan_quick_limit_order(&entry_context, symbol, qty)?;
+
+    let risk = round_to_tick((entry.limit_price - entry.stop_loss_price).abs(), entry.tick_step);
+    if risk <= 0.0 {
+        eprintln!("Error: stop-loss {} is at entry price, widen stop-loss percentage", entry.stop_loss_price);
+        return Err(StatusCode::ErrorIncorrectParameterValue);
+    }
+    let direction = if qty > 0 { 1.0 } else { -1.0 };
+    let target_prices: Vec<f64> = targets.iter()
+        .map(|t| round_to_tick(entry.limit_price + direction * risk * t.r_multiple, entry.tick_step))
+        .collect();
+    if target_prices.iter().any(|p| *p <= 0.0) {
+        eprintln!("Error: target price falls to 0 or below, lower R-multiple of targets");
+        return Err(StatusCode::ErrorIncorrectParameterValue);
+    }
+
+    Ok(BracketPlan { entry, risk, targets: targets.to_vec(), target_prices })
+}
+
+/// Split `filled` quantity of entry across `targets` by their percentage,
+/// each snapped to `lot_size`. Quantity of percentages short of 100 is left
+/// to stop-loss.
+///
+/// # Arguments
+/// * `filled` - filled quantity of entry
+/// * `targets` - `BracketTarget` of each take-profit target
+/// * `lot_size` - quantity step of the symbol
+pub fn get_bracket_target_qtys(filled: u64, targets: &[BracketTarget], lot_size: u64) -> Result<Vec<u64>, StatusCode> {
+    let lot_size = lot_size.max(1);
+    let pcnts: Vec<f64> = targets.iter().map(|t| t.pcnt).collect();
+    let total_pcnt: f64 = pcnts.iter().sum();
+    let lots = (filled as f64 * total_pcnt.min(100.0) / 100.0 / lot_size as f64 + 1e-9).floor() as u64;
+    split_ladder_qty(lots * lot_size, &pcnts, lot_size)
+}
+
+/// Send entry of bracket `plan`, wait for it to be filled within `entry_ttl`
+/// cancelling the rest of it, then place take-profit targets of the filled
+/// quantity as reduce-only limit orders. Targets are then watched every
+/// `ORDER_POLL_INTERVAL_SECS` until none is left active, position is closed,
+/// or `manage_timeout` passes. Stop-loss is resized to quantity left open as
+/// targets are hit, and targets left are cancelled once position is closed
+/// e.g. by stop-loss, or management ends otherwise. It fails if a position of
+/// the symbol is already open, as position tells when the bracket is closed.
+///
+/// Progress is printed as it happens in plain output.
+///
+/// # Arguments
+/// * `context` - `TradingContext` for context information used in trading
+/// * `plan` - `BracketPlan`
+/// * `entry_ttl` - how long entry is left to be filled
+/// * `manage_timeout` - how long targets are managed at most
+pub fn api_run_bracket(context: &TradingContext, plan: &BracketPlan, entry_ttl: Duration, manage_timeout: Duration) -> Result<BracketReport, StatusCode> {
+    let entry_plan = &plan.entry;
+    let symbol = entry_plan.symbol.as_str();
+
+    // closing of the bracket is told by its position, so it must be its own
+    let position = api_get_position(context, symbol)?;
+    if position.size > 0.0 && position.side != "None" {
+        eprintln!("Error: position {} {} {} is already open, close it before placing a bracket", position.side, position.size, symbol);
+        return Err(StatusCode::ErrorIncorrectParameterValue);
+    }
+
+    let start = Instant::now();
+    let entry = api_send_order_plan(context, entry_plan)?;
+    let elapsed = measure_end(&start, false);
+    print_bracket_progress(&format!("entry {} {} {} @ {} accepted ({}), stop-loss {}", entry.side, entry.qty, symbol, entry.price, entry.order_id, entry_plan.stop_loss_price));
+
+    let expiry = api_expire_order(context, None, &entry, entry_ttl, None, Duration::ZERO)?;
+    journal_order_confirmation(context, &expiry.confirmation);
+    let filled = expiry.confirmation.cum_exec_qty as u64;
+    for line in format_order_confirmation(&expiry.confirmation) {
+        print_bracket_progress(&format!("entry {}", line));
+    }
+    let entry_outcome = QuickOrderOutcome {
+        order: entry,
+        elapsed,
+        confirm_elapsed: Some(measure_end(&start, false)),
+        confirmation: Some(Ok(expiry.confirmation)),
+        expired: expiry.expired,
+        fallback: None,
+    };
+    let mut report = BracketReport {
+        entry: to_order_report(None, &entry_outcome),
+        stop_loss: entry_plan.stop_loss_price,
+        risk: plan.risk,
+        targets: Vec::new(),
+        outcome: BracketOutcome::EntryUnfilled,
+        remaining_qty: filled,
+    };
+    if filled == 0 {
+        return Ok(report);
+    }
+
+    // targets close the position, so they are on the opposite side of entry
+    let lot_size = context.lot_sizes.get(symbol).copied().unwrap_or(1);
+    let qtys = get_bracket_target_qtys(filled, &plan.targets, lot_size).inspect_err(|_| {
+        eprintln!("Error: filled {} of entry is left with stop-loss only", filled);
+    })?;
+    let close_direction = if entry_plan.side == "Buy" { -1 } else { 1 };
+    let legs: Vec<(i64, f64)> = qtys.iter().zip(plan.target_prices.iter()).map(|(qty, price)| (close_direction * *qty as i64, *price)).collect();
+    let sent: Vec<SentOrderPlan> = run_concurrently(&legs, |(qty, price)| {
+        let start = Instant::now();
+        let result = api_send_reduce_only_limit_order(context, symbol, *qty, *price);
+        (result, measure_end(&start, false))
+    });
+    for (i, ((result, _), target)) in sent.iter().zip(plan.targets.iter()).enumerate() {
+        let (qty, price) = legs[i];
+        match result {
+            Ok(order) => print_bracket_progress(&format!("target {}: {} {} {} @ {} ({}R) accepted ({})", i + 1, order.side, order.qty, symbol, price, target.r_multiple, order.order_id)),
+            Err(e) => print_bracket_progress(&format!("target {}: failed to place {} {} @ {}: {}", i + 1, qty.unsigned_abs(), symbol, price, get_status_code_message(e))),
+        }
+    }
+
+    let (outcome, confirmations, remaining_qty) = api_manage_bracket_targets(context, plan, filled, &sent, manage_timeout)?;
+    report.outcome = outcome;
+    report.remaining_qty = remaining_qty;
+    report.targets = sent.into_iter().zip(confirmations).zip(legs.iter().zip(plan.targets.iter()))
+        .map(|(((result, elapsed), confirmation), ((qty, price), target))| {
+            let order = match result {
+                Ok(order) => to_order_report(None, &QuickOrderOutcome { order, elapsed, confirm_elapsed: None, confirmation: confirmation.map(Ok), expired: false, fallback: None }),
+                Err(e) => OrderReport { price: Some(*price), ..to_unplaced_order_report(None, symbol, *qty, Some(&e)) },
+            };
+            BracketTargetReport { pcnt: target.pcnt, r_multiple: target.r_multiple, order }
+        })
+        .collect();
+    Ok(report)
+}
+
+/// Watch `targets` of a bracket whose entry filled `filled` until none of them
+/// is left active, position is closed, or `timeout` passes. See
+/// `api_run_bracket`. Return how it ended, the latest state of each target,
+/// and quantity of the bracket left open.
+///
+/// A failed check is retried at the next poll. Targets left are cancelled
+/// before giving up after `BRACKET_MAX_POLL_FAILURES` failed checks in a row,
+/// or once `timeout` passes, so none of them rests without being managed.
+fn api_manage_bracket_targets(context: &TradingContext, plan: &BracketPlan, filled: u64, targets: &[SentOrderPlan], timeout: Duration) -> Result<(BracketOutcome, Vec<Option<OrderConfirmation>>, u64), StatusCode> {
+    let symbol = plan.entry.symbol.as_str();
+    let deadline = Instant::now() + timeout;
+    let mut confirmations: Vec<Option<OrderConfirmation>> = vec![None; targets.len()];
+    let mut reduced = 0;
+    let mut failures = 0;
+
+    loop {
+        std::thread::sleep(Duration::from_secs_f64(ORDER_POLL_INTERVAL_SECS));
+        match api_check_bracket_targets(context, plan, filled, targets, &mut confirmations, &mut reduced) {
+            Ok(Some(outcome)) => return Ok((outcome, confirmations, filled.saturating_sub(reduced))),
+            Ok(None) => failures = 0,
+            Err(e) => {
+                failures += 1;
+                if failures >= BRACKET_MAX_POLL_FAILURES {
+                    eprintln!("Error: checking bracket failed {} times in a row, cancelling targets left", failures);
+                    let _ = api_cancel_bracket_targets(context, symbol, targets, &mut confirmations);
+                    return Err(e);
+                }
+                print_info(&format!("checking bracket failed ({}), retrying", get_status_code_message(&e)));
+            },
+        }
+        if Instant::now() >= deadline {
+            let cancelled = api_cancel_bracket_targets(context, symbol, targets, &mut confirmations).unwrap_or_default();
+            print_bracket_progress(&format!("managed for {} secs, {} targets left are cancelled, stop-loss is left as it is", timeout.as_secs_f64(), cancelled));
+            return Ok((BracketOutcome::TimedOut, confirmations, filled.saturating_sub(reduced)));
+        }
+    }
+}
+
+/// Check `targets` of a bracket once: query targets still active, resize
+/// stop-loss to quantity of the bracket left open as they are hit, and cancel
+/// targets left once position is closed. Return how the bracket ended if it
+/// did.
+///
+/// # Arguments
+/// * `context` - `TradingContext` for context information used in trading
+/// * `plan` - `BracketPlan`
+/// * `filled` - filled quantity of entry
+/// * `targets` - result of placing each target
+/// * `confirmations` - latest state of each target, updated in place
+/// * `reduced` - quantity closed by targets that stop-loss is resized for, updated in place
+fn api_check_bracket_targets(context: &TradingContext, plan: &BracketPlan, filled: u64, targets: &[SentOrderPlan], confirmations: &mut [Option<OrderConfirmation>], reduced: &mut u64) -> Result<Option<BracketOutcome>, StatusCode> {
+    let symbol = plan.entry.symbol.as_str();
+    let stop_loss = plan.entry.stop_loss_price;
+
+    for (i, (result, _)) in targets.iter().enumerate() {
+        let Ok(order) = result else { continue };
+        if !is_bracket_target_active(&confirmations[i]) {
+            continue;
+        }
+        let confirmation = api_get_order_confirmation(context, order, false)?;
+        if is_order_state_final(confirmation.state) {
+            journal_order_confirmation(context, &confirmation);
+            for line in format_order_confirmation(&confirmation) {
+                print_bracket_progress(&format!("target {} {}", i + 1, line));
+            }
+        }
+        confirmations[i] = Some(confirmation);
+    }
+    let target_filled: u64 = confirmations.iter().flatten().map(|c| c.cum_exec_qty as u64).sum();
+
+    // no other position was open when the bracket started, so a closed
+    // position means the rest of the bracket was closed e.g. by stop-loss
+    let position = api_get_position(context, symbol)?;
+    if position.size == 0.0 || position.side == "None" {
+        if target_filled >= filled {
+            *reduced = target_filled;
+            return Ok(Some(BracketOutcome::TargetsHit));
+        }
+        let cancelled = api_cancel_bracket_targets(context, symbol, targets, confirmations)?;
+        *reduced = filled;
+        print_bracket_progress(&format!("position closed, {} targets left are cancelled", cancelled));
+        return Ok(Some(BracketOutcome::Stopped));
+    }
+
+    if target_filled > *reduced {
+        let size = filled.saturating_sub(target_filled);
+        if size > 0 {
+            api_set_stop_loss_size(context, symbol, stop_loss, size)?;
+            print_bracket_progress(&format!("targets filled {} of {}, stop-loss {} resized to {}", target_filled, filled, stop_loss, size));
+        }
+        *reduced = target_filled;
+    }
+    let any_active = targets.iter().zip(confirmations.iter()).any(|((result, _), c)| result.is_ok() && is_bracket_target_active(c));
+    Ok((!any_active).then_some(BracketOutcome::LeftOpen))
+}
+
+/// Cancel targets of a bracket still active, and update their state in
+/// `confirmations`. All of them are tried, then the first failure if any is
+/// returned, otherwise number of targets cancelled.
+fn api_cancel_bracket_targets(context: &TradingContext, symbol: &str, targets: &[SentOrderPlan], confirmations: &mut [Option<OrderConfirmation>]) -> Result<usize, StatusCode> {
+    let mut cancelled = 0;
+    let mut failure = None;
+    for (i, (result, _)) in targets.iter().enumerate() {
+        let Ok(order) = result else { continue };
+        if !is_bracket_target_active(&confirmations[i]) {
+            continue;
+        }
+        match api_cancel_order(context, symbol, &order.order_id).and_then(|_| api_get_order_confirmation(context, order, false)) {
+            Ok(confirmation) => {
+                confirmations[i] = Some(confirmation);
+                cancelled += 1;
+            },
+            Err(e) => {
+                eprintln!("Warning: cannot cancel target {} order {}, cancel it manually", i + 1, order.order_id);
+                failure.get_or_insert(e);
+            },
+        }
+    }
+    match failure {
+        Some(e) => Err(e),
+        None => Ok(cancelled),
+    }
+}
+
+/// Whether target of a bracket is still active as of its latest known state.
+fn is_bracket_target_active(confirmation: &Option<OrderConfirmation>) -> bool {
+    confirmation.as_ref().is_none_or(|c| !is_order_state_final(c.state))
+}
+
+/// Print `line` of progress of a bracket as it happens in plain output.
+fn print_bracket_progress(line: &str) {
+    if is_plain_output() {
+        println!("{}", line);
+    }
+}
+
+/// Describe `outcome` of a bracket to the user.
+pub fn get_bracket_outcome_label(outcome: BracketOutcome) -> &'static str {
+    match outcome {
+        BracketOutcome::EntryUnfilled => "entry unfilled",
+        BracketOutcome::TargetsHit => "all targets hit",
+        BracketOutcome::Stopped => "position closed",
+        BracketOutcome::LeftOpen => "no target left",
+        BracketOutcome::TimedOut => "timed out",
+    }
+}
@@ -0,0 +1,307 @@
+use crate::types::*;
+use crate::defines::*;
+use crate::paper::*;
+use crate::journal::*;
+
+use std::str::FromStr;
+
+/// Get default path of config file.
+/// It is `$XDG_CONFIG_HOME/quicky/config.toml`, or `$HOME/.config/quicky/config.toml`
+/// if `XDG_CONFIG_HOME` is not set.
+pub fn get_default_config_path() -> Option<std::path::PathBuf> {
+    let config_home = match std::env::var("XDG_CONFIG_HOME") {
+        Ok(dir) if !dir.is_empty() => std::path::PathBuf::from(dir),
+        _ => std::path::PathBuf::from(std::env::var("HOME").ok()?).join(".config"),
+    };
+    Some(config_home.join("quicky").join("config.toml"))
+}
+
+/// Load config file.
+/// Path is from `path` if specified, otherwise from `QUICKY_CONFIG` environment
+/// variable, otherwise the default path. Missing file at the default path is
+/// fine, and results in empty config.
+///
+/// # Arguments
+/// * `path` - path of config file as specified at command line
+pub fn load_config(path: Option<&str>) -> Result<Config, StatusCode> {
+    let explicit_path = match path {
+        Some(path) => Some(path.to_owned()),
+        None => get_env_override::<String>("QUICKY_CONFIG")?,
+    };
+    let path = match explicit_path.clone().map(std::path::PathBuf::from).or_else(get_default_config_path) {
+        Some(path) => path,
+        None => return Ok(Config::default()),
+    };
+
+    let content = match std::fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound && explicit_path.is_none() => return Ok(Config::default()),
+        Err(e) => {
+            eprintln!("Error: cannot read config file {}: {}", path.display(), e);
+            return Err(StatusCode::ErrorConfig);
+        }
+    };
+
+    toml::from_str(&content).map_err(|e| {
+        eprintln!("Error: cannot parse config file {}: {}", path.display(), e);
+        StatusCode::ErrorConfig
+    })
+}
+
+/// Select profile from `config`.
+/// Name is from `name` if specified, otherwise from `QUICKY_PROFILE` environment
+/// variable, otherwise `default_profile` of config. Return empty profile if
+/// none of them is specified.
+///
+/// # Arguments
+/// * `config` - loaded config
+/// * `name` - name of profile as specified at command line
+pub fn select_profile(config: &Config, name: Option<&str>) -> Result<Profile, StatusCode> {
+    let name = match name {
+        Some(name) => Some(name.to_owned()),
+        None => get_env_override::<String>("QUICKY_PROFILE")?,
+    }.or_else(|| config.default_profile.clone());
+
+    match name {
+        Some(name) => match config.profiles.get(&name) {
+            Some(profile) => Ok(profile.clone()),
+            None => {
+                eprintln!("Error: profile '{}' not found in config file", name);
+                Err(StatusCode::ErrorConfig)
+            }
+        },
+        None => Ok(Profile::default()),
+    }
+}
+
+/// Construct trading context by merging, in order of precedence, command line's
+/// arguments, environment variables, `profile`, then built-in defaults.
+///
+/// Environment variables are `QUICKY_TESTNET`, `QUICKY_DOMAIN`, `QUICKY_BASE_URL`,
+/// `QUICKY_WS_URL`, `QUICKY_PROXY`, `QUICKY_SL_PCNT`, `QUICKY_TP_PCNT`,
+/// `QUICKY_PRICE_REF`, and `QUICKY_DEDUPE_WINDOW`.
+///
+/// Safety limits are from `config` as a whole. Fail if any of the environment
+/// variables is set but malformed.
+///
+/// # Arguments
+/// * `cmd_args` - command line's arguments
+/// * `config` - loaded config
+/// * `profile` - selected profile
+pub fn resolve_trading_context(cmd_args: &CommandlineArgs, config: &Config, profile: &Profile) -> Result<TradingContext, StatusCode> {
+    // a typo here must not fall back to mainnet, so malformed values fail
+    let env_testnet = get_env_flag("QUICKY_TESTNET")?;
+    let use_testnet = if cmd_args.testnet { true } else if cmd_args.mainnet { false } else {
+        env_testnet.or(profile.testnet).unwrap_or(false)
+    };
+
+    // credentials come from the encrypted store if profile names an entry,
+    // otherwise from environment variables named by profile, or the standard
+    // ones of selected network
+    let (key_env, secret_env) = get_default_credential_env_names(use_testnet);
+    let credentials = match &profile.credentials {
+        Some(name) => CredentialSource::Store {
+            name: name.clone(),
+            unlocked: std::sync::Arc::new(std::sync::OnceLock::new()),
+        },
+        None => CredentialSource::Env {
+            key_env: profile.api_key_env.clone().unwrap_or_else(|| key_env.to_owned()),
+            secret_env: profile.api_secret_env.clone().unwrap_or_else(|| secret_env.to_owned()),
+        },
+    };
+
+    Ok(TradingContext {
+        credentials,
+        tick_steps: get_default_tick_steps(),
+        lot_sizes: get_default_lot_sizes(),
+        stop_loss_pcnt: cmd_args.sl_pcnt.or(get_env_override("QUICKY_SL_PCNT")?).or(profile.sl_pcnt).unwrap_or(DEFAULT_SL_PCNT),
+        take_profit_pcnt: cmd_args.tp_pcnt.or(get_env_override("QUICKY_TP_PCNT")?).or(profile.tp_pcnt),
+        use_testnet,
+        domain: cmd_args.domain.clone().or(get_env_override("QUICKY_DOMAIN")?).or_else(|| profile.domain.clone()).unwrap_or_else(|| DEFAULT_DOMAIN.to_owned()),
+        base_url: cmd_args.base_url.clone().or(get_env_override("QUICKY_BASE_URL")?).or_else(|| profile.base_url.clone()),
+        ws_url: cmd_args.ws_url.clone().or(get_env_override("QUICKY_WS_URL")?).or_else(|| profile.ws_url.clone()),
+        proxy: cmd_args.proxy.clone().or(get_env_override("QUICKY_PROXY")?).or_else(|| profile.proxy.clone()),
+        price_ref: cmd_args.price_ref.or(get_env_override("QUICKY_PRICE_REF")?).or(profile.price_ref).unwrap_or_default(),
+        paper: if cmd_args.paper { Some(resolve_paper_options(cmd_args)) } else { None },
+        limits: config.limits.clone(),
+        dedupe_window_secs: cmd_args.dedupe_window.or(get_env_override("QUICKY_DEDUPE_WINDOW")?).or(profile.dedupe_window_secs).unwrap_or(DEFAULT_DEDUPE_WINDOW_SECS),
+        journal_path: cmd_args.journal.clone().or(get_env_override("QUICKY_JOURNAL")?).or_else(|| profile.journal.clone()).map(std::path::PathBuf::from)
+            .or_else(get_default_journal_path),
+    })
+}
+
+/// Resolve accounts to trade on.
+/// Name is from command line's argument, otherwise from `QUICKY_ACCOUNT`
+/// environment variable, otherwise `profile`. It names either an account, or a
+/// group of accounts in `config`. Without a name, the single account uses
+/// credentials as resolved by `resolve_trading_context`.
+///
+/// # Arguments
+/// * `cmd_args` - command line's arguments
+/// * `config` - loaded config
+/// * `profile` - selected profile
+pub fn resolve_account_selection(cmd_args: &CommandlineArgs, config: &Config, profile: &Profile) -> Result<AccountSelection, StatusCode> {
+    let name = cmd_args.account.clone()
+        .or(get_env_override("QUICKY_ACCOUNT")?)
+        .or_else(|| profile.account.clone());
+
+    let name = match name {
+        Some(name) => name,
+        None => {
+            let context = resolve_trading_context(cmd_args, config, profile)?;
+            check_endpoints(&context)?;
+            return Ok(AccountSelection {
+                group: None,
+                accounts: vec![AccountContext { name: None, context, multiplier: 1.0 }],
+            });
+        },
+    };
+
+    let members = match config.groups.get(&name) {
+        Some(members) => members,
+        None => return Ok(AccountSelection {
+            group: None,
+            accounts: vec![resolve_account_context(cmd_args, config, profile, &name, 1.0)?],
+        }),
+    };
+    if config.accounts.contains_key(&name) {
+        eprintln!("Error: '{}' is both an account and a group in config file", name);
+        return Err(StatusCode::ErrorConfig);
+    }
+    if members.is_empty() {
+        eprintln!("Error: group '{}' has no accounts", name);
+        return Err(StatusCode::ErrorConfig);
+    }
+
+    let accounts = members.iter()
+        .map(|(account, multiplier)| {
+            if !multiplier.is_finite() || *multiplier <= 0.0 {
+                eprintln!("Error: multiplier of account '{}' in group '{}' must be positive", account, name);
+                return Err(StatusCode::ErrorConfig);
+            }
+            resolve_account_context(cmd_args, config, profile, account, *multiplier)
+        })
+        .collect::<Result<Vec<AccountContext>, StatusCode>>()?;
+
+    Ok(AccountSelection { group: Some(name), accounts })
+}
+
+/// Resolve trading context of account `name` of `config`. Its network, and
+/// credentials take place of the ones of `profile`.
+fn resolve_account_context(cmd_args: &CommandlineArgs, config: &Config, profile: &Profile, name: &str, multiplier: f64) -> Result<AccountContext, StatusCode> {
+    let account = config.accounts.get(name).ok_or_else(|| {
+        eprintln!("Error: account '{}' not found in config file", name);
+        StatusCode::ErrorConfig
+    })?;
+    if account.credentials.is_none() && (account.api_key_env.is_none() || account.api_secret_env.is_none()) {
+        eprintln!("Error: account '{}' needs either `credentials`, or both `api_key_env` and `api_secret_env`", name);
+        return Err(StatusCode::ErrorConfig);
+    }
+
+    let account_profile = Profile {
+        testnet: account.testnet.or(profile.testnet),
+        api_key_env: account.api_key_env.clone(),
+        api_secret_env: account.api_secret_env.clone(),
+        credentials: account.credentials.clone(),
+        ..profile.clone()
+    };
+
+    let context = resolve_trading_context(cmd_args, config, &account_profile)?;
+    check_endpoints(&context)?;
+    Ok(AccountContext { name: Some(name.to_owned()), context, multiplier })
+}
+
+/// Check that domain, and URLs of API, and proxy of `context` are well-formed,
+/// so a typo is reported up front instead of as a failed request.
+fn check_endpoints(context: &TradingContext) -> Result<(), StatusCode> {
+    if context.domain.is_empty() || context.domain.contains(|c: char| c == '/' || c == ':' || c.is_whitespace()) {
+        eprintln!("Error: domain '{}' must be a bare host name e.g. bybit.com", context.domain);
+        return Err(StatusCode::ErrorConfig);
+    }
+
+    let urls = [
+        ("base URL", context.base_url.as_ref(), &["http", "https"][..]),
+        ("websocket URL", context.ws_url.as_ref(), &["ws", "wss"][..]),
+        ("proxy", context.proxy.as_ref(), &["http", "https", "socks4", "socks4a", "socks5", "socks5h"][..]),
+    ];
+    for (what, url, schemes) in urls.into_iter() {
+        let url = match url {
+            Some(url) => url,
+            None => continue,
+        };
+        match url::Url::parse(url) {
+            Ok(parsed) if schemes.contains(&parsed.scheme()) && parsed.host_str().is_some() => {},
+            _ => {
+                eprintln!("Error: {} '{}' must be an absolute URL with scheme one of {}", what, url, schemes.join(", "));
+                return Err(StatusCode::ErrorConfig);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Resolve options of paper exchange from command line's arguments.
+///
+/// # Arguments
+/// * `cmd_args` - command line's arguments
+pub fn resolve_paper_options(cmd_args: &CommandlineArgs) -> PaperOptions {
+    let state_path = cmd_args.paper_state.as_ref().map(std::path::PathBuf::from)
+        .or_else(get_default_paper_state_path)
+        .unwrap_or_else(|| std::path::PathBuf::from("quicky-paper.json"));
+
+    PaperOptions {
+        state_path,
+        replay_path: cmd_args.paper_replay.as_ref().map(std::path::PathBuf::from),
+        record_path: cmd_args.paper_record.as_ref().map(std::path::PathBuf::from),
+    }
+}
+
+/// Resolve symbol from command line's argument, `QUICKY_SYMBOL` environment
+/// variable, then `profile`.
+///
+/// # Arguments
+/// * `cli_symbol` - symbol as specified at command line
+/// * `profile` - selected profile
+pub fn resolve_symbol(cli_symbol: Option<&str>, profile: &Profile) -> Result<Option<String>, StatusCode> {
+    Ok(cli_symbol.map(|s| s.to_owned())
+        .or(get_env_override("QUICKY_SYMBOL")?)
+        .or_else(|| profile.symbol.clone()))
+}
+
+/// Resolve quantity from command line's argument, `QUICKY_QTY` environment
+/// variable, then `profile`.
+///
+/// # Arguments
+/// * `cli_qty` - quantity as specified at command line
+/// * `profile` - selected profile
+pub fn resolve_qty(cli_qty: Option<i64>, profile: &Profile) -> Result<Option<i64>, StatusCode> {
+    Ok(cli_qty.or(get_env_override("QUICKY_QTY")?).or(profile.qty))
+}
+
+/// Get value of environment variable `name` parsed as `T`.
+/// Return `None` if it is not set, or empty. Fail if it cannot be parsed, so
+/// a typo is not silently replaced by the next source of the setting.
+pub fn get_env_override<T: FromStr>(name: &str) -> Result<Option<T>, StatusCode> {
+    match std::env::var(name) {
+        Ok(value) if !value.is_empty() => value.parse().map(Some).map_err(|_| {
+            eprintln!("Error: environment variable {} has malformed value '{}'", name, value);
+            StatusCode::ErrorConfig
+        }),
+        _ => Ok(None),
+    }
+}
+
+/// Get value of boolean environment variable `name` as of `get_env_override`.
+/// It is one of `1`, `0`, `yes`, `no`, `true`, or `false` in any case.
+pub fn get_env_flag(name: &str) -> Result<Option<bool>, StatusCode> {
+    let value: Option<String> = get_env_override(name)?;
+    match value.map(|v| v.to_lowercase()).as_deref() {
+        None => Ok(None),
+        Some("1") | Some("yes") | Some("true") => Ok(Some(true)),
+        Some("0") | Some("no") | Some("false") => Ok(Some(false)),
+        Some(_) => {
+            eprintln!("Error: environment variable {} has malformed value '{}', expect one of 1, 0, yes, no, true, false", name, std::env::var(name).unwrap_or_default());
+            Err(StatusCode::ErrorConfig)
+        }
+    }
+}
@@ -0,0 +1,295 @@
+use crate::types::*;
+use crate::util::*;
+use crate::output::*;
+
+use std::collections::BTreeMap;
+use std::num::NonZeroU32;
+use std::path::{Path, PathBuf};
+
+use ring::{aead, pbkdf2};
+use ring::rand::{SecureRandom, SystemRandom};
+use zeroize::Zeroizing;
+
+/// Version of credential store file format.
+const STORE_VERSION: u32 = 1;
+
+/// Iterations of PBKDF2 when creating a new store file.
+const STORE_PBKDF2_ITERATIONS: u32 = 200_000;
+
+/// Additional authenticated data bound to the ciphertext.
+const STORE_AAD: &[u8] = b"quicky-credentials-v1";
+
+/// Get path of credential store file.
+/// It is from `QUICKY_CREDENTIALS_FILE` environment variable if set, otherwise
+/// `$XDG_DATA_HOME/quicky/credentials.enc`, or `$HOME/.local/share/quicky/credentials.enc`.
+pub fn get_credential_store_path() -> Option<PathBuf> {
+    if let Ok(path) = std::env::var("QUICKY_CREDENTIALS_FILE") {
+        if !path.is_empty() {
+            return Some(PathBuf::from(path));
+        }
+    }
+    let data_home = match std::env::var("XDG_DATA_HOME") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var("HOME").ok()?).join(".local").join("share"),
+    };
+    Some(data_home.join("quicky").join("credentials.enc"))
+}
+
+/// Execute `action` on credential store.
+///
+/// # Arguments
+/// * `action` - action to execute
+pub fn run_credentials_command(action: &CredentialsAction) -> Result<(), StatusCode> {
+    let path = get_credential_store_path().ok_or(StatusCode::ErrorCredentialStore)?;
+
+    match action {
+        CredentialsAction::Add { name } => {
+            let is_new = !path.exists();
+            let passphrase = prompt_secret("Passphrase: ")?;
+            if is_new && *prompt_secret("Confirm passphrase: ")? != *passphrase {
+                eprintln!("Error: passphrases do not match");
+                return Err(StatusCode::ErrorCredentialStore);
+            }
+            let mut entries = if is_new { BTreeMap::new() } else { load_credential_store(&path, &passphrase)? };
+
+            let credentials = ApiCredentials {
+                api_key: prompt_secret("API key: ")?.trim().to_owned(),
+                api_secret: prompt_secret("API secret: ")?.trim().to_owned(),
+            };
+            if credentials.api_key.is_empty() || credentials.api_secret.is_empty() {
+                return Err(StatusCode::ErrorIncorrectParameterValue);
+            }
+            entries.insert(name.clone(), credentials);
+
+            save_credential_store(&path, &passphrase, &entries)?;
+            print_credentials_change("added", name)?;
+        },
+        CredentialsAction::Remove { name } => {
+            let passphrase = prompt_secret("Passphrase: ")?;
+            let mut entries = load_credential_store(&path, &passphrase)?;
+            if entries.remove(name).is_none() {
+                eprintln!("Error: no credentials named {}", name);
+                return Err(StatusCode::ErrorCredentialStore);
+            }
+            save_credential_store(&path, &passphrase, &entries)?;
+            print_credentials_change("removed", name)?;
+        },
+        CredentialsAction::List => {
+            let passphrase = prompt_secret("Passphrase: ")?;
+            let names: Vec<String> = load_credential_store(&path, &passphrase)?.into_keys().collect();
+            match get_output_mode() {
+                OutputMode::Json => print_json(&names)?,
+                OutputMode::Table => print_table(&["name"], &names.into_iter().map(|name| vec![name]).collect::<Vec<_>>()),
+                OutputMode::Plain if is_quiet() => {},
+                OutputMode::Plain => {
+                    for name in names.iter() {
+                        println!("{}", name);
+                    }
+                },
+            }
+        },
+    }
+    Ok(())
+}
+
+/// Print that credentials named `name` are added, or removed as of `action`.
+fn print_credentials_change(action: &str, name: &str) -> Result<(), StatusCode> {
+    match get_output_mode() {
+        OutputMode::Json => print_json(&serde_json::json!({"action": action, "name": name})),
+        _ => {
+            if !is_quiet() {
+                println!("{} {}", action, name);
+            }
+            Ok(())
+        },
+    }
+}
+
+/// Prompt for passphrase, then load credentials named `name` from the store.
+///
+/// # Arguments
+/// * `name` - name of the entry
+pub fn unlock_store_credentials(name: &str) -> Result<ApiCredentials, StatusCode> {
+    let path = get_credential_store_path().ok_or(StatusCode::ErrorCredentialStore)?;
+    let passphrase = prompt_secret(&format!("Passphrase to unlock credentials '{}': ", name))?;
+    let mut entries = load_credential_store(&path, &passphrase)?;
+
+    entries.remove(name).ok_or_else(|| {
+        eprintln!("Error: no credentials named {}", name);
+        StatusCode::ErrorMissingCredentials
+    })
+}
+
+/// Unlock credentials from the store for all `contexts` with a single prompt
+/// of passphrase. Contexts with credentials from environment variables, or
+/// already unlocked ones are left untouched.
+///
+/// # Arguments
+/// * `contexts` - trading contexts of accounts to trade on
+pub fn unlock_credential_stores<'a>(contexts: impl IntoIterator<Item = &'a TradingContext>) -> Result<(), StatusCode> {
+    let pending: Vec<_> = contexts.into_iter()
+        .filter_map(|c| match &c.credentials {
+            CredentialSource::Store { name, unlocked } if unlocked.get().is_none() => Some((name, unlocked)),
+            _ => None,
+        })
+        .collect();
+    if pending.is_empty() {
+        return Ok(());
+    }
+
+    let path = get_credential_store_path().ok_or(StatusCode::ErrorCredentialStore)?;
+    let mut names: Vec<String> = pending.iter().map(|(name, _)| format!("'{}'", name)).collect();
+    names.sort();
+    names.dedup();
+    let passphrase = prompt_secret(&format!("Passphrase to unlock credentials {}: ", names.join(", ")))?;
+    let entries = load_credential_store(&path, &passphrase)?;
+
+    for (name, unlocked) in pending {
+        let credentials = entries.get(name).ok_or_else(|| {
+            eprintln!("Error: no credentials named {}", name);
+            StatusCode::ErrorMissingCredentials
+        })?;
+        let _ = unlocked.set(credentials.clone());
+    }
+    Ok(())
+}
+
+/// Decrypt credential store at `path` with `passphrase`.
+///
+/// # Arguments
+/// * `path` - path of store file
+/// * `passphrase` - passphrase of the store
+pub fn load_credential_store(path: &Path, passphrase: &str) -> Result<BTreeMap<String, ApiCredentials>, StatusCode> {
+    let content = std::fs::read_to_string(path).map_err(|e| {
+        eprintln!("Error: cannot read credential store {}: {}", path.display(), e);
+        StatusCode::ErrorCredentialStore
+    })?;
+    let file: CredentialStoreFile = serde_json::from_str(&content).map_err(|_| StatusCode::ErrorCredentialStore)?;
+    if file.version != STORE_VERSION {
+        return Err(StatusCode::ErrorCredentialStore);
+    }
+
+    let salt = from_hex(&file.salt).ok_or(StatusCode::ErrorCredentialStore)?;
+    let nonce = from_hex(&file.nonce).ok_or(StatusCode::ErrorCredentialStore)?;
+    let mut in_out = Zeroizing::new(from_hex(&file.ciphertext).ok_or(StatusCode::ErrorCredentialStore)?);
+
+    let key = derive_store_key(passphrase, &salt, file.iterations)?;
+    let nonce = aead::Nonce::try_assume_unique_for_key(&nonce).map_err(|_| StatusCode::ErrorCredentialStore)?;
+    let plaintext = key.open_in_place(nonce, aead::Aad::from(STORE_AAD), &mut in_out).map_err(|_| {
+        eprintln!("Error: wrong passphrase, or credential store is corrupted");
+        StatusCode::ErrorCredentialStore
+    })?;
+
+    serde_json::from_slice(plaintext).map_err(|_| StatusCode::ErrorCredentialStore)
+}
+
+/// Encrypt `entries` with `passphrase`, then write to credential store at `path`.
+/// Fresh salt and nonce are used on every write.
+///
+/// # Arguments
+/// * `path` - path of store file
+/// * `passphrase` - passphrase of the store
+/// * `entries` - credentials by name
+pub fn save_credential_store(path: &Path, passphrase: &str, entries: &BTreeMap<String, ApiCredentials>) -> Result<(), StatusCode> {
+    let rng = SystemRandom::new();
+    let mut salt = [0u8; 16];
+    let mut nonce = [0u8; aead::NONCE_LEN];
+    rng.fill(&mut salt).map_err(|_| StatusCode::ErrorCredentialStore)?;
+    rng.fill(&mut nonce).map_err(|_| StatusCode::ErrorCredentialStore)?;
+
+    let key = derive_store_key(passphrase, &salt, STORE_PBKDF2_ITERATIONS)?;
+    let mut in_out = Zeroizing::new(serde_json::to_vec(entries).map_err(|_| StatusCode::InternalErrorParsingJsonObject)?);
+    key.seal_in_place_append_tag(aead::Nonce::assume_unique_for_key(nonce), aead::Aad::from(STORE_AAD), &mut *in_out)
+        .map_err(|_| StatusCode::ErrorCredentialStore)?;
+
+    let file = CredentialStoreFile {
+        version: STORE_VERSION,
+        iterations: STORE_PBKDF2_ITERATIONS,
+        salt: to_hex(&salt),
+        nonce: to_hex(&nonce),
+        ciphertext: to_hex(&in_out),
+    };
+    let content = serde_json::to_string_pretty(&file).map_err(|_| StatusCode::InternalErrorParsingJsonObject)?;
+
+    if let Some(dir) = path.parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+    write_private_file(path, content.as_bytes()).map_err(|e| {
+        eprintln!("Error: cannot write credential store {}: {}", path.display(), e);
+        StatusCode::ErrorCredentialStore
+    })
+}
+
+/// Derive key of the store from `passphrase`.
+fn derive_store_key(passphrase: &str, salt: &[u8], iterations: u32) -> Result<aead::LessSafeKey, StatusCode> {
+    let iterations = NonZeroU32::new(iterations).ok_or(StatusCode::ErrorCredentialStore)?;
+    let mut key_bytes = Zeroizing::new([0u8; 32]);
+    pbkdf2::derive(pbkdf2::PBKDF2_HMAC_SHA256, iterations, salt, passphrase.as_bytes(), &mut *key_bytes);
+
+    let unbound = aead::UnboundKey::new(&aead::CHACHA20_POLY1305, &*key_bytes).map_err(|_| StatusCode::ErrorCredentialStore)?;
+    Ok(aead::LessSafeKey::new(unbound))
+}
+
+/// Prompt for a secret on terminal without echoing it.
+fn prompt_secret(prompt: &str) -> Result<Zeroizing<String>, StatusCode> {
+    rpassword::prompt_password(prompt).map(Zeroizing::new).map_err(|_| StatusCode::ErrorCredentialStore)
+}
+
+/// Write `content` to `path` readable by owner only.
+fn write_private_file(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    let tmp_path = path.with_extension("tmp");
+    let mut file = std::fs::OpenOptions::new().write(true).create(true).truncate(true).mode(0o600).open(&tmp_path)?;
+    file.write_all(content)?;
+    file.sync_all()?;
+    std::fs::rename(&tmp_path, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("quicky-credentials-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir.join("credentials.json")
+    }
+
+    #[test]
+    fn store_round_trips_with_passphrase() {
+        let path = store_path("round-trip");
+        let mut entries = BTreeMap::new();
+        entries.insert(String::from("main"), ApiCredentials { api_key: String::from("key-1"), api_secret: String::from("secret-1") });
+        entries.insert(String::from("alt"), ApiCredentials { api_key: String::from("key-2"), api_secret: String::from("secret-2") });
+        assert!(save_credential_store(&path, "correct horse", &entries).is_ok());
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(!content.contains("secret-1") && !content.contains("key-2"));
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+
+        let loaded = match load_credential_store(&path, "correct horse") {
+            Ok(loaded) => loaded,
+            Err(_) => panic!("cannot load credential store"),
+        };
+        assert_eq!(loaded.len(), 2);
+        assert_eq!((loaded["main"].api_key.as_str(), loaded["main"].api_secret.as_str()), ("key-1", "secret-1"));
+        assert_eq!((loaded["alt"].api_key.as_str(), loaded["alt"].api_secret.as_str()), ("key-2", "secret-2"));
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn store_refuses_wrong_passphrase_and_missing_file() {
+        let path = store_path("wrong-passphrase");
+        assert!(matches!(load_credential_store(&path, "any"), Err(StatusCode::ErrorCredentialStore)));
+
+        assert!(save_credential_store(&path, "correct horse", &BTreeMap::new()).is_ok());
+        assert!(matches!(load_credential_store(&path, "battery staple"), Err(StatusCode::ErrorCredentialStore)));
+        assert!(matches!(load_credential_store(&path, "correct horse"), Ok(entries) if entries.is_empty()));
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+}
@@ -0,0 +1,229 @@
+use crate::types::*;
+use crate::util::*;
+use crate::ws::*;
+use crate::orderbook::*;
+use crate::output::*;
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Run daemon listening on Unix domain socket at `socket_path`.
+/// Order books of `symbols`, and private stream are kept up to date in
+/// background threads. Each connection may send multiple commands, one per line,
+/// and receives a json response line for each.
+///
+/// # Arguments
+/// * `context` - `TradingContext` for context information used in trading
+/// * `socket_path` - path of the socket
+/// * `symbols` - symbols to keep order book of
+pub fn run_daemon(context: &TradingContext, socket_path: &str, symbols: &[String]) -> Result<(), StatusCode> {
+    let mut books = HashMap::new();
+    for symbol in symbols.iter() {
+        let tick_step = *context.tick_steps.get(symbol).ok_or(StatusCode::InternalErrorNoTickStepAvailable)?;
+        books.insert(symbol.clone(), Arc::new(Mutex::new(orderbook_new(symbol, tick_step))));
+    }
+
+    let state = Arc::new(DaemonState {
+        books,
+        positions: Mutex::new(HashMap::new()),
+        private_connected: AtomicBool::new(false),
+    });
+    let listener = bind_socket(socket_path)?;
+    print_info(&format!("listening on {}", socket_path));
+
+    // streams live as long as the process, daemon is stopped by killing it
+    let running = Arc::new(AtomicBool::new(true));
+
+    for book in state.books.values() {
+        let (context, book, running) = (context.clone(), book.clone(), running.clone());
+        std::thread::spawn(move || run_orderbook_stream(&context, &book, &running));
+    }
+    {
+        let (context, state, running) = (context.clone(), state.clone(), running.clone());
+        std::thread::spawn(move || run_private_stream(&context, &running, &state.private_connected, |event| {
+            if let BybitPrivateStreamEvent::Position(data) = event {
+                let mut positions = state.positions.lock().unwrap();
+                for d in data.into_iter() {
+                    positions.insert(d.symbol.clone(), to_position_result(d));
+                }
+            }
+        }));
+    }
+
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let (context, state) = (context.clone(), state.clone());
+                std::thread::spawn(move || handle_connection(&context, &state, stream));
+            },
+            Err(e) => eprintln!("Error: {}", e),
+        }
+    }
+    Ok(())
+}
+
+/// Bind Unix domain socket at `socket_path` which only owner can connect to.
+/// Refuse if another daemon answers there, otherwise replace stale socket left from previous run.
+/// Socket is bound inside a private directory then moved into place, so it's never accessible by others.
+///
+/// # Arguments
+/// * `socket_path` - path of the socket
+fn bind_socket(socket_path: &str) -> Result<UnixListener, StatusCode> {
+    if UnixStream::connect(socket_path).is_ok() {
+        eprintln!("Error: daemon is already listening on {}", socket_path);
+        return Err(StatusCode::InternalErrorGeneric);
+    }
+
+    let fail = |e: std::io::Error| {
+        eprintln!("Error: cannot bind {}: {}", socket_path, e);
+        StatusCode::InternalErrorGeneric
+    };
+    let private_dir = format!("{}.{}.tmp", socket_path, std::process::id());
+    std::fs::DirBuilder::new().mode(0o700).create(&private_dir).map_err(fail)?;
+    let private_path = format!("{}/quicky.sock", private_dir);
+    let result = UnixListener::bind(&private_path)
+        .and_then(|listener| {
+            std::fs::set_permissions(&private_path, std::fs::Permissions::from_mode(0o600))?;
+            std::fs::rename(&private_path, socket_path)?;
+            Ok(listener)
+        });
+    let _ = std::fs::remove_file(&private_path);
+    let _ = std::fs::remove_dir(&private_dir);
+    result.map_err(fail)
+}
+
+/// Send a command line to daemon listening at `socket_path`, then print its
+/// response. Return `Ok(false)` if daemon reports failure of the command.
+///
+/// # Arguments
+/// * `socket_path` - path of the socket
+/// * `line` - command line e.g. `place XRPUSD 10`
+pub fn send_daemon_command(socket_path: &str, line: &str) -> Result<bool, StatusCode> {
+    let mut stream = UnixStream::connect(socket_path).map_err(|e| {
+        eprintln!("Error: cannot connect to {}: {}", socket_path, e);
+        StatusCode::InternalErrorGeneric
+    })?;
+    writeln!(stream, "{}", line).map_err(|_| StatusCode::InternalErrorGeneric)?;
+
+    let mut response = String::new();
+    BufReader::new(stream).read_line(&mut response).map_err(|_| StatusCode::InternalErrorGeneric)?;
+    // response is already a json line, printed as is in any output mode
+    if !is_quiet() || matches!(get_output_mode(), OutputMode::Json) {
+        print!("{}", response);
+    }
+
+    let json: serde_json::Value = serde_json::from_str(&response).map_err(|_| StatusCode::ErrorJsonParsing)?;
+    Ok(json["ok"].as_bool() == Some(true))
+}
+
+/// Get default path of daemon's socket.
+/// It is `$XDG_RUNTIME_DIR/quicky.sock` if set, otherwise in temp directory.
+pub fn get_default_socket_path() -> String {
+    match std::env::var("XDG_RUNTIME_DIR") {
+        Ok(dir) if !dir.is_empty() => format!("{}/quicky.sock", dir),
+        _ => std::env::temp_dir().join("quicky.sock").to_string_lossy().into_owned(),
+    }
+}
+
+/// Serve commands of a single connection until it is closed.
+fn handle_connection(context: &TradingContext, state: &DaemonState, stream: UnixStream) {
+    let mut writer = match stream.try_clone() {
+        Ok(writer) => writer,
+        Err(_) => return,
+    };
+
+    for line in BufReader::new(stream).lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => return,
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = match parse_daemon_command(&line) {
+            Ok(command) => execute_daemon_command(context, state, &command),
+            Err(e) => error_response(&e),
+        };
+        if writeln!(writer, "{}", response).is_err() {
+            return;
+        }
+    }
+}
+
+/// Parse a command line, either as words or as json object.
+///
+/// # Arguments
+/// * `line` - command line
+pub fn parse_daemon_command(line: &str) -> Result<DaemonCommand, StatusCode> {
+    let line = line.trim();
+    if line.starts_with('{') {
+        return serde_json::from_str(line).map_err(|_| StatusCode::ErrorJsonParsing);
+    }
+
+    let mut words = line.split_whitespace();
+    let mut command = DaemonCommand {
+        cmd: words.next().unwrap_or_default().to_owned(),
+        ..Default::default()
+    };
+    command.symbol = words.next().map(|w| w.to_owned());
+    if let Some(qty) = words.next() {
+        command.qty = Some(qty.parse().map_err(|_| StatusCode::ErrorIncorrectParameterValue)?);
+    }
+    Ok(command)
+}
+
+/// Execute `command` then return its json response.
+fn execute_daemon_command(context: &TradingContext, state: &DaemonState, command: &DaemonCommand) -> serde_json::Value {
+    let symbol = command.symbol.as_deref();
+
+    match (command.cmd.as_str(), symbol) {
+        ("place", Some(symbol)) => {
+            let qty = command.qty.unwrap_or(0);
+            let result = match state.books.get(symbol) {
+                Some(book) => send_limit_order_joining_book(context, symbol, qty, book),
+                None => api_send_quick_limit_order(context, symbol, qty),
+            };
+            match result {
+                Ok(order) => serde_json::json!({"ok": true, "order": order}),
+                Err(e) => error_response(&e),
+            }
+        },
+        ("cancel", Some(symbol)) => match api_cancel_all_orders(context, symbol) {
+            Ok(()) => serde_json::json!({"ok": true}),
+            Err(e) => error_response(&e),
+        },
+        ("close", Some(symbol)) => match api_close_position(context, symbol) {
+            Ok(order) => serde_json::json!({"ok": true, "order": order}),
+            Err(e) => error_response(&e),
+        },
+        ("status", _) => {
+            let positions = state.positions.lock().unwrap();
+            let books: HashMap<&String, serde_json::Value> = state.books.iter().map(|(symbol, book)| {
+                let book = book.lock().unwrap();
+                (symbol, serde_json::json!({
+                    "synced": book.synced,
+                    "bid": orderbook_best(&book, BookSide::Bid).map(|(price, _)| price),
+                    "ask": orderbook_best(&book, BookSide::Ask).map(|(price, _)| price),
+                }))
+            }).collect();
+            serde_json::json!({
+                "ok": true,
+                "testnet": context.use_testnet,
+                "private_connected": state.private_connected.load(Ordering::SeqCst),
+                "books": books,
+                "positions": *positions,
+            })
+        },
+        _ => error_response(&StatusCode::ErrorIncorrectParameterValue),
+    }
+}
+
+/// Form json response of failure.
+fn error_response(code: &StatusCode) -> serde_json::Value {
+    serde_json::json!({"ok": false, "error": get_status_code_message(code)})
+}
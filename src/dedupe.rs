@@ -0,0 +1,119 @@
+use crate::types::*;
+use crate::util::*;
+
+use std::io::{Read, Seek, Write};
+use std::path::PathBuf;
+
+/// Get path of file keeping recently sent orders.
+/// It is `$XDG_DATA_HOME/quicky/recent-orders.json`, or
+/// `$HOME/.local/share/quicky/recent-orders.json` if `XDG_DATA_HOME` is not set.
+pub fn get_recent_orders_path() -> Option<PathBuf> {
+    let data_home = match std::env::var("XDG_DATA_HOME") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var("HOME").ok()?).join(".local").join("share"),
+    };
+    Some(data_home.join("quicky").join("recent-orders.json"))
+}
+
+/// Key identifying the same order on the same account. Price is left out, so
+/// a repeated order is still caught if price moved in between.
+///
+/// # Arguments
+/// * `context` - `TradingContext` of the account
+/// * `plan` - `OrderPlan`
+pub fn get_order_dedupe_key(context: &TradingContext, plan: &OrderPlan) -> String {
+    let endpoint = match (&context.paper, &context.base_url) {
+        (Some(paper), _) => format!("paper:{}", paper.state_path.display()),
+        (None, Some(base_url)) => base_url.clone(),
+        (None, None) => format!("{}:{}", context.domain, if context.use_testnet { "testnet" } else { "mainnet" }),
+    };
+    format!("{} {} {} {} {}", endpoint, get_account_label(context), plan.symbol, plan.side, plan.qty)
+}
+
+/// Record `plan` as being sent unless the same order was sent within
+/// `TradingContext::dedupe_window_secs`, in which case
+/// `StatusCode::ErrorDuplicateOrder` is returned.
+///
+/// It is recorded before the order is sent, so a concurrent duplicate from
+/// another process is also caught. The file is locked while it is checked.
+///
+/// # Arguments
+/// * `context` - `TradingContext` of the account
+/// * `plan` - `OrderPlan` to be sent
+pub fn reserve_order(context: &TradingContext, plan: &OrderPlan) -> Result<(), StatusCode> {
+    reserve_orders(context, &[plan])
+}
+
+/// Record `plans` e.g. of a batch as being sent as a unit, as of
+/// `reserve_order`. None of them is recorded if any was sent within
+/// `TradingContext::dedupe_window_secs`, while they are not checked against
+/// each other, so identical orders of the same unit don't collide.
+///
+/// # Arguments
+/// * `context` - `TradingContext` of the account
+/// * `plans` - `OrderPlan` of each order to be sent
+pub fn reserve_orders(context: &TradingContext, plans: &[&OrderPlan]) -> Result<(), StatusCode> {
+    if context.dedupe_window_secs <= 0.0 || plans.is_empty() {
+        return Ok(());
+    }
+    let now = get_unix_timestamp_as_millis() as u64;
+    let window_millis = (context.dedupe_window_secs * 1000.0) as u64;
+    let keys: Vec<String> = plans.iter().map(|plan| get_order_dedupe_key(context, plan)).collect();
+
+    update_recent_orders(|recent| {
+        recent.orders.retain(|o| now.saturating_sub(o.time) < window_millis);
+        for (plan, key) in plans.iter().zip(keys.iter()) {
+            if let Some(previous) = recent.orders.iter().find(|o| o.key == *key) {
+                eprintln!("Error: the same order {} {} {} was sent {:.1} secs ago as {}, pass --dedupe-window 0 to send it anyway",
+                    plan.side, plan.qty, plan.symbol, now.saturating_sub(previous.time) as f64 / 1000.0, previous.order_link_id);
+                return Err(StatusCode::ErrorDuplicateOrder);
+            }
+        }
+        for (plan, key) in plans.iter().zip(keys) {
+            recent.orders.push(RecentOrder { key, order_link_id: plan.order_link_id.clone(), time: now });
+        }
+        Ok(())
+    })
+}
+
+/// Forget `plan` recorded by `reserve_order`, as it is known not to be placed.
+///
+/// # Arguments
+/// * `context` - `TradingContext` of the account
+/// * `plan` - `OrderPlan` which was not placed
+pub fn release_order(context: &TradingContext, plan: &OrderPlan) {
+    if context.dedupe_window_secs <= 0.0 {
+        return;
+    }
+    let _ = update_recent_orders(|recent| {
+        recent.orders.retain(|o| o.order_link_id != plan.order_link_id);
+        Ok(())
+    });
+}
+
+/// Apply `f` to recently sent orders while holding exclusive lock of the file,
+/// then write them back if `f` succeeds.
+fn update_recent_orders<F: FnOnce(&mut RecentOrdersFile) -> Result<(), StatusCode>>(f: F) -> Result<(), StatusCode> {
+    let path = get_recent_orders_path().ok_or(StatusCode::InternalErrorGeneric)?;
+    if let Some(dir) = path.parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+    let mut file = std::fs::OpenOptions::new().read(true).write(true).create(true).truncate(false).open(&path).map_err(|e| {
+        eprintln!("Error: cannot open {}: {}", path.display(), e);
+        StatusCode::InternalErrorGeneric
+    })?;
+    file.lock().map_err(|_| StatusCode::InternalErrorGeneric)?;
+
+    let mut content = String::new();
+    file.read_to_string(&mut content).map_err(|_| StatusCode::InternalErrorGeneric)?;
+    // unreadable content is only a list of recent orders, start over
+    let mut recent: RecentOrdersFile = serde_json::from_str(&content).unwrap_or_default();
+
+    f(&mut recent)?;
+
+    let content = serde_json::to_vec(&recent).map_err(|_| StatusCode::InternalErrorParsingJsonObject)?;
+    file.set_len(0).and_then(|_| file.rewind()).and_then(|_| file.write_all(&content)).map_err(|e| {
+        eprintln!("Error: cannot write {}: {}", path.display(), e);
+        StatusCode::InternalErrorGeneric
+    })
+}
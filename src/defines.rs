@@ -1,3 +1,90 @@
-pub static TESTNET_URI_PREFIX: &str = "https://api-testnet.bybit.com";
-pub static URI_PREFIX: &str = "https://api.bybit.com";
+/// Domain of Bybit's API, alternatives are e.g. bytick.com
+pub static DEFAULT_DOMAIN: &str = "bybit.com";
 pub const DEFAULT_SL_PCNT: f64 = 0.5;
+pub const DEFAULT_CONFIRM_TIMEOUT_SECS: f64 = 5.0;
+pub const WS_PING_INTERVAL_SECS: u64 = 20;
+pub const WS_RECONNECT_DELAY_MILLIS: u64 = 1000;
+pub static DEFAULT_WEBHOOK_LISTEN_ADDR: &str = "127.0.0.1:8080";
+pub const DEFAULT_WEBHOOK_MAX_AGE_SECS: u64 = 30;
+/// Fee rates of inverse perpetual, used for estimation only
+pub const MAKER_FEE_RATE: f64 = 0.0001;
+pub const TAKER_FEE_RATE: f64 = 0.0006;
+/// Seconds within which the same order is refused as a duplicate
+pub const DEFAULT_DEDUPE_WINDOW_SECS: f64 = 2.0;
+/// Timeout of a single HTTP request
+pub const HTTP_TIMEOUT_SECS: u64 = 10;
+/// Number of orders e.g. of a ladder sent at the same time at most
+pub const MAX_CONCURRENT_ORDERS: usize = 4;
+/// Seconds between queries of an order watched without private stream
+pub const ORDER_POLL_INTERVAL_SECS: f64 = 1.0;
+/// Number of times an order is sent at most, if it didn't land due to network error
+pub const ORDER_SEND_ATTEMPTS: u32 = 3;
+/// `ret_code` of a request rejected as its timestamp is outside of `recv_window`
+pub const TIMESTAMP_RET_CODE: u32 = 10002;
+/// Records per page of closed PnL history, the most Bybit allows
+pub const CLOSED_PNL_PAGE_SIZE: u32 = 50;
+/// Records per page of execution history, the most Bybit allows
+pub const EXECUTION_PAGE_SIZE: u32 = 200;
+/// Pages of history fetched at most, Bybit doesn't serve pages beyond it
+pub const HISTORY_MAX_PAGES: u32 = 50;
+/// Number of times a request is retried at most, if it is rejected by rate limit
+pub const RATE_LIMIT_RETRIES: u32 = 3;
+/// Base of exponential backoff after a request is rejected by rate limit
+pub const RATE_LIMIT_BACKOFF_BASE_MILLIS: u64 = 250;
+/// Longest time to wait for rate-limit budget before giving up on a request
+pub const RATE_LIMIT_MAX_WAIT_MILLIS: u64 = 5000;
+/// Balance of each settlement coin a new paper exchange starts with
+pub const PAPER_INITIAL_BALANCE: f64 = 1000.0;
+/// Number of price levels of a ladder if not specified
+pub const DEFAULT_LADDER_LEVELS: u32 = 5;
+/// Ratio between quantities of consecutive levels of geometric ladder
+pub const DEFAULT_LADDER_GEOMETRIC_RATIO: f64 = 1.5;
+/// Take-profit targets of a bracket if not specified, percentage@R-multiple
+pub const DEFAULT_BRACKET_TARGETS: &str = "50@1,30@2,20@3";
+/// Seconds entry of a bracket is left to be filled if not specified
+pub const DEFAULT_BRACKET_ENTRY_TTL_SECS: f64 = 60.0;
+/// Seconds targets of a bracket are managed at most if not specified
+pub const DEFAULT_BRACKET_MANAGE_TIMEOUT_SECS: f64 = 24.0 * 60.0 * 60.0;
+/// Failed checks in a row of targets of a bracket before giving up on them
+pub const BRACKET_MAX_POLL_FAILURES: u32 = 5;
+/// Fields whose values are replaced by `<redacted>` in logs of requests, and responses
+pub const REDACTED_LOG_FIELDS: [&str; 5] = ["api_key", "sign", "signature", "secret", "api_secret"];
+
+/// Base URL of REST API of mainnet, or testnet at `domain`.
+pub fn get_preset_base_url(domain: &str, use_testnet: bool) -> String {
+    format!("https://{}.{}", if use_testnet { "api-testnet" } else { "api" }, domain)
+}
+
+/// Base URL of websocket API of mainnet, or testnet at `domain`.
+pub fn get_preset_ws_url(domain: &str, use_testnet: bool) -> String {
+    format!("wss://{}.{}", if use_testnet { "stream-testnet" } else { "stream" }, domain)
+}
+
+/// Names of environment variables holding API key, and secret of mainnet, or testnet.
+pub fn get_default_credential_env_names(use_testnet: bool) -> (&'static str, &'static str) {
+    if use_testnet {
+        ("QUICKY_BYBIT_TESTNET_API_KEY", "QUICKY_BYBIT_TESTNET_API_SECRET")
+    } else {
+        ("QUICKY_BYBIT_API_KEY", "QUICKY_BYBIT_API_SECRET")
+    }
+}
+
+/// Default source of API credentials of mainnet, or testnet.
+pub fn get_default_credential_source(use_testnet: bool) -> crate::types::CredentialSource {
+    let (key_env, secret_env) = get_default_credential_env_names(use_testnet);
+    crate::types::CredentialSource::Env { key_env: key_env.to_owned(), secret_env: secret_env.to_owned() }
+}
+
+/// Tick steps information for symbols we know of.
+pub fn get_default_tick_steps() -> std::collections::HashMap<String, f64> {
+    std::collections::HashMap::from([
+        ("XRPUSD".to_string(), 0.0001)
+    ])
+}
+
+/// Lot sizes of symbols we know of. Inverse contracts are traded in whole USD.
+pub fn get_default_lot_sizes() -> std::collections::HashMap<String, u64> {
+    std::collections::HashMap::from([
+        ("XRPUSD".to_string(), 1)
+    ])
+}
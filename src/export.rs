@@ -0,0 +1,153 @@
+use crate::types::*;
+use crate::util::*;
+
+use std::io::Write;
+
+/// Columns of exported closed PnL, in order of fields of `ClosedPnlExport`.
+const CLOSED_PNL_COLUMNS: [&str; 12] = ["time", "symbol", "order_id", "side", "exec_type", "closed_size", "avg_entry_price",
+    "avg_exit_price", "closed_pnl", "coin", "closed_pnl_usd", "fill_count"];
+
+/// Columns of exported executions, in order of fields of `ExecutionExport`.
+const EXECUTION_COLUMNS: [&str; 15] = ["time", "symbol", "order_id", "order_link_id", "exec_id", "exec_type", "side", "price",
+    "qty", "value", "fee", "fee_rate", "liquidity", "coin", "fee_usd"];
+
+/// Columns of exported funding payments, in order of fields of `FundingExport`.
+const FUNDING_COLUMNS: [&str; 9] = ["time", "symbol", "side", "position_size", "mark_price", "funding_rate", "funding_fee",
+    "coin", "funding_fee_usd"];
+
+/// Convert `amount` of coin to USD at `price`, rounded to 8 decimals.
+fn to_usd(amount: f64, price: f64) -> f64 {
+    (amount * price * 1e8).round() / 1e8
+}
+
+/// Convert closed PnL into its exported form. PnL of inverse contract is in
+/// coin, so it is converted to USD at the exit price.
+pub fn to_closed_pnl_export(record: &BybitClosedPnlRecord) -> ClosedPnlExport {
+    ClosedPnlExport {
+        time: format_unix_millis(record.created_at * 1000),
+        symbol: record.symbol.clone(),
+        order_id: record.order_id.clone(),
+        side: record.side.clone(),
+        exec_type: record.exec_type.clone(),
+        closed_size: record.closed_size,
+        avg_entry_price: record.avg_entry_price,
+        avg_exit_price: record.avg_exit_price,
+        closed_pnl: record.closed_pnl,
+        coin: get_settle_coin(&record.symbol).to_owned(),
+        closed_pnl_usd: to_usd(record.closed_pnl, record.avg_exit_price),
+        fill_count: record.fill_count,
+    }
+}
+
+/// Convert execution into its exported form. Fee is converted to USD at the
+/// execution price.
+pub fn to_execution_export(record: &BybitExecutionRecord) -> ExecutionExport {
+    ExecutionExport {
+        time: format_unix_millis(record.trade_time_ms),
+        symbol: record.symbol.clone(),
+        order_id: record.order_id.clone(),
+        order_link_id: record.order_link_id.clone(),
+        exec_id: record.exec_id.clone(),
+        exec_type: record.exec_type.clone(),
+        side: record.side.clone(),
+        price: record.exec_price,
+        qty: record.exec_qty,
+        value: record.exec_value,
+        fee: record.exec_fee,
+        fee_rate: record.fee_rate,
+        liquidity: String::from(if record.last_liquidity_ind == "AddedLiquidity" { "maker" } else { "taker" }),
+        coin: get_settle_coin(&record.symbol).to_owned(),
+        fee_usd: to_usd(record.exec_fee, record.exec_price),
+    }
+}
+
+/// Convert funding settlement into its exported form. Funding fee is
+/// converted to USD at the mark price it was settled at.
+pub fn to_funding_export(record: &BybitExecutionRecord) -> FundingExport {
+    FundingExport {
+        time: format_unix_millis(record.trade_time_ms),
+        symbol: record.symbol.clone(),
+        side: record.side.clone(),
+        position_size: record.exec_qty,
+        mark_price: record.exec_price,
+        funding_rate: record.fee_rate,
+        funding_fee: record.exec_fee,
+        coin: get_settle_coin(&record.symbol).to_owned(),
+        funding_fee_usd: to_usd(record.exec_fee, record.exec_price),
+    }
+}
+
+/// Fetch history of `kind` of `symbol` within the time range from Bybit, then
+/// return it as json objects along with their columns in order.
+///
+/// # Arguments
+/// * `context` - `TradingContext` for context information used in trading
+/// * `kind` - `ExportKind` of history
+/// * `symbol` - symbol to export history of
+/// * `since` - unix timestamp in milliseconds, inclusive
+/// * `until` - unix timestamp in milliseconds, exclusive
+pub fn api_get_export_rows(context: &TradingContext, kind: ExportKind, symbol: &str, since: Option<u64>, until: Option<u64>) -> Result<(&'static [&'static str], Vec<serde_json::Value>), StatusCode> {
+    let to_json = |row: Result<serde_json::Value, serde_json::Error>| row.map_err(|_| StatusCode::InternalErrorParsingJsonObject);
+    match kind {
+        ExportKind::ClosedPnl => {
+            let rows = api_get_closed_pnl(context, symbol, since, until)?.iter()
+                .map(|r| to_json(serde_json::to_value(to_closed_pnl_export(r))))
+                .collect::<Result<_, _>>()?;
+            Ok((&CLOSED_PNL_COLUMNS, rows))
+        },
+        ExportKind::Executions => {
+            let rows = api_get_executions(context, symbol, since, until)?.iter()
+                .filter(|r| r.exec_type != "Funding")
+                .map(|r| to_json(serde_json::to_value(to_execution_export(r))))
+                .collect::<Result<_, _>>()?;
+            Ok((&EXECUTION_COLUMNS, rows))
+        },
+        ExportKind::Funding => {
+            let rows = api_get_executions(context, symbol, since, until)?.iter()
+                .filter(|r| r.exec_type == "Funding")
+                .map(|r| to_json(serde_json::to_value(to_funding_export(r))))
+                .collect::<Result<_, _>>()?;
+            Ok((&FUNDING_COLUMNS, rows))
+        },
+    }
+}
+
+/// Write `rows` in `format` to `writer`. CSV has a header of `columns`, json
+/// is an array of objects.
+///
+/// # Arguments
+/// * `writer` - where to write to
+/// * `format` - `ExportFormat`
+/// * `columns` - columns of `rows` in order
+/// * `rows` - json objects to write
+pub fn write_export_rows<W: Write>(writer: &mut W, format: ExportFormat, columns: &[&str], rows: &[serde_json::Value]) -> std::io::Result<()> {
+    match format {
+        ExportFormat::Json => {
+            serde_json::to_writer_pretty(&mut *writer, rows)?;
+            writeln!(writer)
+        },
+        ExportFormat::Csv => {
+            writeln!(writer, "{}", columns.join(","))?;
+            for row in rows.iter() {
+                let fields: Vec<String> = columns.iter()
+                    .map(|c| match &row[*c] {
+                        serde_json::Value::String(s) => to_csv_field(s),
+                        serde_json::Value::Null => String::new(),
+                        v => v.to_string(),
+                    })
+                    .collect();
+                writeln!(writer, "{}", fields.join(","))?;
+            }
+            Ok(())
+        },
+    }
+}
+
+/// Quote `s` as a field of CSV if it has a separator, quote, or line break.
+fn to_csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_owned()
+    }
+}
@@ -1,22 +1,165 @@
-use crate::types::TradingContext;
+use crate::types::{TradingContext, PriceReference, ExportKind, ExportFormat, BatchFormat, OrderFallback, OutputMode, LogFormat, LadderWeighting, BracketTarget};
 use crate::defines::*;
 
-use std::collections::HashMap;
+use std::str::FromStr;
 
 /// Provide default values for `TradingContext`
 impl Default for TradingContext {
     fn default() -> TradingContext {
         TradingContext {
-            // panic if required api-keys/api-secrets are not set
-            api_key: std::env::var("QUICKY_BYBIT_API_KEY").expect("Required env variable QUICKY_BYBIT_API_KEY to be set"),
-            api_secret: std::env::var("QUICKY_BYBIT_API_SECRET").expect("Required env variable QUICKY_BYBIT_API_SECRET to be set"),
-            testnet_api_key: std::env::var("QUICKY_BYBIT_TESTNET_API_KEY").expect("Required env variable QUICKY_BYBIT_TESTNET_API_KEY to be set"),
-            testnet_api_secret: std::env::var("QUICKY_BYBIT_TESTNET_API_SECRET").expect("Required env variable QUICKY_BYBIT_TESTNET_API_SECRET to be set"),
-            tick_steps: HashMap::from([
-                                      ("XRPUSD".to_string(), 0.0001)
-            ]),
+            // loaded lazily when needed, see `get_api_credentials`
+            credentials: get_default_credential_source(true),
+            tick_steps: get_default_tick_steps(),
+            lot_sizes: get_default_lot_sizes(),
             stop_loss_pcnt: DEFAULT_SL_PCNT,
+            take_profit_pcnt: None,
             use_testnet: true,      // default for safety use testnet
+            domain: DEFAULT_DOMAIN.to_owned(),
+            base_url: None,
+            ws_url: None,
+            proxy: None,
+            price_ref: PriceReference::Last,
+            paper: None,
+            limits: Default::default(),
+            dedupe_window_secs: DEFAULT_DEDUPE_WINDOW_SECS,
+            journal_path: None,
+        }
+    }
+}
+
+/// Parse `PriceReference` from command line's argument, or environment variable
+impl FromStr for PriceReference {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "last" => Ok(PriceReference::Last),
+            "mark" => Ok(PriceReference::Mark),
+            "index" => Ok(PriceReference::Index),
+            "bidask" => Ok(PriceReference::BidAsk),
+            _ => Err(format!("unknown price reference '{}', expect one of last, mark, index, bidask", s)),
+        }
+    }
+}
+
+/// Display `PriceReference` as it is specified at command line
+impl std::fmt::Display for PriceReference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            PriceReference::Last => "last",
+            PriceReference::Mark => "mark",
+            PriceReference::Index => "index",
+            PriceReference::BidAsk => "bidask",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Parse `ExportKind` from command line's argument
+impl FromStr for ExportKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "closed-pnl" => Ok(ExportKind::ClosedPnl),
+            "executions" => Ok(ExportKind::Executions),
+            "funding" => Ok(ExportKind::Funding),
+            _ => Err(format!("unknown history '{}', expect one of closed-pnl, executions, funding", s)),
+        }
+    }
+}
+
+/// Parse `ExportFormat` from command line's argument
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "csv" => Ok(ExportFormat::Csv),
+            "json" => Ok(ExportFormat::Json),
+            _ => Err(format!("unknown format '{}', expect one of csv, json", s)),
+        }
+    }
+}
+
+/// Parse `OrderFallback` from command line's argument
+impl FromStr for OrderFallback {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "market" => Ok(OrderFallback::Market),
+            _ => Err(format!("unknown fallback '{}', expect market", s)),
+        }
+    }
+}
+
+/// Parse `BracketTarget` from command line's argument e.g. `50@1` as 50% at 1R
+impl FromStr for BracketTarget {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (pcnt, r_multiple) = s.split_once('@').ok_or_else(|| format!("malformed target '{}', expect percentage@R-multiple e.g. 50@1", s))?;
+        let pcnt: f64 = pcnt.trim().parse().map_err(|_| format!("malformed percentage '{}'", pcnt))?;
+        let r_multiple: f64 = r_multiple.trim().parse().map_err(|_| format!("malformed R-multiple '{}'", r_multiple))?;
+        if !(pcnt > 0.0 && pcnt <= 100.0 && r_multiple > 0.0) {
+            return Err(format!("target '{}' expects percentage within (0, 100], and positive R-multiple", s));
+        }
+        Ok(BracketTarget { pcnt, r_multiple })
+    }
+}
+
+/// Parse `BatchFormat` from command line's argument
+impl FromStr for BatchFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "csv" => Ok(BatchFormat::Csv),
+            "json" => Ok(BatchFormat::Json),
+            "lines" => Ok(BatchFormat::Lines),
+            _ => Err(format!("unknown format '{}', expect one of csv, json, lines", s)),
+        }
+    }
+}
+
+/// Parse `OutputMode` from command line's argument
+impl FromStr for OutputMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "plain" => Ok(OutputMode::Plain),
+            "table" => Ok(OutputMode::Table),
+            "json" => Ok(OutputMode::Json),
+            _ => Err(format!("unknown output '{}', expect one of plain, table, json", s)),
+        }
+    }
+}
+
+/// Parse `LogFormat` from command line's argument
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!("unknown log format '{}', expect one of text, json", s)),
+        }
+    }
+}
+
+/// Parse `LadderWeighting` from command line's argument
+impl FromStr for LadderWeighting {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "linear" => Ok(LadderWeighting::Linear),
+            "geometric" => Ok(LadderWeighting::Geometric),
+            "custom" => Ok(LadderWeighting::Custom),
+            _ => Err(format!("unknown weighting '{}', expect one of linear, geometric, custom", s)),
         }
     }
 }
@@ -0,0 +1,176 @@
+use crate::types::*;
+use crate::util::*;
+use crate::ws::*;
+use crate::orderbook::*;
+
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::{cursor, execute, queue, terminal};
+
+/// Run interactive mode for `symbol`.
+/// Order book, and private stream are kept open in background threads while
+/// keystrokes are mapped to actions.
+///
+/// * `b`/`s` - buy/sell `qty` joining the best bid/ask
+/// * `c` - cancel all active orders
+/// * `x` - close position with a reduce-only market order
+/// * `+`/`-` - adjust `qty` by `qty_step`
+/// * `q`/`Esc` - quit
+///
+/// # Arguments
+/// * `context` - `TradingContext` for context information used in trading
+/// * `symbol` - symbol to trade
+/// * `qty` - default size of an order
+/// * `qty_step` - size to adjust `qty` with
+pub fn run_interactive(context: &TradingContext, symbol: &str, qty: u64, qty_step: u64) -> Result<(), StatusCode> {
+    let tick_step = *context.tick_steps.get(symbol).ok_or(StatusCode::InternalErrorNoTickStepAvailable)?;
+
+    let book = Arc::new(Mutex::new(orderbook_new(symbol, tick_step)));
+    let state = Arc::new(Mutex::new(InteractiveState {
+        position: api_get_position(context, symbol).ok(),
+        status: String::from("ready"),
+    }));
+    let running = Arc::new(AtomicBool::new(true));
+    let private_connected = Arc::new(AtomicBool::new(false));
+
+    let book_thread = {
+        let (context, book, running) = (context.clone(), book.clone(), running.clone());
+        std::thread::spawn(move || run_orderbook_stream(&context, &book, &running))
+    };
+    let private_thread = {
+        let (context, state, running, private_connected) = (context.clone(), state.clone(), running.clone(), private_connected.clone());
+        let symbol = symbol.to_owned();
+        std::thread::spawn(move || run_private_stream(&context, &running, &private_connected, |event| {
+            on_private_event(&symbol, &state, event)
+        }))
+    };
+
+    let result = run_key_loop(context, symbol, qty, qty_step, &book, &state, &private_connected);
+
+    running.store(false, Ordering::SeqCst);
+    let _ = book_thread.join();
+    let _ = private_thread.join();
+    result
+}
+
+/// Raw mode and alternate screen of the terminal, restored once dropped, so
+/// the terminal is usable again even if key loop returns early or panics.
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn enter() -> Result<TerminalGuard, StatusCode> {
+        terminal::enable_raw_mode().map_err(|_| StatusCode::InternalErrorGeneric)?;
+        let _ = execute!(std::io::stdout(), terminal::EnterAlternateScreen, cursor::Hide);
+        Ok(TerminalGuard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = execute!(std::io::stdout(), cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Set up terminal, then handle keystrokes until user quits.
+fn run_key_loop(context: &TradingContext, symbol: &str, mut qty: u64, qty_step: u64, book: &Mutex<OrderBook>, state: &Mutex<InteractiveState>, private_connected: &AtomicBool) -> Result<(), StatusCode> {
+    let mut stdout = std::io::stdout();
+    let _terminal = TerminalGuard::enter()?;
+
+    loop {
+        render(&mut stdout, symbol, qty, &book.lock().unwrap(), &state.lock().unwrap(), private_connected.load(Ordering::SeqCst));
+
+        if !event::poll(Duration::from_millis(100)).unwrap_or(false) {
+            continue;
+        }
+        let key = match event::read() {
+            Ok(Event::Key(key)) if key.kind != KeyEventKind::Release => key,
+            _ => continue,
+        };
+
+        let status = match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => break,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => break,
+            KeyCode::Char('b') => place_order(context, symbol, qty as i64, book),
+            KeyCode::Char('s') => place_order(context, symbol, -(qty as i64), book),
+            KeyCode::Char('c') => match api_cancel_all_orders(context, symbol) {
+                Ok(()) => String::from("cancelled all active orders"),
+                Err(e) => format!("cancel failed: {}", get_status_code_message(&e)),
+            },
+            KeyCode::Char('x') => match api_close_position(context, symbol) {
+                Ok(Some(order)) => format!("closing {} {} with market order {}", order.side, order.qty, order.order_id),
+                Ok(None) => String::from("no position to close"),
+                Err(e) => format!("close failed: {}", get_status_code_message(&e)),
+            },
+            KeyCode::Char('+') | KeyCode::Char('=') => {
+                qty += qty_step;
+                format!("size {}", qty)
+            },
+            KeyCode::Char('-') => {
+                qty = qty.saturating_sub(qty_step).max(1);
+                format!("size {}", qty)
+            },
+            _ => continue,
+        };
+        state.lock().unwrap().status = status;
+    }
+
+    Ok(())
+}
+
+/// Place a limit order joining the best level of our side of `book`. Fall
+/// back to `api_send_quick_limit_order` if the book is not synced.
+/// Return status message to show.
+fn place_order(context: &TradingContext, symbol: &str, qty: i64, book: &Mutex<OrderBook>) -> String {
+    match send_limit_order_joining_book(context, symbol, qty, book) {
+        Ok(order) => format!("placed {} {} @ {} ({})", order.side, order.qty, order.price, order.order_id),
+        Err(e) => format!("order failed: {}", get_status_code_message(&e)),
+    }
+}
+
+/// Update `state` from private stream `event` of `symbol`.
+fn on_private_event(symbol: &str, state: &Mutex<InteractiveState>, event: BybitPrivateStreamEvent) {
+    match event {
+        BybitPrivateStreamEvent::Position(data) => {
+            if let Some(d) = data.into_iter().find(|d| d.symbol == symbol) {
+                state.lock().unwrap().position = Some(to_position_result(d));
+            }
+        },
+        BybitPrivateStreamEvent::Order(data) => {
+            if let Some(d) = data.into_iter().rev().find(|d| d.symbol == symbol) {
+                state.lock().unwrap().status = format!("order {} {} {}", d.order_id, d.side, d.order_status);
+            }
+        },
+        _ => {},
+    }
+}
+
+/// Draw the screen.
+fn render(stdout: &mut std::io::Stdout, symbol: &str, qty: u64, book: &OrderBook, state: &InteractiveState, private_connected: bool) {
+    let level = |side| match orderbook_best(book, side) {
+        Some((price, size)) => format!("{} x {}", price, size),
+        None => String::from("-"),
+    };
+    let position = match &state.position {
+        Some(p) if p.size > 0.0 => format!("{} {} @ {} (upnl {})", p.side, p.size, p.entry_price, p.unrealised_pnl),
+        _ => String::from("flat"),
+    };
+    let connection = |connected| if connected { "up" } else { "down" };
+
+    let _ = queue!(stdout, cursor::MoveTo(0, 0), terminal::Clear(terminal::ClearType::All));
+    let lines = [
+        format!("quicky {} | size {} | book {} | private {}", symbol, qty, connection(book.synced), connection(private_connected)),
+        format!("bid {} | ask {}", level(BookSide::Bid), level(BookSide::Ask)),
+        format!("position {}", position),
+        format!("> {}", state.status),
+        String::from("[b]uy [s]ell [c]ancel all [x] close [+/-] size [q]uit"),
+    ];
+    for line in lines.iter() {
+        let _ = write!(stdout, "{}\r\n", line);
+    }
+    let _ = stdout.flush();
+}
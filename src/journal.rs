@@ -0,0 +1,296 @@
+use crate::types::*;
+use crate::util::*;
+use crate::output::*;
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+
+/// Get default path of trade journal.
+/// It is `$XDG_DATA_HOME/quicky/journal.jsonl`, or
+/// `$HOME/.local/share/quicky/journal.jsonl` if `XDG_DATA_HOME` is not set.
+pub fn get_default_journal_path() -> Option<PathBuf> {
+    let data_home = match std::env::var("XDG_DATA_HOME") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var("HOME").ok()?).join(".local").join("share"),
+    };
+    Some(data_home.join("quicky").join("journal.jsonl"))
+}
+
+/// Network of `context` as recorded in trade journal.
+fn get_journal_network(context: &TradingContext) -> String {
+    if context.paper.is_some() {
+        String::from("paper")
+    } else if context.use_testnet {
+        String::from("testnet")
+    } else {
+        String::from("mainnet")
+    }
+}
+
+/// Append `entry` to trade journal of `context`. Failing to do so is reported
+/// but doesn't fail the order, which is already sent.
+///
+/// # Arguments
+/// * `context` - `TradingContext` for path of its journal
+/// * `entry` - `JournalEntry` to append
+pub fn append_journal_entry(context: &TradingContext, entry: &JournalEntry) {
+    let path = match &context.journal_path {
+        Some(path) => path,
+        None => return,
+    };
+    if let Err(e) = append_journal_line(path, entry) {
+        eprintln!("Warning: cannot record order in journal {}: {}", path.display(), e);
+    }
+}
+
+/// Append `entry` as a line, while holding exclusive lock of the file so lines
+/// of concurrent processes don't interleave.
+fn append_journal_line(path: &Path, entry: &JournalEntry) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut line = serde_json::to_vec(entry)?;
+    line.push(b'\n');
+
+    let mut file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+    file.lock()?;
+    file.write_all(&line)
+}
+
+/// Record order create request of `params` as sent, and its `result`.
+///
+/// # Arguments
+/// * `context` - `TradingContext` of the account
+/// * `params` - parameters of order create request
+/// * `reference_price` - price the order was computed from, if any
+/// * `latency` - time it took until the order was accepted, or failed
+/// * `response` - exchange's response as received, if any
+/// * `result` - the order as accepted, or error
+pub fn journal_order_request(context: &TradingContext, params: &BTreeMap<&str, serde_json::Value>, reference_price: Option<f64>, latency: std::time::Duration, response: Option<serde_json::Value>, result: &Result<BybitOrderResult, StatusCode>) {
+    let string_param = |name: &str| params.get(name).and_then(|v| v.as_str()).unwrap_or_default().to_owned();
+    let f64_param = |name: &str| params.get(name).and_then(|v| v.as_f64());
+
+    let entry = JournalOrder {
+        time: get_unix_timestamp_as_millis() as u64,
+        account: get_account_label(context),
+        network: get_journal_network(context),
+        order_link_id: string_param("order_link_id"),
+        order_id: result.as_ref().ok().map(|order| order.order_id.clone()),
+        symbol: string_param("symbol"),
+        side: string_param("side"),
+        order_type: string_param("order_type"),
+        time_in_force: string_param("time_in_force"),
+        qty: params.get("qty").and_then(|v| v.as_u64()).unwrap_or_default(),
+        price: f64_param("price"),
+        reference_price,
+        stop_loss: f64_param("stop_loss"),
+        take_profit: f64_param("take_profit"),
+        reduce_only: params.get("reduce_only").and_then(|v| v.as_bool()).unwrap_or(false),
+        latency_ms: latency.as_secs_f64() * 1000.0,
+        // exchange's message tells more than our status code
+        error: result.as_ref().err().map(|e| {
+            response.as_ref()
+                .filter(|r| r.get("ret_code").and_then(|c| c.as_i64()).is_some_and(|c| c != 0))
+                .and_then(|r| r.get("ret_msg")).and_then(|m| m.as_str())
+                .unwrap_or_else(|| get_status_code_message(e))
+                .to_owned()
+        }),
+        response,
+    };
+    append_journal_entry(context, &JournalEntry::Order(entry));
+}
+
+/// Record state, and fills of an order as observed in `confirmation`.
+///
+/// # Arguments
+/// * `context` - `TradingContext` of the account
+/// * `confirmation` - `OrderConfirmation` of the order
+pub fn journal_order_confirmation(context: &TradingContext, confirmation: &OrderConfirmation) {
+    let entry = JournalFill {
+        time: get_unix_timestamp_as_millis() as u64,
+        account: get_account_label(context),
+        network: get_journal_network(context),
+        order_id: confirmation.order_id.clone(),
+        symbol: confirmation.symbol.clone(),
+        side: confirmation.side.clone(),
+        state: confirmation.state,
+        cum_exec_qty: confirmation.cum_exec_qty,
+        cum_exec_value: confirmation.cum_exec_value,
+        cum_exec_fee: confirmation.cum_exec_fee,
+        leaves_qty: confirmation.leaves_qty,
+    };
+    append_journal_entry(context, &JournalEntry::Fill(entry));
+}
+
+/// Read all entries of trade journal at `path`. No journal yet means no entries.
+/// Unreadable lines e.g. a partially written one are skipped.
+///
+/// # Arguments
+/// * `path` - path of trade journal
+pub fn read_journal(path: &Path) -> Result<Vec<JournalEntry>, StatusCode> {
+    let file = match std::fs::File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => {
+            eprintln!("Error: cannot open {}: {}", path.display(), e);
+            return Err(StatusCode::ErrorJournal);
+        }
+    };
+    let mut entries = Vec::new();
+    for (i, line) in std::io::BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|_| StatusCode::ErrorJournal)?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<JournalEntry>(&line) {
+            Ok(entry) => entries.push(entry),
+            Err(e) => eprintln!("Warning: skipped line {} of {}: {}", i + 1, path.display(), e),
+        }
+    }
+    Ok(entries)
+}
+
+/// Orders of trade journal, each along with its latest observed state.
+///
+/// # Arguments
+/// * `entries` - entries of trade journal in order they are recorded
+pub fn get_journal_orders(entries: &[JournalEntry]) -> Vec<(&JournalOrder, Option<&JournalFill>)> {
+    let mut fills: HashMap<(&str, &str), &JournalFill> = HashMap::new();
+    for entry in entries.iter() {
+        if let JournalEntry::Fill(fill) = entry {
+            fills.insert((&fill.account, &fill.order_id), fill);
+        }
+    }
+    entries.iter()
+        .filter_map(|entry| match entry {
+            JournalEntry::Order(order) => Some(order),
+            _ => None,
+        })
+        .map(|order| {
+            let fill = order.order_id.as_ref().and_then(|order_id| fills.get(&(order.account.as_str(), order_id.as_str())).copied());
+            (order, fill)
+        })
+        .collect()
+}
+
+/// Query orders of the account of `context` recorded in trade journal whose
+/// final state is not yet known, and record their latest state if it changed.
+/// Return number of orders whose state is recorded.
+///
+/// # Arguments
+/// * `context` - `TradingContext` of the account
+pub fn sync_journal(context: &TradingContext) -> Result<usize, StatusCode> {
+    let path = context.journal_path.as_ref().ok_or(StatusCode::ErrorJournal)?;
+    let entries = read_journal(path)?;
+    let account = get_account_label(context);
+    let network = get_journal_network(context);
+
+    let mut synced = 0;
+    for (order, fill) in get_journal_orders(&entries) {
+        if order.account != account || order.network != network || fill.is_some_and(|f| is_order_state_final(f.state)) {
+            continue;
+        }
+        let order_id = match &order.order_id {
+            Some(order_id) => order_id,
+            None => continue,
+        };
+        let queried = api_query_order(context, &order.symbol, order_id)?;
+        let state = to_order_state(&queried.order_status, &queried.reject_reason, &queried.cancel_type);
+        if fill.is_some_and(|f| f.state == state && f.cum_exec_qty == queried.cum_exec_qty) {
+            continue;
+        }
+        journal_order_confirmation(context, &OrderConfirmation {
+            order_id: order_id.clone(),
+            symbol: queried.symbol,
+            side: queried.side,
+            price: queried.price,
+            qty: queried.qty,
+            state,
+            cum_exec_qty: queried.cum_exec_qty,
+            cum_exec_value: queried.cum_exec_value,
+            cum_exec_fee: queried.cum_exec_fee,
+            leaves_qty: queried.leaves_qty,
+            position: None,
+        });
+        synced += 1;
+    }
+    Ok(synced)
+}
+
+/// Filter on orders of trade journal.
+pub struct JournalFilter {
+    pub symbol: Option<String>,
+    /// Unix timestamp in milliseconds, inclusive
+    pub since: Option<u64>,
+    /// Unix timestamp in milliseconds, exclusive
+    pub until: Option<u64>,
+}
+
+/// Whether `order` passes `filter`.
+pub fn is_journal_order_matched(order: &JournalOrder, filter: &JournalFilter) -> bool {
+    filter.symbol.as_ref().is_none_or(|symbol| order.symbol.eq_ignore_ascii_case(symbol))
+        && filter.since.is_none_or(|since| order.time >= since)
+        && filter.until.is_none_or(|until| order.time < until)
+}
+
+/// Format `order` along with its latest state `fill` into a line.
+///
+/// # Arguments
+/// * `order` - order as recorded
+/// * `fill` - latest observed state of the order, if any
+pub fn format_journal_order(order: &JournalOrder, fill: Option<&JournalFill>) -> String {
+    let price = match order.price {
+        Some(price) => format!(" @ {}", price),
+        None => String::from(" @ market"),
+    };
+    let reference = match order.reference_price {
+        Some(reference_price) => format!(" (ref {})", reference_price),
+        None => String::new(),
+    };
+    let outcome = match (&order.error, fill) {
+        (Some(error), _) => format!("failed: {}", error),
+        (None, None) => String::from("accepted"),
+        (None, Some(fill)) => {
+            let avg = if fill.cum_exec_qty > 0.0 && fill.cum_exec_value > 0.0 {
+                format!(", filled {} @ {:.6} (fee {:.8} {})", fill.cum_exec_qty, fill.cum_exec_qty / fill.cum_exec_value, fill.cum_exec_fee, get_settle_coin(&order.symbol))
+            } else {
+                String::new()
+            };
+            format!("{}{}", get_order_state_label(fill.state), avg)
+        }
+    };
+    format!("{} {} {} {} {} {}{}{} [{:.0} ms] {} {}",
+        format_unix_millis(order.time), order.network, order.account, order.side, order.qty, order.symbol, price, reference,
+        order.latency_ms, order.order_link_id, outcome)
+}
+
+/// Columns of orders of trade journal in table output, in order of `get_journal_order_row`.
+pub const JOURNAL_ORDER_COLUMNS: [&str; 14] = ["time", "network", "account", "side", "qty", "symbol", "price", "ref_price",
+    "latency_ms", "order_link_id", "status", "filled", "avg_price", "fee"];
+
+/// Cells of `order` along with its latest state `fill` in order of `JOURNAL_ORDER_COLUMNS`.
+pub fn get_journal_order_row(order: &JournalOrder, fill: Option<&JournalFill>) -> Vec<String> {
+    let status = match (&order.error, fill) {
+        (Some(_), _) => String::from("failed"),
+        (None, None) => String::from("accepted"),
+        (None, Some(fill)) => get_order_state_name(fill.state),
+    };
+    let avg_price = fill.filter(|f| f.cum_exec_qty > 0.0 && f.cum_exec_value > 0.0).map(|f| format!("{:.6}", f.cum_exec_qty / f.cum_exec_value));
+    vec![
+        format_unix_millis(order.time),
+        order.network.clone(),
+        order.account.clone(),
+        order.side.clone(),
+        order.qty.to_string(),
+        order.symbol.clone(),
+        order.price.map(|p| p.to_string()).unwrap_or_else(|| String::from("market")),
+        to_table_cell(order.reference_price),
+        format!("{:.0}", order.latency_ms),
+        order.order_link_id.clone(),
+        status,
+        to_table_cell(fill.map(|f| f.cum_exec_qty)),
+        to_table_cell(avg_price),
+        to_table_cell(fill.map(|f| format!("{:.8}", f.cum_exec_fee))),
+    ]
+}
@@ -0,0 +1,195 @@
+use crate::types::*;
+use crate::defines::*;
+use crate::util::*;
+use crate::output::*;
+
+/// Compute orders of a ladder of `qty` split across price levels of `options`
+/// from the reference price of `symbol`, without sending them. The nearest
+/// level comes first. All levels have the same stop-loss, and take-profit
+/// computed from the reference price.
+///
+/// # Arguments
+/// * `context` - `TradingContext` for context information used in trading
+/// * `symbol` - symbol to create orders for
+/// * `qty` - total quantity, positive for buy, negative for sell
+/// * `options` - `LadderOptions`
+pub fn api_plan_ladder_orders(context: &TradingContext, symbol: &str, qty: i64, options: &LadderOptions) -> Result<Vec<OrderPlan>, StatusCode> {
+    let tick_step = *context.tick_steps.get(symbol).ok_or(StatusCode::InternalErrorNoTickStepAvailable)?;
+    let lot_size = context.lot_sizes.get(symbol).copied().unwrap_or(1);
+    let weights = get_ladder_weights(options)?;
+    let offsets = get_ladder_tick_offsets(weights.len() as u32, options.from_ticks, options.to_ticks)?;
+    let qtys = split_ladder_qty(qty.unsigned_abs(), &weights, lot_size)?;
+    if !qty.unsigned_abs().is_multiple_of(lot_size) {
+        print_info(&format!("quantity {} is snapped down to {} in lots of {}", qty.unsigned_abs(), qtys.iter().sum::<u64>(), lot_size));
+    }
+
+    let is_buy_side = qty > 0;
+    let reference_price = api_get_reference_price(context, symbol, is_buy_side)?;
+    let reference_ticks = price_to_ticks(reference_price, tick_step);
+
+    offsets.iter().zip(qtys.iter())
+        .map(|(offset, level_qty)| {
+            let ticks = if is_buy_side { reference_ticks - *offset as i64 } else { reference_ticks + *offset as i64 };
+            if ticks <= 0 {
+                eprintln!("Error: level {} ticks away from reference price {} is not a valid price", offset, reference_price);
+                return Err(StatusCode::ErrorIncorrectParameterValue);
+            }
+            let signed_qty = if is_buy_side { *level_qty as i64 } else { -(*level_qty as i64) };
+            plan_limit_order(context, symbol, signed_qty, ticks_to_price(ticks, tick_step), reference_price)
+        })
+        .collect()
+}
+
+/// Weight of each level of a ladder from the nearest one. Custom weights are
+/// used if any is specified regardless of weighting.
+///
+/// # Arguments
+/// * `options` - `LadderOptions`
+pub fn get_ladder_weights(options: &LadderOptions) -> Result<Vec<f64>, StatusCode> {
+    let invalid = |message: String| {
+        eprintln!("Error: {}", message);
+        Err(StatusCode::ErrorIncorrectParameterValue)
+    };
+
+    if !options.weights.is_empty() || matches!(options.weighting, LadderWeighting::Custom) {
+        if options.weights.is_empty() {
+            return invalid(String::from("custom weighting needs --weights e.g. 3,2,1"));
+        }
+        if options.levels.is_some_and(|levels| levels as usize != options.weights.len()) {
+            return invalid(format!("{} weights are specified for {} levels", options.weights.len(), options.levels.unwrap_or_default()));
+        }
+        if options.weights.iter().any(|w| !w.is_finite() || *w < 0.0) || options.weights.iter().sum::<f64>() <= 0.0 {
+            return invalid(String::from("weights must not be negative, and at least one must be positive"));
+        }
+        return Ok(options.weights.clone());
+    }
+
+    let levels = options.levels.unwrap_or(DEFAULT_LADDER_LEVELS);
+    if levels == 0 {
+        return invalid(String::from("ladder needs at least 1 level"));
+    }
+    match options.weighting {
+        LadderWeighting::Geometric if !options.ratio.is_finite() || options.ratio <= 0.0 => invalid(format!("ratio {} must be positive", options.ratio)),
+        LadderWeighting::Geometric => Ok((0..levels).map(|i| options.ratio.powi(i as i32)).collect()),
+        _ => Ok((1..=levels).map(|i| i as f64).collect()),
+    }
+}
+
+/// Ticks away from reference price of each of `levels` spread evenly from
+/// `from_ticks` to `to_ticks`, each at a distinct tick.
+///
+/// # Arguments
+/// * `levels` - number of levels
+/// * `from_ticks` - ticks away of the nearest level
+/// * `to_ticks` - ticks away of the farthest level
+pub fn get_ladder_tick_offsets(levels: u32, from_ticks: u32, to_ticks: u32) -> Result<Vec<u32>, StatusCode> {
+    if to_ticks < from_ticks || (levels > 1 && to_ticks - from_ticks + 1 < levels) {
+        eprintln!("Error: {} levels don't fit at distinct ticks from {} to {} ticks away", levels, from_ticks, to_ticks);
+        return Err(StatusCode::ErrorIncorrectParameterValue);
+    }
+    if levels == 1 {
+        return Ok(vec![from_ticks]);
+    }
+    let span = (to_ticks - from_ticks) as f64;
+    Ok((0..levels).map(|i| from_ticks + (span * i as f64 / (levels - 1) as f64).round() as u32).collect())
+}
+
+/// Split `qty` across levels in proportion to `weights`, each snapped to
+/// `lot_size`. Lots left over by rounding down go to levels with the largest
+/// fraction cut off, so levels add up to `qty` snapped down to `lot_size`.
+/// Fail if any level would be left empty.
+///
+/// # Arguments
+/// * `qty` - total quantity
+/// * `weights` - weight of each level
+/// * `lot_size` - quantity step of the symbol
+pub fn split_ladder_qty(qty: u64, weights: &[f64], lot_size: u64) -> Result<Vec<u64>, StatusCode> {
+    let lot_size = lot_size.max(1);
+    let lots = qty / lot_size;
+    let total_weight: f64 = weights.iter().sum();
+
+    let shares: Vec<f64> = weights.iter().map(|w| lots as f64 * w / total_weight).collect();
+    let mut split: Vec<u64> = shares.iter().map(|s| s.floor() as u64).collect();
+    let mut by_fraction: Vec<usize> = (0..shares.len()).collect();
+    by_fraction.sort_by(|a, b| (shares[*b] - shares[*b].floor()).total_cmp(&(shares[*a] - shares[*a].floor())));
+    let left = lots.saturating_sub(split.iter().sum());
+    for i in by_fraction.into_iter().take(left as usize) {
+        split[i] += 1;
+    }
+
+    if split.contains(&0) {
+        eprintln!("Error: quantity {} is too small to split into {} levels of lot size {}", qty, weights.len(), lot_size);
+        return Err(StatusCode::ErrorIncorrectParameterValue);
+    }
+    Ok(split.into_iter().map(|l| l * lot_size).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(levels: Option<u32>, weighting: LadderWeighting, weights: Vec<f64>) -> LadderOptions {
+        LadderOptions { levels, from_ticks: 0, to_ticks: 10, weighting, ratio: DEFAULT_LADDER_GEOMETRIC_RATIO, weights }
+    }
+
+    #[test]
+    fn split_is_proportional_to_weights() {
+        assert_eq!(split_ladder_qty(60, &[1.0, 2.0, 3.0], 1).ok(), Some(vec![10, 20, 30]));
+        assert_eq!(split_ladder_qty(600, &[1.0, 2.0, 3.0], 100).ok(), Some(vec![100, 200, 300]));
+    }
+
+    #[test]
+    fn split_remainder_goes_to_largest_fractions() {
+        // 16.67, 33.33, 50
+        assert_eq!(split_ladder_qty(100, &[1.0, 2.0, 3.0], 1).ok(), Some(vec![17, 33, 50]));
+        // ties are broken by nearest level first
+        assert_eq!(split_ladder_qty(10, &[1.0, 1.0, 1.0], 1).ok(), Some(vec![4, 3, 3]));
+        assert_eq!(split_ladder_qty(11, &[1.0, 1.0, 1.0], 1).ok(), Some(vec![4, 4, 3]));
+    }
+
+    #[test]
+    fn split_is_snapped_down_to_lot_size() {
+        let split = split_ladder_qty(105, &[1.0, 2.0, 3.0], 10).ok();
+        assert_eq!(split, Some(vec![20, 30, 50]));
+        // lot size of 0 is taken as 1
+        assert_eq!(split_ladder_qty(3, &[1.0, 1.0, 1.0], 0).ok(), Some(vec![1, 1, 1]));
+    }
+
+    #[test]
+    fn split_fails_if_any_level_is_left_empty() {
+        assert!(split_ladder_qty(0, &[1.0, 1.0], 1).is_err());
+        assert!(split_ladder_qty(2, &[1.0, 1.0, 1.0], 1).is_err());
+        assert!(split_ladder_qty(25, &[1.0, 1.0, 1.0], 10).is_err());
+        assert!(split_ladder_qty(10, &[1.0, 0.0], 1).is_err());
+    }
+
+    #[test]
+    fn weights_follow_weighting() {
+        assert_eq!(get_ladder_weights(&options(Some(3), LadderWeighting::Linear, vec![])).ok(), Some(vec![1.0, 2.0, 3.0]));
+        assert_eq!(get_ladder_weights(&options(None, LadderWeighting::Linear, vec![])).ok().map(|w| w.len()), Some(DEFAULT_LADDER_LEVELS as usize));
+        let geometric = LadderOptions { ratio: 2.0, ..options(Some(3), LadderWeighting::Geometric, vec![]) };
+        assert_eq!(get_ladder_weights(&geometric).ok(), Some(vec![1.0, 2.0, 4.0]));
+        // custom weights win over weighting
+        assert_eq!(get_ladder_weights(&options(None, LadderWeighting::Linear, vec![3.0, 0.0, 1.0])).ok(), Some(vec![3.0, 0.0, 1.0]));
+    }
+
+    #[test]
+    fn invalid_weights_are_rejected() {
+        assert!(get_ladder_weights(&options(Some(0), LadderWeighting::Linear, vec![])).is_err());
+        assert!(get_ladder_weights(&options(Some(3), LadderWeighting::Custom, vec![])).is_err());
+        assert!(get_ladder_weights(&options(Some(2), LadderWeighting::Custom, vec![1.0, 2.0, 3.0])).is_err());
+        assert!(get_ladder_weights(&options(None, LadderWeighting::Custom, vec![1.0, -1.0])).is_err());
+        assert!(get_ladder_weights(&options(None, LadderWeighting::Custom, vec![0.0, 0.0])).is_err());
+        let geometric = LadderOptions { ratio: 0.0, ..options(Some(3), LadderWeighting::Geometric, vec![]) };
+        assert!(get_ladder_weights(&geometric).is_err());
+    }
+
+    #[test]
+    fn tick_offsets_are_spread_evenly_at_distinct_ticks() {
+        assert_eq!(get_ladder_tick_offsets(3, 0, 10).ok(), Some(vec![0, 5, 10]));
+        assert_eq!(get_ladder_tick_offsets(4, 0, 3).ok(), Some(vec![0, 1, 2, 3]));
+        assert_eq!(get_ladder_tick_offsets(1, 4, 9).ok(), Some(vec![4]));
+        assert!(get_ladder_tick_offsets(5, 0, 3).is_err());
+        assert!(get_ladder_tick_offsets(2, 5, 3).is_err());
+    }
+}
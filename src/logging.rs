@@ -0,0 +1,102 @@
+use crate::types::*;
+use crate::defines::*;
+
+use tracing_subscriber::EnvFilter;
+
+/// Set up leveled logs of the process to stderr, or `log_file`.
+/// Filter is taken from `RUST_LOG` e.g. `quicky=debug`, or logs are off if it is
+/// not set, then raised for quicky by `-v` to debug, or `-vv` to trace.
+///
+/// # Arguments
+/// * `verbosity` - number of times `-v` is specified
+/// * `log_file` - path of file to append logs to instead of stderr
+/// * `format` - `LogFormat` of each line
+pub fn init_logging(verbosity: u64, log_file: Option<&str>, format: LogFormat) -> Result<(), StatusCode> {
+    let mut filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("off"));
+    let level = match verbosity {
+        0 => None,
+        1 => Some("quicky=debug"),
+        _ => Some("quicky=trace"),
+    };
+    if let Some(directive) = level.and_then(|l| l.parse().ok()) {
+        filter = filter.add_directive(directive);
+    }
+
+    let builder = tracing_subscriber::fmt().with_env_filter(filter);
+    let initialized = match log_file {
+        Some(path) => {
+            let file = open_log_file(path)?;
+            let builder = builder.with_ansi(false).with_writer(std::sync::Mutex::new(file));
+            match format {
+                LogFormat::Text => builder.try_init(),
+                LogFormat::Json => builder.json().try_init(),
+            }
+        },
+        None => {
+            let builder = builder.with_ansi(std::io::IsTerminal::is_terminal(&std::io::stderr())).with_writer(std::io::stderr);
+            match format {
+                LogFormat::Text => builder.try_init(),
+                LogFormat::Json => builder.json().try_init(),
+            }
+        },
+    };
+    initialized.map_err(|_| StatusCode::InternalErrorGeneric)
+}
+
+/// Open `path` to append logs to, creating its directory if needed.
+fn open_log_file(path: &str) -> Result<std::fs::File, StatusCode> {
+    let path = std::path::Path::new(path);
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        let _ = std::fs::create_dir_all(dir);
+    }
+    std::fs::OpenOptions::new().create(true).append(true).open(path).map_err(|e| {
+        eprintln!("Error: cannot open log file {}: {}", path.display(), e);
+        StatusCode::InternalErrorGeneric
+    })
+}
+
+/// Whether or not value of field `name` must not be logged.
+fn is_redacted_field(name: &str) -> bool {
+    REDACTED_LOG_FIELDS.iter().any(|f| f.eq_ignore_ascii_case(name))
+}
+
+/// Copy of `url` with values of `REDACTED_LOG_FIELDS` in its query string
+/// replaced by `<redacted>`.
+///
+/// # Arguments
+/// * `url` - URL as sent
+pub fn redact_url(url: &str) -> String {
+    let (base, query) = match url.split_once('?') {
+        Some(parts) => parts,
+        None => return url.to_owned(),
+    };
+    let query: Vec<String> = query.split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some((name, _)) if is_redacted_field(name) => format!("{}=<redacted>", name),
+            _ => pair.to_owned(),
+        })
+        .collect();
+    format!("{}?{}", base, query.join("&"))
+}
+
+/// Copy of json `value` with values of `REDACTED_LOG_FIELDS` at any depth
+/// replaced by `<redacted>`. So are arguments of websocket `auth` operation,
+/// which are api key, and signature.
+///
+/// # Arguments
+/// * `value` - json as sent, or received
+pub fn redact_json(value: &serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(map) => {
+            let is_auth = map.get("op").and_then(|op| op.as_str()) == Some("auth");
+            map.iter()
+                .map(|(name, v)| {
+                    let v = if is_redacted_field(name) || (is_auth && name == "args") { serde_json::json!("<redacted>") } else { redact_json(v) };
+                    (name.clone(), v)
+                })
+                .collect()
+        },
+        serde_json::Value::Array(values) => values.iter().map(redact_json).collect(),
+        v => v.clone(),
+    }
+}
@@ -2,31 +2,900 @@ mod types;
 mod util;
 mod impls;
 mod defines;
+mod ws;
+mod orderbook;
+mod interactive;
+mod daemon;
+mod webhook;
+mod config;
+mod credentials;
+mod paper;
+mod dedupe;
+mod ratelimit;
+mod journal;
+mod report;
+mod export;
+mod output;
+mod logging;
+mod ladder;
+mod batch;
+mod ttl;
+mod bracket;
 
 use clap::Parser;
 use types::*;
 use util::*;
+use ws::*;
+use orderbook::*;
+use interactive::*;
+use daemon::*;
+use webhook::*;
+use config::*;
+use credentials::*;
+use paper::*;
+use journal::*;
+use report::*;
+use export::*;
+use output::*;
+use logging::*;
+use ladder::*;
+use batch::*;
+use ttl::*;
+use bracket::*;
 
 fn main() {    
     // parse arguments via clap
     let cmd_args = CommandlineArgs::parse();
+    set_output_options(cmd_args.output, cmd_args.quiet);
+    if let Err(e) = init_logging(cmd_args.verbose, cmd_args.log_file.as_deref(), cmd_args.log_format) {
+        print_error_if_necessary(e);
+        std::process::exit(1);
+    }
+
+    match &cmd_args.command {
+        Some(Command::Interactive { symbol, qty, qty_step }) => {
+            let (trading_context, profile) = make_trading_context(&cmd_args);
+            let symbol = require_arg(require_ok(resolve_symbol(symbol.as_deref(), &profile)), "--symbol");
+            let qty = qty.or(profile.qty.map(|q| q.unsigned_abs())).unwrap_or(1);
+            if let Err(e) = run_interactive(&trading_context, &symbol, qty, *qty_step) {
+                print_error_if_necessary(e);
+                std::process::exit(1);
+            }
+        },
+        Some(Command::Ladder { symbol, qty, levels, from_ticks, to_ticks, weighting, ratio, weights, dry_run }) => {
+            let (selection, profile) = make_single_account_selection(&cmd_args);
+            let symbol = require_arg(require_ok(resolve_symbol(symbol.as_deref(), &profile)), "--symbol");
+            let qty = require_arg(qty.or(profile.qty), "--qty");
+            let options = LadderOptions {
+                levels: *levels,
+                from_ticks: *from_ticks,
+                to_ticks: *to_ticks,
+                weighting: *weighting,
+                ratio: *ratio,
+                weights: weights.clone(),
+            };
+            if let Err(e) = run_ladder_command(&selection, &cmd_args, &symbol, qty, &options, *dry_run) {
+                print_error_if_necessary(e);
+                std::process::exit(1);
+            }
+        },
+        Some(Command::Bracket { symbol, qty, targets, entry_ttl, manage_timeout, dry_run }) => {
+            let (selection, profile) = make_single_account_selection(&cmd_args);
+            let symbol = require_arg(require_ok(resolve_symbol(symbol.as_deref(), &profile)), "--symbol");
+            let qty = require_arg(qty.or(profile.qty), "--qty");
+            let options = BracketOptions {
+                targets: targets.clone(),
+                entry_ttl: *entry_ttl,
+                manage_timeout: *manage_timeout,
+            };
+            if let Err(e) = run_bracket_command(&selection, &cmd_args, &symbol, qty, &options, *dry_run) {
+                print_error_if_necessary(e);
+                std::process::exit(1);
+            }
+        },
+        Some(Command::Batch { file, format, dry_run }) => {
+            let (selection, _) = make_single_account_selection(&cmd_args);
+            if let Err(e) = run_batch_command(&selection, &cmd_args, file.as_deref(), *format, *dry_run) {
+                print_error_if_necessary(e);
+                std::process::exit(1);
+            }
+        },
+        Some(Command::Daemon { socket, symbols }) => {
+            let socket_path = socket.clone().unwrap_or_else(get_default_socket_path);
+            if let Err(e) = run_daemon(&make_trading_context(&cmd_args).0, &socket_path, symbols) {
+                print_error_if_necessary(e);
+                std::process::exit(1);
+            }
+        },
+        Some(Command::Send { socket, words }) => {
+            let socket_path = socket.clone().unwrap_or_else(get_default_socket_path);
+            match send_daemon_command(&socket_path, &words.join(" ")) {
+                Ok(true) => {},
+                Ok(false) => std::process::exit(1),
+                Err(e) => {
+                    print_error_if_necessary(e);
+                    std::process::exit(1);
+                }
+            }
+        },
+        Some(Command::Credentials { action }) => {
+            if let Err(e) = run_credentials_command(action) {
+                print_error_if_necessary(e);
+                std::process::exit(1);
+            }
+        },
+        Some(Command::Journal { symbol, since, until, limit, sync }) => {
+            let filter = JournalFilter {
+                symbol: symbol.clone(),
+                since: since.as_deref().map(require_time_arg),
+                until: until.as_deref().map(require_time_arg),
+            };
+            let trading_context = make_trading_context(&cmd_args).0;
+            if let Err(e) = run_journal_command(&trading_context, &filter, *limit, *sync) {
+                print_error_if_necessary(e);
+                std::process::exit(1);
+            }
+        },
+        Some(Command::Report { symbol, since, until }) => {
+            let filter = JournalFilter {
+                symbol: symbol.clone(),
+                since: since.as_deref().map(require_time_arg),
+                until: until.as_deref().map(require_time_arg),
+            };
+            if let Err(e) = run_report_command(&make_trading_context(&cmd_args).0, &filter) {
+                print_error_if_necessary(e);
+                std::process::exit(1);
+            }
+        },
+        Some(Command::Export { kind, symbol, since, until, format, out }) => {
+            let (trading_context, profile) = make_trading_context(&cmd_args);
+            let symbol = require_arg(require_ok(resolve_symbol(symbol.as_deref(), &profile)), "--symbol");
+            let (since, until) = (since.as_deref().map(require_time_arg), until.as_deref().map(require_time_arg));
+            if let Err(e) = run_export_command(&trading_context, *kind, &symbol, since, until, *format, out.as_deref()) {
+                print_error_if_necessary(e);
+                std::process::exit(1);
+            }
+        },
+        Some(Command::Paper { action }) => {
+            let mut trading_context = make_trading_context(&cmd_args).0;
+            trading_context.paper = Some(resolve_paper_options(&cmd_args));
+            if let Err(e) = run_paper_command(&trading_context, action) {
+                print_error_if_necessary(e);
+                std::process::exit(1);
+            }
+        },
+        Some(Command::Webhook { listen, secret_env, max_age }) => {
+            let secret = std::env::var(secret_env).unwrap_or_default();
+            if secret.is_empty() {
+                eprintln!("Error: required env variable {} to be set", secret_env);
+                std::process::exit(1);
+            }
+            if let Err(e) = run_webhook(&make_trading_context(&cmd_args).0, listen, &secret, *max_age) {
+                print_error_if_necessary(e);
+                std::process::exit(1);
+            }
+        },
+        None => {
+            let (selection, profile) = make_account_selection(&cmd_args);
+            let symbol = require_arg(require_ok(resolve_symbol(cmd_args.symbol.as_deref(), &profile)), "--symbol");
+            let qty = require_arg(require_ok(resolve_qty(cmd_args.qty, &profile)), "--qty");
+            if cmd_args.ttl.is_some_and(|ttl| ttl.is_nan() || ttl <= 0.0) {
+                eprintln!("Error: --ttl must be positive seconds");
+                std::process::exit(1);
+            }
+            if cmd_args.dry_run {
+                run_dry_run_order(&selection, &cmd_args, &symbol, qty);
+                return;
+            }
+            require_ok(confirm_mainnet_order(&selection, &cmd_args, &symbol, qty));
+            match &selection.group {
+                Some(group) => run_group_quick_limit_order(group, &selection.accounts, &cmd_args, &symbol, qty),
+                None => run_quick_limit_order(&selection.accounts[0].context, &cmd_args, &symbol, qty),
+            }
+        }
+    }
+}
+
+/// Construct trading context of a single account from command line's arguments,
+/// environment variables, and selected profile of config file. Also return the
+/// profile for its defaults. Exit if config file is invalid, or a group of
+/// accounts is selected.
+/// Sending a command to daemon, or managing credentials doesn't need one.
+fn make_trading_context(cmd_args: &CommandlineArgs) -> (TradingContext, Profile) {
+    let (mut selection, profile) = make_single_account_selection(cmd_args);
+    (selection.accounts.remove(0).context, profile)
+}
+
+/// Construct selection of a single account for commands placing orders on
+/// it. See `make_trading_context`.
+fn make_single_account_selection(cmd_args: &CommandlineArgs) -> (AccountSelection, Profile) {
+    let (selection, profile) = make_account_selection(cmd_args);
+    if let Some(group) = &selection.group {
+        eprintln!("Error: group '{}' is only supported when placing an order, select a single account", group);
+        std::process::exit(1);
+    }
+    (selection, profile)
+}
+
+/// Construct trading contexts of selected accounts. See `make_trading_context`.
+/// Credentials from the encrypted store are unlocked here with a single prompt,
+/// so passphrase is prompted before any terminal mode change, background thread,
+/// or timing.
+fn make_account_selection(cmd_args: &CommandlineArgs) -> (AccountSelection, Profile) {
+    let resolved = load_config(cmd_args.config.as_deref()).and_then(|config| {
+        let profile = select_profile(&config, cmd_args.profile.as_deref())?;
+        let selection = resolve_account_selection(cmd_args, &config, &profile)?;
+        // paper exchange, and reading journal need no credentials
+        if !cmd_args.paper && !matches!(cmd_args.command, Some(Command::Paper { .. }) | Some(Command::Journal { sync: false, .. }) | Some(Command::Report { .. })) {
+            unlock_credential_stores(selection.accounts.iter().map(|a| &a.context))?;
+        }
+        Ok((selection, profile))
+    });
+    match resolved {
+        Ok(resolved) => resolved,
+        Err(e) => {
+            print_error_if_necessary(e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Return value of `result`, or exit with its error.
+fn require_ok<T>(result: Result<T, StatusCode>) -> T {
+    match result {
+        Ok(value) => value,
+        Err(e) => {
+            print_error_if_necessary(e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Return `value`, or exit if it is not specified by any means.
+fn require_arg<T>(value: Option<T>, name: &str) -> T {
+    match value {
+        Some(value) => value,
+        None => {
+            eprintln!("Error: {} is required, specify it at command line or in profile", name);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Return time parsed by `parse_time_arg`, or exit if it is invalid.
+fn require_time_arg(s: &str) -> u64 {
+    match parse_time_arg(s) {
+        Ok(time) => time,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
 
-    // construct trading context with some which specified via command line's arguments,
-    // and the less with default values.
-    let trading_context = TradingContext {
-        use_testnet: cmd_args.testnet,
-        stop_loss_pcnt: cmd_args.sl_pcnt,
-        ..Default::default()
+/// Place a single quick limit order then report its confirmation. Exit with
+/// failure if it is not placed.
+fn run_quick_limit_order(trading_context: &TradingContext, cmd_args: &CommandlineArgs, symbol: &str, qty: i64) {
+    let (plan, result) = place_quick_limit_order(trading_context, cmd_args, symbol, qty, is_plain_output());
+    let report = match (&result, &plan) {
+        (Ok(outcome), _) => to_order_report(None, outcome),
+        (Err(e), Some(plan)) => to_unplaced_order_plan_report(None, plan, e),
+        (Err(e), None) => to_unplaced_order_report(None, symbol, qty, Some(e)),
     };
+    // plain output is printed below along with errors
+    if let Err(e) = print_order_command_output(&report, &ORDER_REPORT_COLUMNS, || get_order_report_rows(&report), || {}) {
+        print_error_if_necessary(e);
+    }
+
+    match result {
+        Ok(outcome) => {
+            let expiry = format_order_expiry(&outcome, cmd_args.ttl.unwrap_or_default());
+            let QuickOrderOutcome { order, confirmation, .. } = outcome;
+            match confirmation {
+                None if is_plain_output() => println!("accepted {} {} {} @ {}", order.side, order.qty, order.symbol, order.price),
+                Some(Ok(confirmation)) if is_plain_output() => {
+                    print_order_confirmation(&confirmation);
+                    for line in expiry {
+                        println!("{}", line);
+                    }
+                },
+                Some(Err(e)) => {
+                    if is_plain_output() {
+                        println!("accepted {} {} {} @ {} (final state unknown)", order.side, order.qty, order.symbol, order.price);
+                    }
+                    print_error_if_necessary(e);
+                },
+                _ => {},
+            }
+        },
+        Err(e) => {
+            print_error_if_necessary(e);
+            std::process::exit(1);
+        }
+    }
+}
 
+/// Place the same quick limit order on all `accounts` of `group` concurrently,
+/// Exit with failure if placing it fails on any account.
+/// Exit with failure if it is not placed on any of them.
+fn run_group_quick_limit_order(group: &str, accounts: &[AccountContext], cmd_args: &CommandlineArgs, symbol: &str, qty: i64) {
+    let results: Vec<_> = std::thread::scope(|scope| {
+        let handles: Vec<_> = accounts.iter()
+            .map(|account| {
+                let account_qty = (qty as f64 * account.multiplier).round() as i64;
+                let handle = (account_qty != 0).then(|| scope.spawn(move || place_quick_limit_order(&account.context, cmd_args, symbol, account_qty, false)));
+                (account, account_qty, handle)
+            })
+            .collect();
+        handles.into_iter()
+            .map(|(account, account_qty, handle)| (account, account_qty, handle.map(|h| h.join().unwrap_or((None, Err(StatusCode::InternalErrorGeneric))))))
+            .collect()
+    });
+
+    let (mut placed, mut placed_qty, mut filled_qty) = (0, 0, 0.0);
+    let mut reports = Vec::new();
+    for (account, account_qty, result) in results.iter() {
+        let name = account.name.as_deref().unwrap_or("default");
+        let report = match result {
+            None => to_unplaced_order_report(Some(name), symbol, *account_qty, None),
+            Some((_, Ok(outcome))) => to_order_report(Some(name), outcome),
+            Some((Some(plan), Err(e))) => to_unplaced_order_plan_report(Some(name), plan, e),
+            Some((None, Err(e))) => to_unplaced_order_report(Some(name), symbol, *account_qty, Some(e)),
+        };
+        if report.order_id.is_some() {
+            placed += 1;
+            placed_qty += account_qty;
+            filled_qty += report.filled_qty + report.fallback.as_ref().map_or(0.0, |f| f.filled_qty);
+        }
+        if !is_plain_output() {
+            reports.push(report);
+            continue;
+        }
+        match result {
+            None => println!("[{}] skipped, quantity {} x {} rounds to 0", name, qty, account.multiplier),
+            Some((_, Ok(outcome))) => {
+                let order = &outcome.order;
+                match &outcome.confirmation {
+                    None => println!("[{}] accepted {} {} {} @ {} (elapsed = {:.2} secs)", name, order.side, order.qty, order.symbol, order.price, outcome.elapsed),
+                    Some(Ok(confirmation)) => {
+                        for line in format_order_confirmation(confirmation).into_iter().chain(format_order_expiry(outcome, cmd_args.ttl.unwrap_or_default())) {
+                            println!("[{}] {}", name, line);
+                        }
+                    },
+                    Some(Err(e)) => println!("[{}] accepted {} {} {} @ {} (final state unknown: {})", name, order.side, order.qty, order.symbol, order.price, get_status_code_message(e)),
+                }
+            },
+            Some((_, Err(e))) => println!("[{}] failed to place {} {}: {}", name, account_qty, symbol, get_status_code_message(e)),
+        }
+    }
+
+    let report = GroupOrderReport {
+        group: group.to_owned(),
+        placed,
+        accounts: results.len(),
+        qty: placed_qty.unsigned_abs(),
+        filled_qty: (!cmd_args.no_confirm).then_some(filled_qty),
+        orders: reports,
+    };
+    let printed = print_order_command_output(&report, &ORDER_REPORT_COLUMNS, || report.orders.iter().flat_map(get_order_report_rows).collect(), || {
+        print!("group {}: placed on {}/{} accounts, qty {}", group, placed, results.len(), placed_qty);
+        if cmd_args.no_confirm {
+            println!();
+        } else {
+            println!(", filled {}", filled_qty);
+        }
+    });
+    if let Err(e) = printed {
+        print_error_if_necessary(e);
+    }
+    // accounts skipped as their quantity rounds to 0 are not failures
+    if placed < results.iter().filter(|(_, _, result)| result.is_some()).count() {
+        std::process::exit(1);
+    }
+}
+
+/// Place a ladder of limit orders of `qty` across price levels of `options`,
+/// or only print them if `dry_run` is `true`. Orders are reported as accepted,
+/// their fills are picked up later by `journal --sync`.
+fn run_ladder_command(selection: &AccountSelection, cmd_args: &CommandlineArgs, symbol: &str, qty: i64, options: &LadderOptions, dry_run: bool) -> Result<(), StatusCode> {
+    let trading_context = &selection.accounts[0].context;
+    let plans = api_plan_ladder_orders(trading_context, symbol, qty, options)?;
+    let total_qty: u64 = plans.iter().map(|p| p.qty).sum();
+    let (first, last) = (&plans[0], &plans[plans.len() - 1]);
+
+    if dry_run {
+        let checked = check_order_plans_limits(trading_context, &plans);
+        let reports: Vec<OrderPlanReport> = plans.iter()
+            .map(|plan| OrderPlanReport { within_limits: checked.is_ok(), ..to_order_plan_report(None, plan) })
+            .collect();
+        print_order_command_output(&reports, &ORDER_PLAN_REPORT_COLUMNS, || reports.iter().map(get_order_plan_report_row).collect(), || {
+            println!("dry run, orders are not sent");
+            for plan in plans.iter() {
+                println!("  {} {} {} PostOnly limit @ {}, {} ticks from reference {}", plan.side, plan.qty, plan.symbol, plan.limit_price,
+                    ((plan.limit_price - plan.reference_price).abs() / plan.tick_step).round(), plan.reference_price);
+            }
+            print_ladder_summary(first, total_qty, plans.len(), plans.len(), last);
+            if checked.is_ok() {
+                println!("  within safety limits");
+            }
+        })?;
+        return checked;
+    }
+
+    confirm_mainnet_order(selection, cmd_args, symbol, if qty > 0 { total_qty as i64 } else { -(total_qty as i64) })?;
+
+    let results = api_send_order_plans(trading_context, &plans)?;
+    let reports: Vec<OrderReport> = plans.iter().zip(results)
+        .map(|(plan, (result, elapsed))| match result {
+            Ok(order) => to_order_report(None, &QuickOrderOutcome { order, elapsed, confirm_elapsed: None, confirmation: None, expired: false, fallback: None }),
+            Err(e) => to_unplaced_order_plan_report(None, plan, &e),
+        })
+        .collect();
+    let placed = reports.iter().filter(|r| r.order_id.is_some()).count();
+
+    let report = LadderReport {
+        symbol: symbol.to_owned(),
+        side: first.side.clone(),
+        qty: total_qty,
+        reference_price: first.reference_price,
+        stop_loss: first.stop_loss_price,
+        placed,
+        orders: reports,
+    };
+    print_order_command_output(&report, &ORDER_REPORT_COLUMNS, || report.orders.iter().map(get_order_report_row).collect(), || {
+        for r in report.orders.iter() {
+            match (&r.order_id, &r.error) {
+                (Some(order_id), _) => println!("accepted {} {} {} @ {} ({})", r.side, r.qty, r.symbol, to_table_cell(r.price), order_id),
+                (None, error) => println!("failed to place {} {} {} @ {}: {}", r.side, r.qty, r.symbol, to_table_cell(r.price), to_table_cell(error.as_ref())),
+            }
+        }
+        print_ladder_summary(first, total_qty, placed, plans.len(), last);
+    })?;
+    if placed < plans.len() {
+        return Err(StatusCode::ErrorApiResponse);
+    }
+    Ok(())
+}
+
+/// Print summary line of a ladder from its nearest level `first` to its
+/// farthest level `last`.
+fn print_ladder_summary(first: &OrderPlan, total_qty: u64, placed: usize, levels: usize, last: &OrderPlan) {
+    print!("ladder {} {} {}: {}/{} levels from {} to {}, stop-loss {}", first.side, total_qty, first.symbol, placed, levels, first.limit_price, last.limit_price, first.stop_loss_price);
+    match first.take_profit_price {
+        Some(take_profit_price) => println!(", take-profit {}", take_profit_price),
+        None => println!(),
+    }
+}
+
+/// Place a bracket of an entry of `qty` with stop-loss, and take-profit
+/// targets of `options` then manage it until it is done, or only print it if
+/// `dry_run` is `true`. Progress is printed as it happens, followed by a
+/// summary.
+fn run_bracket_command(selection: &AccountSelection, cmd_args: &CommandlineArgs, symbol: &str, qty: i64, options: &BracketOptions, dry_run: bool) -> Result<(), StatusCode> {
+    let trading_context = &selection.accounts[0].context;
+    let BracketOptions { targets, entry_ttl, manage_timeout } = options;
+    if !(*entry_ttl > 0.0 && *manage_timeout > 0.0 && entry_ttl.is_finite() && manage_timeout.is_finite()) {
+        eprintln!("Error: --entry-ttl, and --manage-timeout must be positive seconds");
+        return Err(StatusCode::ErrorIncorrectParameterValue);
+    }
+    let plan = api_plan_bracket(trading_context, symbol, qty, targets)?;
+    let entry = &plan.entry;
+
+    if dry_run {
+        let lot_size = trading_context.lot_sizes.get(symbol).copied().unwrap_or(1);
+        let qtys = get_bracket_target_qtys(entry.qty, targets, lot_size)?;
+        let close_side = if qty > 0 { "Sell" } else { "Buy" };
+        let checked = check_order_limits(trading_context, entry);
+        let mut reports = vec![OrderPlanReport { within_limits: checked.is_ok(), ..to_order_plan_report(None, entry) }];
+        reports.extend(qtys.iter().zip(plan.target_prices.iter()).map(|(target_qty, price)| OrderPlanReport {
+            symbol: symbol.to_owned(),
+            side: close_side.to_owned(),
+            qty: *target_qty,
+            price: Some(*price),
+            within_limits: checked.is_ok(),
+            ..Default::default()
+        }));
+        print_order_command_output(&reports, &ORDER_PLAN_REPORT_COLUMNS, || reports.iter().map(get_order_plan_report_row).collect(), || {
+            println!("dry run, orders are not sent");
+            println!("  entry {} {} {} PostOnly limit @ {}, stop-loss {}, risk {}", entry.side, entry.qty, symbol, entry.limit_price, entry.stop_loss_price, plan.risk);
+            for (i, ((target_qty, price), target)) in qtys.iter().zip(plan.target_prices.iter()).zip(targets.iter()).enumerate() {
+                println!("  target {}: {} {} {} reduce-only limit @ {}, {}% at {}R", i + 1, close_side, target_qty, symbol, price, target.pcnt, target.r_multiple);
+            }
+            if checked.is_ok() {
+                println!("  within safety limits");
+            }
+        })?;
+        return checked;
+    }
+
+    confirm_mainnet_order(selection, cmd_args, symbol, qty)?;
+
+    let report = api_run_bracket(trading_context, &plan, std::time::Duration::from_secs_f64(*entry_ttl), std::time::Duration::from_secs_f64(*manage_timeout))?;
+    let failed = report.targets.iter().any(|t| t.order.order_id.is_none());
+    let rows = || std::iter::once(&report.entry).chain(report.targets.iter().map(|t| &t.order))
+        .map(get_order_report_row)
+        .collect();
+    print_order_command_output(&report, &ORDER_REPORT_COLUMNS, rows, || {
+        let hit = report.targets.iter().filter(|t| t.order.status == "filled").count();
+        println!("bracket {} {} {}: entry filled {}, {}/{} targets hit, {}, {} left open", entry.side, entry.qty, symbol, report.entry.filled_qty,
+            hit, report.targets.len(), get_bracket_outcome_label(report.outcome), report.remaining_qty);
+    })?;
+    if failed {
+        return Err(StatusCode::ErrorApiResponse);
+    }
+    Ok(())
+}
+
+/// Place orders of a batch read from `file`, or stdin concurrently, or only
+/// print them if `dry_run` is `true`. Orders are reported line by line as
+/// accepted, or failed along with a summary.
+fn run_batch_command(selection: &AccountSelection, cmd_args: &CommandlineArgs, file: Option<&str>, format: Option<BatchFormat>, dry_run: bool) -> Result<(), StatusCode> {
+    let trading_context = &selection.accounts[0].context;
+    let orders = read_batch_orders(file, format)?;
+    let signed_qty = |order: &BatchOrder| if order.side == "Sell" { -(order.qty as i64) } else { order.qty as i64 };
+
+    if dry_run {
+        let planned = run_concurrently(&orders, |order| api_plan_batch_order(trading_context, order).map(|(_, plan)| plan));
+        let checked = check_batch_orders_limits(trading_context, &planned.iter().map(|p| p.as_ref().ok()).collect::<Vec<_>>());
+        let plans: Vec<_> = planned.into_iter().zip(checked)
+            .map(|(planned, checked)| planned.map(|plan| (checked.is_ok(), plan)))
+            .collect();
+        let reports: Vec<BatchLineReport<OrderPlanReport>> = orders.iter().zip(plans.iter())
+            .map(|(order, planned)| BatchLineReport {
+                line: order.line,
+                report: match planned {
+                    Ok((within_limits, plan)) => OrderPlanReport { within_limits: *within_limits, ..to_order_plan_report(None, plan) },
+                    Err(e) => OrderPlanReport {
+                        symbol: order.symbol.clone(),
+                        side: order.side.clone(),
+                        qty: order.qty,
+                        error: Some(get_status_code_message(e).to_owned()),
+                        ..Default::default()
+                    },
+                },
+            })
+            .collect();
+        let rows = || reports.iter().map(|r| [vec![r.line.to_string()], get_order_plan_report_row(&r.report)].concat()).collect();
+        print_order_command_output(&reports, &[&["line"], &ORDER_PLAN_REPORT_COLUMNS[..]].concat(), rows, || {
+            println!("dry run, orders are not sent");
+            for (order, planned) in orders.iter().zip(plans.iter()) {
+                match planned {
+                    Ok((within_limits, plan)) => println!("  line {}: {} {} {} PostOnly limit @ {}, stop-loss {}{}{}", order.line, plan.side, plan.qty, plan.symbol, plan.limit_price, plan.stop_loss_price,
+                        plan.take_profit_price.map(|p| format!(", take-profit {}", p)).unwrap_or_default(), if *within_limits { "" } else { ", beyond safety limits" }),
+                    Err(e) => println!("  line {}: {} {} {}: {}", order.line, order.side, order.qty, order.symbol, get_status_code_message(e)),
+                }
+            }
+        })?;
+        if plans.iter().any(|p| !matches!(p, Ok((true, _)))) {
+            return Err(StatusCode::ErrorIncorrectParameterValue);
+        }
+        return Ok(());
+    }
+
+    confirm_mainnet_orders(selection, cmd_args, &sum_batch_orders_qty(&orders))?;
+
+    let results = api_send_batch_orders(trading_context, &orders);
+    let reports: Vec<BatchLineReport<OrderReport>> = orders.iter().zip(results)
+        .map(|(order, (plan, (result, elapsed)))| BatchLineReport {
+            line: order.line,
+            report: match result {
+                Ok(placed) => to_order_report(None, &QuickOrderOutcome { order: placed, elapsed, confirm_elapsed: None, confirmation: None, expired: false, fallback: None }),
+                Err(e) => match &plan {
+                    Some(plan) => to_unplaced_order_plan_report(None, plan, &e),
+                    None => to_unplaced_order_report(None, &order.symbol, signed_qty(order), Some(&e)),
+                },
+            },
+        })
+        .collect();
+    let placed = reports.iter().filter(|r| r.report.order_id.is_some()).count();
+    let failed = reports.len() - placed;
+
+    let report = BatchReport { placed, failed, orders: reports };
+    let rows = || report.orders.iter().map(|r| [vec![r.line.to_string()], get_order_report_row(&r.report)].concat()).collect();
+    print_order_command_output(&report, &[&["line"], &ORDER_REPORT_COLUMNS[..]].concat(), rows, || {
+        for BatchLineReport { line, report: r } in report.orders.iter() {
+            match (&r.order_id, &r.error) {
+                (Some(order_id), _) => println!("line {}: accepted {} {} {} @ {} ({})", line, r.side, r.qty, r.symbol, to_table_cell(r.price), order_id),
+                (None, error) => println!("line {}: failed {} {} {}: {}", line, r.side, r.qty, r.symbol, to_table_cell(error.as_ref())),
+            }
+        }
+        println!("batch: placed {}/{} orders, {} failed", placed, placed + failed, failed);
+    })?;
+    if failed > 0 {
+        return Err(StatusCode::ErrorApiResponse);
+    }
+    Ok(())
+}
+
+/// Place a quick limit order, then wait for its final state on the private
+/// stream unless confirmation is turned off. Return its plan if it could be
+/// computed, so its client order id is known even if it fails e.g. on network
+/// error, along with the outcome.
+///
+/// # Arguments
+/// * `trading_context` - `TradingContext` of the account
+/// * `cmd_args` - command line's arguments
+/// * `symbol` - symbol to trade
+/// * `qty` - quantity, positive for buy, negative for sell
+/// * `print_elapsed` - whether or not to print elapsed time once the order is accepted
+fn place_quick_limit_order(trading_context: &TradingContext, cmd_args: &CommandlineArgs, symbol: &str, qty: i64, print_elapsed: bool) -> (Option<OrderPlan>, Result<QuickOrderOutcome, StatusCode>) {
     let mut start = std::time::Instant::now();
     measure_start(&mut start);
- 
-    match api_send_quick_limit_order(&trading_context, &cmd_args.symbol, cmd_args.qty) {
-        Ok(_) => {
-            println!("done");
-            measure_end(&start, true);
+
+    // connect to private stream along with sending the order, so it won't add
+    // up to the time of placing the order. Paper exchange knows the final
+    // state right away.
+    let stream_handle = if cmd_args.no_confirm || trading_context.paper.is_some() { None } else {
+        let ctx = trading_context.clone();
+        Some(std::thread::spawn(move || ws_connect_private_stream(&ctx)))
+    };
+
+    let plan = match cmd_args.book_step_ahead {
+        Some(step_ahead_size) => plan_limit_order_from_book(trading_context, symbol, qty, step_ahead_size, cmd_args.book_depth_ticks),
+        None => api_plan_quick_limit_order(trading_context, symbol, qty),
+    };
+    let plan = match plan {
+        Ok(plan) => plan,
+        Err(e) => return (None, Err(e)),
+    };
+    let order = match api_send_order_plan(trading_context, &plan) {
+        Ok(order) => order,
+        Err(e) => return (Some(plan), Err(e)),
+    };
+    let elapsed = measure_end(&start, print_elapsed);
+
+    let timeout = std::time::Duration::from_secs_f64(cmd_args.confirm_timeout);
+    let (confirmation, expired, fallback) = match (cmd_args.ttl, stream_handle) {
+        (Some(ttl), stream_handle) => {
+            // without private stream, the order is watched by querying it
+            let mut ws = stream_handle.and_then(|handle| handle.join().ok()).and_then(|ws| ws.ok());
+            match api_expire_order(trading_context, ws.as_mut(), &order, std::time::Duration::from_secs_f64(ttl), cmd_args.fallback, timeout) {
+                Ok(expiry) => (Some(Ok(expiry.confirmation)), expiry.expired, expiry.fallback),
+                Err(e) => (Some(Err(e)), false, None),
+            }
+        },
+        (None, Some(handle)) => {
+            let confirmation = handle.join().unwrap_or(Err(StatusCode::ErrorWebSocket))
+                .and_then(|mut ws| wait_for_order_confirmation(trading_context, &mut ws, &order, timeout));
+            (Some(confirmation), false, None)
+        },
+        (None, None) if trading_context.paper.is_some() && !cmd_args.no_confirm => (Some(api_get_order_confirmation(trading_context, &order, true)), false, None),
+        (None, None) => (None, false, None),
+    };
+    let confirm_elapsed = confirmation.is_some().then(|| measure_end(&start, false));
+    if let Some(Ok(confirmation)) = &confirmation {
+        journal_order_confirmation(trading_context, confirmation);
+    }
+    if let Some(Ok(Some(Ok(confirmation)))) = fallback.as_ref().map(|f| f.as_ref().map(|f| &f.confirmation)) {
+        journal_order_confirmation(trading_context, confirmation);
+    }
+
+    (Some(plan), Ok(QuickOrderOutcome { order, elapsed, confirm_elapsed, confirmation, expired, fallback }))
+}
+
+/// Ask for confirmation on terminal before placing an order on mainnet whose
+/// quantity on any account is above `confirm_mainnet_above` of safety limits.
+/// Return `ErrorNotConfirmed` unless it is confirmed, not needed, or skipped
+/// by `--yes`. It cannot be asked if stdin is not a terminal, so the order is
+/// declined.
+fn confirm_mainnet_order(selection: &AccountSelection, cmd_args: &CommandlineArgs, symbol: &str, qty: i64) -> Result<(), StatusCode> {
+    confirm_mainnet_orders(selection, cmd_args, &[(symbol, qty)])
+}
+
+/// Ask for confirmation of `orders` of symbol, and signed quantity e.g. of a
+/// batch at once, if quantity of any of them is above `confirm_mainnet_above`.
+/// See `confirm_mainnet_order`.
+fn confirm_mainnet_orders(selection: &AccountSelection, cmd_args: &CommandlineArgs, orders: &[(&str, i64)]) -> Result<(), StatusCode> {
+    use std::io::{IsTerminal, Write};
+
+    if cmd_args.yes {
+        return Ok(());
+    }
+    let needs_confirmation = selection.accounts.iter().any(|account| {
+        let context = &account.context;
+        !context.use_testnet && context.paper.is_none() && orders.iter().any(|(_, qty)| {
+            let account_qty = (qty.unsigned_abs() as f64 * account.multiplier).round() as u64;
+            context.limits.confirm_mainnet_above.is_some_and(|threshold| account_qty > threshold)
+        })
+    });
+    if !needs_confirmation {
+        return Ok(());
+    }
+
+    let description = orders.iter()
+        .map(|(symbol, qty)| format!("{} {} {}", if *qty > 0 { "Buy" } else { "Sell" }, qty.unsigned_abs(), symbol))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let target = match &selection.group {
+        Some(group) => format!("mainnet accounts of group {}", group),
+        None => String::from("mainnet"),
+    };
+    if !std::io::stdin().is_terminal() {
+        eprintln!("Error: {} on {} needs confirmation, pass --yes to place it non-interactively", description, target);
+        return Err(StatusCode::ErrorNotConfirmed);
+    }
+    eprint!("Place {} on {}? [y/N] ", description, target);
+    let _ = std::io::stderr().flush();
+
+    let mut answer = String::new();
+    if std::io::stdin().read_line(&mut answer).is_err() || !matches!(answer.trim().to_lowercase().as_str(), "y" | "yes") {
+        return Err(StatusCode::ErrorNotConfirmed);
+    }
+    Ok(())
+}
+
+/// Print result of a command placing orders in current output mode: `json`
+/// as json, `rows` as a table with `columns` as its header, or by
+/// `print_plain` unless output is quiet.
+///
+/// # Arguments
+/// * `json` - value printed as json
+/// * `columns` - header of each column of table
+/// * `rows` - cells of each row of table
+/// * `print_plain` - prints plain output
+fn print_order_command_output<T: serde::Serialize>(json: &T, columns: &[&str], rows: impl FnOnce() -> Vec<Vec<String>>, print_plain: impl FnOnce()) -> Result<(), StatusCode> {
+    match get_output_mode() {
+        OutputMode::Json => print_json(json)?,
+        OutputMode::Table => print_table(columns, &rows()),
+        OutputMode::Plain if is_quiet() => {},
+        OutputMode::Plain => print_plain(),
+    }
+    Ok(())
+}
+
+/// Show, or reset state of paper exchange.
+fn run_paper_command(trading_context: &TradingContext, action: &PaperAction) -> Result<(), StatusCode> {
+    let paper = trading_context.paper.as_ref().ok_or(StatusCode::ErrorPaper)?;
+    match action {
+        PaperAction::Status => print_paper_status(&paper_sync(trading_context, paper)?)?,
+        PaperAction::Reset { balance } => {
+            let _lock = paper_lock_state(&paper.state_path)?;
+            paper_save_state(&paper.state_path, &paper_new_state(*balance))?;
+            match get_output_mode() {
+                OutputMode::Json => print_json(&serde_json::json!({"reset": true, "balance": balance}))?,
+                _ if is_quiet() => {},
+                _ => println!("paper exchange reset with balance {} of each settlement coin", balance),
+            }
+        },
+    }
+    Ok(())
+}
+
+/// Print orders of trade journal matching `filter`, the latest `limit` of them
+/// if specified. Orders of the account whose final state is not yet known are
+/// queried first if `sync` is `true`.
+fn run_journal_command(trading_context: &TradingContext, filter: &JournalFilter, limit: Option<usize>, sync: bool) -> Result<(), StatusCode> {
+    if sync {
+        let synced = sync_journal(trading_context)?;
+        print_info(&format!("recorded latest state of {} orders", synced));
+    }
+    let path = trading_context.journal_path.as_ref().ok_or(StatusCode::ErrorJournal)?;
+    let entries = read_journal(path)?;
+    let orders: Vec<_> = get_journal_orders(&entries).into_iter().filter(|(order, _)| is_journal_order_matched(order, filter)).collect();
+    let skipped = limit.map(|limit| orders.len().saturating_sub(limit)).unwrap_or(0);
+    let orders = &orders[skipped..];
+
+    match get_output_mode() {
+        OutputMode::Json => {
+            let orders: Vec<_> = orders.iter().map(|(order, fill)| serde_json::json!({"order": order, "fill": fill})).collect();
+            print_json(&orders)?;
+        },
+        OutputMode::Table => print_table(&JOURNAL_ORDER_COLUMNS, &orders.iter().map(|(order, fill)| get_journal_order_row(order, *fill)).collect::<Vec<_>>()),
+        OutputMode::Plain if is_quiet() => {},
+        OutputMode::Plain => {
+            for (order, fill) in orders.iter() {
+                println!("{}", format_journal_order(order, *fill));
+            }
+        },
+    }
+    Ok(())
+}
+
+/// Print execution quality of orders of trade journal matching `filter`.
+fn run_report_command(trading_context: &TradingContext, filter: &JournalFilter) -> Result<(), StatusCode> {
+    let path = trading_context.journal_path.as_ref().ok_or(StatusCode::ErrorJournal)?;
+    let reports = get_execution_reports(&read_journal(path)?, filter);
+    match get_output_mode() {
+        OutputMode::Json => return print_json(&reports),
+        OutputMode::Table => print_table(&EXECUTION_REPORT_COLUMNS, &reports.iter().map(get_execution_report_row).collect::<Vec<_>>()),
+        OutputMode::Plain if is_quiet() => {},
+        OutputMode::Plain => {
+            if reports.is_empty() {
+                println!("no orders in journal {}", path.display());
+            }
+            for report in reports.iter() {
+                for line in format_execution_report(report) {
+                    println!("{}", line);
+                }
+            }
+        },
+    }
+    if reports.iter().any(|r| r.pending > 0) {
+        print_info("final state of pending orders is not known yet, run `quicky journal --sync` to query them");
+    }
+    Ok(())
+}
+
+/// Export history of `kind` of `symbol` from Bybit to `out`, or stdout.
+fn run_export_command(trading_context: &TradingContext, kind: ExportKind, symbol: &str, since: Option<u64>, until: Option<u64>, format: ExportFormat, out: Option<&str>) -> Result<(), StatusCode> {
+    let (columns, rows) = api_get_export_rows(trading_context, kind, symbol, since, until)?;
+    let written = match out {
+        Some(path) => std::fs::File::create(path)
+            .and_then(|file| {
+                let mut writer = std::io::BufWriter::new(file);
+                write_export_rows(&mut writer, format, columns, &rows)?;
+                std::io::Write::flush(&mut writer)
+            })
+            .map(|_| print_info(&format!("exported {} records to {}", rows.len(), path))),
+        None => write_export_rows(&mut std::io::stdout().lock(), format, columns, &rows),
+    };
+    written.map_err(|e| {
+        eprintln!("Error: cannot write exported history: {}", e);
+        StatusCode::InternalErrorGeneric
+    })
+}
+
+/// Compute the order for every selected account as it would be placed, then
+/// print it without sending.
+fn run_dry_run_order(selection: &AccountSelection, cmd_args: &CommandlineArgs, symbol: &str, qty: i64) {
+    let mut reports = Vec::new();
+    for account in selection.accounts.iter() {
+        let account_qty = (qty as f64 * account.multiplier).round() as i64;
+        if let (Some(name), true) = (&account.name, is_plain_output()) {
+            println!("[{}]", name);
         }
-        Err(e) => print_error_if_necessary(e)
+        let plan = match cmd_args.book_step_ahead {
+            Some(step_ahead_size) => plan_limit_order_from_book(&account.context, symbol, account_qty, step_ahead_size, cmd_args.book_depth_ticks),
+            None => api_plan_quick_limit_order(&account.context, symbol, account_qty),
+        };
+        let mut report = match &plan {
+            Ok(plan) => to_order_plan_report(account.name.as_deref(), plan),
+            Err(e) => OrderPlanReport {
+                account: account.name.clone(),
+                symbol: symbol.to_owned(),
+                side: String::from(if account_qty < 0 { "Sell" } else { "Buy" }),
+                qty: account_qty.unsigned_abs(),
+                error: Some(get_status_code_message(e).to_owned()),
+                ..Default::default()
+            },
+        };
+        let checked = plan.and_then(|plan| {
+            if is_plain_output() {
+                print_order_plan(&account.context, &plan)?;
+            }
+            check_order_limits(&account.context, &plan)?;
+            if is_plain_output() {
+                println!("  within safety limits");
+            }
+            Ok(())
+        });
+        match checked {
+            Ok(()) => report.within_limits = true,
+            Err(e) => {
+                report.error.get_or_insert_with(|| get_status_code_message(&e).to_owned());
+                print_error_if_necessary(e);
+            }
+        }
+        reports.push(report);
+    }
+
+    // plain output is printed above along with planning
+    if let Err(e) = print_order_command_output(&reports, &ORDER_PLAN_REPORT_COLUMNS, || reports.iter().map(get_order_plan_report_row).collect(), || {}) {
+        print_error_if_necessary(e);
     }
 }
+
+/// Compute a limit order priced from the order book of `symbol` without
+/// sending it. See `choose_limit_price_from_book`.
+fn plan_limit_order_from_book(context: &TradingContext, symbol: &str, qty: i64, step_ahead_size: f64, depth_ticks: u32) -> Result<OrderPlan, StatusCode> {
+    let tick_step = *context.tick_steps.get(symbol).ok_or(StatusCode::InternalErrorNoTickStepAvailable)?;
+    let mut book = orderbook_new(symbol, tick_step);
+    let mut ws = ws_connect_orderbook_stream(context, symbol)?;
+    ws_wait_orderbook_synced(&mut ws, &mut book, std::time::Duration::from_secs(5))?;
+
+    let side = if qty > 0 { BookSide::Bid } else { BookSide::Ask };
+    let (reference_price, _) = orderbook_best(&book, side).ok_or(StatusCode::ApiEmptyResult)?;
+    let limit_price = choose_limit_price_from_book(&book, qty > 0, step_ahead_size, depth_ticks).ok_or(StatusCode::ApiEmptyResult)?;
+
+    plan_limit_order(context, symbol, qty, limit_price, reference_price)
+}
@@ -0,0 +1,471 @@
+use crate::types::*;
+use crate::defines::*;
+use crate::util::*;
+use crate::ws::*;
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// Create an empty order book for `symbol`.
+/// It is not synced until snapshot is applied.
+///
+/// # Arguments
+/// * `symbol` - symbol of the order book
+/// * `tick_step` - tick step of the symbol
+pub fn orderbook_new(symbol: &str, tick_step: f64) -> OrderBook {
+    OrderBook {
+        symbol: symbol.to_owned(),
+        tick_step,
+        bids: BTreeMap::new(),
+        asks: BTreeMap::new(),
+        cross_seq: 0,
+        synced: false,
+    }
+}
+
+/// Connect to public stream then subscribe to `orderBookL2_25` topic of `symbol`.
+/// Snapshot will be the first message to arrive.
+///
+/// # Arguments
+/// * `context` - `TradingContext` for context information used in trading
+/// * `symbol` - symbol to subscribe to its order book
+pub fn ws_connect_orderbook_stream(context: &TradingContext, symbol: &str) -> Result<BybitWebSocket, StatusCode> {
+    let mut ws = ws_connect(&get_full_ws_uri(context, "/realtime"), context.proxy.as_deref())?;
+    ws_request(&mut ws, "subscribe", serde_json::json!([get_orderbook_topic(symbol)]), Duration::from_secs(5))?;
+    Ok(ws)
+}
+
+/// Read a message from order book stream, then apply it onto `book`.
+/// If `book` goes out of sync, it is cleared and resubscribed to receive a
+/// fresh snapshot automatically.
+/// Return whether or not `book` has been updated.
+///
+/// # Arguments
+/// * `ws` - websocket connected via `ws_connect_orderbook_stream`
+/// * `book` - order book to apply the message onto
+pub fn ws_read_orderbook(ws: &mut BybitWebSocket, book: &mut OrderBook) -> Result<bool, StatusCode> {
+    let json = match ws_read_json(ws)? {
+        Some(json) => json,
+        None => return Ok(false),
+    };
+
+    if json["topic"].as_str() != Some(&get_orderbook_topic(&book.symbol)) {
+        return Ok(false);
+    }
+
+    match orderbook_apply_message(book, &json) {
+        Ok(()) => Ok(true),
+        Err(StatusCode::ErrorOrderBookOutOfSync) => {
+            orderbook_resync(ws, book)?;
+            Ok(false)
+        },
+        Err(e) => Err(e),
+    }
+}
+
+/// Keep `book` up to date until `running` is false. Reconnect if connection
+/// drops. `book` is not synced while disconnected.
+///
+/// # Arguments
+/// * `context` - `TradingContext` for context information used in trading
+/// * `book` - order book shared with other threads
+/// * `running` - flag to keep running
+pub fn run_orderbook_stream(context: &TradingContext, book: &Mutex<OrderBook>, running: &AtomicBool) {
+    let (symbol, tick_step) = {
+        let book = book.lock().unwrap();
+        (book.symbol.clone(), book.tick_step)
+    };
+
+    while running.load(Ordering::SeqCst) {
+        let mut ws = match ws_connect_orderbook_stream(context, &symbol) {
+            Ok(ws) => ws,
+            Err(_) => {
+                std::thread::sleep(Duration::from_millis(WS_RECONNECT_DELAY_MILLIS));
+                continue;
+            }
+        };
+
+        // apply onto local copy, so that lock is not held while waiting for message
+        let mut local_book = orderbook_new(&symbol, tick_step);
+        let mut last_ping = Instant::now();
+        while running.load(Ordering::SeqCst) {
+            if last_ping.elapsed().as_secs() >= WS_PING_INTERVAL_SECS {
+                last_ping = Instant::now();
+                if ws_ping(&mut ws).is_err() {
+                    break;
+                }
+            }
+            match ws_read_orderbook(&mut ws, &mut local_book) {
+                Ok(true) => *book.lock().unwrap() = local_book.clone(),
+                Ok(false) => {
+                    // it might have been resynced
+                    if !local_book.synced {
+                        book.lock().unwrap().synced = false;
+                    }
+                },
+                Err(_) => break,
+            }
+        }
+
+        book.lock().unwrap().synced = false;
+    }
+}
+
+/// Wait until `book` has received its snapshot, or until `timeout`.
+///
+/// # Arguments
+/// * `ws` - websocket connected via `ws_connect_orderbook_stream`
+/// * `book` - order book to wait for
+/// * `timeout` - how long to wait
+pub fn ws_wait_orderbook_synced(ws: &mut BybitWebSocket, book: &mut OrderBook, timeout: Duration) -> Result<(), StatusCode> {
+    let start = Instant::now();
+    while !book.synced {
+        if start.elapsed() >= timeout {
+            return Err(StatusCode::ErrorOrderBookOutOfSync);
+        }
+        ws_read_orderbook(ws, book)?;
+    }
+    Ok(())
+}
+
+/// Clear `book`, then resubscribe to its topic so that a fresh snapshot will
+/// be sent.
+///
+/// # Arguments
+/// * `ws` - websocket connected via `ws_connect_orderbook_stream`
+/// * `book` - order book to resync
+pub fn orderbook_resync(ws: &mut BybitWebSocket, book: &mut OrderBook) -> Result<(), StatusCode> {
+    orderbook_clear(book);
+
+    let topic = get_orderbook_topic(&book.symbol);
+    ws_request(ws, "unsubscribe", serde_json::json!([topic]), Duration::from_secs(5))?;
+    ws_request(ws, "subscribe", serde_json::json!([topic]), Duration::from_secs(5))
+}
+
+/// Clear `book` so that it is not synced until a fresh snapshot is applied.
+///
+/// # Arguments
+/// * `book` - order book to clear
+pub fn orderbook_clear(book: &mut OrderBook) {
+    book.bids.clear();
+    book.asks.clear();
+    book.cross_seq = 0;
+    book.synced = false;
+}
+
+/// Apply snapshot or delta message onto `book`.
+/// Return `StatusCode::ErrorOrderBookOutOfSync` if the message cannot be applied
+/// consistently. Inverse order book topic carries no checksum, so we validate
+/// sequence, level existence, and that the book is not crossed instead.
+///
+/// # Arguments
+/// * `book` - order book
+/// * `json` - message of `orderBookL2_25` topic
+pub fn orderbook_apply_message(book: &mut OrderBook, json: &serde_json::Value) -> Result<(), StatusCode> {
+    let cross_seq = json["cross_seq"].as_u64().unwrap_or(0);
+
+    match json["type"].as_str() {
+        Some("snapshot") => {
+            // data is either list of entries, or wrapped inside `order_book`
+            let data = if json["data"].is_array() { json["data"].clone() } else { json["data"]["order_book"].clone() };
+            let entries: Vec<BybitWsOrderBookEntry> = serde_json::from_value(data).map_err(|_| StatusCode::ErrorJsonParsing)?;
+
+            book.bids.clear();
+            book.asks.clear();
+            for e in entries.iter() {
+                let key = price_to_ticks(e.price, book.tick_step);
+                get_book_levels_mut(book, &e.side)?.insert(key, e.size);
+            }
+            book.cross_seq = cross_seq;
+            book.synced = true;
+        },
+        Some("delta") => {
+            if !book.synced {
+                // deltas before snapshot are useless
+                return Ok(());
+            }
+            if cross_seq <= book.cross_seq {
+                return Err(StatusCode::ErrorOrderBookOutOfSync);
+            }
+
+            let delta: BybitWsOrderBookDelta = serde_json::from_value(json["data"].clone()).map_err(|_| StatusCode::ErrorJsonParsing)?;
+            let tick_step = book.tick_step;
+
+            for e in delta.delete.iter() {
+                let levels = get_book_levels_mut(book, &e.side)?;
+                if levels.remove(&price_to_ticks(e.price, tick_step)).is_none() {
+                    return Err(StatusCode::ErrorOrderBookOutOfSync);
+                }
+            }
+            for e in delta.update.iter() {
+                let levels = get_book_levels_mut(book, &e.side)?;
+                match levels.get_mut(&price_to_ticks(e.price, tick_step)) {
+                    Some(size) => *size = e.size,
+                    None => return Err(StatusCode::ErrorOrderBookOutOfSync),
+                }
+            }
+            for e in delta.insert.iter() {
+                let levels = get_book_levels_mut(book, &e.side)?;
+                if levels.insert(price_to_ticks(e.price, tick_step), e.size).is_some() {
+                    return Err(StatusCode::ErrorOrderBookOutOfSync);
+                }
+            }
+            book.cross_seq = cross_seq;
+        },
+        _ => return Err(StatusCode::MalformedAPIResponseFormat),
+    }
+
+    if let (Some((bid, _)), Some((ask, _))) = (orderbook_best(book, BookSide::Bid), orderbook_best(book, BookSide::Ask)) {
+        if bid >= ask {
+            return Err(StatusCode::ErrorOrderBookOutOfSync);
+        }
+    }
+    Ok(())
+}
+
+/// Get best price and its size of the specified `side`.
+///
+/// # Arguments
+/// * `book` - order book
+/// * `side` - side of the book
+pub fn orderbook_best(book: &OrderBook, side: BookSide) -> Option<(f64, f64)> {
+    let level = match side {
+        BookSide::Bid => book.bids.iter().next_back(),
+        BookSide::Ask => book.asks.iter().next(),
+    };
+    level.map(|(k, size)| (ticks_to_price(*k, book.tick_step), *size))
+}
+
+/// Get size at best price of the specified `side`, or 0 if that side is empty.
+///
+/// # Arguments
+/// * `book` - order book
+/// * `side` - side of the book
+pub fn orderbook_size_at_best(book: &OrderBook, side: BookSide) -> f64 {
+    orderbook_best(book, side).map(|(_, size)| size).unwrap_or(0.0)
+}
+
+/// Get cumulative size of the specified `side` from its best price up to
+/// `ticks` away from it, inclusively.
+///
+/// # Arguments
+/// * `book` - order book
+/// * `side` - side of the book
+/// * `ticks` - number of ticks away from best price
+pub fn orderbook_cumulative_size_within(book: &OrderBook, side: BookSide, ticks: u32) -> f64 {
+    let ticks = ticks as i64;
+    match side {
+        BookSide::Bid => match book.bids.keys().next_back() {
+            Some(best) => book.bids.range(best - ticks..=*best).map(|(_, size)| size).sum(),
+            None => 0.0,
+        },
+        BookSide::Ask => match book.asks.keys().next() {
+            Some(best) => book.asks.range(*best..=best + ticks).map(|(_, size)| size).sum(),
+            None => 0.0,
+        },
+    }
+}
+
+/// Choose a limit price from `book` that won't take liquidity.
+/// Join the best level of our side, or step one tick ahead of it if the size
+/// queued from best level up to `depth_ticks` behind it is larger than
+/// `step_ahead_size`, and there is room before the opposite side.
+/// Return `None` if our side of the book is empty.
+///
+/// # Arguments
+/// * `book` - order book
+/// * `is_buy_side` - whether or not it is buy side
+/// * `step_ahead_size` - queued size beyond which we step ahead of best level
+/// * `depth_ticks` - number of ticks behind best level to count as queued, 0
+///   counts only the best level
+pub fn choose_limit_price_from_book(book: &OrderBook, is_buy_side: bool, step_ahead_size: f64, depth_ticks: u32) -> Option<f64> {
+    let (side, opposite, direction) = if is_buy_side { (BookSide::Bid, BookSide::Ask, 1) } else { (BookSide::Ask, BookSide::Bid, -1) };
+    let (best, _) = orderbook_best(book, side)?;
+    let best_ticks = price_to_ticks(best, book.tick_step);
+    let queued_size = if depth_ticks == 0 { orderbook_size_at_best(book, side) } else { orderbook_cumulative_size_within(book, side, depth_ticks) };
+
+    if queued_size > step_ahead_size {
+        let ahead_ticks = best_ticks + direction;
+        let has_room = match orderbook_best(book, opposite) {
+            Some((opposite_best, _)) => {
+                let opposite_ticks = price_to_ticks(opposite_best, book.tick_step);
+                if is_buy_side { ahead_ticks < opposite_ticks } else { ahead_ticks > opposite_ticks }
+            },
+            None => true,
+        };
+        if has_room {
+            return Some(ticks_to_price(ahead_ticks, book.tick_step));
+        }
+    }
+    Some(best)
+}
+
+/// Send a limit order joining the best level of our side of shared `book`.
+/// Fall back to `api_send_quick_limit_order` if the book is not synced, so
+/// that the order still goes out.
+///
+/// # Arguments
+/// * `context` - `TradingContext` for context information used in trading
+/// * `symbol` - symbol to create an order for
+/// * `qty` - quantity. It can be negative for sell, or positive buy.
+/// * `book` - order book of `symbol` kept up to date by `run_orderbook_stream`
+pub fn send_limit_order_joining_book(context: &TradingContext, symbol: &str, qty: i64, book: &Mutex<OrderBook>) -> Result<BybitOrderResult, StatusCode> {
+    let book_price = {
+        let book = book.lock().unwrap();
+        if book.synced { choose_limit_price_from_book(&book, qty > 0, f64::INFINITY, 0) } else { None }
+    };
+
+    match book_price {
+        Some(price) => api_send_limit_order(context, symbol, qty, price, price),
+        None => api_send_quick_limit_order(context, symbol, qty),
+    }
+}
+
+/// Get topic name of order book of `symbol`.
+pub fn get_orderbook_topic(symbol: &str) -> String {
+    format!("orderBookL2_25.{}", symbol)
+}
+
+/// Get price levels of `book` for the specified side string as returned from
+/// Bybit i.e. `Buy` or `Sell`.
+fn get_book_levels_mut<'a>(book: &'a mut OrderBook, side: &str) -> Result<&'a mut BTreeMap<i64, f64>, StatusCode> {
+    match side {
+        "Buy" => Ok(&mut book.bids),
+        "Sell" => Ok(&mut book.asks),
+        _ => Err(StatusCode::MalformedAPIResponseFormat),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TICK_STEP: f64 = 0.0001;
+
+    fn entry(side: &str, price: f64, size: f64) -> serde_json::Value {
+        serde_json::json!({"price": price.to_string(), "symbol": "XRPUSD", "id": (price / TICK_STEP).round() as u64, "side": side, "size": size})
+    }
+
+    fn snapshot(cross_seq: u64, entries: Vec<serde_json::Value>) -> serde_json::Value {
+        serde_json::json!({"topic": "orderBookL2_25.XRPUSD", "type": "snapshot", "data": entries, "cross_seq": cross_seq})
+    }
+
+    fn delta(cross_seq: u64, delete: Vec<serde_json::Value>, update: Vec<serde_json::Value>, insert: Vec<serde_json::Value>) -> serde_json::Value {
+        serde_json::json!({"topic": "orderBookL2_25.XRPUSD", "type": "delta", "data": {"delete": delete, "update": update, "insert": insert}, "cross_seq": cross_seq})
+    }
+
+    /// Book of bids 0.4998 x 300, 0.4999 x 100, and asks 0.5001 x 200, 0.5002 x 400.
+    fn synced_book() -> OrderBook {
+        let mut book = orderbook_new("XRPUSD", TICK_STEP);
+        let entries = vec![entry("Buy", 0.4998, 300.0), entry("Buy", 0.4999, 100.0), entry("Sell", 0.5001, 200.0), entry("Sell", 0.5002, 400.0)];
+        assert!(orderbook_apply_message(&mut book, &snapshot(10, entries)).is_ok());
+        book
+    }
+
+    #[test]
+    fn snapshot_syncs_book() {
+        let book = synced_book();
+        assert!(book.synced);
+        assert_eq!(book.cross_seq, 10);
+        assert_eq!(orderbook_best(&book, BookSide::Bid), Some((0.4999, 100.0)));
+        assert_eq!(orderbook_best(&book, BookSide::Ask), Some((0.5001, 200.0)));
+        assert_eq!(orderbook_cumulative_size_within(&book, BookSide::Bid, 1), 400.0);
+    }
+
+    #[test]
+    fn snapshot_wrapped_in_order_book_is_applied() {
+        let mut book = orderbook_new("XRPUSD", TICK_STEP);
+        let json = serde_json::json!({"type": "snapshot", "data": {"order_book": [entry("Buy", 0.4999, 100.0)]}, "cross_seq": 3});
+        assert!(orderbook_apply_message(&mut book, &json).is_ok());
+        assert_eq!(orderbook_best(&book, BookSide::Bid), Some((0.4999, 100.0)));
+    }
+
+    #[test]
+    fn delta_deletes_updates_and_inserts_levels() {
+        let mut book = synced_book();
+        let json = delta(11, vec![entry("Buy", 0.4998, 0.0)], vec![entry("Sell", 0.5001, 50.0)], vec![entry("Buy", 0.5, 70.0)]);
+        assert!(orderbook_apply_message(&mut book, &json).is_ok());
+
+        assert_eq!(book.cross_seq, 11);
+        assert_eq!(orderbook_best(&book, BookSide::Bid), Some((0.5, 70.0)));
+        assert_eq!(orderbook_best(&book, BookSide::Ask), Some((0.5001, 50.0)));
+        assert_eq!(book.bids.len(), 2);
+        assert!(!book.bids.contains_key(&price_to_ticks(0.4998, TICK_STEP)));
+    }
+
+    #[test]
+    fn delta_before_snapshot_is_ignored() {
+        let mut book = orderbook_new("XRPUSD", TICK_STEP);
+        assert!(orderbook_apply_message(&mut book, &delta(1, vec![], vec![], vec![entry("Buy", 0.4999, 100.0)])).is_ok());
+        assert!(!book.synced);
+        assert!(book.bids.is_empty());
+    }
+
+    #[test]
+    fn delta_out_of_sequence_is_out_of_sync() {
+        let mut book = synced_book();
+        for cross_seq in [9, 10] {
+            let result = orderbook_apply_message(&mut book, &delta(cross_seq, vec![], vec![entry("Buy", 0.4999, 1.0)], vec![]));
+            assert!(matches!(result, Err(StatusCode::ErrorOrderBookOutOfSync)), "cross_seq {}", cross_seq);
+        }
+    }
+
+    #[test]
+    fn delta_of_missing_or_existing_level_is_out_of_sync() {
+        for json in [
+            delta(11, vec![entry("Buy", 0.4990, 0.0)], vec![], vec![]),
+            delta(11, vec![], vec![entry("Sell", 0.5010, 10.0)], vec![]),
+            delta(11, vec![], vec![], vec![entry("Buy", 0.4999, 10.0)]),
+        ] {
+            let mut book = synced_book();
+            assert!(matches!(orderbook_apply_message(&mut book, &json), Err(StatusCode::ErrorOrderBookOutOfSync)), "{}", json);
+        }
+    }
+
+    #[test]
+    fn crossed_book_is_out_of_sync_until_resynced() {
+        let mut book = synced_book();
+        let result = orderbook_apply_message(&mut book, &delta(11, vec![], vec![], vec![entry("Buy", 0.5001, 10.0)]));
+        assert!(matches!(result, Err(StatusCode::ErrorOrderBookOutOfSync)));
+
+        // resync clears the book, then a fresh snapshot syncs it again
+        orderbook_clear(&mut book);
+        assert!(!book.synced && book.bids.is_empty() && book.asks.is_empty() && book.cross_seq == 0);
+        assert!(orderbook_apply_message(&mut book, &snapshot(20, vec![entry("Buy", 0.4999, 10.0), entry("Sell", 0.5, 20.0)])).is_ok());
+        assert!(book.synced);
+        assert_eq!((orderbook_best(&book, BookSide::Bid), orderbook_best(&book, BookSide::Ask)), (Some((0.4999, 10.0)), Some((0.5, 20.0))));
+    }
+
+    #[test]
+    fn unknown_message_type_is_malformed() {
+        let mut book = synced_book();
+        let result = orderbook_apply_message(&mut book, &serde_json::json!({"type": "partial", "data": [], "cross_seq": 11}));
+        assert!(matches!(result, Err(StatusCode::MalformedAPIResponseFormat)));
+    }
+
+    #[test]
+    fn limit_price_joins_best_level_unless_queue_is_large() {
+        let book = synced_book();
+        assert_eq!(choose_limit_price_from_book(&book, true, 150.0, 0), Some(0.4999));
+        assert_eq!(choose_limit_price_from_book(&book, false, 150.0, 0), Some(0.5));
+        // queue behind best level counts with depth
+        assert_eq!(choose_limit_price_from_book(&book, true, 150.0, 1), Some(0.5));
+        assert_eq!(choose_limit_price_from_book(&book, true, f64::INFINITY, 5), Some(0.4999));
+    }
+
+    #[test]
+    fn limit_price_does_not_step_into_opposite_side() {
+        let mut book = orderbook_new("XRPUSD", TICK_STEP);
+        assert!(orderbook_apply_message(&mut book, &snapshot(1, vec![entry("Buy", 0.4999, 500.0), entry("Sell", 0.5, 500.0)])).is_ok());
+        assert_eq!(choose_limit_price_from_book(&book, true, 100.0, 0), Some(0.4999));
+        assert_eq!(choose_limit_price_from_book(&book, false, 100.0, 0), Some(0.5));
+
+        // without opposite side, there is always room
+        let mut book = orderbook_new("XRPUSD", TICK_STEP);
+        assert!(orderbook_apply_message(&mut book, &snapshot(1, vec![entry("Buy", 0.4999, 500.0)])).is_ok());
+        assert_eq!(choose_limit_price_from_book(&book, true, 100.0, 0), Some(0.5));
+        assert_eq!(choose_limit_price_from_book(&book, false, 100.0, 0), None);
+    }
+}
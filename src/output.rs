@@ -0,0 +1,234 @@
+use crate::types::*;
+use crate::util::*;
+
+use std::sync::Mutex;
+
+/// Output mode, and whether or not output is quiet, as selected at command line
+/// for the whole process.
+static OUTPUT_OPTIONS: Mutex<(OutputMode, bool)> = Mutex::new((OutputMode::Plain, false));
+
+/// Select output mode, and quietness for the rest of the process.
+///
+/// # Arguments
+/// * `mode` - `OutputMode` results are printed in
+/// * `quiet` - whether or not to suppress everything but errors, and json
+pub fn set_output_options(mode: OutputMode, quiet: bool) {
+    if let Ok(mut options) = OUTPUT_OPTIONS.lock() {
+        *options = (mode, quiet);
+    }
+}
+
+/// Get the selected `OutputMode`.
+pub fn get_output_mode() -> OutputMode {
+    OUTPUT_OPTIONS.lock().map(|o| o.0).unwrap_or(OutputMode::Plain)
+}
+
+/// Whether or not `--quiet` is selected.
+pub fn is_quiet() -> bool {
+    OUTPUT_OPTIONS.lock().map(|o| o.1).unwrap_or(false)
+}
+
+/// Whether or not results are printed as plain text lines. It is `false` for
+/// other modes, and when output is quiet.
+pub fn is_plain_output() -> bool {
+    matches!(get_output_mode(), OutputMode::Plain) && !is_quiet()
+}
+
+/// Print informational `message` e.g. progress, or retry to stderr unless
+/// output is quiet. Results, and errors are not informational.
+pub fn print_info(message: &str) {
+    if !is_quiet() {
+        eprintln!("{}", message);
+    }
+}
+
+/// Print `value` as a single json document to stdout. Json is printed even if
+/// output is quiet as it is meant for other tools.
+pub fn print_json<T: serde::Serialize>(value: &T) -> Result<(), StatusCode> {
+    let json = serde_json::to_string_pretty(value).map_err(|_| StatusCode::InternalErrorParsingJsonObject)?;
+    println!("{}", json);
+    Ok(())
+}
+
+/// Print `rows` as a table with `columns` as its header, each column padded
+/// to its widest cell. Nothing is printed if output is quiet.
+///
+/// # Arguments
+/// * `columns` - header of each column
+/// * `rows` - cells of each row, as many as `columns`
+pub fn print_table(columns: &[&str], rows: &[Vec<String>]) {
+    if is_quiet() {
+        return;
+    }
+    let mut widths: Vec<usize> = columns.iter().map(|c| c.chars().count()).collect();
+    for row in rows.iter() {
+        for (width, cell) in widths.iter_mut().zip(row.iter()) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let format_row = |cells: Vec<&str>| {
+        let padded: Vec<String> = cells.iter().zip(widths.iter()).map(|(cell, width)| format!("{:<width$}", cell, width = width)).collect();
+        padded.join("  ").trim_end().to_owned()
+    };
+    println!("{}", format_row(columns.iter().map(|c| c.to_uppercase()).collect::<Vec<_>>().iter().map(|c| c.as_str()).collect()));
+    for row in rows.iter() {
+        println!("{}", format_row(row.iter().map(|c| c.as_str()).collect()));
+    }
+}
+
+/// Format optional `value` as a cell of table, `-` if there is none.
+pub fn to_table_cell<T: std::fmt::Display>(value: Option<T>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_else(|| String::from("-"))
+}
+
+/// Columns of order results in table output, in order of `get_order_report_row`.
+pub const ORDER_REPORT_COLUMNS: [&str; 13] = ["account", "order_id", "order_link_id", "side", "qty", "symbol", "price",
+    "stop_loss", "take_profit", "status", "filled", "avg_price", "elapsed"];
+
+/// Make `OrderReport` of `outcome` of placing an order.
+///
+/// # Arguments
+/// * `account` - name of account if the order is part of a group
+/// * `outcome` - `QuickOrderOutcome` of the order
+pub fn to_order_report(account: Option<&str>, outcome: &QuickOrderOutcome) -> OrderReport {
+    let order = &outcome.order;
+    let mut report = OrderReport {
+        account: account.map(|a| a.to_owned()),
+        order_id: Some(order.order_id.clone()),
+        order_link_id: Some(order.order_link_id.clone()).filter(|id| !id.is_empty()),
+        symbol: order.symbol.clone(),
+        side: order.side.clone(),
+        qty: order.qty as u64,
+        price: Some(order.price),
+        stop_loss: Some(order.stop_loss).filter(|p| *p > 0.0),
+        take_profit: Some(order.take_profit).filter(|p| *p > 0.0),
+        status: String::from("accepted"),
+        elapsed_secs: Some(outcome.elapsed),
+        confirm_elapsed_secs: outcome.confirm_elapsed,
+        ..Default::default()
+    };
+    match &outcome.confirmation {
+        Some(Ok(confirmation)) => {
+            report.status = get_order_state_name(confirmation.state);
+            report.filled_qty = confirmation.cum_exec_qty;
+            report.avg_fill_price = get_avg_fill_price(confirmation);
+            report.fee = confirmation.cum_exec_fee;
+        },
+        Some(Err(e)) => report.error = Some(get_status_code_message(e).to_owned()),
+        None => {},
+    }
+    report.expired = outcome.expired;
+    report.fallback = match &outcome.fallback {
+        Some(Ok(fallback)) => Some(Box::new(to_order_report(account, fallback))),
+        Some(Err(e)) => {
+            let unfilled = (report.qty as f64 - report.filled_qty).max(0.0) as i64;
+            Some(Box::new(to_unplaced_order_report(account, &report.symbol, if report.side == "Sell" { -unfilled } else { unfilled }, Some(e))))
+        },
+        None => None,
+    };
+    report
+}
+
+/// Make `OrderReport` of an order which is not placed due to `error`, or
+/// skipped if there is none.
+///
+/// # Arguments
+/// * `account` - name of account if the order is part of a group
+/// * `symbol` - symbol of the order
+/// * `qty` - quantity, positive for buy, negative for sell
+/// * `error` - why the order is not placed
+pub fn to_unplaced_order_report(account: Option<&str>, symbol: &str, qty: i64, error: Option<&StatusCode>) -> OrderReport {
+    OrderReport {
+        account: account.map(|a| a.to_owned()),
+        symbol: symbol.to_owned(),
+        side: String::from(if qty < 0 { "Sell" } else { "Buy" }),
+        qty: qty.unsigned_abs(),
+        status: String::from(if error.is_some() { "failed" } else { "skipped" }),
+        error: error.map(|e| get_status_code_message(e).to_owned()),
+        ..Default::default()
+    }
+}
+
+/// Make `OrderReport` of an order of `plan` which is not placed due to
+/// `error`. It carries client order id of `plan`, so an order which might have
+/// landed after all e.g. on network error can still be looked up by it.
+///
+/// # Arguments
+/// * `account` - name of account if the order is part of a group
+/// * `plan` - `OrderPlan` of the order
+/// * `error` - why the order is not placed
+pub fn to_unplaced_order_plan_report(account: Option<&str>, plan: &OrderPlan, error: &StatusCode) -> OrderReport {
+    let qty = plan.qty as i64;
+    OrderReport {
+        order_link_id: Some(plan.order_link_id.clone()),
+        price: Some(plan.limit_price),
+        stop_loss: Some(plan.stop_loss_price),
+        take_profit: plan.take_profit_price,
+        ..to_unplaced_order_report(account, &plan.symbol, if plan.side == "Sell" { -qty } else { qty }, Some(error))
+    }
+}
+
+/// Cells of `report` in order of `ORDER_REPORT_COLUMNS`.
+pub fn get_order_report_row(report: &OrderReport) -> Vec<String> {
+    vec![
+        to_table_cell(report.account.as_ref()),
+        to_table_cell(report.order_id.as_ref()),
+        to_table_cell(report.order_link_id.as_ref()),
+        report.side.clone(),
+        report.qty.to_string(),
+        report.symbol.clone(),
+        to_table_cell(report.price),
+        to_table_cell(report.stop_loss),
+        to_table_cell(report.take_profit),
+        report.status.clone(),
+        report.filled_qty.to_string(),
+        to_table_cell(report.avg_fill_price.map(|p| format!("{:.6}", p))),
+        to_table_cell(report.elapsed_secs.map(|s| format!("{:.2}s", s))),
+    ]
+}
+
+/// Rows of `report` in table output, followed by its fallback order if any.
+pub fn get_order_report_rows(report: &OrderReport) -> Vec<Vec<String>> {
+    let mut rows = vec![get_order_report_row(report)];
+    if let Some(fallback) = &report.fallback {
+        rows.push(get_order_report_row(fallback));
+    }
+    rows
+}
+
+/// Name of `state` as of json output e.g. `cancelled_post_only`.
+pub fn get_order_state_name(state: OrderState) -> String {
+    serde_json::to_value(state).ok()
+        .and_then(|v| v.as_str().map(|s| s.to_owned()))
+        .unwrap_or_default()
+}
+
+/// Columns of orders of dry run in table output, in order of `get_order_plan_report_row`.
+pub const ORDER_PLAN_REPORT_COLUMNS: [&str; 12] = ["account", "order_link_id", "side", "qty", "symbol", "price", "reference",
+    "stop_loss", "take_profit", "notional", "loss_at_stop", "within_limits"];
+
+/// Cells of `report` in order of `ORDER_PLAN_REPORT_COLUMNS`.
+pub fn get_order_plan_report_row(report: &OrderPlanReport) -> Vec<String> {
+    vec![
+        to_table_cell(report.account.as_ref()),
+        to_table_cell(report.order_link_id.as_ref()),
+        report.side.clone(),
+        report.qty.to_string(),
+        report.symbol.clone(),
+        to_table_cell(report.price),
+        to_table_cell(report.reference_price),
+        to_table_cell(report.stop_loss),
+        to_table_cell(report.take_profit),
+        to_table_cell(report.notional.map(|v| format!("{:.8}", v))),
+        to_table_cell(report.loss_at_stop.map(|v| format!("{:.8}", v))),
+        String::from(if report.within_limits { "yes" } else { "no" }),
+    ]
+}
+
+/// Print `value` as json in a single line to stdout, for results printed as
+/// they happen e.g. orders received by webhook.
+pub fn print_json_line<T: serde::Serialize>(value: &T) -> Result<(), StatusCode> {
+    let json = serde_json::to_string(value).map_err(|_| StatusCode::InternalErrorParsingJsonObject)?;
+    println!("{}", json);
+    Ok(())
+}
@@ -0,0 +1,885 @@
+use crate::types::*;
+use crate::defines::*;
+use crate::util::*;
+use crate::output::*;
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// Funding is settled every 8 hours at 00:00, 08:00, and 16:00 UTC.
+const FUNDING_INTERVAL_MILLIS: u64 = 8 * 60 * 60 * 1000;
+
+/// Number of finished orders kept in state for querying.
+const MAX_FINISHED_ORDERS: usize = 200;
+
+/// How often private stream is emulated by polling paper exchange.
+const PAPER_POLL_INTERVAL_MILLIS: u64 = 1000;
+
+/// Get default path of paper exchange's state file.
+/// It is `$XDG_DATA_HOME/quicky/paper.json`, or `$HOME/.local/share/quicky/paper.json`.
+pub fn get_default_paper_state_path() -> Option<PathBuf> {
+    let data_home = match std::env::var("XDG_DATA_HOME") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var("HOME").ok()?).join(".local").join("share"),
+    };
+    Some(data_home.join("quicky").join("paper.json"))
+}
+
+/// Create a fresh state of paper exchange.
+///
+/// # Arguments
+/// * `balance` - balance each settlement coin starts with
+pub fn paper_new_state(balance: f64) -> PaperState {
+    PaperState {
+        initial_balance: balance,
+        next_order_id: 1,
+        ..Default::default()
+    }
+}
+
+/// Hold exclusive lock of state file of paper exchange at `path` until the
+/// returned file is dropped, so state loaded, changed, then saved by one
+/// thread, or process is not overwritten by another in between. Lock is held
+/// on a separate `.lock` file, as state file is replaced when it is saved.
+///
+/// # Arguments
+/// * `path` - path of state file
+pub fn paper_lock_state(path: &Path) -> Result<std::fs::File, StatusCode> {
+    if let Some(dir) = path.parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+    let lock_path = path.with_extension("lock");
+    let file = std::fs::OpenOptions::new().write(true).create(true).truncate(false).open(&lock_path).map_err(|e| {
+        eprintln!("Error: cannot open {}: {}", lock_path.display(), e);
+        StatusCode::ErrorPaper
+    })?;
+    file.lock().map_err(|e| {
+        eprintln!("Error: cannot lock {}: {}", lock_path.display(), e);
+        StatusCode::ErrorPaper
+    })?;
+    Ok(file)
+}
+
+/// Load state of paper exchange from `path`. Missing file results in a fresh state.
+///
+/// # Arguments
+/// * `path` - path of state file
+pub fn paper_load_state(path: &Path) -> Result<PaperState, StatusCode> {
+    match std::fs::read_to_string(path) {
+        Ok(content) => serde_json::from_str(&content).map_err(|e| {
+            eprintln!("Error: cannot parse paper state {}: {}", path.display(), e);
+            StatusCode::ErrorPaper
+        }),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(paper_new_state(PAPER_INITIAL_BALANCE)),
+        Err(e) => {
+            eprintln!("Error: cannot read paper state {}: {}", path.display(), e);
+            Err(StatusCode::ErrorPaper)
+        }
+    }
+}
+
+/// Save state of paper exchange to `path`.
+///
+/// # Arguments
+/// * `path` - path of state file
+/// * `state` - state to save
+pub fn paper_save_state(path: &Path, state: &PaperState) -> Result<(), StatusCode> {
+    let content = serde_json::to_string_pretty(state).map_err(|_| StatusCode::InternalErrorParsingJsonObject)?;
+    if let Some(dir) = path.parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+
+    let tmp_path = path.with_extension("tmp");
+    std::fs::write(&tmp_path, content)
+        .and_then(|_| std::fs::rename(&tmp_path, path))
+        .map_err(|e| {
+            eprintln!("Error: cannot write paper state {}: {}", path.display(), e);
+            StatusCode::ErrorPaper
+        })
+}
+
+/// Handle a private API request on paper exchange instead of Bybit.
+/// Market data is synced before the request is handled, so resting orders are
+/// matched, and stop-loss/take-profit are triggered up to now. Response is
+/// shaped as of Bybit's then parsed as `T`.
+///
+/// Supported end-points are order create, cancel, cancel all, order list, query
+/// order, and position list.
+///
+/// # Arguments
+/// * `context` - `TradingContext` for market data
+/// * `paper` - options of paper exchange
+/// * `end_point` - end-point URL
+/// * `params` - request's parameters
+pub fn paper_private_request<T: serde::de::DeserializeOwned>(context: &TradingContext, paper: &PaperOptions, end_point: &str, params: &BTreeMap<&str, serde_json::Value>) -> Result<T, StatusCode> {
+    let _lock = paper_lock_state(&paper.state_path)?;
+    let mut state = paper_load_state(&paper.state_path)?;
+    let symbol = params.get("symbol").and_then(|v| v.as_str()).unwrap_or_default().to_owned();
+
+    paper_sync_market(context, paper, &mut state, &symbol)?;
+
+    let result = match end_point {
+        "/v2/private/order/create" => paper_create_order(context, paper, &mut state, params),
+        "/v2/private/order/cancelAll" => {
+            paper_cancel_all_orders(&mut state, &symbol);
+            Ok(serde_json::Value::Null)
+        },
+        "/v2/private/order/cancel" => {
+            let order_id = params.get("order_id").and_then(|v| v.as_str()).unwrap_or_default();
+            match state.orders.iter_mut().find(|o| o.order_id == order_id && paper_is_order_active(o)) {
+                Some(o) => {
+                    o.order_status = String::from("Cancelled");
+                    o.cancel_type = String::from("CancelByUser");
+                    o.leaves_qty = 0.0;
+                    Ok(serde_json::to_value(&*o).map_err(|_| StatusCode::InternalErrorParsingJsonObject)?)
+                },
+                None => Err((20001, String::from("order not exists or too late to cancel"))),
+            }
+        },
+        "/v2/private/order/list" => {
+            let statuses: Vec<&str> = params.get("order_status").and_then(|v| v.as_str()).map(|s| s.split(',').collect()).unwrap_or_default();
+            let data: Vec<&PaperOrder> = state.orders.iter().rev()
+                .filter(|o| o.symbol == symbol && (statuses.is_empty() || statuses.contains(&o.order_status.as_str())))
+                .collect();
+            Ok(serde_json::json!({"data": data, "cursor": ""}))
+        },
+        "/v2/private/order" => {
+            let order_id = params.get("order_id").and_then(|v| v.as_str()).unwrap_or_default();
+            let order_link_id = params.get("order_link_id").and_then(|v| v.as_str()).unwrap_or_default();
+            match state.orders.iter().find(|o| if order_id.is_empty() { o.order_link_id == order_link_id } else { o.order_id == order_id }) {
+                Some(order) => Ok(serde_json::to_value(order).map_err(|_| StatusCode::InternalErrorParsingJsonObject)?),
+                None => Err((20001, String::from("order not exists or too late to cancel"))),
+            }
+        },
+        "/v2/private/position/list" => Ok(paper_position_json(&state, &symbol)),
+        // stop-loss always closes the whole position, so its size is not kept
+        "/v2/private/position/trading-stop" => match state.positions.get_mut(&symbol).filter(|p| p.size > 0.0) {
+            Some(position) => {
+                if let Some(stop_loss) = params.get("stop_loss").and_then(|v| v.as_f64()) {
+                    position.stop_loss = stop_loss;
+                }
+                Ok(serde_json::json!({}))
+            },
+            None => Err((30024, String::from("position is not exists"))),
+        },
+        _ => {
+            eprintln!("Error: {} is not supported by paper exchange", end_point);
+            return Err(StatusCode::ErrorPaper);
+        }
+    };
+
+    paper_trim_orders(&mut state);
+    paper_save_state(&paper.state_path, &state)?;
+
+    let (ret_code, ret_msg, result) = match result {
+        Ok(result) => (0, String::from("OK"), result),
+        Err((ret_code, ret_msg)) => (ret_code, ret_msg, serde_json::Value::Null),
+    };
+    let json = serde_json::json!({
+        "ret_code": ret_code,
+        "ret_msg": ret_msg,
+        "ext_code": "",
+        "ext_info": "",
+        "result": result,
+        "time_now": format!("{:.6}", get_unix_timestamp_as_millis() as f64 / 1000.0),
+    });
+    serde_json::from_value(json).map_err(|_| StatusCode::ErrorJsonParsing)
+}
+
+/// Bring paper exchange up to now, then return its state.
+///
+/// # Arguments
+/// * `context` - `TradingContext` for market data
+/// * `paper` - options of paper exchange
+pub fn paper_sync(context: &TradingContext, paper: &PaperOptions) -> Result<PaperState, StatusCode> {
+    let _lock = paper_lock_state(&paper.state_path)?;
+    let mut state = paper_load_state(&paper.state_path)?;
+    paper_sync_market(context, paper, &mut state, "")?;
+    paper_trim_orders(&mut state);
+    paper_save_state(&paper.state_path, &state)?;
+    Ok(state)
+}
+
+/// Get latest information of `symbol` from replayed market data.
+///
+/// # Arguments
+/// * `context` - `TradingContext`
+/// * `paper` - options of paper exchange
+/// * `symbol` - symbol to get its latest information
+pub fn paper_get_ticker(context: &TradingContext, paper: &PaperOptions, symbol: &str) -> Result<BybitLatestInformationSymbolResult, StatusCode> {
+    let _lock = paper_lock_state(&paper.state_path)?;
+    let mut state = paper_load_state(&paper.state_path)?;
+    paper_sync_market(context, paper, &mut state, symbol)?;
+    paper_save_state(&paper.state_path, &state)?;
+
+    let quote = state.quotes.get(symbol).ok_or_else(|| {
+        eprintln!("Error: no market data of {} replayed yet", symbol);
+        StatusCode::ApiEmptyResult
+    })?;
+    Ok(BybitLatestInformationSymbolResult {
+        symbol: symbol.to_owned(),
+        bid_price: quote.bid.to_string(),
+        ask_price: quote.ask.to_string(),
+        last_price: quote.last.to_string(),
+        mark_price: quote.mark.to_string(),
+        index_price: quote.index.to_string(),
+        funding_rate: quote.funding_rate.to_string(),
+        ..Default::default()
+    })
+}
+
+/// Emulate private stream by polling paper exchange, until `running` is `false`.
+/// Changes of orders, and positions are reported to `on_event` as if they came
+/// from Bybit's private stream.
+///
+/// # Arguments
+/// * `context` - `TradingContext` for market data
+/// * `paper` - options of paper exchange
+/// * `running` - flag to stop the stream
+/// * `connected` - set while the stream is running
+/// * `on_event` - callback for each event
+pub fn run_paper_private_stream<F: FnMut(BybitPrivateStreamEvent)>(context: &TradingContext, paper: &PaperOptions, running: &AtomicBool, connected: &AtomicBool, mut on_event: F) {
+    let mut seen_orders: HashMap<String, (String, f64)> = HashMap::new();
+    let mut seen_positions: HashMap<String, (String, f64)> = HashMap::new();
+    let mut first_poll = true;
+    connected.store(true, Ordering::SeqCst);
+
+    while running.load(Ordering::SeqCst) {
+        if let Ok(state) = paper_sync(context, paper) {
+            let orders: Vec<BybitWsOrderData> = state.orders.iter()
+                .filter(|o| seen_orders.get(&o.order_id) != Some(&(o.order_status.clone(), o.cum_exec_qty)))
+                .map(|o| BybitWsOrderData {
+                    order_id: o.order_id.clone(),
+                    order_link_id: String::new(),
+                    symbol: o.symbol.clone(),
+                    side: o.side.clone(),
+                    order_status: o.order_status.clone(),
+                    leaves_qty: o.leaves_qty,
+                    cum_exec_qty: o.cum_exec_qty,
+                    cum_exec_value: o.cum_exec_value,
+                    cum_exec_fee: o.cum_exec_fee,
+                    reject_reason: o.reject_reason.clone(),
+                    cancel_type: o.cancel_type.clone(),
+                })
+                .collect();
+            let positions: Vec<BybitWsPositionData> = state.positions.values()
+                .filter(|p| seen_positions.get(&p.symbol) != Some(&(p.side.clone(), p.size)))
+                .map(|p| BybitWsPositionData {
+                    symbol: p.symbol.clone(),
+                    side: p.side.clone(),
+                    size: p.size,
+                    entry_price: p.entry_price,
+                    unrealised_pnl: paper_unrealised_pnl(&state, p),
+                })
+                .collect();
+
+            for o in orders.iter() {
+                seen_orders.insert(o.order_id.clone(), (o.order_status.clone(), o.cum_exec_qty));
+            }
+            for p in positions.iter() {
+                seen_positions.insert(p.symbol.clone(), (p.side.clone(), p.size));
+            }
+            // first poll only learns what is already there
+            if !first_poll {
+                if !orders.is_empty() {
+                    on_event(BybitPrivateStreamEvent::Order(orders));
+                }
+                if !positions.is_empty() {
+                    on_event(BybitPrivateStreamEvent::Position(positions));
+                }
+            }
+            first_poll = false;
+        }
+
+        let mut waited = 0;
+        while waited < PAPER_POLL_INTERVAL_MILLIS && running.load(Ordering::SeqCst) {
+            std::thread::sleep(Duration::from_millis(100));
+            waited += 100;
+        }
+    }
+
+    connected.store(false, Ordering::SeqCst);
+}
+
+/// Print balances, positions, and active orders of paper exchange.
+///
+/// # Arguments
+/// * `state` - state of paper exchange
+pub fn print_paper_status(state: &PaperState) -> Result<(), StatusCode> {
+    let positions: Vec<&PaperPosition> = state.positions.values().filter(|p| p.size > 0.0).collect();
+    let orders: Vec<&PaperOrder> = state.orders.iter().filter(|o| paper_is_order_active(o)).collect();
+
+    match get_output_mode() {
+        OutputMode::Json => {
+            let positions: Vec<_> = positions.iter()
+                .map(|p| serde_json::json!({
+                    "symbol": p.symbol, "side": p.side, "size": p.size, "entry_price": p.entry_price,
+                    "stop_loss": p.stop_loss, "take_profit": p.take_profit,
+                    "unrealised_pnl": paper_unrealised_pnl(state, p), "realised_pnl": p.realised_pnl,
+                    "funding_paid": p.funding_paid, "coin": get_settle_coin(&p.symbol),
+                }))
+                .collect();
+            print_json(&serde_json::json!({
+                "balances": state.balances,
+                "initial_balance": state.initial_balance,
+                "positions": positions,
+                "orders": orders,
+            }))?;
+        },
+        OutputMode::Table => {
+            let mut balances: Vec<Vec<String>> = state.balances.iter().map(|(coin, balance)| vec![coin.clone(), format!("{:.8}", balance)]).collect();
+            if balances.is_empty() {
+                balances.push(vec![String::from("*"), state.initial_balance.to_string()]);
+            }
+            print_table(&["coin", "balance"], &balances);
+            if !positions.is_empty() {
+                let rows: Vec<Vec<String>> = positions.iter()
+                    .map(|p| vec![p.symbol.clone(), p.side.clone(), p.size.to_string(), p.entry_price.to_string(), p.stop_loss.to_string(),
+                        p.take_profit.to_string(), format!("{:.8}", paper_unrealised_pnl(state, p)), format!("{:.8}", p.realised_pnl), format!("{:.8}", p.funding_paid)])
+                    .collect();
+                print_table(&["symbol", "side", "size", "entry_price", "stop_loss", "take_profit", "unrealised_pnl", "realised_pnl", "funding_paid"], &rows);
+            }
+            if !orders.is_empty() {
+                let rows: Vec<Vec<String>> = orders.iter()
+                    .map(|o| vec![o.order_id.clone(), o.side.clone(), o.qty.to_string(), o.symbol.clone(), o.price.to_string(), o.order_status.clone(),
+                        o.cum_exec_qty.to_string(), o.queue_ahead.to_string()])
+                    .collect();
+                print_table(&["order_id", "side", "qty", "symbol", "price", "status", "filled", "queue_ahead"], &rows);
+            }
+        },
+        OutputMode::Plain if is_quiet() => {},
+        OutputMode::Plain => {
+            if state.balances.is_empty() {
+                println!("balance {} of each settlement coin, nothing traded yet", state.initial_balance);
+            }
+            for (coin, balance) in state.balances.iter() {
+                println!("balance {:.8} {}", balance, coin);
+            }
+            for p in positions.iter() {
+                let coin = get_settle_coin(&p.symbol);
+                println!("position {} {} {} @ {} (sl {}, tp {}), unrealised {:.8} {}, realised {:.8} {}, funding paid {:.8} {}",
+                    p.symbol, p.side, p.size, p.entry_price, p.stop_loss, p.take_profit,
+                    paper_unrealised_pnl(state, p), coin, p.realised_pnl, coin, p.funding_paid, coin);
+            }
+            for o in orders.iter() {
+                println!("order {} {} {} {} @ {}: {}, filled {}, queue ahead {}", o.order_id, o.side, o.qty, o.symbol, o.price, o.order_status, o.cum_exec_qty, o.queue_ahead);
+            }
+        },
+    }
+    Ok(())
+}
+
+/// Bring market data of symbols with active orders, positions, and `symbol` up
+/// to now, matching resting orders, triggering stop-loss/take-profit, and
+/// settling funding along the way.
+fn paper_sync_market(context: &TradingContext, paper: &PaperOptions, state: &mut PaperState, symbol: &str) -> Result<(), StatusCode> {
+    let market_time = match &paper.replay_path {
+        Some(path) => paper_sync_replay(state, path)?,
+        None => {
+            let mut symbols: BTreeSet<String> = state.orders.iter().filter(|o| paper_is_order_active(o)).map(|o| o.symbol.clone()).collect();
+            symbols.extend(state.positions.values().filter(|p| p.size > 0.0).map(|p| p.symbol.clone()));
+            if !symbol.is_empty() {
+                symbols.insert(symbol.to_owned());
+            }
+            for s in symbols.iter() {
+                paper_sync_live(context, paper, state, s)?;
+            }
+            get_unix_timestamp_as_millis() as u64
+        }
+    };
+    paper_settle_funding(state, market_time);
+    Ok(())
+}
+
+/// Sync live market data of `symbol` from Bybit's public API.
+fn paper_sync_live(context: &TradingContext, paper: &PaperOptions, state: &mut PaperState, symbol: &str) -> Result<(), StatusCode> {
+    let now = get_unix_timestamp_as_millis() as u64;
+    let ticker = api_get_ticker(context, symbol)?;
+    let parse = |v: &str| v.parse::<f64>().unwrap_or(0.0);
+
+    let book = paper_get_live_book(context, symbol)?;
+    let best_size = |side: &str, price: f64| book.iter().find(|l| l.side == side && l.price == price).map(|l| l.size).unwrap_or(0.0);
+    let (bid, ask) = (parse(&ticker.bid_price), parse(&ticker.ask_price));
+    let quote = PaperQuote {
+        symbol: symbol.to_owned(),
+        time: now,
+        bid,
+        ask,
+        bid_size: best_size("Buy", bid),
+        ask_size: best_size("Sell", ask),
+        last: parse(&ticker.last_price),
+        mark: parse(&ticker.mark_price),
+        index: parse(&ticker.index_price),
+        funding_rate: parse(&ticker.funding_rate),
+    };
+
+    let mut trades = api_public_get::<BybitTradingRecordsResponse>(context, &format!("/v2/public/trading-records?symbol={}&limit=1000", symbol))?
+        .result.unwrap_or_default();
+    trades.sort_by_key(|t| t.id);
+
+    // trades before the first sync are history, not to be matched
+    let last_trade_id = state.last_trade_ids.get(symbol).copied();
+    if let Some(last) = trades.last() {
+        state.last_trade_ids.insert(symbol.to_owned(), last.id);
+    }
+    let mut events: Vec<PaperMarketEvent> = match last_trade_id {
+        Some(last_trade_id) => trades.into_iter()
+            .filter(|t| t.id > last_trade_id)
+            .map(|t| PaperMarketEvent::Trade { symbol: symbol.to_owned(), time: now, price: t.price, qty: t.qty, side: t.side })
+            .collect(),
+        None => Vec::new(),
+    };
+    events.push(PaperMarketEvent::Quote(quote));
+
+    if let Some(record_path) = &paper.record_path {
+        paper_record_events(record_path, &events);
+    }
+    for event in events {
+        paper_apply_market_event(state, event);
+    }
+    Ok(())
+}
+
+/// Get order book snapshot of `symbol` from Bybit's public API.
+fn paper_get_live_book(context: &TradingContext, symbol: &str) -> Result<Vec<BybitOrderBookLevel>, StatusCode> {
+    let json = api_public_get::<BybitOrderBookResponse>(context, &format!("/v2/public/orderBook/L2?symbol={}", symbol))?;
+    if json.ret_code != 0 {
+        eprintln!("Error: {}", json.ret_msg);
+        return Err(StatusCode::ErrorApiResponse);
+    }
+    Ok(json.result.unwrap_or_default())
+}
+
+/// Append `events` to recording at `path` as JSONL.
+fn paper_record_events(path: &Path, events: &[PaperMarketEvent]) {
+    let file = std::fs::OpenOptions::new().create(true).append(true).open(path);
+    if let Ok(mut file) = file {
+        for event in events {
+            if let Ok(line) = serde_json::to_string(event) {
+                let _ = writeln!(file, "{}", line);
+            }
+        }
+    }
+}
+
+/// Replay recorded market data at `path` in real time since the last replay.
+/// Return current market time of the replay.
+fn paper_sync_replay(state: &mut PaperState, path: &Path) -> Result<u64, StatusCode> {
+    let file = std::fs::File::open(path).map_err(|e| {
+        eprintln!("Error: cannot read recorded market data {}: {}", path.display(), e);
+        StatusCode::ErrorPaper
+    })?;
+    let now = get_unix_timestamp_as_millis() as u64;
+    let path_str = path.display().to_string();
+
+    let mut cursor = match state.replay.take() {
+        Some(cursor) if cursor.path == path_str => cursor,
+        _ => PaperReplayCursor { path: path_str, ..Default::default() },
+    };
+
+    let mut consumed = 0;
+    for (i, line) in std::io::BufReader::new(file).lines().enumerate().skip(cursor.line) {
+        let line = line.map_err(|_| StatusCode::ErrorPaper)?;
+        if line.trim().is_empty() {
+            consumed = i + 1;
+            continue;
+        }
+        let event: PaperMarketEvent = serde_json::from_str(&line).map_err(|e| {
+            eprintln!("Error: malformed recorded market data at line {}: {}", i + 1, e);
+            StatusCode::ErrorPaper
+        })?;
+        let time = match &event {
+            PaperMarketEvent::Quote(q) => q.time,
+            PaperMarketEvent::Trade { time, .. } => *time,
+        };
+
+        // replay starts at the first event, then follows wall-clock time
+        if cursor.market_time == 0 {
+            cursor.market_time = time;
+            cursor.wall_time = now;
+        }
+        if time > cursor.market_time + now.saturating_sub(cursor.wall_time) {
+            break;
+        }
+        paper_apply_market_event(state, event);
+        consumed = i + 1;
+    }
+
+    cursor.market_time += now.saturating_sub(cursor.wall_time);
+    cursor.wall_time = now;
+    cursor.line = cursor.line.max(consumed);
+    let market_time = cursor.market_time;
+    state.replay = Some(cursor);
+    Ok(market_time)
+}
+
+/// Apply a market event, matching resting orders, and triggering stop-loss/take-profit.
+fn paper_apply_market_event(state: &mut PaperState, event: PaperMarketEvent) {
+    match event {
+        PaperMarketEvent::Quote(mut quote) => {
+            // without last traded price, keep the previous one as long as it is
+            // still within the spread
+            if quote.last <= 0.0 {
+                quote.last = match state.quotes.get(&quote.symbol).map(|q| q.last).filter(|v| *v > 0.0) {
+                    Some(last) => last.clamp(quote.bid.min(quote.ask), quote.ask.max(quote.bid)),
+                    None => (quote.bid + quote.ask) / 2.0,
+                };
+            }
+            if quote.mark <= 0.0 {
+                quote.mark = quote.last;
+            }
+            if quote.index <= 0.0 {
+                quote.index = quote.mark;
+            }
+            let symbol = quote.symbol.clone();
+            let (bid, ask, last) = (quote.bid, quote.ask, quote.last);
+            state.quotes.insert(symbol.clone(), quote);
+
+            // book moved through resting orders, so they are taken
+            for i in 0..state.orders.len() {
+                let o = &state.orders[i];
+                if o.symbol != symbol || !paper_is_order_active(o) || o.order_type != "Limit" {
+                    continue;
+                }
+                let crossed = if o.side == "Buy" { ask > 0.0 && ask <= o.price } else { bid > 0.0 && bid >= o.price };
+                if crossed {
+                    let (qty, price) = (o.leaves_qty, o.price);
+                    paper_fill(state, i, qty, price, true);
+                }
+            }
+            paper_check_triggers(state, &symbol, last);
+        },
+        PaperMarketEvent::Trade { symbol, price, qty, side, .. } => {
+            if let Some(quote) = state.quotes.get_mut(&symbol) {
+                quote.last = price;
+            }
+            let tick_step = get_default_tick_steps().get(&symbol).copied().unwrap_or(f64::EPSILON);
+
+            let mut remaining = qty;
+            for i in 0..state.orders.len() {
+                let o = &mut state.orders[i];
+                if o.symbol != symbol || !paper_is_order_active(o) || o.order_type != "Limit" {
+                    continue;
+                }
+                let is_buy = o.side == "Buy";
+                let through = if is_buy { price < o.price } else { price > o.price };
+                // only takers from the other side trade at our level
+                let at_level = (price - o.price).abs() < tick_step / 2.0 && (side == "Buy") != is_buy;
+
+                let fill_qty = if through {
+                    o.leaves_qty
+                } else if at_level {
+                    let consumed = remaining.min(o.queue_ahead);
+                    o.queue_ahead -= consumed;
+                    remaining -= consumed;
+                    let fill_qty = remaining.min(o.leaves_qty);
+                    remaining -= fill_qty;
+                    fill_qty
+                } else {
+                    0.0
+                };
+                if fill_qty > 0.0 {
+                    let order_price = o.price;
+                    paper_fill(state, i, fill_qty, order_price, true);
+                }
+            }
+            paper_check_triggers(state, &symbol, price);
+        },
+    }
+}
+
+/// Create an order from `params` of order create end-point.
+/// PostOnly order which would take liquidity is cancelled, market order, and
+/// crossing limit order are filled at best price of the other side as taker.
+/// Return the order, or Bybit's error code along with its message.
+fn paper_create_order(context: &TradingContext, paper: &PaperOptions, state: &mut PaperState, params: &BTreeMap<&str, serde_json::Value>) -> Result<serde_json::Value, (u32, String)> {
+    let str_param = |name: &str| params.get(name).and_then(|v| v.as_str()).unwrap_or_default().to_owned();
+    let f64_param = |name: &str| params.get(name).and_then(|v| v.as_f64());
+
+    let symbol = str_param("symbol");
+    let side = str_param("side");
+    let order_type = str_param("order_type");
+    let mut qty = f64_param("qty").unwrap_or(0.0);
+    let reduce_only = params.get("reduce_only").and_then(|v| v.as_bool()).unwrap_or(false);
+
+    let quote = match state.quotes.get(&symbol) {
+        Some(quote) if quote.bid > 0.0 && quote.ask > 0.0 => quote.clone(),
+        _ => return Err((10001, format!("no market data of {}", symbol))),
+    };
+    if !(side == "Buy" || side == "Sell") || qty <= 0.0 || !(order_type == "Limit" || order_type == "Market") {
+        return Err((10001, String::from("invalid order parameters")));
+    }
+    if reduce_only {
+        let position = state.positions.get(&symbol).filter(|p| p.size > 0.0 && p.side != side);
+        match position {
+            Some(position) => qty = qty.min(position.size),
+            None => return Err((30063, String::from("reduce-only order has no position to reduce"))),
+        }
+    }
+
+    let order_link_id = str_param("order_link_id");
+    if !order_link_id.is_empty() && state.orders.iter().any(|o| o.order_link_id == order_link_id) {
+        return Err((10001, format!("order_link_id {} is repeated", order_link_id)));
+    }
+
+    let is_buy = side == "Buy";
+    let price = if order_type == "Market" { if is_buy { quote.ask } else { quote.bid } } else { f64_param("price").unwrap_or(0.0) };
+    if price <= 0.0 {
+        return Err((10001, String::from("invalid price")));
+    }
+
+    let order = PaperOrder {
+        order_id: format!("paper-{}", state.next_order_id),
+        order_link_id,
+        symbol: symbol.clone(),
+        side: side.clone(),
+        order_type: order_type.clone(),
+        price,
+        qty,
+        time_in_force: str_param("time_in_force"),
+        order_status: String::from("New"),
+        leaves_qty: qty,
+        stop_loss: f64_param("stop_loss").filter(|v| *v > 0.0),
+        take_profit: f64_param("take_profit").filter(|v| *v > 0.0),
+        reduce_only,
+        created_time: get_unix_timestamp_as_millis() as u64,
+        ..Default::default()
+    };
+    state.next_order_id += 1;
+    state.orders.push(order);
+    let index = state.orders.len() - 1;
+
+    let crosses = if is_buy { price >= quote.ask } else { price <= quote.bid };
+    if order_type == "Market" || crosses {
+        if state.orders[index].time_in_force == "PostOnly" {
+            let o = &mut state.orders[index];
+            o.order_status = String::from("Cancelled");
+            o.reject_reason = String::from("EC_PostOnlyWillTakeLiquidity");
+            o.leaves_qty = 0.0;
+        } else {
+            let fill_price = if is_buy { quote.ask } else { quote.bid };
+            paper_fill(state, index, qty, fill_price, false);
+            let o = &mut state.orders[index];
+            if o.leaves_qty > 0.0 && o.time_in_force != "GoodTillCancel" {
+                o.order_status = String::from("Cancelled");
+                o.leaves_qty = 0.0;
+            }
+        }
+    } else {
+        // join the queue behind what is already resting at our price
+        let best = if is_buy { quote.bid } else { quote.ask };
+        let better = if is_buy { price > best } else { price < best };
+        state.orders[index].queue_ahead = if better {
+            0.0
+        } else if price == best {
+            if is_buy { quote.bid_size } else { quote.ask_size }
+        } else if paper.replay_path.is_none() {
+            let book_side = if is_buy { "Buy" } else { "Sell" };
+            paper_get_live_book(context, &symbol).map_err(|e| (10016, get_status_code_message(&e).to_owned()))?.iter().find(|l| l.side == book_side && l.price == price).map(|l| l.size).unwrap_or(0.0)
+        } else {
+            0.0
+        };
+    }
+
+    serde_json::to_value(&state.orders[index]).map_err(|_| (10016, String::from("cannot serialize order")))
+}
+
+/// Cancel all active orders of `symbol`.
+fn paper_cancel_all_orders(state: &mut PaperState, symbol: &str) {
+    for o in state.orders.iter_mut().filter(|o| o.symbol == symbol && paper_is_order_active(o)) {
+        o.order_status = String::from("Cancelled");
+        o.cancel_type = String::from("CancelByUser");
+        o.leaves_qty = 0.0;
+    }
+}
+
+/// Fill `qty` of order at `index` at `price`, then update position and balance.
+/// Reduce-only order is filled no more than position left to reduce, as it
+/// might have shrunk since the order was created, and the rest of it is
+/// cancelled.
+fn paper_fill(state: &mut PaperState, index: usize, mut qty: f64, price: f64, is_maker: bool) {
+    let o = &state.orders[index];
+    let reducible = state.positions.get(&o.symbol).filter(|p| p.size > 0.0 && p.side != o.side).map(|p| p.size).unwrap_or(0.0);
+    let clamped = o.reduce_only && qty > reducible;
+    if clamped {
+        qty = reducible;
+    }
+    let fee = qty / price * if is_maker { MAKER_FEE_RATE } else { TAKER_FEE_RATE };
+
+    let o = &mut state.orders[index];
+    if clamped {
+        o.order_status = String::from("Cancelled");
+        o.cancel_type = String::from("CancelByReduceOnly");
+        o.leaves_qty = 0.0;
+        if qty <= 0.0 {
+            return;
+        }
+    } else if qty <= 0.0 {
+        return;
+    } else {
+        o.leaves_qty -= qty;
+        o.order_status = String::from(if o.leaves_qty <= 0.0 { "Filled" } else { "PartiallyFilled" });
+    }
+    o.cum_exec_qty += qty;
+    o.cum_exec_value += qty / price;
+    o.cum_exec_fee += fee;
+    let (symbol, side, stop_loss, take_profit) = (o.symbol.clone(), o.side.clone(), o.stop_loss, o.take_profit);
+
+    let coin = get_settle_coin(&symbol).to_owned();
+    let initial_balance = state.initial_balance;
+    let position = state.positions.entry(symbol.clone()).or_insert_with(|| PaperPosition {
+        symbol: symbol.clone(),
+        side: String::from("None"),
+        ..Default::default()
+    });
+
+    let mut realised = -fee;
+    if position.size == 0.0 || position.side == side {
+        // inverse contract averages entry price by value in coin
+        let value = if position.size > 0.0 { position.size / position.entry_price } else { 0.0 } + qty / price;
+        position.size += qty;
+        position.entry_price = position.size / value;
+        position.side = side;
+    } else {
+        let closed = qty.min(position.size);
+        realised += if position.side == "Buy" {
+            closed * (1.0 / position.entry_price - 1.0 / price)
+        } else {
+            closed * (1.0 / price - 1.0 / position.entry_price)
+        };
+        position.size -= closed;
+        if qty > closed {
+            position.size = qty - closed;
+            position.entry_price = price;
+            position.side = side;
+        } else if position.size == 0.0 {
+            position.side = String::from("None");
+            position.entry_price = 0.0;
+            position.stop_loss = 0.0;
+            position.take_profit = 0.0;
+        }
+    }
+
+    // stop-loss, and take-profit of the order apply to the position it opens
+    if position.size > 0.0 {
+        if let Some(stop_loss) = stop_loss {
+            position.stop_loss = stop_loss;
+        }
+        if let Some(take_profit) = take_profit {
+            position.take_profit = take_profit;
+        }
+    }
+    position.realised_pnl += realised;
+    *state.balances.entry(coin).or_insert(initial_balance) += realised;
+}
+
+/// Close position of `symbol` with a market order if its stop-loss, or
+/// take-profit is triggered by `price`.
+fn paper_check_triggers(state: &mut PaperState, symbol: &str, price: f64) {
+    let position = match state.positions.get(symbol) {
+        Some(p) if p.size > 0.0 && price > 0.0 => p,
+        _ => return,
+    };
+    let is_long = position.side == "Buy";
+    let stop_hit = position.stop_loss > 0.0 && if is_long { price <= position.stop_loss } else { price >= position.stop_loss };
+    let take_hit = position.take_profit > 0.0 && if is_long { price >= position.take_profit } else { price <= position.take_profit };
+    let exit_price = match (stop_hit, take_hit) {
+        (true, _) => position.stop_loss,
+        (false, true) => position.take_profit,
+        (false, false) => return,
+    };
+
+    let qty = position.size;
+    let order = PaperOrder {
+        order_id: format!("paper-{}", state.next_order_id),
+        symbol: symbol.to_owned(),
+        side: String::from(if is_long { "Sell" } else { "Buy" }),
+        order_type: String::from("Market"),
+        price: exit_price,
+        qty,
+        time_in_force: String::from("ImmediateOrCancel"),
+        order_status: String::from("New"),
+        leaves_qty: qty,
+        reduce_only: true,
+        created_time: get_unix_timestamp_as_millis() as u64,
+        ..Default::default()
+    };
+    state.next_order_id += 1;
+    state.orders.push(order);
+    let index = state.orders.len() - 1;
+    paper_fill(state, index, qty, exit_price, false);
+}
+
+/// Settle funding of all positions for every funding time passed up to `market_time`.
+fn paper_settle_funding(state: &mut PaperState, market_time: u64) {
+    if state.last_funding_time == 0 || market_time < state.last_funding_time {
+        state.last_funding_time = market_time;
+        return;
+    }
+
+    let mut funding_time = (state.last_funding_time / FUNDING_INTERVAL_MILLIS + 1) * FUNDING_INTERVAL_MILLIS;
+    while funding_time <= market_time {
+        for position in state.positions.values_mut().filter(|p| p.size > 0.0) {
+            let quote = match state.quotes.get(&position.symbol) {
+                Some(quote) if quote.mark > 0.0 => quote,
+                _ => continue,
+            };
+            // longs pay shorts when rate is positive
+            let value = position.size / quote.mark;
+            let paid = value * quote.funding_rate * if position.side == "Buy" { 1.0 } else { -1.0 };
+            position.funding_paid += paid;
+            position.realised_pnl -= paid;
+            *state.balances.entry(get_settle_coin(&position.symbol).to_owned()).or_insert(state.initial_balance) -= paid;
+        }
+        funding_time += FUNDING_INTERVAL_MILLIS;
+    }
+    state.last_funding_time = market_time;
+}
+
+/// Json of position of `symbol` shaped as of Bybit's position list.
+fn paper_position_json(state: &PaperState, symbol: &str) -> serde_json::Value {
+    match state.positions.get(symbol).filter(|p| p.size > 0.0) {
+        Some(p) => serde_json::json!({
+            "symbol": p.symbol,
+            "side": p.side,
+            "size": p.size,
+            "entry_price": p.entry_price,
+            "unrealised_pnl": paper_unrealised_pnl(state, p),
+            "stop_loss": p.stop_loss,
+            "take_profit": p.take_profit,
+            "realised_pnl": p.realised_pnl,
+        }),
+        None => serde_json::json!({"symbol": symbol, "side": "None", "size": 0, "entry_price": 0, "unrealised_pnl": 0}),
+    }
+}
+
+/// Unrealised PnL of `position` in settlement coin at latest mark price.
+fn paper_unrealised_pnl(state: &PaperState, position: &PaperPosition) -> f64 {
+    let mark = state.quotes.get(&position.symbol).map(|q| q.mark).unwrap_or(0.0);
+    if position.size == 0.0 || mark <= 0.0 || position.entry_price <= 0.0 {
+        return 0.0;
+    }
+    let pnl = position.size * (1.0 / position.entry_price - 1.0 / mark);
+    if position.side == "Buy" { pnl } else { -pnl }
+}
+
+/// Whether or not order is still resting on paper exchange.
+fn paper_is_order_active(order: &PaperOrder) -> bool {
+    matches!(order.order_status.as_str(), "New" | "PartiallyFilled")
+}
+
+/// Drop the oldest finished orders beyond `MAX_FINISHED_ORDERS`.
+fn paper_trim_orders(state: &mut PaperState) {
+    let finished = state.orders.iter().filter(|o| !paper_is_order_active(o)).count();
+    let mut to_drop = finished.saturating_sub(MAX_FINISHED_ORDERS);
+    state.orders.retain(|o| {
+        if to_drop > 0 && !paper_is_order_active(o) {
+            to_drop -= 1;
+            return false;
+        }
+        true
+    });
+}
@@ -0,0 +1,188 @@
+use crate::types::*;
+use crate::defines::*;
+use crate::util::*;
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Token buckets by account, and end-point, shared by all requests of the process.
+static TOKEN_BUCKETS: Mutex<Option<HashMap<String, TokenBucket>>> = Mutex::new(None);
+
+/// Window of Bybit's rate limits.
+const RATE_LIMIT_WINDOW_MILLIS: u64 = 60 * 1000;
+
+/// Bybit's ret_codes of a request rejected by rate limit: too many visits, and
+/// exceeded IP rate limit.
+const RATE_LIMIT_RET_CODES: [i64; 2] = [10006, IP_RATE_LIMIT_RET_CODE];
+
+/// Bybit's ret_code of a request rejected as its IP address exceeded rate limit.
+const IP_RATE_LIMIT_RET_CODE: i64 = 10018;
+
+/// Rate-limit budget as reported by Bybit along with a response.
+#[derive(Debug, Default)]
+pub struct RateLimitStatus {
+    pub limit: Option<u64>,
+    pub remaining: Option<u64>,
+    pub reset_at: Option<u64>,
+}
+
+/// Key of token bucket of `end_point`. Private end-points are limited per
+/// account, public ones per IP address.
+///
+/// # Arguments
+/// * `context` - `TradingContext` of the account
+/// * `end_point` - end-point URL, query string is ignored
+/// * `private` - whether `end_point` is of private API
+pub fn get_rate_limit_key(context: &TradingContext, end_point: &str, private: bool) -> String {
+    let path = end_point.split('?').next().unwrap_or(end_point);
+    if private {
+        format!("{} {}", get_account_label(context), path)
+    } else {
+        format!("public {}", path)
+    }
+}
+
+/// Requests per minute allowed on `end_point` until Bybit tells otherwise.
+/// Placing, and cancelling orders are limited tighter than querying.
+fn get_default_rate_limit(end_point: &str) -> u64 {
+    match end_point {
+        "/v2/private/order/create" | "/v2/private/order/cancel" | "/v2/private/order/cancelAll" => 100,
+        _ => 600,
+    }
+}
+
+/// Take a token from bucket of `key` before sending a request, waiting for it
+/// to be refilled if the budget is used up. Return `StatusCode::ErrorRateLimited`
+/// if it won't be refilled within `RATE_LIMIT_MAX_WAIT_MILLIS`.
+///
+/// # Arguments
+/// * `key` - key of token bucket, see `get_rate_limit_key`
+pub fn acquire_rate_limit_token(key: &str) -> Result<(), StatusCode> {
+    let wait_millis = {
+        let mut buckets = TOKEN_BUCKETS.lock().unwrap();
+        let now = get_unix_timestamp_as_millis() as u64;
+        let bucket = buckets.get_or_insert_with(HashMap::new).entry(key.to_owned()).or_insert_with(|| {
+            let limit = get_default_rate_limit(key.rsplit(' ').next().unwrap_or(key));
+            TokenBucket { limit, tokens: limit as f64, updated_at: now, reset_at: None }
+        });
+        refill_token_bucket(bucket, now);
+
+        let wait_millis = if bucket.tokens >= 1.0 {
+            0
+        } else {
+            match bucket.reset_at.filter(|reset_at| *reset_at > now) {
+                Some(reset_at) => reset_at - now,
+                None => ((1.0 - bucket.tokens) * RATE_LIMIT_WINDOW_MILLIS as f64 / bucket.limit.max(1) as f64).ceil() as u64,
+            }
+        };
+        if wait_millis > RATE_LIMIT_MAX_WAIT_MILLIS {
+            eprintln!("Error: rate limit of {} is used up, it resets in {:.1} secs", key, wait_millis as f64 / 1000.0);
+            return Err(StatusCode::ErrorRateLimited);
+        }
+        // taken ahead, so concurrent requests wait in turn
+        bucket.tokens -= 1.0;
+        wait_millis
+    };
+
+    if wait_millis > 0 {
+        tracing::debug!(key, wait_millis, "rate limit is used up, waiting");
+        std::thread::sleep(Duration::from_millis(wait_millis));
+    }
+    Ok(())
+}
+
+/// Refill `bucket` for time passed since it was last updated.
+fn refill_token_bucket(bucket: &mut TokenBucket, now: u64) {
+    if bucket.reset_at.is_some_and(|reset_at| reset_at <= now) {
+        bucket.tokens = bucket.limit as f64;
+        bucket.reset_at = None;
+    } else {
+        let elapsed = now.saturating_sub(bucket.updated_at) as f64;
+        bucket.tokens = (bucket.tokens + elapsed * bucket.limit as f64 / RATE_LIMIT_WINDOW_MILLIS as f64).min(bucket.limit as f64);
+    }
+    bucket.updated_at = now;
+}
+
+/// Parse rate-limit budget from `headers` of a response, falling back to
+/// `rate_limit*` fields of its `json` body as of v2 API.
+///
+/// # Arguments
+/// * `headers` - headers of the response
+/// * `json` - body of the response, if it is json
+pub fn parse_rate_limit_status(headers: &isahc::http::HeaderMap, json: Option<&serde_json::Value>) -> RateLimitStatus {
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok()).and_then(|v| v.trim().parse::<u64>().ok());
+    let field = |name: &str| json.and_then(|j| j.get(name)).and_then(|v| v.as_u64());
+    RateLimitStatus {
+        limit: header("X-Bapi-Limit").or_else(|| field("rate_limit")),
+        remaining: header("X-Bapi-Limit-Status").or_else(|| field("rate_limit_status")),
+        reset_at: header("X-Bapi-Limit-Reset-Timestamp").or_else(|| field("rate_limit_reset_ms")),
+    }
+}
+
+/// Sync bucket of `key` with budget reported by Bybit, and log it.
+///
+/// # Arguments
+/// * `key` - key of token bucket, see `get_rate_limit_key`
+/// * `status` - budget parsed by `parse_rate_limit_status`
+pub fn update_rate_limit(key: &str, status: &RateLimitStatus) {
+    let now = get_unix_timestamp_as_millis() as u64;
+    let mut buckets = TOKEN_BUCKETS.lock().unwrap();
+    if let Some(bucket) = buckets.as_mut().and_then(|b| b.get_mut(key)) {
+        if let Some(limit) = status.limit.filter(|l| *l > 0) {
+            bucket.limit = limit;
+        }
+        // tokens taken by requests still in flight are not counted by Bybit yet
+        if let Some(remaining) = status.remaining {
+            refill_token_bucket(bucket, now);
+            bucket.tokens = bucket.tokens.min(remaining as f64);
+        }
+        bucket.reset_at = status.reset_at.filter(|reset_at| *reset_at > now);
+    }
+
+    if let Some(remaining) = status.remaining {
+        let resets_in_millis = status.reset_at.map(|reset_at| reset_at.saturating_sub(now));
+        tracing::debug!(key, remaining, limit = status.limit, resets_in_millis, "rate limit budget");
+    }
+}
+
+/// Whether response with HTTP `status`, and `json` body is a rejection by rate limit.
+/// HTTP 403 is only one along with Bybit's IP rate limit ret_code, otherwise it
+/// is a failure of authentication, or permission which retrying won't fix.
+pub fn is_rate_limited(status: isahc::http::StatusCode, json: Option<&serde_json::Value>) -> bool {
+    let ret_code = json.and_then(|j| j.get("ret_code")).and_then(|c| c.as_i64());
+    match status {
+        isahc::http::StatusCode::TOO_MANY_REQUESTS => true,
+        isahc::http::StatusCode::FORBIDDEN => ret_code == Some(IP_RATE_LIMIT_RET_CODE),
+        _ => ret_code.is_some_and(|c| RATE_LIMIT_RET_CODES.contains(&c)),
+    }
+}
+
+/// Time to wait before retrying a request rejected by rate limit for the
+/// `attempt`-th time, starting from 0. It is until the budget resets if Bybit
+/// reports it used up, otherwise exponential backoff with jitter.
+/// `None` if the wait would be longer than `RATE_LIMIT_MAX_WAIT_MILLIS`.
+///
+/// # Arguments
+/// * `attempt` - number of retries so far
+/// * `status` - budget reported along with the rejection
+pub fn get_rate_limit_backoff(attempt: u32, status: &RateLimitStatus) -> Option<Duration> {
+    let now = get_unix_timestamp_as_millis() as u64;
+    let backoff_millis = RATE_LIMIT_BACKOFF_BASE_MILLIS << attempt.min(16);
+    // half fixed, half random to spread out concurrent retries
+    let mut random = [0u8; 2];
+    let _ = ring::rand::SecureRandom::fill(&ring::rand::SystemRandom::new(), &mut random);
+    let jitter_millis = (backoff_millis / 2) * u16::from_le_bytes(random) as u64 / u16::MAX as u64;
+    let mut wait_millis = backoff_millis / 2 + jitter_millis;
+
+    if status.remaining == Some(0) {
+        if let Some(reset_at) = status.reset_at.filter(|reset_at| *reset_at > now) {
+            wait_millis = wait_millis.max(reset_at - now);
+        }
+    }
+    if wait_millis > RATE_LIMIT_MAX_WAIT_MILLIS {
+        None
+    } else {
+        Some(Duration::from_millis(wait_millis))
+    }
+}
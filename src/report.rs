@@ -0,0 +1,155 @@
+use crate::types::*;
+use crate::defines::*;
+use crate::util::*;
+use crate::output::*;
+use crate::journal::*;
+
+use std::collections::{BTreeMap, HashMap};
+
+/// Totals of a symbol accumulated into its `ExecutionReport`.
+#[derive(Default)]
+struct ExecutionTotals {
+    report: ExecutionReport,
+    times_to_fill: Vec<f64>,
+    /// Quantity of orders whose final state is known
+    final_qty: f64,
+    filled_qty: f64,
+    /// Price improvement in basis points times filled quantity
+    weighted_improvement: f64,
+    /// Filled quantity of orders with reference price
+    improvement_qty: f64,
+}
+
+/// Compute execution quality of orders of trade journal matching `filter`,
+/// per symbol in alphabetical order.
+///
+/// Time to fill is measured until the order was first seen filled, which is
+/// exact if it was confirmed on private stream, and at most the time of
+/// `journal --sync` otherwise.
+///
+/// # Arguments
+/// * `entries` - entries of trade journal in order they are recorded
+/// * `filter` - `JournalFilter` on orders to report
+pub fn get_execution_reports(entries: &[JournalEntry], filter: &JournalFilter) -> Vec<ExecutionReport> {
+    let mut filled_at: HashMap<(&str, &str), u64> = HashMap::new();
+    for entry in entries.iter() {
+        if let JournalEntry::Fill(fill) = entry {
+            if fill.state == OrderState::Filled {
+                filled_at.entry((&fill.account, &fill.order_id)).or_insert(fill.time);
+            }
+        }
+    }
+
+    let mut totals: BTreeMap<String, ExecutionTotals> = BTreeMap::new();
+
+    for (order, fill) in get_journal_orders(entries).into_iter().filter(|(order, _)| is_journal_order_matched(order, filter)) {
+        let t = totals.entry(order.symbol.clone()).or_insert_with(|| ExecutionTotals {
+            report: ExecutionReport { symbol: order.symbol.clone(), settle_coin: get_settle_coin(&order.symbol).to_owned(), ..Default::default() },
+            ..Default::default()
+        });
+        t.report.orders += 1;
+
+        let fill = match (&order.error, fill) {
+            (Some(_), _) => {
+                t.report.failed += 1;
+                continue;
+            },
+            (None, Some(fill)) if is_order_state_final(fill.state) => fill,
+            (None, _) => {
+                t.report.pending += 1;
+                continue;
+            }
+        };
+        t.final_qty += order.qty as f64;
+
+        match fill.state {
+            OrderState::Filled => t.report.filled += 1,
+            OrderState::CancelledPostOnly => t.report.post_only_rejected += 1,
+            _ if fill.cum_exec_qty > 0.0 => t.report.partially_filled += 1,
+            _ => t.report.cancelled += 1,
+        }
+        if fill.cum_exec_qty <= 0.0 || fill.cum_exec_value <= 0.0 {
+            continue;
+        }
+
+        t.filled_qty += fill.cum_exec_qty;
+        t.report.fees_paid += fill.cum_exec_fee;
+        t.report.market_order_fees += fill.cum_exec_value * TAKER_FEE_RATE;
+        if let Some(time) = filled_at.get(&(order.account.as_str(), fill.order_id.as_str())) {
+            t.times_to_fill.push(time.saturating_sub(order.time) as f64 / 1000.0);
+        }
+        if let Some(reference_price) = order.reference_price.filter(|p| *p > 0.0) {
+            let avg_price = fill.cum_exec_qty / fill.cum_exec_value;
+            let better_by = if order.side == "Buy" { reference_price - avg_price } else { avg_price - reference_price };
+            t.weighted_improvement += better_by / reference_price * 10000.0 * fill.cum_exec_qty;
+            t.improvement_qty += fill.cum_exec_qty;
+        }
+    }
+
+    totals.into_values()
+        .map(|mut t| {
+            let r = &mut t.report;
+            r.fill_rate_pcnt = (t.final_qty > 0.0).then(|| t.filled_qty / t.final_qty * 100.0);
+            let final_orders = r.filled + r.partially_filled + r.post_only_rejected + r.cancelled;
+            r.post_only_rejection_pcnt = (final_orders > 0).then(|| r.post_only_rejected as f64 / final_orders as f64 * 100.0);
+            if !t.times_to_fill.is_empty() {
+                t.times_to_fill.sort_by(|a, b| a.total_cmp(b));
+                let (times, n) = (&t.times_to_fill, t.times_to_fill.len());
+                r.avg_time_to_fill_secs = Some(times.iter().sum::<f64>() / n as f64);
+                r.median_time_to_fill_secs = Some(if n % 2 == 1 { times[n / 2] } else { (times[n / 2 - 1] + times[n / 2]) / 2.0 });
+            }
+            r.price_improvement_bps = (t.improvement_qty > 0.0).then(|| t.weighted_improvement / t.improvement_qty);
+            t.report
+        })
+        .collect()
+}
+
+/// Format `report` into lines to print.
+///
+/// # Arguments
+/// * `report` - `ExecutionReport` of a symbol
+pub fn format_execution_report(report: &ExecutionReport) -> Vec<String> {
+    let pcnt = |value: Option<f64>| value.map(|v| format!("{:.1}%", v)).unwrap_or_else(|| String::from("n/a"));
+    let secs = |value: Option<f64>| value.map(|v| format!("{:.1} secs", v)).unwrap_or_else(|| String::from("n/a"));
+    let coin = &report.settle_coin;
+
+    let mut lines = vec![
+        format!("{}: {} orders, {} failed, {} pending", report.symbol, report.orders, report.failed, report.pending),
+        format!("  filled {}, partially filled {}, post-only rejected {} ({}), cancelled {}",
+            report.filled, report.partially_filled, report.post_only_rejected, pcnt(report.post_only_rejection_pcnt), report.cancelled),
+        format!("  fill rate {} of qty, time to fill avg {}, median {}",
+            pcnt(report.fill_rate_pcnt), secs(report.avg_time_to_fill_secs), secs(report.median_time_to_fill_secs)),
+    ];
+    if let Some(bps) = report.price_improvement_bps {
+        lines.push(format!("  price improvement vs reference {:+.1} bps", bps));
+    }
+    lines.push(format!("  fees paid {:.8} {} vs {:.8} {} as market orders, saved {:.8} {}",
+        report.fees_paid, coin, report.market_order_fees, coin, report.market_order_fees - report.fees_paid, coin));
+    lines
+}
+
+/// Columns of execution quality in table output, in order of `get_execution_report_row`.
+pub const EXECUTION_REPORT_COLUMNS: [&str; 14] = ["symbol", "orders", "failed", "pending", "filled", "partial", "post_only_rejected",
+    "cancelled", "fill_rate", "avg_time_to_fill", "median_time_to_fill", "improvement_bps", "fees_paid", "market_order_fees"];
+
+/// Cells of `report` in order of `EXECUTION_REPORT_COLUMNS`.
+pub fn get_execution_report_row(report: &ExecutionReport) -> Vec<String> {
+    let pcnt = |value: Option<f64>| to_table_cell(value.map(|v| format!("{:.1}%", v)));
+    let secs = |value: Option<f64>| to_table_cell(value.map(|v| format!("{:.1}s", v)));
+    vec![
+        report.symbol.clone(),
+        report.orders.to_string(),
+        report.failed.to_string(),
+        report.pending.to_string(),
+        report.filled.to_string(),
+        report.partially_filled.to_string(),
+        format!("{} ({})", report.post_only_rejected, pcnt(report.post_only_rejection_pcnt)),
+        report.cancelled.to_string(),
+        pcnt(report.fill_rate_pcnt),
+        secs(report.avg_time_to_fill_secs),
+        secs(report.median_time_to_fill_secs),
+        to_table_cell(report.price_improvement_bps.map(|bps| format!("{:+.1}", bps))),
+        format!("{:.8} {}", report.fees_paid, report.settle_coin),
+        format!("{:.8} {}", report.market_order_fees, report.settle_coin),
+    ]
+}
@@ -0,0 +1,150 @@
+use crate::types::*;
+use crate::defines::*;
+use crate::util::*;
+use crate::ws::*;
+use crate::output::*;
+
+use std::time::{Duration, Instant};
+
+/// Wait for `order` to be fully filled within `ttl`, otherwise cancel it, then
+/// send quantity it left unfilled as a market order if `fallback` says so.
+/// Order which reaches its final state in time e.g. cancelled as post-only
+/// would take liquidity is left as it is. If waiting fails, the order is still
+/// cancelled once `ttl` passes before the error is returned.
+///
+/// # Arguments
+/// * `context` - `TradingContext` for context information used in trading
+/// * `ws` - private stream to watch the order, otherwise it is queried every
+///   `ORDER_POLL_INTERVAL_SECS`
+/// * `order` - order as returned when it was created
+/// * `ttl` - how long the order is left to be fully filled
+/// * `fallback` - what to do with quantity left unfilled
+/// * `confirm_timeout` - how long to wait for the final state of fallback order
+pub fn api_expire_order(context: &TradingContext, mut ws: Option<&mut BybitWebSocket>, order: &BybitOrderResult, ttl: Duration, fallback: Option<OrderFallback>, confirm_timeout: Duration) -> Result<OrderExpiry, StatusCode> {
+    let deadline = Instant::now() + ttl;
+    match wait_for_order_state(context, ws.as_deref_mut(), order, ttl) {
+        Ok(confirmation) if is_order_state_final(confirmation.state) => {
+            return Ok(OrderExpiry { confirmation, expired: false, fallback: None });
+        },
+        Ok(_) => {},
+        Err(e) => {
+            // state of the order is unknown, it must not be left resting beyond its ttl
+            std::thread::sleep(deadline.saturating_duration_since(Instant::now()));
+            if api_cancel_order(context, &order.symbol, &order.order_id).is_err() {
+                eprintln!("Error: cannot cancel order {} after its ttl, cancel it manually", order.order_id);
+            }
+            return Err(e);
+        },
+    }
+
+    api_cancel_order(context, &order.symbol, &order.order_id)?;
+    // it might have been filled further until it was cancelled
+    let confirmation = api_get_order_confirmation(context, order, false)?;
+    let unfilled = (order.qty - confirmation.cum_exec_qty).max(0.0) as i64;
+    let fallback = match fallback {
+        Some(OrderFallback::Market) if unfilled > 0 => {
+            let qty = if order.side == "Sell" { -unfilled } else { unfilled };
+            Some(api_send_fallback_order(context, ws, order, qty, confirm_timeout))
+        },
+        _ => None,
+    };
+    Ok(OrderExpiry { confirmation, expired: true, fallback })
+}
+
+/// Send market order of `qty` left unfilled by `order` with the same stop-loss,
+/// and take-profit, then wait for its final state.
+fn api_send_fallback_order(context: &TradingContext, ws: Option<&mut BybitWebSocket>, order: &BybitOrderResult, qty: i64, confirm_timeout: Duration) -> Result<Box<QuickOrderOutcome>, StatusCode> {
+    let start = Instant::now();
+    let stop_loss = Some(order.stop_loss).filter(|p| *p > 0.0);
+    let take_profit = Some(order.take_profit).filter(|p| *p > 0.0);
+    let fallback = api_send_market_order(context, &order.symbol, qty, stop_loss, take_profit)?;
+    let elapsed = measure_end(&start, false);
+
+    let confirmation = wait_for_order_state(context, ws, &fallback, confirm_timeout);
+    Ok(Box::new(QuickOrderOutcome {
+        order: fallback,
+        elapsed,
+        confirm_elapsed: Some(measure_end(&start, false)),
+        confirmation: Some(confirmation),
+        expired: false,
+        fallback: None,
+    }))
+}
+
+/// Wait for `order` to reach its final state, or until `timeout` on private
+/// stream if `ws` is connected, otherwise by querying it every
+/// `ORDER_POLL_INTERVAL_SECS`. Querying takes over for the rest of `timeout`
+/// if the stream fails, and a failed query is retried on the next poll.
+/// Return the latest known state of the order, or the error of the last query
+/// if it failed.
+///
+/// # Arguments
+/// * `context` - `TradingContext` for context information used in trading
+/// * `ws` - websocket connected via `ws_connect_private_stream`, if any
+/// * `order` - order as returned when it was created
+/// * `timeout` - how long to wait for the final state
+pub fn wait_for_order_state(context: &TradingContext, ws: Option<&mut BybitWebSocket>, order: &BybitOrderResult, timeout: Duration) -> Result<OrderConfirmation, StatusCode> {
+    let start = Instant::now();
+    if let Some(ws) = ws {
+        match wait_for_order_confirmation(context, ws, order, timeout) {
+            Ok(confirmation) => return Ok(confirmation),
+            Err(e) => print_info(&format!("watching order {} on private stream failed ({}), querying it instead", order.order_id, get_status_code_message(&e))),
+        }
+    }
+
+    loop {
+        let queried = api_get_order_confirmation(context, order, false);
+        let left = timeout.saturating_sub(start.elapsed());
+        match queried {
+            Ok(confirmation) if is_order_state_final(confirmation.state) || left.is_zero() => return Ok(confirmation),
+            Err(e) if left.is_zero() => return Err(e),
+            Ok(_) => {},
+            Err(e) => print_info(&format!("querying order {} failed ({}), retrying", order.order_id, get_status_code_message(&e))),
+        }
+        std::thread::sleep(left.min(Duration::from_secs_f64(ORDER_POLL_INTERVAL_SECS)));
+    }
+}
+
+/// Format what is left of `outcome` of an order with time to live after its
+/// final state into lines: whether it expired, its fallback order, and where
+/// its quantity was filled. Nothing if it didn't expire.
+///
+/// # Arguments
+/// * `outcome` - `QuickOrderOutcome` of the order
+/// * `ttl` - seconds the order was left to be fully filled
+pub fn format_order_expiry(outcome: &QuickOrderOutcome, ttl: f64) -> Vec<String> {
+    if !outcome.expired {
+        return Vec::new();
+    }
+    let order = &outcome.order;
+    let filled = |confirmation: &Option<Result<OrderConfirmation, StatusCode>>| match confirmation {
+        Some(Ok(c)) => (c.cum_exec_qty, get_avg_fill_price(c)),
+        _ => (0.0, None),
+    };
+    let (limit_qty, limit_price) = filled(&outcome.confirmation);
+    let mut lines = vec![format!("cancelled after ttl of {} secs, {} of {} left unfilled", ttl, order.qty - limit_qty, order.qty)];
+
+    let (market_qty, market_price) = match &outcome.fallback {
+        Some(Ok(fallback)) => {
+            match &fallback.confirmation {
+                Some(Ok(c)) => lines.extend(format_order_confirmation(c).into_iter().map(|l| format!("fallback {}", l))),
+                Some(Err(e)) => lines.push(format!("fallback order {} {} {} {} accepted (final state unknown: {})", fallback.order.order_id, fallback.order.side, fallback.order.qty, fallback.order.symbol, get_status_code_message(e))),
+                None => {},
+            }
+            filled(&fallback.confirmation)
+        },
+        Some(Err(e)) => {
+            lines.push(format!("fallback market order failed: {}", get_status_code_message(e)));
+            (0.0, None)
+        },
+        None => (0.0, None),
+    };
+
+    let at = |price: Option<f64>| price.map(|p| format!(" @ {:.6}", p)).unwrap_or_default();
+    let mut summary = format!("filled {}{} by limit", limit_qty, at(limit_price));
+    if outcome.fallback.is_some() {
+        summary += &format!(", {}{} by market", market_qty, at(market_price));
+    }
+    lines.push(format!("{}, {} unfilled", summary, (order.qty - limit_qty - market_qty).max(0.0)));
+    lines
+}
@@ -6,14 +6,32 @@ use std::collections::HashMap;
 #[clap(name="quicky")]
 #[clap(about="quicky lets you place limit order quickly (consider volatility of the price)", long_about=None)]
 pub struct CommandlineArgs {
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+
+    /// Symbol to trade [default: from profile]
     #[clap(short='s', long)]
-    pub symbol: String,
+    pub symbol: Option<String>,
 
     /// Quantity as part of the trade operation.
     /// Positive for buy side.
     /// Negative for sell side.
-    #[clap(short='q', long)]
-    pub qty: i64,
+    /// [default: from profile]
+    #[clap(short='q', long, allow_hyphen_values=true)]
+    pub qty: Option<i64>,
+
+    /// Path of config file [default: $XDG_CONFIG_HOME/quicky/config.toml]
+    #[clap(long, global=true)]
+    pub config: Option<String>,
+
+    /// Name of profile in config file to use [default: `default_profile` in config file]
+    #[clap(short='p', long, global=true)]
+    pub profile: Option<String>,
+
+    /// Name of account, or group of accounts in config file to trade on
+    /// [default: from profile, otherwise credentials of the selected network]
+    #[clap(short='a', long, global=true)]
+    pub account: Option<String>,
 
     /// Whether or not to execute against testnet
     // We dont need to explicitly specify value for bool here, so just --testnet
@@ -23,16 +41,456 @@ pub struct CommandlineArgs {
     //
     // Use the following when we need to explicitly specify value
     // `#[clap(long, parse(try_from_str), default_value="false")]`
-    #[clap(long="testnet", multiple_values=false, default_missing_value="true", takes_value=false)]
+    #[clap(long="testnet", multiple_values=false, default_missing_value="true", takes_value=false, global=true)]
     pub testnet: bool,
 
-    /// Stop-loss percentage
-    #[clap(long, default_value_t=crate::defines::DEFAULT_SL_PCNT)]
-    pub sl_pcnt: f64,
+    /// Execute against mainnet even if profile says otherwise
+    #[clap(long="mainnet", multiple_values=false, default_missing_value="true", takes_value=false, global=true, conflicts_with="testnet")]
+    pub mainnet: bool,
+
+    /// Domain of Bybit's API e.g. bybit.com, or bytick.com. Hosts of REST and
+    /// websocket API of the selected network are derived from it
+    /// [default: bybit.com]
+    #[clap(long, global=true)]
+    pub domain: Option<String>,
+
+    /// Base URL of REST API e.g. of a local mock, or a recording proxy,
+    /// overriding the one derived from network and domain
+    #[clap(long, global=true)]
+    pub base_url: Option<String>,
+
+    /// Base URL of websocket API, overriding the one derived from network and domain
+    #[clap(long, global=true)]
+    pub ws_url: Option<String>,
+
+    /// Proxy to connect through e.g. http://127.0.0.1:3128. Without it, REST API
+    /// honours standard environment variables e.g. HTTPS_PROXY
+    #[clap(long, global=true)]
+    pub proxy: Option<String>,
+
+    /// Stop-loss percentage [default: 0.5]
+    #[clap(long, global=true)]
+    pub sl_pcnt: Option<f64>,
+
+    /// Take-profit percentage. No take-profit if not specified.
+    #[clap(long, global=true)]
+    pub tp_pcnt: Option<f64>,
+
+    /// Reference price to place the order from: last, mark, index, or bidask
+    /// [default: last]
+    #[clap(long, global=true)]
+    pub price_ref: Option<PriceReference>,
+
+    /// Seconds to wait on the private stream for the order to reach its final
+    /// state before reporting
+    #[clap(long, default_value_t=crate::defines::DEFAULT_CONFIRM_TIMEOUT_SECS)]
+    pub confirm_timeout: f64,
+
+    /// Price from the order book instead of last traded price. Join the best
+    /// level, or step one tick ahead of it if its size is larger than this
+    #[clap(long)]
+    pub book_step_ahead: Option<f64>,
+
+    /// Number of ticks behind the best level to also count as queued size
+    /// when deciding to step ahead
+    #[clap(long, default_value_t=0)]
+    pub book_depth_ticks: u32,
+
+    /// Seconds the order is left to be fully filled before it is cancelled
+    #[clap(long)]
+    pub ttl: Option<f64>,
+
+    /// What to do with quantity left unfilled when --ttl expires: market to
+    /// send it as a market order
+    #[clap(long, requires="ttl")]
+    pub fallback: Option<OrderFallback>,
+
+    /// Don't wait for order confirmation from the private stream, just report
+    /// that the order is accepted
+    #[clap(long="no-confirm", multiple_values=false, default_missing_value="true", takes_value=false)]
+    pub no_confirm: bool,
+
+    /// Place the order on mainnet without asking for confirmation even if its
+    /// quantity is above `confirm_mainnet_above` of safety limits
+    #[clap(short='y', long="yes", multiple_values=false, default_missing_value="true", takes_value=false)]
+    pub yes: bool,
+
+    /// Seconds within which the same order on the same account is refused as a
+    /// duplicate. 0 turns it off [default: 2]
+    #[clap(long, global=true)]
+    pub dedupe_window: Option<f64>,
+
+    /// Path of trade journal every placed order is appended to
+    /// [default: $XDG_DATA_HOME/quicky/journal.jsonl]
+    #[clap(long, global=true)]
+    pub journal: Option<String>,
+
+    /// Print results as plain text, aligned table, or json for other tools
+    #[clap(long, global=true, default_value="plain")]
+    pub output: OutputMode,
+
+    /// Print nothing but errors, and json output
+    #[clap(long="quiet", multiple_values=false, default_missing_value="true", takes_value=false, global=true, conflicts_with="verbose")]
+    pub quiet: bool,
+
+    /// Log requests, and responses along with their timing, and remaining
+    /// rate-limit budget. Twice to also log response bodies. Logs are
+    /// filtered further by `RUST_LOG` e.g. `RUST_LOG=quicky::ws=debug`
+    #[clap(short='v', long="verbose", parse(from_occurrences), global=true)]
+    pub verbose: u64,
+
+    /// File to append logs to instead of stderr
+    #[clap(long, global=true)]
+    pub log_file: Option<String>,
+
+    /// Format of logs: text, or json
+    #[clap(long, global=true, default_value="text")]
+    pub log_format: LogFormat,
+
+    /// Fetch the price, and compute the order then print it without sending it
+    #[clap(long="dry-run", multiple_values=false, default_missing_value="true", takes_value=false)]
+    pub dry_run: bool,
+
+    /// Trade against local paper exchange simulator instead of Bybit
+    #[clap(long="paper", multiple_values=false, default_missing_value="true", takes_value=false, global=true)]
+    pub paper: bool,
+
+    /// Path of paper exchange's state file [default: $XDG_DATA_HOME/quicky/paper.json]
+    #[clap(long, global=true)]
+    pub paper_state: Option<String>,
+
+    /// Replay recorded market data from this JSONL file on paper exchange,
+    /// instead of live market data
+    #[clap(long, global=true)]
+    pub paper_replay: Option<String>,
+
+    /// Append live market data consumed by paper exchange to this JSONL file,
+    /// to be replayed later
+    #[clap(long, global=true)]
+    pub paper_record: Option<String>,
+}
+
+/// Sub-commands. Without any, quicky places a single quick limit order as
+/// specified by `--symbol` and `--qty`.
+#[derive(Debug, clap::Subcommand)]
+pub enum Command {
+    /// Keep connections open, and place orders with single keystrokes
+    Interactive {
+        /// Symbol to trade [default: from profile]
+        #[clap(short='s', long)]
+        symbol: Option<String>,
+
+        /// Default size of an order [default: from profile, otherwise 1]
+        #[clap(short='q', long)]
+        qty: Option<u64>,
+
+        /// Size to add or remove from default size with +/- keys
+        #[clap(long, default_value_t=1)]
+        qty_step: u64,
+    },
+
+    /// Split an order into limit orders across several price levels, all
+    /// with the same stop-loss
+    Ladder {
+        /// Symbol to trade [default: from profile]
+        #[clap(short='s', long)]
+        symbol: Option<String>,
+
+        /// Total quantity of all levels. Positive for buy side, negative for
+        /// sell side [default: from profile]
+        #[clap(short='q', long, allow_hyphen_values=true)]
+        qty: Option<i64>,
+
+        /// Number of price levels [default: 5, or number of --weights]
+        #[clap(long)]
+        levels: Option<u32>,
+
+        /// Ticks away from reference price of the nearest level
+        #[clap(long, default_value_t=1)]
+        from_ticks: u32,
+
+        /// Ticks away from reference price of the farthest level
+        #[clap(long, default_value_t=10)]
+        to_ticks: u32,
+
+        /// How quantity is split across levels from the nearest one: linear
+        /// (1, 2, 3, ...), geometric (1, r, r^2, ...), or custom with --weights
+        #[clap(long, default_value="linear")]
+        weighting: LadderWeighting,
+
+        /// Ratio between quantities of consecutive levels of geometric weighting
+        #[clap(long, default_value_t=crate::defines::DEFAULT_LADDER_GEOMETRIC_RATIO)]
+        ratio: f64,
+
+        /// Weights of levels from the nearest one for custom weighting e.g. 3,2,1
+        #[clap(long, value_delimiter=',')]
+        weights: Vec<f64>,
+
+        /// Compute the levels, and print them without sending
+        #[clap(long="dry-run", multiple_values=false, default_missing_value="true", takes_value=false)]
+        dry_run: bool,
+    },
+
+    /// Place prepared orders from a file, or stdin concurrently
+    Batch {
+        /// File of orders, or `-` for stdin. Each order has symbol, side, qty,
+        /// and optionally stop-loss, and take-profit percentage [default: stdin]
+        file: Option<String>,
+
+        /// Format of orders: csv, json, or lines of `symbol side qty [sl] [tp]`
+        /// [default: from extension of file, otherwise lines]
+        #[clap(long)]
+        format: Option<BatchFormat>,
+
+        /// Compute the orders, and print them without sending
+        #[clap(long="dry-run", multiple_values=false, default_missing_value="true", takes_value=false)]
+        dry_run: bool,
+    },
+
+    /// Place an entry limit order with stop-loss, then reduce-only take-profit
+    /// targets once it fills, resizing stop-loss as targets are hit
+    Bracket {
+        /// Symbol to trade [default: from profile]
+        #[clap(short='s', long)]
+        symbol: Option<String>,
+
+        /// Quantity of entry. Positive for buy side, negative for sell side
+        /// [default: from profile]
+        #[clap(short='q', long, allow_hyphen_values=true)]
+        qty: Option<i64>,
+
+        /// Take-profit targets as percentage of filled quantity at a multiple
+        /// of risk R i.e. distance from entry to stop-loss, e.g. 50@1,30@2,20@3
+        #[clap(long, value_delimiter=',', default_value=crate::defines::DEFAULT_BRACKET_TARGETS)]
+        targets: Vec<BracketTarget>,
+
+        /// Seconds entry is left to be filled, the rest of it is cancelled then
+        #[clap(long, default_value_t=crate::defines::DEFAULT_BRACKET_ENTRY_TTL_SECS)]
+        entry_ttl: f64,
+
+        /// Seconds targets are managed at most, then the ones left are
+        /// cancelled, and position is left with its stop-loss
+        #[clap(long, default_value_t=crate::defines::DEFAULT_BRACKET_MANAGE_TIMEOUT_SECS)]
+        manage_timeout: f64,
+
+        /// Compute entry, and targets, and print them without sending
+        #[clap(long="dry-run", multiple_values=false, default_missing_value="true", takes_value=false)]
+        dry_run: bool,
+    },
+
+    /// Hold warm connections, and accept commands over a Unix domain socket
+    Daemon {
+        /// Path of the socket [default: $XDG_RUNTIME_DIR/quicky.sock]
+        #[clap(long)]
+        socket: Option<String>,
+
+        /// Symbols to keep order book of, so placing an order costs a single
+        /// request. Separated by comma.
+        #[clap(long, use_value_delimiter=true)]
+        symbols: Vec<String>,
+    },
+
+    /// Send a command to the daemon e.g. `place XRPUSD 10`, `cancel XRPUSD`,
+    /// `close XRPUSD`, or `status`
+    Send {
+        /// Path of the socket [default: $XDG_RUNTIME_DIR/quicky.sock]
+        #[clap(long)]
+        socket: Option<String>,
+
+        /// Command and its arguments
+        #[clap(required=true, allow_hyphen_values=true)]
+        words: Vec<String>,
+    },
+
+    /// Manage API credentials in passphrase-encrypted local store
+    Credentials {
+        #[clap(subcommand)]
+        action: CredentialsAction,
+    },
+
+    /// Show orders recorded in trade journal along with their final state
+    Journal {
+        /// Only orders of this symbol
+        #[clap(short='s', long)]
+        symbol: Option<String>,
+
+        /// Only orders placed since this time, either UTC date e.g. 2022-05-01,
+        /// 2022-05-01T08:00:00, or time ago e.g. 30m, 24h, 7d
+        #[clap(long)]
+        since: Option<String>,
+
+        /// Only orders placed before this time, in the same form as `--since`
+        #[clap(long)]
+        until: Option<String>,
+
+        /// Show only the latest number of orders
+        #[clap(long)]
+        limit: Option<usize>,
+
+        /// Query orders of the selected account whose final state is not yet
+        /// known, and record their latest fills first
+        #[clap(long="sync", multiple_values=false, default_missing_value="true", takes_value=false)]
+        sync: bool,
+    },
+
+    /// Report execution quality of orders recorded in trade journal, per symbol
+    Report {
+        /// Only orders of this symbol
+        #[clap(short='s', long)]
+        symbol: Option<String>,
+
+        /// Only orders placed since this time, in the same form as of `journal`
+        #[clap(long)]
+        since: Option<String>,
+
+        /// Only orders placed before this time, in the same form as of `journal`
+        #[clap(long)]
+        until: Option<String>,
+    },
+
+    /// Export history of closed PnL, executions along with their fees, or
+    /// funding payments of a symbol from Bybit
+    Export {
+        /// What to export: closed-pnl, executions, or funding
+        kind: ExportKind,
+
+        /// Symbol to export history of [default: from profile]
+        #[clap(short='s', long)]
+        symbol: Option<String>,
+
+        /// Only records since this time, in the same form as of `journal`
+        #[clap(long)]
+        since: Option<String>,
+
+        /// Only records before this time, in the same form as of `journal`
+        #[clap(long)]
+        until: Option<String>,
+
+        /// Format to export in: csv, or json
+        #[clap(long, default_value="csv")]
+        format: ExportFormat,
+
+        /// File to write to instead of stdout
+        #[clap(short='o', long)]
+        out: Option<String>,
+    },
+
+    /// Show, or reset state of paper exchange
+    Paper {
+        #[clap(subcommand)]
+        action: PaperAction,
+    },
+
+    /// Receive alert-driven orders as authenticated json over local HTTP
+    Webhook {
+        /// Address to listen on
+        #[clap(long, default_value=crate::defines::DEFAULT_WEBHOOK_LISTEN_ADDR)]
+        listen: String,
+
+        /// Name of environment variable holding the shared secret
+        #[clap(long, default_value="QUICKY_WEBHOOK_SECRET")]
+        secret_env: String,
+
+        /// Max age in seconds of a payload's timestamp before it is rejected
+        #[clap(long, default_value_t=crate::defines::DEFAULT_WEBHOOK_MAX_AGE_SECS)]
+        max_age: u64,
+    },
+}
+
+/// Actions on credential store.
+#[derive(Debug, clap::Subcommand)]
+pub enum CredentialsAction {
+    /// Add, or replace API key and secret with the specified name
+    Add { name: String },
+    /// Remove API key and secret with the specified name
+    Remove { name: String },
+    /// List names of stored credentials
+    List,
+}
+
+/// Actions on paper exchange.
+#[derive(Debug, clap::Subcommand)]
+pub enum PaperAction {
+    /// Show balances, positions, and active orders
+    Status,
+    /// Discard all positions, and orders then start over with the specified balance
+    Reset {
+        /// Balance of each settlement coin
+        #[clap(long, default_value_t=crate::defines::PAPER_INITIAL_BALANCE)]
+        balance: f64,
+    },
+}
+
+/// How quantity of a ladder is split across its levels.
+#[derive(Debug, Clone, Copy)]
+pub enum LadderWeighting {
+    /// Each level farther has one more unit of weight
+    Linear,
+    /// Each level farther has weight of the nearer one times ratio
+    Geometric,
+    /// Weights are specified level by level
+    Custom,
+}
+
+/// History to export.
+#[derive(Debug, Clone, Copy)]
+pub enum ExportKind {
+    /// Closed profit and loss of positions
+    ClosedPnl,
+    /// Executions of orders along with their fees
+    Executions,
+    /// Funding payments of positions
+    Funding,
+}
+
+/// What to do with quantity an order left unfilled when its time to live expires.
+#[derive(Debug, Clone, Copy)]
+pub enum OrderFallback {
+    /// Send it as a market order
+    Market,
+}
+
+/// Format of orders of a batch.
+#[derive(Debug, Clone, Copy)]
+pub enum BatchFormat {
+    /// Comma separated, with optional header naming its columns
+    Csv,
+    /// Array of objects, or an object per line
+    Json,
+    /// Whitespace separated `symbol side qty [sl] [tp]` per line
+    Lines,
+}
+
+/// Format of exported history.
+#[derive(Debug, Clone, Copy)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+/// Form of results printed to stdout.
+#[derive(Debug, Clone, Copy)]
+pub enum OutputMode {
+    /// Lines of text to read
+    Plain,
+    /// Columns aligned under a header
+    Table,
+    /// Json document to parse
+    Json,
+}
+
+/// Format of log lines.
+#[derive(Debug, Clone, Copy)]
+pub enum LogFormat {
+    Text,
+    /// A json object per line with level, target, and fields
+    Json,
 }
 
 /// Status code represents the result of API related calls & its internal operations.
 pub enum StatusCode {
+    // never returned as an error, it anchors numbering of the other codes
+    #[allow(dead_code)]
     Success=0,
     InternalErrorGeneric,
     InternalErrorParsingRawUrl,
@@ -45,32 +503,307 @@ pub enum StatusCode {
     MalformedAPIResponseFormat,
     ApiEmptyResult,
     ErrorIncorrectParameterValue,
+    ErrorWebSocket,
+    ErrorWebSocketAuth,
+    ErrorOrderBookOutOfSync,
+    ErrorConfig,
+    ErrorMissingCredentials,
+    ErrorCredentialStore,
+    ErrorPaper,
+    ErrorLimitExceeded,
+    ErrorNotConfirmed,
+    ErrorNetwork,
+    ErrorDuplicateOrder,
+    ErrorRateLimited,
+    ErrorJournal,
 }
 
 /// `TradingContext` contains information used during trading.
 /// It also contains cached information we know before hand as we don't have to
 /// make unnecessary API requests which waste time.
+#[derive(Clone)]
 pub struct TradingContext {
-    /// Set environment variable with name BYBIT_API_KEY
-    pub api_key: String,
-
-    /// Set environment variable with name BYBIT_API_SECRET
-    pub api_secret: String,
-
-    /// Set environment variable with name BYBIT_TESTNET_API_KEY
-    pub testnet_api_key: String,
-
-    /// Set environment variable with name BYBIT_TESTNET_API_SECRET
-    pub testnet_api_secret: String,
+    /// Where to load API credentials of the selected network from.
+    /// They are loaded only when a private API call needs them.
+    pub credentials: CredentialSource,
 
     /// Tick steps information for symbols
     pub tick_steps: HashMap<String, f64>,
 
+    /// Lot sizes i.e. quantity steps of symbols, 1 for symbols not listed
+    pub lot_sizes: HashMap<String, u64>,
+
     /// Stop-loss percentage
     pub stop_loss_pcnt: f64,
 
+    /// Take-profit percentage, no take-profit if `None`
+    pub take_profit_pcnt: Option<f64>,
+
     /// Whether or not to execute API against testnet
-    pub use_testnet: bool
+    pub use_testnet: bool,
+
+    /// Domain of Bybit's API to derive hosts of mainnet/testnet from
+    pub domain: String,
+
+    /// Base URL of REST API overriding the one of mainnet/testnet
+    pub base_url: Option<String>,
+
+    /// Base URL of websocket API overriding the one of mainnet/testnet
+    pub ws_url: Option<String>,
+
+    /// Proxy to connect to REST, and websocket API through
+    pub proxy: Option<String>,
+
+    /// Reference price to place the order from
+    pub price_ref: PriceReference,
+
+    /// Route private API calls to paper exchange if set
+    pub paper: Option<PaperOptions>,
+
+    /// Safety limits checked before an order is sent
+    pub limits: Limits,
+
+    /// Seconds within which the same order is refused as a duplicate, 0 turns
+    /// it off
+    pub dedupe_window_secs: f64,
+
+    /// Trade journal every placed order is appended to, no journal if `None`
+    pub journal_path: Option<std::path::PathBuf>,
+}
+
+/// Options of paper exchange.
+#[derive(Debug, Clone)]
+pub struct PaperOptions {
+    /// File where simulated balances, positions, and orders are persisted
+    pub state_path: std::path::PathBuf,
+    /// Recorded market data to replay, live market data is used if `None`
+    pub replay_path: Option<std::path::PathBuf>,
+    /// File to append live market data to
+    pub record_path: Option<std::path::PathBuf>,
+}
+
+/// Levels of a ladder, and how its quantity is split across them.
+#[derive(Debug, Clone)]
+pub struct LadderOptions {
+    /// Number of levels, `None` for number of `weights`, or default
+    pub levels: Option<u32>,
+    /// Ticks away from reference price of the nearest level
+    pub from_ticks: u32,
+    /// Ticks away from reference price of the farthest level
+    pub to_ticks: u32,
+    pub weighting: LadderWeighting,
+    /// Ratio of geometric weighting
+    pub ratio: f64,
+    /// Weights of custom weighting from the nearest level
+    pub weights: Vec<f64>,
+}
+
+/// Targets of a bracket, and how long it is managed.
+#[derive(Debug, Clone)]
+pub struct BracketOptions {
+    pub targets: Vec<BracketTarget>,
+    /// Seconds entry is left to be filled
+    pub entry_ttl: f64,
+    /// Seconds targets are managed at most
+    pub manage_timeout: f64,
+}
+
+/// Take-profit target of a bracket.
+#[derive(Debug, Clone, Copy)]
+pub struct BracketTarget {
+    /// Percentage of filled quantity of entry to take profit on
+    pub pcnt: f64,
+    /// Distance from entry price as a multiple of risk
+    pub r_multiple: f64,
+}
+
+/// Bracket as computed before its entry is sent.
+#[derive(Debug, Clone)]
+pub struct BracketPlan {
+    /// Entry limit order along with its stop-loss, without take-profit
+    pub entry: OrderPlan,
+    /// Risk i.e. distance from entry price to stop-loss
+    pub risk: f64,
+    pub targets: Vec<BracketTarget>,
+    /// Price of each of `targets`
+    pub target_prices: Vec<f64>,
+}
+
+/// How management of a bracket ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BracketOutcome {
+    /// Entry was cancelled without any fill
+    EntryUnfilled,
+    /// All targets were filled
+    TargetsHit,
+    /// Position was closed before all targets were filled e.g. by stop-loss
+    Stopped,
+    /// No target is left active while position is still open
+    LeftOpen,
+    /// Management timed out, targets left were cancelled
+    TimedOut,
+}
+
+/// Source of API credentials.
+#[derive(Clone)]
+pub enum CredentialSource {
+    /// Environment variables with the specified names
+    Env { key_env: String, secret_env: String },
+
+    /// Entry with the specified name in encrypted credential store.
+    /// Store is unlocked once, then credentials are kept in `unlocked` shared
+    /// by clones of the context for the rest of the session.
+    Store { name: String, unlocked: std::sync::Arc<std::sync::OnceLock<ApiCredentials>> },
+}
+
+/// API key, and secret loaded from `CredentialSource`.
+/// Zeroized in memory when dropped.
+#[derive(Clone, Default, zeroize::Zeroize, zeroize::ZeroizeOnDrop, serde::Deserialize, serde::Serialize)]
+pub struct ApiCredentials {
+    pub api_key: String,
+    pub api_secret: String,
+}
+
+/// Encrypted credential store file.
+/// Plaintext is json of `BTreeMap<String, ApiCredentials>` encrypted with
+/// CHACHA20-POLY1305, keyed by PBKDF2-HMAC-SHA256 of the passphrase.
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+pub struct CredentialStoreFile {
+    pub version: u32,
+    pub iterations: u32,
+    /// hex of salt for key derivation
+    pub salt: String,
+    /// hex of nonce
+    pub nonce: String,
+    /// hex of ciphertext along with its tag
+    pub ciphertext: String,
+}
+
+/// Reference price that quick limit order is placed from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PriceReference {
+    /// Last traded price, one tick away
+    #[default]
+    Last,
+    /// Mark price, one tick away
+    Mark,
+    /// Index price, one tick away
+    Index,
+    /// Join best bid for buy side, best ask for sell side
+    BidAsk,
+}
+
+/// Config file.
+// Located at $XDG_CONFIG_HOME/quicky/config.toml by default.
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Profile to use if not specified
+    pub default_profile: Option<String>,
+
+    #[serde(default)]
+    pub profiles: HashMap<String, Profile>,
+
+    #[serde(default)]
+    pub accounts: HashMap<String, Account>,
+
+    /// Groups of accounts to fan an order out to, each maps name of account to
+    /// its sizing multiplier
+    #[serde(default)]
+    pub groups: HashMap<String, std::collections::BTreeMap<String, f64>>,
+
+    #[serde(default)]
+    pub limits: Limits,
+}
+
+/// Pre-trade safety limits in config file.
+/// All are optional, only configured ones are checked.
+#[derive(Debug, Default, Clone, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Limits {
+    /// Maximum number of open orders of a symbol including the new one
+    pub max_open_orders: Option<u32>,
+    /// Maximum deviation in percentage of limit price from mark, and index price
+    pub max_price_deviation_pcnt: Option<f64>,
+    /// Ask for confirmation before placing an order on mainnet with quantity
+    /// above this
+    pub confirm_mainnet_above: Option<u64>,
+    /// Limits by symbol
+    #[serde(default)]
+    pub symbols: HashMap<String, SymbolLimits>,
+}
+
+/// Safety limits of a single symbol.
+#[derive(Debug, Default, Clone, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SymbolLimits {
+    /// Maximum quantity of an order
+    pub max_qty: Option<u64>,
+    /// Maximum value of an order in settlement coin
+    pub max_notional: Option<f64>,
+    /// Maximum size of position once an order is fully filled
+    pub max_position: Option<u64>,
+}
+
+/// Named account in config file e.g. a sub-account.
+/// Either `credentials`, or both `api_key_env` and `api_secret_env` are required.
+#[derive(Debug, Default, Clone, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Account {
+    pub testnet: Option<bool>,
+    /// Name of environment variable holding API key
+    pub api_key_env: Option<String>,
+    /// Name of environment variable holding API secret
+    pub api_secret_env: Option<String>,
+    /// Name of entry in encrypted credential store
+    pub credentials: Option<String>,
+}
+
+/// Trading context of an account to place orders on.
+pub struct AccountContext {
+    /// Name of account, `None` for credentials of the selected network
+    pub name: Option<String>,
+    pub context: TradingContext,
+    /// Multiplier of order's quantity for this account
+    pub multiplier: f64,
+}
+
+/// Accounts selected by name of an account, or a group of accounts.
+pub struct AccountSelection {
+    /// Name of group if a group is selected
+    pub group: Option<String>,
+    pub accounts: Vec<AccountContext>,
+}
+
+/// Named profile in config file.
+/// All fields are optional, unspecified ones fall back to built-in defaults.
+#[derive(Debug, Default, Clone, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    pub testnet: Option<bool>,
+    pub domain: Option<String>,
+    pub base_url: Option<String>,
+    pub ws_url: Option<String>,
+    pub proxy: Option<String>,
+    /// Name of environment variable holding API key
+    pub api_key_env: Option<String>,
+    /// Name of environment variable holding API secret
+    pub api_secret_env: Option<String>,
+    /// Name of entry in encrypted credential store, takes precedence over
+    /// environment variables
+    pub credentials: Option<String>,
+    /// Name of account, or group of accounts to trade on
+    pub account: Option<String>,
+    pub symbol: Option<String>,
+    pub qty: Option<i64>,
+    pub sl_pcnt: Option<f64>,
+    pub tp_pcnt: Option<f64>,
+    pub price_ref: Option<PriceReference>,
+    pub dedupe_window_secs: Option<f64>,
+    /// Path of trade journal
+    pub journal: Option<String>,
 }
 
 /// Generic response structure with no result field.
@@ -86,6 +819,8 @@ pub struct BybitGenericNoResultResponse {
 /// Server time response from Bybit
 /// NOTE: Currently we didn't use this as it is not necessary, such that we
 /// can use local timestamp if local one's time synced with time server online.
+/// It is only fetched to tell how far off local clock is once a request is
+/// rejected for its timestamp.
 // https://bybit-exchange.github.io/docs/inverse/?python--old#t-servertime
 #[derive(Debug, serde::Deserialize, serde::Serialize)]
 pub struct BybitServerTimeResponse {
@@ -97,10 +832,10 @@ pub struct BybitServerTimeResponse {
 }
 
 /// Result field of symbol latest information response from Bybit.
-/// NOTE: Currently not used, to reduce time spent for making and waiting for
-/// response of HTTP request. We hard-coded certain information of target asset
-/// instead for now.
-#[derive(Debug, serde::Deserialize, serde::Serialize)]
+/// NOTE: Used to get reference price of the order. Other information e.g. tick
+/// step is hard-coded to reduce time spent for making and waiting for response
+/// of HTTP request.
+#[derive(Debug, Default, serde::Deserialize, serde::Serialize)]
 pub struct BybitLatestInformationSymbolResult {
     pub symbol: String,
     pub bid_price: String,
@@ -130,8 +865,48 @@ pub struct BybitLatestInformationSymbolResult {
     pub delivery_time: String,
 }
 
+/// Entry of order book snapshot from REST API.
+// https://bybit-exchange.github.io/docs/inverse/#t-orderbook
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+pub struct BybitOrderBookLevel {
+    #[serde(deserialize_with = "crate::util::deserialize_f64")]
+    pub price: f64,
+    #[serde(default, deserialize_with = "crate::util::deserialize_f64")]
+    pub size: f64,
+    pub side: String,
+}
+
+/// Order book snapshot response from Bybit.
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+pub struct BybitOrderBookResponse {
+    pub ret_code: u32,
+    pub ret_msg: String,
+    pub result: Option<Vec<BybitOrderBookLevel>>,
+}
+
+/// Public trade from REST API.
+// https://bybit-exchange.github.io/docs/inverse/#t-publictradingrecords
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+pub struct BybitTradingRecord {
+    pub id: u64,
+    #[serde(deserialize_with = "crate::util::deserialize_f64")]
+    pub price: f64,
+    #[serde(deserialize_with = "crate::util::deserialize_f64")]
+    pub qty: f64,
+    /// Side of the taker
+    pub side: String,
+}
+
+/// Public trading records response from Bybit.
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+pub struct BybitTradingRecordsResponse {
+    pub ret_code: u32,
+    pub ret_msg: String,
+    pub result: Option<Vec<BybitTradingRecord>>,
+}
+
 /// Symbol latest information response from Bybit.
-/// NOTE: Currently not used at the moment. See comment in `BybitLatestInformationSymbolResult`.
+/// See comment in `BybitLatestInformationSymbolResult`.
 #[derive(Debug, serde::Deserialize, serde::Serialize)]
 pub struct BybitLatestInformationSymbolResponse {
     pub ret_code: u32,
@@ -141,3 +916,873 @@ pub struct BybitLatestInformationSymbolResponse {
     pub result: Option<Vec<BybitLatestInformationSymbolResult>>, // use Option<> for error case
     pub time_now: String,
 }
+
+/// Result field of order creation, and order query response from Bybit.
+/// Only fields we make use of are listed here.
+// https://bybit-exchange.github.io/docs/inverse/#t-placeactive
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct BybitOrderResult {
+    pub order_id: String,
+    #[serde(default)]
+    pub order_link_id: String,
+    pub symbol: String,
+    pub side: String,
+    pub order_type: String,
+    #[serde(deserialize_with = "crate::util::deserialize_f64")]
+    pub price: f64,
+    #[serde(deserialize_with = "crate::util::deserialize_f64")]
+    pub qty: f64,
+    pub time_in_force: String,
+    pub order_status: String,
+    #[serde(default, deserialize_with = "crate::util::deserialize_f64")]
+    pub leaves_qty: f64,
+    #[serde(default, deserialize_with = "crate::util::deserialize_f64")]
+    pub cum_exec_qty: f64,
+    #[serde(default, deserialize_with = "crate::util::deserialize_f64")]
+    pub cum_exec_value: f64,
+    #[serde(default, deserialize_with = "crate::util::deserialize_f64")]
+    pub cum_exec_fee: f64,
+    #[serde(default)]
+    pub reject_reason: String,
+    #[serde(default)]
+    pub cancel_type: String,
+    /// Stop-loss price, 0 if not set
+    #[serde(default, deserialize_with = "crate::util::deserialize_f64")]
+    pub stop_loss: f64,
+    /// Take-profit price, 0 if not set
+    #[serde(default, deserialize_with = "crate::util::deserialize_f64")]
+    pub take_profit: f64,
+}
+
+/// Order creation, or order query response from Bybit.
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+pub struct BybitOrderResponse {
+    pub ret_code: u32,
+    pub ret_msg: String,
+    pub ext_code: String,
+    pub ext_info: String,
+    pub result: Option<BybitOrderResult>,   // use Option<> for error case
+}
+
+/// Result field of order list response from Bybit.
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+pub struct BybitOrderListResult {
+    /// `null` if there is no order
+    pub data: Option<Vec<BybitOrderResult>>,
+    #[serde(default)]
+    pub cursor: String,
+}
+
+/// Closed profit and loss of a position closed by an order.
+/// Only fields we make use of are listed here.
+// https://bybit-exchange.github.io/docs/inverse/#t-closedprofitandloss
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct BybitClosedPnlRecord {
+    pub id: u64,
+    pub symbol: String,
+    pub order_id: String,
+    /// Side of the order closing the position
+    pub side: String,
+    /// e.g. `Trade`, `BustTrade`, `AdlTrade`
+    pub exec_type: String,
+    #[serde(deserialize_with = "crate::util::deserialize_f64")]
+    pub closed_size: f64,
+    #[serde(deserialize_with = "crate::util::deserialize_f64")]
+    pub avg_entry_price: f64,
+    #[serde(deserialize_with = "crate::util::deserialize_f64")]
+    pub avg_exit_price: f64,
+    /// In settlement coin for inverse contract
+    #[serde(deserialize_with = "crate::util::deserialize_f64")]
+    pub closed_pnl: f64,
+    pub fill_count: u64,
+    /// Unix timestamp in seconds
+    pub created_at: u64,
+}
+
+/// Result field of closed profit and loss response from Bybit.
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+pub struct BybitClosedPnlResult {
+    pub current_page: u64,
+    /// `null` if there is no record
+    pub data: Option<Vec<BybitClosedPnlRecord>>,
+}
+
+/// Closed profit and loss response from Bybit.
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+pub struct BybitClosedPnlResponse {
+    pub ret_code: u32,
+    pub ret_msg: String,
+    pub result: Option<BybitClosedPnlResult>,
+}
+
+/// Execution of an order, or funding settlement of a position.
+/// Only fields we make use of are listed here.
+// https://bybit-exchange.github.io/docs/inverse/#t-usertraderecords
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct BybitExecutionRecord {
+    pub order_id: String,
+    #[serde(default)]
+    pub order_link_id: String,
+    pub symbol: String,
+    pub side: String,
+    pub exec_id: String,
+    /// `Trade`, `AdlTrade`, `BustTrade`, or `Funding`
+    pub exec_type: String,
+    /// Mark price for funding
+    #[serde(deserialize_with = "crate::util::deserialize_f64")]
+    pub exec_price: f64,
+    /// Position size for funding
+    #[serde(deserialize_with = "crate::util::deserialize_f64")]
+    pub exec_qty: f64,
+    /// In settlement coin for inverse contract
+    #[serde(deserialize_with = "crate::util::deserialize_f64")]
+    pub exec_value: f64,
+    /// Fee, or funding fee in settlement coin, negative means received
+    #[serde(deserialize_with = "crate::util::deserialize_f64")]
+    pub exec_fee: f64,
+    /// Fee rate, or funding rate
+    #[serde(deserialize_with = "crate::util::deserialize_f64")]
+    pub fee_rate: f64,
+    /// `AddedLiquidity` for maker, `RemovedLiquidity` for taker
+    #[serde(default)]
+    pub last_liquidity_ind: String,
+    pub trade_time_ms: u64,
+}
+
+/// Result field of user trade records response from Bybit.
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+pub struct BybitExecutionListResult {
+    /// `null` if there is no record
+    pub trade_list: Option<Vec<BybitExecutionRecord>>,
+}
+
+/// User trade records response from Bybit.
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+pub struct BybitExecutionListResponse {
+    pub ret_code: u32,
+    pub ret_msg: String,
+    pub result: Option<BybitExecutionListResult>,
+}
+
+/// Order list response from Bybit.
+// https://bybit-exchange.github.io/docs/inverse/#t-getactive
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+pub struct BybitOrderListResponse {
+    pub ret_code: u32,
+    pub ret_msg: String,
+    pub ext_code: String,
+    pub ext_info: String,
+    pub result: Option<BybitOrderListResult>,   // use Option<> for error case
+}
+
+/// Data of `order` topic from private websocket stream.
+// https://bybit-exchange.github.io/docs/inverse/#t-websocketorder
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct BybitWsOrderData {
+    pub order_id: String,
+    #[serde(default)]
+    pub order_link_id: String,
+    pub symbol: String,
+    pub side: String,
+    pub order_status: String,
+    #[serde(default, deserialize_with = "crate::util::deserialize_f64")]
+    pub leaves_qty: f64,
+    #[serde(default, deserialize_with = "crate::util::deserialize_f64")]
+    pub cum_exec_qty: f64,
+    #[serde(default, deserialize_with = "crate::util::deserialize_f64")]
+    pub cum_exec_value: f64,
+    #[serde(default, deserialize_with = "crate::util::deserialize_f64")]
+    pub cum_exec_fee: f64,
+    #[serde(default)]
+    pub reject_reason: String,
+    #[serde(default)]
+    pub cancel_type: String,
+}
+
+/// Data of `execution` topic from private websocket stream.
+// https://bybit-exchange.github.io/docs/inverse/#t-websocketexecution
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct BybitWsExecutionData {
+    pub symbol: String,
+    pub side: String,
+    pub order_id: String,
+    pub exec_id: String,
+    #[serde(default)]
+    pub order_link_id: String,
+    #[serde(deserialize_with = "crate::util::deserialize_f64")]
+    pub price: f64,
+    #[serde(deserialize_with = "crate::util::deserialize_f64")]
+    pub exec_qty: f64,
+    #[serde(default, deserialize_with = "crate::util::deserialize_f64")]
+    pub exec_fee: f64,
+    #[serde(default, deserialize_with = "crate::util::deserialize_f64")]
+    pub leaves_qty: f64,
+    #[serde(default)]
+    pub is_maker: bool,
+}
+
+/// Data of `position` topic from private websocket stream.
+// https://bybit-exchange.github.io/docs/inverse/#t-websocketposition
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct BybitWsPositionData {
+    pub symbol: String,
+    pub side: String,
+    #[serde(deserialize_with = "crate::util::deserialize_f64")]
+    pub size: f64,
+    #[serde(default, deserialize_with = "crate::util::deserialize_f64")]
+    pub entry_price: f64,
+    #[serde(default, deserialize_with = "crate::util::deserialize_f64")]
+    pub unrealised_pnl: f64,
+}
+
+/// Event received from private websocket stream.
+#[derive(Debug)]
+pub enum BybitPrivateStreamEvent {
+    Order(Vec<BybitWsOrderData>),
+    Execution(Vec<BybitWsExecutionData>),
+    Position(Vec<BybitWsPositionData>),
+    /// Other messages e.g. pong, or response of subscription
+    Other,
+}
+
+/// State of an order as we report it to the user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderState {
+    New,
+    PartiallyFilled,
+    Filled,
+    Cancelled,
+    /// Cancelled by exchange because PostOnly order would take liquidity
+    CancelledPostOnly,
+    Rejected,
+}
+
+/// Confirmation of an order after it has been placed.
+/// Accumulated from order query, and events from private stream.
+#[derive(Debug, Clone)]
+pub struct OrderConfirmation {
+    pub order_id: String,
+    pub symbol: String,
+    pub side: String,
+    pub price: f64,
+    pub qty: f64,
+    pub state: OrderState,
+    pub cum_exec_qty: f64,
+    pub cum_exec_value: f64,
+    /// Fee in settlement coin, negative means rebate
+    pub cum_exec_fee: f64,
+    pub leaves_qty: f64,
+    /// Latest position of the symbol if it was updated while waiting
+    pub position: Option<BybitWsPositionData>,
+}
+
+/// Limit order as computed before it is sent.
+#[derive(Debug, Clone)]
+pub struct OrderPlan {
+    /// Client id of the order, the same one is used if the order is re-sent
+    pub order_link_id: String,
+    pub symbol: String,
+    /// `Buy`, or `Sell`
+    pub side: String,
+    pub qty: u64,
+    pub limit_price: f64,
+    /// Price stop-loss, and take-profit are computed from
+    pub reference_price: f64,
+    pub stop_loss_price: f64,
+    pub take_profit_price: Option<f64>,
+    pub tick_step: f64,
+}
+
+/// Result of sending an order of `OrderPlan`, along with seconds it took until
+/// it was accepted, or failed.
+pub type SentOrderPlan = (Result<BybitOrderResult, StatusCode>, f64);
+
+/// Outcome of placing a quick limit order.
+pub struct QuickOrderOutcome {
+    pub order: BybitOrderResult,
+    /// Seconds it took until the order was accepted
+    pub elapsed: f64,
+    /// Seconds it took until final state of the order was known, or waiting
+    /// for it gave up, `None` if confirmation is not requested
+    pub confirm_elapsed: Option<f64>,
+    /// Final state of the order, `None` if confirmation is not requested
+    pub confirmation: Option<Result<OrderConfirmation, StatusCode>>,
+    /// Whether or not the order was cancelled as it wasn't fully filled in time
+    pub expired: bool,
+    /// Market order of quantity left unfilled by the expired order, if any
+    pub fallback: Option<Result<Box<QuickOrderOutcome>, StatusCode>>,
+}
+
+/// Final state of an order with time to live, along with its fallback order.
+pub struct OrderExpiry {
+    pub confirmation: OrderConfirmation,
+    /// Whether or not the order was cancelled as it wasn't fully filled in time
+    pub expired: bool,
+    /// Market order of quantity left unfilled, if any
+    pub fallback: Option<Result<Box<QuickOrderOutcome>, StatusCode>>,
+}
+
+/// Entry of `orderBookL2_25` topic from public websocket stream.
+// https://bybit-exchange.github.io/docs/inverse/#t-websocketorderbook25
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct BybitWsOrderBookEntry {
+    #[serde(deserialize_with = "crate::util::deserialize_f64")]
+    pub price: f64,
+    pub symbol: String,
+    pub id: u64,
+    pub side: String,
+    #[serde(default, deserialize_with = "crate::util::deserialize_f64")]
+    pub size: f64,
+}
+
+/// Data of delta message of `orderBookL2_25` topic.
+#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize)]
+pub struct BybitWsOrderBookDelta {
+    #[serde(default)]
+    pub delete: Vec<BybitWsOrderBookEntry>,
+    #[serde(default)]
+    pub update: Vec<BybitWsOrderBookEntry>,
+    #[serde(default)]
+    pub insert: Vec<BybitWsOrderBookEntry>,
+}
+
+/// Side of the order book.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BookSide {
+    Bid,
+    Ask,
+}
+
+/// In-memory L2 order book built from snapshot, and delta messages.
+/// Price levels are keyed by price in number of ticks.
+#[derive(Debug, Clone)]
+pub struct OrderBook {
+    pub symbol: String,
+    pub tick_step: f64,
+    pub bids: std::collections::BTreeMap<i64, f64>,
+    pub asks: std::collections::BTreeMap<i64, f64>,
+    /// Sequence of the last applied message
+    pub cross_seq: u64,
+    /// Whether or not snapshot has been received, and all deltas since then
+    /// applied cleanly
+    pub synced: bool,
+}
+
+/// Result field of position list response from Bybit.
+// https://bybit-exchange.github.io/docs/inverse/#t-myposition
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct BybitPositionResult {
+    pub symbol: String,
+    pub side: String,
+    #[serde(deserialize_with = "crate::util::deserialize_f64")]
+    pub size: f64,
+    #[serde(default, deserialize_with = "crate::util::deserialize_f64")]
+    pub entry_price: f64,
+    #[serde(default, deserialize_with = "crate::util::deserialize_f64")]
+    pub unrealised_pnl: f64,
+}
+
+/// Position list response from Bybit for a single symbol.
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+pub struct BybitPositionResponse {
+    pub ret_code: u32,
+    pub ret_msg: String,
+    pub ext_code: String,
+    pub ext_info: String,
+    pub result: Option<BybitPositionResult>,    // use Option<> for error case
+}
+
+/// State shared between threads of interactive mode.
+pub struct InteractiveState {
+    pub position: Option<BybitPositionResult>,
+    /// Last status message shown to user
+    pub status: String,
+}
+
+/// State shared between connections of daemon mode.
+pub struct DaemonState {
+    /// Order books of symbols specified at start, kept up to date
+    pub books: HashMap<String, std::sync::Arc<std::sync::Mutex<OrderBook>>>,
+    /// Latest positions by symbol as reported by private stream
+    pub positions: std::sync::Mutex<HashMap<String, BybitPositionResult>>,
+    pub private_connected: std::sync::atomic::AtomicBool,
+}
+
+/// Command accepted by daemon.
+/// It is either a line of words e.g. `place XRPUSD 10`, or a json object with
+/// the same fields e.g. `{"cmd": "place", "symbol": "XRPUSD", "qty": 10}`.
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct DaemonCommand {
+    pub cmd: String,
+    pub symbol: Option<String>,
+    pub qty: Option<i64>,
+}
+
+/// Payload of webhook order.
+/// Request is authenticated by `X-Quicky-Signature` header holding hex of
+/// HMAC-SHA256 of the body with the shared secret, or by `secret` field for
+/// alert sources that cannot compute a signature.
+#[derive(Debug, serde::Deserialize)]
+pub struct WebhookOrderPayload {
+    pub symbol: String,
+    /// `Buy` or `Sell`
+    pub side: String,
+    pub qty: u64,
+    pub sl_pcnt: Option<f64>,
+    pub tp_pcnt: Option<f64>,
+    /// Unix timestamp in milliseconds when the payload was created
+    pub timestamp: u64,
+    /// Unique value of the payload, used along with `timestamp` against replay
+    pub nonce: String,
+    pub secret: Option<String>,
+}
+
+/// State of paper exchange persisted between runs.
+#[derive(Debug, Default, serde::Deserialize, serde::Serialize)]
+pub struct PaperState {
+    /// Wallet balance by settlement coin
+    pub balances: std::collections::BTreeMap<String, f64>,
+    /// Balance a settlement coin starts with when it is first traded
+    pub initial_balance: f64,
+    pub positions: std::collections::BTreeMap<String, PaperPosition>,
+    /// Active orders, followed by recently finished ones
+    pub orders: Vec<PaperOrder>,
+    pub next_order_id: u64,
+    /// Latest market data by symbol
+    pub quotes: std::collections::BTreeMap<String, PaperQuote>,
+    /// Id of the latest public trade applied by symbol
+    pub last_trade_ids: std::collections::BTreeMap<String, u64>,
+    /// Market time in unix milliseconds that funding was last settled up to
+    pub last_funding_time: u64,
+    pub replay: Option<PaperReplayCursor>,
+}
+
+/// Position on paper exchange.
+#[derive(Debug, Default, Clone, serde::Deserialize, serde::Serialize)]
+pub struct PaperPosition {
+    pub symbol: String,
+    /// `Buy`, `Sell`, or `None`
+    pub side: String,
+    pub size: f64,
+    pub entry_price: f64,
+    /// Stop-loss price, 0 if not set
+    pub stop_loss: f64,
+    /// Take-profit price, 0 if not set
+    pub take_profit: f64,
+    /// Realised PnL including fees, and funding in settlement coin
+    pub realised_pnl: f64,
+    /// Funding paid in settlement coin, negative means received
+    pub funding_paid: f64,
+}
+
+/// Order on paper exchange. Serialized with the same fields as `BybitOrderResult`.
+#[derive(Debug, Default, Clone, serde::Deserialize, serde::Serialize)]
+pub struct PaperOrder {
+    pub order_id: String,
+    #[serde(default)]
+    pub order_link_id: String,
+    pub symbol: String,
+    pub side: String,
+    pub order_type: String,
+    pub price: f64,
+    pub qty: f64,
+    pub time_in_force: String,
+    pub order_status: String,
+    pub leaves_qty: f64,
+    pub cum_exec_qty: f64,
+    pub cum_exec_value: f64,
+    pub cum_exec_fee: f64,
+    pub reject_reason: String,
+    pub cancel_type: String,
+    /// Size queued ahead of the order at its price level
+    pub queue_ahead: f64,
+    pub stop_loss: Option<f64>,
+    pub take_profit: Option<f64>,
+    pub reduce_only: bool,
+    pub created_time: u64,
+}
+
+/// Market data of a symbol as used by paper exchange.
+/// Also a line of recorded market data with `"type": "quote"`.
+#[derive(Debug, Default, Clone, serde::Deserialize, serde::Serialize)]
+pub struct PaperQuote {
+    pub symbol: String,
+    /// Unix timestamp in milliseconds
+    pub time: u64,
+    pub bid: f64,
+    pub ask: f64,
+    #[serde(default)]
+    pub bid_size: f64,
+    #[serde(default)]
+    pub ask_size: f64,
+    /// Last traded price, mid price is used if not set
+    #[serde(default)]
+    pub last: f64,
+    /// Mark price, last traded price is used if not set
+    #[serde(default)]
+    pub mark: f64,
+    #[serde(default)]
+    pub index: f64,
+    #[serde(default)]
+    pub funding_rate: f64,
+}
+
+/// Line of recorded market data replayed by paper exchange.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum PaperMarketEvent {
+    Quote(PaperQuote),
+    Trade {
+        symbol: String,
+        time: u64,
+        price: f64,
+        qty: f64,
+        /// Side of the taker
+        side: String,
+    },
+}
+
+/// Position of replay in recorded market data.
+#[derive(Debug, Default, Clone, serde::Deserialize, serde::Serialize)]
+pub struct PaperReplayCursor {
+    pub path: String,
+    /// Number of lines consumed
+    pub line: usize,
+    /// Replayed market time in unix milliseconds
+    pub market_time: u64,
+    /// Wall-clock time in unix milliseconds of the last replay
+    pub wall_time: u64,
+}
+
+/// Order recently sent, kept to detect a duplicate of it.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct RecentOrder {
+    /// Account, symbol, side, and quantity of the order, see `get_order_dedupe_key`
+    pub key: String,
+    pub order_link_id: String,
+    /// Unix timestamp in milliseconds when it was sent
+    pub time: u64,
+}
+
+/// File of recently sent orders shared by all quicky processes.
+#[derive(Debug, Default, serde::Deserialize, serde::Serialize)]
+pub struct RecentOrdersFile {
+    pub orders: Vec<RecentOrder>,
+}
+
+/// Local token bucket of rate limit of an end-point on an account.
+/// It is refilled over time, and synced with rate-limit headers of responses.
+#[derive(Debug, Clone)]
+pub struct TokenBucket {
+    /// Number of requests allowed per minute
+    pub limit: u64,
+    /// Requests that can be made right now, fraction of which is being refilled
+    pub tokens: f64,
+    /// Unix timestamp in milliseconds when the budget was last updated
+    pub updated_at: u64,
+    /// Unix timestamp in milliseconds when Bybit resets the budget, if known
+    pub reset_at: Option<u64>,
+}
+
+/// Entry of trade journal, one json per line.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum JournalEntry {
+    /// Order create request as sent, along with exchange's response
+    Order(JournalOrder),
+    /// State, and fills of an order as observed after it was placed
+    Fill(JournalFill),
+}
+
+/// Order create request recorded in trade journal.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct JournalOrder {
+    /// Unix timestamp in milliseconds when it was sent
+    pub time: u64,
+    /// Account it was sent on, see `get_account_label`
+    pub account: String,
+    /// `mainnet`, `testnet`, or `paper`
+    pub network: String,
+    pub order_link_id: String,
+    /// Order id assigned by exchange, `None` if it was not placed
+    pub order_id: Option<String>,
+    pub symbol: String,
+    pub side: String,
+    pub order_type: String,
+    pub time_in_force: String,
+    pub qty: u64,
+    /// Limit price, `None` for market order
+    pub price: Option<f64>,
+    /// Price the order was computed from
+    pub reference_price: Option<f64>,
+    pub stop_loss: Option<f64>,
+    pub take_profit: Option<f64>,
+    pub reduce_only: bool,
+    /// Milliseconds it took until the order was accepted, or failed
+    pub latency_ms: f64,
+    /// Error if the order was not placed
+    pub error: Option<String>,
+    /// Exchange's response as received, `None` if there was none
+    pub response: Option<serde_json::Value>,
+}
+
+/// Observed state of an order recorded in trade journal.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct JournalFill {
+    /// Unix timestamp in milliseconds when it was observed
+    pub time: u64,
+    pub account: String,
+    pub network: String,
+    pub order_id: String,
+    pub symbol: String,
+    pub side: String,
+    pub state: OrderState,
+    pub cum_exec_qty: f64,
+    /// Executed value in settlement coin
+    pub cum_exec_value: f64,
+    /// Fee in settlement coin, negative means rebate
+    pub cum_exec_fee: f64,
+    pub leaves_qty: f64,
+}
+
+/// Execution quality of orders of a symbol as of trade journal.
+/// Rates are over orders whose final state is known, `None` if there is none.
+#[derive(Debug, Default, serde::Serialize)]
+pub struct ExecutionReport {
+    pub symbol: String,
+    /// Coin fees are denominated in
+    pub settle_coin: String,
+    /// Orders sent
+    pub orders: usize,
+    /// Orders not placed due to error
+    pub failed: usize,
+    /// Orders placed whose final state is not yet known
+    pub pending: usize,
+    pub filled: usize,
+    /// Orders cancelled after filled partially
+    pub partially_filled: usize,
+    /// Orders cancelled as post-only would take liquidity
+    pub post_only_rejected: usize,
+    /// Orders cancelled, or rejected without any fill
+    pub cancelled: usize,
+    /// Filled quantity over quantity of orders
+    pub fill_rate_pcnt: Option<f64>,
+    pub post_only_rejection_pcnt: Option<f64>,
+    /// Seconds from sending an order until it was seen filled
+    pub avg_time_to_fill_secs: Option<f64>,
+    pub median_time_to_fill_secs: Option<f64>,
+    /// Average fill price better than reference price at submission, weighted
+    /// by filled quantity, in basis points
+    pub price_improvement_bps: Option<f64>,
+    /// Fees paid in settlement coin, negative means rebate
+    pub fees_paid: f64,
+    /// Fees the filled quantity would have cost as market orders
+    pub market_order_fees: f64,
+}
+
+/// Closed profit and loss as exported.
+#[derive(Debug, serde::Serialize)]
+pub struct ClosedPnlExport {
+    /// UTC time it was closed
+    pub time: String,
+    pub symbol: String,
+    pub order_id: String,
+    pub side: String,
+    pub exec_type: String,
+    pub closed_size: f64,
+    pub avg_entry_price: f64,
+    pub avg_exit_price: f64,
+    /// In `coin`
+    pub closed_pnl: f64,
+    pub coin: String,
+    /// `closed_pnl` converted to USD at exit price
+    pub closed_pnl_usd: f64,
+    pub fill_count: u64,
+}
+
+/// Execution as exported.
+#[derive(Debug, serde::Serialize)]
+pub struct ExecutionExport {
+    /// UTC time it was executed
+    pub time: String,
+    pub symbol: String,
+    pub order_id: String,
+    pub order_link_id: String,
+    pub exec_id: String,
+    pub exec_type: String,
+    pub side: String,
+    pub price: f64,
+    pub qty: f64,
+    /// In `coin`
+    pub value: f64,
+    /// In `coin`, negative means rebate
+    pub fee: f64,
+    pub fee_rate: f64,
+    /// `maker`, or `taker`
+    pub liquidity: String,
+    pub coin: String,
+    /// `fee` converted to USD at execution price
+    pub fee_usd: f64,
+}
+
+/// Funding payment as exported.
+#[derive(Debug, serde::Serialize)]
+pub struct FundingExport {
+    /// UTC time it was settled
+    pub time: String,
+    pub symbol: String,
+    /// Side of the position
+    pub side: String,
+    pub position_size: f64,
+    pub mark_price: f64,
+    pub funding_rate: f64,
+    /// In `coin`, positive means paid, negative means received
+    pub funding_fee: f64,
+    pub coin: String,
+    /// `funding_fee` converted to USD at mark price
+    pub funding_fee_usd: f64,
+}
+
+/// Result of placing an order on an account as printed in json, or table output.
+#[derive(Debug, Default, serde::Serialize)]
+pub struct OrderReport {
+    /// Name of account in config file, if it is part of a group
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub account: Option<String>,
+    /// `None` if the order is not placed
+    pub order_id: Option<String>,
+    pub order_link_id: Option<String>,
+    pub symbol: String,
+    /// `Buy`, or `Sell`
+    pub side: String,
+    pub qty: u64,
+    pub price: Option<f64>,
+    pub stop_loss: Option<f64>,
+    pub take_profit: Option<f64>,
+    /// Final state of the order e.g. `filled`, `cancelled_post_only`, or
+    /// `accepted` if it is not known, `failed` if the order is not placed,
+    /// `skipped` if its quantity rounds to 0
+    pub status: String,
+    pub filled_qty: f64,
+    pub avg_fill_price: Option<f64>,
+    /// Fee in settlement coin, negative means rebate
+    pub fee: f64,
+    /// Seconds it took until the order was accepted
+    pub elapsed_secs: Option<f64>,
+    /// Seconds it took until its final state was known, or waiting for it gave up
+    pub confirm_elapsed_secs: Option<f64>,
+    pub error: Option<String>,
+    /// Whether or not the order was cancelled as it wasn't fully filled in time
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub expired: bool,
+    /// Market order of quantity left unfilled by the expired order
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fallback: Option<Box<OrderReport>>,
+}
+
+/// Result of placing an order on a group of accounts as printed in json output.
+#[derive(Debug, serde::Serialize)]
+pub struct GroupOrderReport {
+    pub group: String,
+    /// Number of accounts the order is placed on
+    pub placed: usize,
+    /// Number of accounts of the group
+    pub accounts: usize,
+    /// Quantity placed on all accounts combined
+    pub qty: u64,
+    /// Quantity filled on all accounts combined, `None` if confirmation is not requested
+    pub filled_qty: Option<f64>,
+    pub orders: Vec<OrderReport>,
+}
+
+/// Order of dry run as printed in json, or table output.
+#[derive(Debug, Default, serde::Serialize)]
+pub struct OrderPlanReport {
+    /// Name of account in config file, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub account: Option<String>,
+    pub order_link_id: Option<String>,
+    pub symbol: String,
+    pub side: String,
+    pub qty: u64,
+    pub price: Option<f64>,
+    pub reference_price: Option<f64>,
+    pub stop_loss: Option<f64>,
+    pub take_profit: Option<f64>,
+    /// Notional value in settlement coin
+    pub notional: Option<f64>,
+    pub maker_fee: Option<f64>,
+    /// Loss at stop-loss including fees in settlement coin
+    pub loss_at_stop: Option<f64>,
+    pub within_limits: bool,
+    pub error: Option<String>,
+}
+
+/// Result of placing a ladder as printed in json output.
+#[derive(Debug, serde::Serialize)]
+pub struct LadderReport {
+    pub symbol: String,
+    pub side: String,
+    /// Quantity of all levels combined
+    pub qty: u64,
+    pub reference_price: f64,
+    /// Stop-loss price common to all levels
+    pub stop_loss: f64,
+    /// Number of levels placed
+    pub placed: usize,
+    pub orders: Vec<OrderReport>,
+}
+
+/// Order of a batch as read from its file, or stdin.
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct BatchOrder {
+    /// Line, or position in json array it is read from, counted from 1
+    #[serde(skip)]
+    pub line: usize,
+    pub symbol: String,
+    /// `Buy` or `Sell`
+    pub side: String,
+    pub qty: u64,
+    pub sl_pcnt: Option<f64>,
+    pub tp_pcnt: Option<f64>,
+}
+
+/// Report of an order of a batch along with the line it is read from.
+#[derive(Debug, serde::Serialize)]
+pub struct BatchLineReport<T> {
+    pub line: usize,
+    #[serde(flatten)]
+    pub report: T,
+}
+
+/// Result of placing a batch as printed in json output.
+#[derive(Debug, serde::Serialize)]
+pub struct BatchReport {
+    pub placed: usize,
+    pub failed: usize,
+    pub orders: Vec<BatchLineReport<OrderReport>>,
+}
+
+/// Take-profit target of a bracket along with its order.
+#[derive(Debug, serde::Serialize)]
+pub struct BracketTargetReport {
+    pub pcnt: f64,
+    pub r_multiple: f64,
+    #[serde(flatten)]
+    pub order: OrderReport,
+}
+
+/// Result of a bracket as printed in json output.
+#[derive(Debug, serde::Serialize)]
+pub struct BracketReport {
+    pub entry: OrderReport,
+    /// Latest stop-loss price of position
+    pub stop_loss: f64,
+    /// Distance from entry price to stop-loss
+    pub risk: f64,
+    pub targets: Vec<BracketTargetReport>,
+    pub outcome: BracketOutcome,
+    /// Quantity of the bracket left open when management ended
+    pub remaining_qty: u64,
+}
@@ -1,23 +1,45 @@
 use crate::types::*;
 use crate::defines::*;
+use crate::credentials::*;
+use crate::paper::*;
+use crate::dedupe::*;
+use crate::ratelimit::*;
+use crate::journal::*;
+use crate::output::*;
+use crate::logging::*;
 
 use isahc::prelude::*;
 use url::Url;
 use ring::*;
 use regex::Regex;
 
+use std::collections::BTreeMap;
+
 /// Send a quick limit order.
 /// Side depends on specified `qty`. If negative, then it is sell side, otherwise
 /// it is buy side.
+/// In success, return the order as accepted by Bybit. Note that accepted order
+/// is not necessarily filled, see `wait_for_order_confirmation`.
 ///
 /// # Arguments
 /// * `context` - `TradingContext` for information that we know before hand. This
-///               will reduce time in sending unnecessary API request to get
-///               such information.
+///   will reduce time in sending unnecessary API request to get such information.
 /// * `symbol` - symbol to create an order for
 /// * `qty` - quantity. It can be negative for sell, or positive buy. If specified
-///           as 0, then it will be ignored.
-pub fn api_send_quick_limit_order(context: &TradingContext, symbol: &str, qty: i64) -> Result<(), StatusCode> {
+///   as 0, then it will be ignored.
+pub fn api_send_quick_limit_order(context: &TradingContext, symbol: &str, qty: i64) -> Result<BybitOrderResult, StatusCode> {
+    let plan = api_plan_quick_limit_order(context, symbol, qty)?;
+    api_send_order_plan(context, &plan)
+}
+
+/// Compute a quick limit order as of `api_send_quick_limit_order` without
+/// sending it. Only the reference price is fetched.
+///
+/// # Arguments
+/// * `context` - `TradingContext` for context information used in trading
+/// * `symbol` - symbol to create an order for
+/// * `qty` - quantity. It can be negative for sell, or positive buy.
+pub fn api_plan_quick_limit_order(context: &TradingContext, symbol: &str, qty: i64) -> Result<OrderPlan, StatusCode> {
     // We can get the price step from API, use
     // https://bybit-exchange.github.io/docs/inverse/?console#t-querysymbol
     // but that would be too much of time consuming.
@@ -25,149 +47,1153 @@ pub fn api_send_quick_limit_order(context: &TradingContext, symbol: &str, qty: i
         return Err(StatusCode::InternalErrorNoTickStepAvailable);
     }
 
-    let price = api_get_current_price(context, symbol)?;
+    if qty == 0 {
+        return Err(StatusCode::ErrorIncorrectParameterValue);
+    }
+
+    let price = api_get_reference_price(context, symbol, qty > 0)?;
+
+    // join best bid/ask, otherwise a tick away from reference price
+    let tick_step = context.tick_steps[symbol];
+    let target_limit_price = if context.price_ref == PriceReference::BidAsk { price } else if qty > 0 { round_to_tick(price - tick_step, tick_step) } else { round_to_tick(price + tick_step, tick_step) };
+
+    plan_limit_order(context, symbol, qty, target_limit_price, price)
+}
 
+/// Validate fields of an order e.g. from webhook, or batch, then return its
+/// signed quantity.
+///
+/// # Arguments
+/// * `context` - `TradingContext` for symbols we know of
+/// * `symbol` - symbol of the order
+/// * `side` - `Buy` or `Sell`
+/// * `qty` - quantity
+/// * `sl_pcnt` - stop-loss percentage if not the default one
+/// * `tp_pcnt` - take-profit percentage if any
+pub fn validate_order_fields(context: &TradingContext, symbol: &str, side: &str, qty: u64, sl_pcnt: Option<f64>, tp_pcnt: Option<f64>) -> Result<i64, StatusCode> {
+    if !context.tick_steps.contains_key(symbol) {
+        return Err(StatusCode::InternalErrorNoTickStepAvailable);
+    }
+    if qty == 0 || qty > i64::MAX as u64 {
+        return Err(StatusCode::ErrorIncorrectParameterValue);
+    }
+    let pcnt_valid = |pcnt: Option<f64>| pcnt.map(|v| v > 0.0 && v < 100.0).unwrap_or(true);
+    if !pcnt_valid(sl_pcnt) || !pcnt_valid(tp_pcnt) {
+        return Err(StatusCode::ErrorIncorrectParameterValue);
+    }
+
+    match side {
+        "Buy" => Ok(qty as i64),
+        "Sell" => Ok(-(qty as i64)),
+        _ => Err(StatusCode::ErrorIncorrectParameterValue),
+    }
+}
+
+/// Copy of `context` with stop-loss, and take-profit percentage of an order
+/// if specified.
+pub fn get_order_context(context: &TradingContext, sl_pcnt: Option<f64>, tp_pcnt: Option<f64>) -> TradingContext {
+    let mut order_context = context.clone();
+    if let Some(sl_pcnt) = sl_pcnt {
+        order_context.stop_loss_pcnt = sl_pcnt;
+    }
+    if tp_pcnt.is_some() {
+        order_context.take_profit_pcnt = tp_pcnt;
+    }
+    order_context
+}
+
+/// Send a PostOnly limit order at the specified `limit_price`.
+/// Side depends on specified `qty` as of `api_send_quick_limit_order`.
+/// Stop-loss is placed `TradingContext::stop_loss_pcnt` away from `reference_price`,
+/// and so is take-profit if `TradingContext::take_profit_pcnt` is set.
+///
+/// # Arguments
+/// * `context` - `TradingContext` for context information used in trading
+/// * `symbol` - symbol to create an order for
+/// * `qty` - quantity. It can be negative for sell, or positive buy.
+/// * `limit_price` - price of the limit order
+/// * `reference_price` - price to compute stop-loss price from
+pub fn api_send_limit_order(context: &TradingContext, symbol: &str, qty: i64, limit_price: f64, reference_price: f64) -> Result<BybitOrderResult, StatusCode> {
+    let plan = plan_limit_order(context, symbol, qty, limit_price, reference_price)?;
+    api_send_order_plan(context, &plan)
+}
+
+/// Compute a PostOnly limit order at the specified `limit_price` along with its
+/// stop-loss, and take-profit. See `api_send_limit_order`.
+///
+/// # Arguments
+/// * `context` - `TradingContext` for context information used in trading
+/// * `symbol` - symbol to create an order for
+/// * `qty` - quantity. It can be negative for sell, or positive buy.
+/// * `limit_price` - price of the limit order
+/// * `reference_price` - price to compute stop-loss price from
+pub fn plan_limit_order(context: &TradingContext, symbol: &str, qty: i64, limit_price: f64, reference_price: f64) -> Result<OrderPlan, StatusCode> {
+    if !context.tick_steps.contains_key(symbol) {
+        return Err(StatusCode::InternalErrorNoTickStepAvailable);
+    }
     if qty == 0 {
         return Err(StatusCode::ErrorIncorrectParameterValue);
     }
 
     let is_buy_side = qty > 0;
     let tick_step = context.tick_steps[symbol];
-    let tick_step_value_roundup = 10.0_f64.powi(count_tick_steps(tick_step));
     let stop_loss_pcnt = context.stop_loss_pcnt;
-    let target_limit_price:f64 = if is_buy_side { ((price - tick_step)*tick_step_value_roundup).round() / tick_step_value_roundup } else { ((price + tick_step)*tick_step_value_roundup).round() / tick_step_value_roundup };
-    let curr_unix_timestamp = get_unix_timestamp_as_millis();
-    let curr_unix_timestamp_str = curr_unix_timestamp.to_string();
-    let side = if is_buy_side {"Buy"} else {"Sell"};
-    let qty_abs:u64 = qty.abs() as u64;
-
-    let stop_loss_price:f64 = if is_buy_side { ((price * (1.0 - stop_loss_pcnt/100.0))*tick_step_value_roundup).round() / tick_step_value_roundup } else { ((price * (1.0 + stop_loss_pcnt/100.0))*tick_step_value_roundup).round() / tick_step_value_roundup };
-
-    // TODO: add into hash, then sort alphabetically
-    // prepare request's parameters for private API
-    let param_str = format!("api_key={api_key}&order_type=Limit&price={price}&qty={qty}&side={side}&stop_loss={stop_loss}&symbol={symbol}&time_in_force=PostOnly&timestamp={timestamp}", api_key=get_api_key(context), price=target_limit_price, qty=qty_abs.to_string(), side=side, stop_loss=stop_loss_price, symbol=symbol, timestamp=curr_unix_timestamp_str);
-    let sign = sign_private_request_params(&param_str, get_api_secret(context));
-
-    // Serialize in serde is ok to work with &str, but not Deserialize
-    #[derive(Debug, serde::Serialize)]
-    struct RequestObj<'a> {
-        api_key: &'a str,
-        order_type: &'a str,
-        price: f64,
-        qty: u64,
-        side: &'a str,
-        stop_loss: f64,
-        symbol: &'a str,
-        timestamp: &'a str,
-        time_in_force: &'a str,
-        sign: &'a str,
-    }
-
-    let request_json_obj = RequestObj {
-        api_key: get_api_key(context),
-        order_type: "Limit",
-        price: target_limit_price,
-        qty: qty_abs,
-        side: side,
-        stop_loss: stop_loss_price,
-        symbol: symbol,
-        timestamp: &curr_unix_timestamp_str,
-        time_in_force: "PostOnly",
-        sign: &sign,
+
+    let stop_loss_price:f64 = if is_buy_side { round_to_tick(reference_price * (1.0 - stop_loss_pcnt/100.0), tick_step) } else { round_to_tick(reference_price * (1.0 + stop_loss_pcnt/100.0), tick_step) };
+    let take_profit_price = context.take_profit_pcnt.map(|take_profit_pcnt| {
+        if is_buy_side { round_to_tick(reference_price * (1.0 + take_profit_pcnt/100.0), tick_step) } else { round_to_tick(reference_price * (1.0 - take_profit_pcnt/100.0), tick_step) }
+    });
+
+    Ok(OrderPlan {
+        order_link_id: new_order_link_id()?,
+        symbol: symbol.to_owned(),
+        side: if is_buy_side {"Buy"} else {"Sell"}.to_owned(),
+        qty: qty.unsigned_abs(),
+        limit_price,
+        reference_price,
+        stop_loss_price,
+        take_profit_price,
+        tick_step,
+    })
+}
+
+/// Form request's parameters of order create end-point from `plan`.
+///
+/// # Arguments
+/// * `plan` - `OrderPlan`
+pub fn get_order_plan_params(plan: &OrderPlan) -> BTreeMap<&'static str, serde_json::Value> {
+    let mut params = BTreeMap::from([
+        ("order_link_id", serde_json::json!(plan.order_link_id)),
+        ("order_type", serde_json::json!("Limit")),
+        ("price", serde_json::json!(plan.limit_price)),
+        ("qty", serde_json::json!(plan.qty)),
+        ("side", serde_json::json!(plan.side)),
+        ("stop_loss", serde_json::json!(plan.stop_loss_price)),
+        ("symbol", serde_json::json!(plan.symbol)),
+        ("time_in_force", serde_json::json!("PostOnly")),
+    ]);
+    if let Some(take_profit_price) = plan.take_profit_price {
+        params.insert("take_profit", serde_json::json!(take_profit_price));
+    }
+    params
+}
+
+/// Send the order as computed in `plan`, once it passes safety limits, and
+/// is not a duplicate of an order just sent. See `check_order_limits`, and
+/// `reserve_order`.
+///
+/// # Arguments
+/// * `context` - `TradingContext` for context information used in trading
+/// * `plan` - `OrderPlan`
+pub fn api_send_order_plan(context: &TradingContext, plan: &OrderPlan) -> Result<BybitOrderResult, StatusCode> {
+    let (result, _) = api_send_order_plans(context, std::slice::from_ref(plan))?.remove(0);
+    result
+}
+
+/// Send orders of `plans` of the same symbol, and side e.g. a ladder
+/// concurrently, `MAX_CONCURRENT_ORDERS` at a time, once they pass safety limits as a whole, and are not a
+/// duplicate of orders just sent. They count as a single order of their
+/// combined quantity for duplicate protection. See `check_order_plans_limits`,
+/// and `reserve_order`.
+///
+/// Return the result of each order in order of `plans`, along with seconds it
+/// took until it was accepted, or failed.
+///
+/// # Arguments
+/// * `context` - `TradingContext` for context information used in trading
+/// * `plans` - `OrderPlan` of each order
+pub fn api_send_order_plans(context: &TradingContext, plans: &[OrderPlan]) -> Result<Vec<SentOrderPlan>, StatusCode> {
+    check_order_plans_limits(context, plans)?;
+    let combined = OrderPlan { qty: plans.iter().map(|p| p.qty).sum(), ..plans[0].clone() };
+    reserve_order(context, &combined)?;
+
+    let send = |plan: &OrderPlan| {
+        let start = std::time::Instant::now();
+        let result = api_create_order(context, get_order_plan_params(plan), Some(plan.reference_price));
+        (result, measure_end(&start, false))
     };
-    
-    let raw_url_str = get_full_uri(context.use_testnet, "/v2/private/order/create");
-    let url = Url::parse(&raw_url_str);
-    if let Err(_) = url {
-        return Err(StatusCode::InternalErrorCreatingHttpRequest);
+    let results = run_concurrently(plans, send);
+    // an order might have landed on network error, so it still counts
+    if results.iter().all(|(result, _)| result.as_ref().is_err_and(|e| !matches!(e, StatusCode::ErrorNetwork))) {
+        release_order(context, &combined);
+    }
+    Ok(results)
+}
+
+/// Send order of `plan` which already passed safety limits, and was reserved
+/// e.g. as part of a batch by `reserve_orders`. It is released if it is known
+/// not to be placed. Return its result along with seconds it took until it was
+/// accepted, or failed.
+///
+/// # Arguments
+/// * `context` - `TradingContext` for context information used in trading
+/// * `plan` - `OrderPlan` of the order
+pub fn api_send_reserved_order_plan(context: &TradingContext, plan: &OrderPlan) -> SentOrderPlan {
+    let start = std::time::Instant::now();
+    let result = api_create_order(context, get_order_plan_params(plan), Some(plan.reference_price));
+    // an order might have landed on network error, so it still counts
+    if result.as_ref().is_err_and(|e| !matches!(e, StatusCode::ErrorNetwork)) {
+        release_order(context, plan);
     }
+    (result, measure_end(&start, false))
+}
 
-    let request_json_obj_body = serde_json::to_vec(&request_json_obj);
-    if request_json_obj_body.is_err() {
-        return Err(StatusCode::InternalErrorParsingJsonObject);
+/// Run `f` on each of `items` concurrently, `MAX_CONCURRENT_ORDERS` at a time
+/// not to burst through rate limit. Return the results in order of `items`.
+///
+/// # Arguments
+/// * `items` - items e.g. orders to run `f` on
+/// * `f` - function to run on each item
+pub fn run_concurrently<T: Sync, R: Send>(items: &[T], f: impl Fn(&T) -> R + Sync) -> Vec<R> {
+    if items.len() == 1 {
+        return vec![f(&items[0])];
     }
+    let f = &f;
+    items.chunks(MAX_CONCURRENT_ORDERS).flat_map(|chunk| std::thread::scope(|scope| {
+        let handles: Vec<_> = chunk.iter().map(|item| scope.spawn(move || f(item))).collect();
+        handles.into_iter().map(|h| h.join().unwrap_or_else(|e| std::panic::resume_unwind(e))).collect::<Vec<_>>()
+    })).collect()
+}
 
-    let request = isahc::Request::builder()
-        .method("POST")
-        .uri(url.unwrap().as_str())
-        .header("content-type", "application/json")
-        .version_negotiation(isahc::config::VersionNegotiation::http2())
-        .body(request_json_obj_body.unwrap());
-
-    match isahc::send(request.unwrap()) {
-        Ok(mut res) => {
-            match res.json::<BybitGenericNoResultResponse>() {
-                Ok(json) => {
-                    if json.ret_code == 0 { return Ok(()); } else {
-                        eprintln!("{:?}", json);
-                        return Err(StatusCode::ErrorApiResponse);
+/// Send order create request with `params` having `order_link_id`, then
+/// record it along with its outcome in trade journal.
+/// On network error, the order may, or may not have landed. So it is queried
+/// by its link id first, then re-sent with the same link id only if it is not
+/// found, up to `ORDER_SEND_ATTEMPTS` times in total. Bybit refuses a repeated
+/// link id, so the order cannot be placed twice.
+///
+/// # Arguments
+/// * `context` - `TradingContext` for context information used in trading
+/// * `params` - request's parameters
+/// * `reference_price` - price the order was computed from, if any
+fn api_create_order(context: &TradingContext, params: BTreeMap<&str, serde_json::Value>, reference_price: Option<f64>) -> Result<BybitOrderResult, StatusCode> {
+    let start = std::time::Instant::now();
+    let mut response = None;
+    let result = api_create_order_with_retries(context, &params, &mut response);
+    journal_order_request(context, &params, reference_price, start.elapsed(), response, &result);
+    result
+}
+
+/// Send order create request as of `api_create_order`, keeping the last
+/// response received in `response`.
+fn api_create_order_with_retries(context: &TradingContext, params: &BTreeMap<&str, serde_json::Value>, response: &mut Option<serde_json::Value>) -> Result<BybitOrderResult, StatusCode> {
+    let symbol = params.get("symbol").and_then(|v| v.as_str()).unwrap_or_default().to_owned();
+    let order_link_id = params.get("order_link_id").and_then(|v| v.as_str()).unwrap_or_default().to_owned();
+
+    let mut attempt = 1;
+    loop {
+        match api_private_post::<serde_json::Value>(context, "/v2/private/order/create", params.clone()) {
+            Ok(raw) => {
+                *response = Some(raw.clone());
+                let json = serde_json::from_value::<BybitOrderResponse>(raw).map_err(|e| {
+                    eprintln!("Error: unexpected response of order create: {}", e);
+                    StatusCode::ErrorJsonParsing
+                })?;
+                if json.ret_code != 0 {
+                    eprintln!("Error: {} (ret_code {})", json.ret_msg, json.ret_code);
+                    if json.ret_code == TIMESTAMP_RET_CODE {
+                        print_clock_skew(context);
                     }
+                    return Err(StatusCode::ErrorApiResponse);
                 }
-                Err(e) => {
-                    eprintln!("{:?}", e);
-                    Err(StatusCode::ErrorJsonParsing)
+                // guarantee to have result for success case
+                return json.result.ok_or(StatusCode::MalformedAPIResponseFormat);
+            },
+            Err(StatusCode::ErrorNetwork) if attempt < ORDER_SEND_ATTEMPTS && !order_link_id.is_empty() => {
+                match api_query_order_by_link_id(context, &symbol, &order_link_id) {
+                    Ok(Some(order)) => return Ok(order),
+                    Ok(None) => {
+                        print_info(&format!("order {} didn't land due to network error, sending it again", order_link_id));
+                        attempt += 1;
+                    },
+                    Err(e) => {
+                        eprintln!("Error: order {} may have been placed, check it before placing again", order_link_id);
+                        return Err(e);
+                    }
                 }
+            },
+            Err(StatusCode::ErrorNetwork) => {
+                eprintln!("Error: order {} may have been placed, check it before placing again", order_link_id);
+                return Err(StatusCode::ErrorNetwork);
+            },
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Query an order by its client id. Return `None` if there is no such order.
+///
+/// # Arguments
+/// * `context` - `TradingContext` for context information used in trading
+/// * `symbol` - symbol of the order
+/// * `order_link_id` - client id of the order as sent when it was created
+pub fn api_query_order_by_link_id(context: &TradingContext, symbol: &str, order_link_id: &str) -> Result<Option<BybitOrderResult>, StatusCode> {
+    let params = BTreeMap::from([
+        ("order_link_id", serde_json::json!(order_link_id)),
+        ("symbol", serde_json::json!(symbol)),
+    ]);
+
+    let json = api_private_get::<BybitOrderResponse>(context, "/v2/private/order", params)?;
+    match json.ret_code {
+        0 => Ok(json.result),
+        // order not exists
+        20001 => Ok(None),
+        _ => {
+            eprintln!("Error: {}", json.ret_msg);
+            Err(StatusCode::ErrorApiResponse)
+        }
+    }
+}
+
+/// Label of account of `context` e.g. `env:QUICKY_BYBIT_API_KEY`, or
+/// `store:main`, without revealing its api key.
+///
+/// # Arguments
+/// * `context` - `TradingContext` of the account
+pub fn get_account_label(context: &TradingContext) -> String {
+    match &context.credentials {
+        CredentialSource::Env { key_env, .. } => format!("env:{}", key_env),
+        CredentialSource::Store { name, .. } => format!("store:{}", name),
+    }
+}
+
+/// Generate a new client id of an order.
+/// It is unique enough to be used as `order_link_id`, which is up to 36 characters.
+pub fn new_order_link_id() -> Result<String, StatusCode> {
+    let mut random = [0u8; 4];
+    ring::rand::SecureRandom::fill(&ring::rand::SystemRandom::new(), &mut random).map_err(|_| StatusCode::InternalErrorGeneric)?;
+    Ok(format!("quicky-{}-{}", get_unix_timestamp_as_millis(), to_hex(&random)))
+}
+
+/// Check `plan` against `TradingContext::limits`. Each violated limit is
+/// reported, then `StatusCode::ErrorLimitExceeded` is returned.
+///
+/// Limits on quantity, and notional are checked locally. Price deviation,
+/// open orders, and position size each need a request, which is only made if
+/// that limit is configured.
+///
+/// # Arguments
+/// * `context` - `TradingContext` for its limits
+/// * `plan` - `OrderPlan` to check
+pub fn check_order_limits(context: &TradingContext, plan: &OrderPlan) -> Result<(), StatusCode> {
+    check_order_plans_limits(context, std::slice::from_ref(plan))
+}
+
+/// Check orders of `plans` of the same symbol, and side as a whole e.g. a
+/// ladder against `TradingContext::limits`. Quantity, notional, and position
+/// are of all orders combined, while price deviation is of each order. See
+/// `check_order_limits`.
+///
+/// # Arguments
+/// * `context` - `TradingContext` for its limits
+/// * `plans` - `OrderPlan` of each order to check
+pub fn check_order_plans_limits(context: &TradingContext, plans: &[OrderPlan]) -> Result<(), StatusCode> {
+    let first = plans.first().ok_or(StatusCode::ErrorIncorrectParameterValue)?;
+    let limits = &context.limits;
+    let symbol_limits = limits.symbols.get(&first.symbol).cloned().unwrap_or_default();
+    let qty: u64 = plans.iter().map(|p| p.qty).sum();
+    let mut violations = Vec::new();
+
+    if let Some(max_qty) = symbol_limits.max_qty {
+        if qty > max_qty {
+            violations.push(format!("quantity {} is above max_qty {} of {}", qty, max_qty, first.symbol));
+        }
+    }
+    if let Some(max_notional) = symbol_limits.max_notional {
+        let notional: f64 = plans.iter().map(|p| p.qty as f64 / p.limit_price).sum();
+        if notional > max_notional {
+            violations.push(format!("notional {:.8} {} is above max_notional {} of {}", notional, get_settle_coin(&first.symbol), max_notional, first.symbol));
+        }
+    }
+    if let Some(max_deviation_pcnt) = limits.max_price_deviation_pcnt {
+        let ticker = api_get_ticker(context, &first.symbol)?;
+        for (name, price) in [("mark", &ticker.mark_price), ("index", &ticker.index_price)] {
+            let price: f64 = price.parse().unwrap_or(0.0);
+            if price <= 0.0 {
+                continue;
             }
-        },
-        Err(_) => {
+            for plan in plans.iter() {
+                let deviation_pcnt = (plan.limit_price - price).abs() / price * 100.0;
+                if deviation_pcnt > max_deviation_pcnt {
+                    violations.push(format!("limit price {} is {:.3}% away from {} price {}, above max_price_deviation_pcnt {}", plan.limit_price, deviation_pcnt, name, price, max_deviation_pcnt));
+                }
+            }
+        }
+    }
+    if let Some(max_open_orders) = limits.max_open_orders {
+        let open_orders = api_get_open_orders(context, &first.symbol)?.len();
+        if open_orders + plans.len() > max_open_orders as usize {
+            violations.push(format!("{} open orders of {} already, max_open_orders is {}", open_orders, first.symbol, max_open_orders));
+        }
+    }
+    if let Some(max_position) = symbol_limits.max_position {
+        let position = api_get_position(context, &first.symbol)?;
+        let signed_size = match position.side.as_str() {
+            "Buy" => position.size,
+            "Sell" => -position.size,
+            _ => 0.0,
+        };
+        let signed_qty = if first.side == "Buy" { qty as f64 } else { -(qty as f64) };
+        let size = (signed_size + signed_qty).abs();
+        if size > max_position as f64 {
+            violations.push(format!("position of {} would be {} once filled, above max_position {}", first.symbol, size, max_position));
+        }
+    }
+
+    if violations.is_empty() {
+        return Ok(());
+    }
+    for violation in violations.iter() {
+        eprintln!("Error: {}", violation);
+    }
+    Err(StatusCode::ErrorLimitExceeded)
+}
+
+/// Print `plan` along with the signed parameter string that would be sent,
+/// with its signature redacted. Also print estimated notional, maker fee, and
+/// distance to stop-loss.
+///
+/// # Arguments
+/// * `context` - `TradingContext` for api key, and URL
+/// * `plan` - `OrderPlan`
+pub fn print_order_plan(context: &TradingContext, plan: &OrderPlan) -> Result<(), StatusCode> {
+    let mut params = get_order_plan_params(plan);
+    sign_private_params(context, &mut params)?;
+
+    let coin = get_settle_coin(&plan.symbol);
+    let (notional, fee, loss_at_stop) = get_order_plan_estimate(plan);
+    let stop_distance = (plan.limit_price - plan.stop_loss_price).abs();
+
+    println!("dry run, order is not sent");
+    println!("  {} {} {} PostOnly limit @ {} (reference {} from {})", plan.side, plan.qty, plan.symbol, plan.limit_price, plan.reference_price, context.price_ref);
+    println!("  stop-loss {}, {} ticks ({:.3}%) from limit price", plan.stop_loss_price, (stop_distance / plan.tick_step).round(), stop_distance / plan.limit_price * 100.0);
+    if let Some(take_profit_price) = plan.take_profit_price {
+        let distance = (take_profit_price - plan.limit_price).abs();
+        println!("  take-profit {}, {} ticks ({:.3}%) from limit price", take_profit_price, (distance / plan.tick_step).round(), distance / plan.limit_price * 100.0);
+    }
+    println!("  notional {:.8} {}, maker fee {:.8} {} ({}%)", notional, coin, fee, coin, MAKER_FEE_RATE * 100.0);
+    println!("  loss at stop-loss including fees {:.8} {}", loss_at_stop, coin);
+    println!("  POST {}", get_full_uri(context, "/v2/private/order/create"));
+    println!("  {}&sign=<redacted>", form_param_str(&params));
+    Ok(())
+}
+
+/// Estimate notional, maker fee, and loss at stop-loss including fees of
+/// `plan`, all in settlement coin.
+pub fn get_order_plan_estimate(plan: &OrderPlan) -> (f64, f64, f64) {
+    // inverse contract is quoted in USD, its value is in settlement coin
+    let notional = plan.qty as f64 / plan.limit_price;
+    let fee = notional * MAKER_FEE_RATE;
+    let loss_at_stop = (plan.qty as f64 / plan.stop_loss_price - notional).abs() + plan.qty as f64 / plan.stop_loss_price * TAKER_FEE_RATE + fee;
+    (notional, fee, loss_at_stop)
+}
+
+/// Make `OrderPlanReport` of `plan`, not yet checked against safety limits.
+///
+/// # Arguments
+/// * `account` - name of account in config file, if any
+/// * `plan` - `OrderPlan`
+pub fn to_order_plan_report(account: Option<&str>, plan: &OrderPlan) -> OrderPlanReport {
+    let (notional, fee, loss_at_stop) = get_order_plan_estimate(plan);
+    OrderPlanReport {
+        account: account.map(|a| a.to_owned()),
+        order_link_id: Some(plan.order_link_id.clone()),
+        symbol: plan.symbol.clone(),
+        side: plan.side.clone(),
+        qty: plan.qty,
+        price: Some(plan.limit_price),
+        reference_price: Some(plan.reference_price),
+        stop_loss: Some(plan.stop_loss_price),
+        take_profit: plan.take_profit_price,
+        notional: Some(notional),
+        maker_fee: Some(fee),
+        loss_at_stop: Some(loss_at_stop),
+        within_limits: false,
+        error: None,
+    }
+}
+
+/// Cancel all active orders of `symbol`.
+///
+/// # Arguments
+/// * `context` - `TradingContext` for context information used in trading
+/// * `symbol` - symbol to cancel all of its active orders
+pub fn api_cancel_all_orders(context: &TradingContext, symbol: &str) -> Result<(), StatusCode> {
+    let params = BTreeMap::from([
+        ("symbol", serde_json::json!(symbol)),
+    ]);
+
+    let json = api_private_post::<BybitGenericNoResultResponse>(context, "/v2/private/order/cancelAll", params)?;
+    if json.ret_code != 0 {
+        eprintln!("Error: {}", json.ret_msg);
+        return Err(StatusCode::ErrorApiResponse);
+    }
+    Ok(())
+}
+
+/// Get open orders i.e. new, or partially filled ones of `symbol`.
+///
+/// # Arguments
+/// * `context` - `TradingContext` for context information used in trading
+/// * `symbol` - symbol to get its open orders
+pub fn api_get_open_orders(context: &TradingContext, symbol: &str) -> Result<Vec<BybitOrderResult>, StatusCode> {
+    let params = BTreeMap::from([
+        ("order_status", serde_json::json!("New,PartiallyFilled")),
+        ("symbol", serde_json::json!(symbol)),
+    ]);
+
+    let json = api_private_get::<BybitOrderListResponse>(context, "/v2/private/order/list", params)?;
+    if json.ret_code != 0 {
+        eprintln!("Error: {}", json.ret_msg);
+        return Err(StatusCode::ErrorApiResponse);
+    }
+
+    Ok(json.result.and_then(|r| r.data).unwrap_or_default())
+}
+
+/// Get closed profit and loss of `symbol` within the time range, page by page
+/// up to `HISTORY_MAX_PAGES` pages, oldest first.
+///
+/// # Arguments
+/// * `context` - `TradingContext` for context information used in trading
+/// * `symbol` - symbol to get its closed profit and loss
+/// * `since` - unix timestamp in milliseconds, inclusive
+/// * `until` - unix timestamp in milliseconds, exclusive
+pub fn api_get_closed_pnl(context: &TradingContext, symbol: &str, since: Option<u64>, until: Option<u64>) -> Result<Vec<BybitClosedPnlRecord>, StatusCode> {
+    let mut records = Vec::new();
+    for page in 1..=HISTORY_MAX_PAGES {
+        let mut params = BTreeMap::from([
+            ("limit", serde_json::json!(CLOSED_PNL_PAGE_SIZE)),
+            ("page", serde_json::json!(page)),
+            ("symbol", serde_json::json!(symbol)),
+        ]);
+        // in seconds, end time rounded up to keep the last second
+        if let Some(since) = since {
+            params.insert("start_time", serde_json::json!(since / 1000));
+        }
+        if let Some(until) = until {
+            params.insert("end_time", serde_json::json!(until.div_ceil(1000)));
+        }
+
+        let json = api_private_get::<BybitClosedPnlResponse>(context, "/v2/private/trade/closed-pnl/list", params)?;
+        if json.ret_code != 0 {
+            eprintln!("Error: {}", json.ret_msg);
+            return Err(StatusCode::ErrorApiResponse);
+        }
+        let data = json.result.and_then(|r| r.data).unwrap_or_default();
+        let is_last_page = data.len() < CLOSED_PNL_PAGE_SIZE as usize;
+        records.extend(data);
+        if is_last_page {
+            break;
+        }
+        if page == HISTORY_MAX_PAGES {
+            eprintln!("Warning: closed PnL beyond {} records is not exported, narrow down the time range", records.len());
+        }
+    }
+
+    records.retain(|r| since.is_none_or(|since| r.created_at * 1000 >= since) && until.is_none_or(|until| r.created_at * 1000 < until));
+    records.sort_by_key(|r| (r.created_at, r.id));
+    Ok(records)
+}
+
+/// Get executions, and funding settlements of `symbol` within the time range,
+/// page by page up to `HISTORY_MAX_PAGES` pages, oldest first.
+///
+/// End time is not supported by Bybit, so if `until` is specified pages are
+/// requested oldest first, and no more of them once a page reaches `until`.
+///
+/// # Arguments
+/// * `context` - `TradingContext` for context information used in trading
+/// * `symbol` - symbol to get its executions
+/// * `since` - unix timestamp in milliseconds, inclusive
+/// * `until` - unix timestamp in milliseconds, exclusive
+pub fn api_get_executions(context: &TradingContext, symbol: &str, since: Option<u64>, until: Option<u64>) -> Result<Vec<BybitExecutionRecord>, StatusCode> {
+    let mut records = Vec::new();
+    let mut truncated = false;
+    for page in 1..=HISTORY_MAX_PAGES {
+        let mut params = BTreeMap::from([
+            ("limit", serde_json::json!(EXECUTION_PAGE_SIZE)),
+            ("page", serde_json::json!(page)),
+            ("symbol", serde_json::json!(symbol)),
+        ]);
+        if let Some(since) = since {
+            params.insert("start_time", serde_json::json!(since));
+        }
+        if until.is_some() {
+            params.insert("order", serde_json::json!("asc"));
+        }
+
+        let json = api_private_get::<BybitExecutionListResponse>(context, "/v2/private/execution/list", params)?;
+        if json.ret_code != 0 {
+            eprintln!("Error: {}", json.ret_msg);
+            return Err(StatusCode::ErrorApiResponse);
+        }
+        let data = json.result.and_then(|r| r.trade_list).unwrap_or_default();
+        let is_last_page = data.len() < EXECUTION_PAGE_SIZE as usize
+            || until.is_some_and(|until| data.iter().any(|r| r.trade_time_ms >= until));
+        records.extend(data);
+        if is_last_page {
+            break;
+        }
+        truncated = page == HISTORY_MAX_PAGES;
+    }
+
+    records.retain(|r| since.is_none_or(|since| r.trade_time_ms >= since) && until.is_none_or(|until| r.trade_time_ms < until));
+    records.sort_by(|a, b| a.trade_time_ms.cmp(&b.trade_time_ms).then_with(|| a.exec_id.cmp(&b.exec_id)));
+    if truncated {
+        eprintln!("Warning: executions beyond {} records are not exported, narrow down the time range", records.len());
+    }
+    Ok(records)
+}
+
+/// Get current position of `symbol`.
+///
+/// # Arguments
+/// * `context` - `TradingContext` for context information used in trading
+/// * `symbol` - symbol to get its position
+pub fn api_get_position(context: &TradingContext, symbol: &str) -> Result<BybitPositionResult, StatusCode> {
+    let params = BTreeMap::from([
+        ("symbol", serde_json::json!(symbol)),
+    ]);
+
+    let json = api_private_get::<BybitPositionResponse>(context, "/v2/private/position/list", params)?;
+    if json.ret_code != 0 {
+        eprintln!("Error: {}", json.ret_msg);
+        return Err(StatusCode::ErrorApiResponse);
+    }
+
+    json.result.ok_or(StatusCode::ApiEmptyResult)
+}
+
+/// Close the whole position of `symbol` with a reduce-only market order.
+/// Return `None` if there is no position to close.
+///
+/// # Arguments
+/// * `context` - `TradingContext` for context information used in trading
+/// * `symbol` - symbol to close its position
+pub fn api_close_position(context: &TradingContext, symbol: &str) -> Result<Option<BybitOrderResult>, StatusCode> {
+    let position = api_get_position(context, symbol)?;
+    if position.size == 0.0 || position.side == "None" {
+        return Ok(None);
+    }
+
+    let params = BTreeMap::from([
+        ("order_link_id", serde_json::json!(new_order_link_id()?)),
+        ("order_type", serde_json::json!("Market")),
+        ("qty", serde_json::json!(position.size as u64)),
+        ("reduce_only", serde_json::json!(true)),
+        ("side", serde_json::json!(if position.side == "Buy" { "Sell" } else { "Buy" })),
+        ("symbol", serde_json::json!(symbol)),
+        ("time_in_force", serde_json::json!("ImmediateOrCancel")),
+    ]);
+
+    api_create_order(context, params, None).map(Some)
+}
+
+/// Send a market order of `qty` e.g. of quantity a limit order left unfilled.
+/// It is not checked against safety limits, nor duplicates as it completes an
+/// order which already was.
+///
+/// # Arguments
+/// * `context` - `TradingContext` for context information used in trading
+/// * `symbol` - symbol to create an order for
+/// * `qty` - quantity. It can be negative for sell, or positive buy.
+/// * `stop_loss` - stop-loss price if any
+/// * `take_profit` - take-profit price if any
+pub fn api_send_market_order(context: &TradingContext, symbol: &str, qty: i64, stop_loss: Option<f64>, take_profit: Option<f64>) -> Result<BybitOrderResult, StatusCode> {
+    let mut params = BTreeMap::from([
+        ("order_link_id", serde_json::json!(new_order_link_id()?)),
+        ("order_type", serde_json::json!("Market")),
+        ("qty", serde_json::json!(qty.unsigned_abs())),
+        ("side", serde_json::json!(if qty > 0 { "Buy" } else { "Sell" })),
+        ("symbol", serde_json::json!(symbol)),
+        ("time_in_force", serde_json::json!("ImmediateOrCancel")),
+    ]);
+    if let Some(stop_loss) = stop_loss {
+        params.insert("stop_loss", serde_json::json!(stop_loss));
+    }
+    if let Some(take_profit) = take_profit {
+        params.insert("take_profit", serde_json::json!(take_profit));
+    }
+
+    api_create_order(context, params, None)
+}
+
+/// Cancel an active order by its order id. Order which is no longer active
+/// e.g. as it was filled meanwhile is not an error.
+///
+/// # Arguments
+/// * `context` - `TradingContext` for context information used in trading
+/// * `symbol` - symbol of the order
+/// * `order_id` - order id as returned when the order was created
+pub fn api_cancel_order(context: &TradingContext, symbol: &str, order_id: &str) -> Result<(), StatusCode> {
+    let params = BTreeMap::from([
+        ("order_id", serde_json::json!(order_id)),
+        ("symbol", serde_json::json!(symbol)),
+    ]);
+
+    let json = api_private_post::<BybitOrderResponse>(context, "/v2/private/order/cancel", params)?;
+    match json.ret_code {
+        // order not exists, or too late to cancel
+        0 | 20001 => Ok(()),
+        _ => {
+            eprintln!("Error: {}", json.ret_msg);
             Err(StatusCode::ErrorApiResponse)
         }
     }
 }
 
-/// Get current price of the specified `symbol`.
+/// Send a reduce-only limit order of `qty` at `price` e.g. take-profit target
+/// of a bracket, resting until it is filled. It is not checked against safety
+/// limits, nor duplicates as it only reduces a position.
 ///
 /// # Arguments
 /// * `context` - `TradingContext` for context information used in trading
-/// * `symbol` - symbol to get the current price (current price is **last traded price**)
-pub fn api_get_current_price(context: &TradingContext, symbol: &str) -> Result<f64, StatusCode> {
+/// * `symbol` - symbol to create an order for
+/// * `qty` - quantity. It can be negative for sell, or positive buy.
+/// * `price` - price of the limit order
+pub fn api_send_reduce_only_limit_order(context: &TradingContext, symbol: &str, qty: i64, price: f64) -> Result<BybitOrderResult, StatusCode> {
+    let params = BTreeMap::from([
+        ("order_link_id", serde_json::json!(new_order_link_id()?)),
+        ("order_type", serde_json::json!("Limit")),
+        ("price", serde_json::json!(price)),
+        ("qty", serde_json::json!(qty.unsigned_abs())),
+        ("reduce_only", serde_json::json!(true)),
+        ("side", serde_json::json!(if qty > 0 { "Buy" } else { "Sell" })),
+        ("symbol", serde_json::json!(symbol)),
+        ("time_in_force", serde_json::json!("GoodTillCancel")),
+    ]);
 
-    let raw_url_str = get_full_uri(context.use_testnet, &("/v2/public/tickers?symbol=".to_owned() + symbol));
-    let url = Url::parse(&raw_url_str);
-    if let Err(_) = url {
-        return Err(StatusCode::InternalErrorParsingRawUrl);
+    api_create_order(context, params, None)
+}
+
+/// Set stop-loss of position of `symbol` at `stop_loss` covering `size` of it
+/// e.g. what is left once some take-profit targets are hit.
+///
+/// # Arguments
+/// * `context` - `TradingContext` for context information used in trading
+/// * `symbol` - symbol of the position
+/// * `stop_loss` - stop-loss price
+/// * `size` - quantity of the position closed at stop-loss
+pub fn api_set_stop_loss_size(context: &TradingContext, symbol: &str, stop_loss: f64, size: u64) -> Result<(), StatusCode> {
+    let params = BTreeMap::from([
+        ("sl_size", serde_json::json!(size)),
+        ("stop_loss", serde_json::json!(stop_loss)),
+        ("symbol", serde_json::json!(symbol)),
+    ]);
+
+    let json = api_private_post::<BybitGenericNoResultResponse>(context, "/v2/private/position/trading-stop", params)?;
+    if json.ret_code != 0 {
+        eprintln!("Error: {}", json.ret_msg);
+        return Err(StatusCode::ErrorApiResponse);
     }
+    Ok(())
+}
 
-    let request = isahc::Request::builder()
-        .method("GET")
-        .uri(url.unwrap().as_str())
-        .header("content-type", "application/json")
-        .version_negotiation(isahc::config::VersionNegotiation::http2())
-        .body(());
-    if let Err(_) = request {
-        return Err(StatusCode::InternalErrorCreatingHttpRequest);
-    }
-
-    match isahc::send(request.unwrap()) {
-        Ok(mut res) => {
-            match res.json::<BybitLatestInformationSymbolResponse>() {
-                Ok(json) => {
-                    // early return if error
-                    if json.ret_code != 0 {
-                        eprintln!("Error: {}", json.ret_msg);
-                        return Err(StatusCode::ErrorApiResponse);
-                    }
+/// Query an active order by its order id.
+/// This is real-time query, it returns the latest state of the order.
+///
+/// # Arguments
+/// * `context` - `TradingContext` for context information used in trading
+/// * `symbol` - symbol of the order
+/// * `order_id` - order id as returned when the order was created
+pub fn api_query_order(context: &TradingContext, symbol: &str, order_id: &str) -> Result<BybitOrderResult, StatusCode> {
+    let params = BTreeMap::from([
+        ("order_id", serde_json::json!(order_id)),
+        ("symbol", serde_json::json!(symbol)),
+    ]);
 
-                    // guarantee to have result for success case, safe to unwrap
-                    let result = json.result.unwrap();
+    let json = api_private_get::<BybitOrderResponse>(context, "/v2/private/order", params)?;
+    if json.ret_code != 0 {
+        eprintln!("Error: {}", json.ret_msg);
+        return Err(StatusCode::ErrorApiResponse);
+    }
 
-                    if result.len() == 0 {
-                        return Err(StatusCode::ApiEmptyResult);
-                    }
+    json.result.ok_or(StatusCode::ApiEmptyResult)
+}
 
-                    match result[0].last_price.parse::<f64>() {
-                        Ok(price) => Ok(price),
-                        Err(_) => Err(StatusCode::ErrorNumericJsonParsing)
-                    }
+/// Query the latest state of `order` as `OrderConfirmation`, along with its
+/// position if `with_position` is `true`.
+///
+/// # Arguments
+/// * `context` - `TradingContext` for context information used in trading
+/// * `order` - order as returned when it was created
+/// * `with_position` - whether or not to also query position of the order's symbol
+pub fn api_get_order_confirmation(context: &TradingContext, order: &BybitOrderResult, with_position: bool) -> Result<OrderConfirmation, StatusCode> {
+    let queried = api_query_order(context, &order.symbol, &order.order_id)?;
+    let position = if with_position {
+        let p = api_get_position(context, &order.symbol)?;
+        Some(BybitWsPositionData { symbol: p.symbol, side: p.side, size: p.size, entry_price: p.entry_price, unrealised_pnl: p.unrealised_pnl })
+    } else {
+        None
+    };
+
+    Ok(OrderConfirmation {
+        order_id: order.order_id.clone(),
+        symbol: order.symbol.clone(),
+        side: order.side.clone(),
+        price: order.price,
+        qty: order.qty,
+        state: to_order_state(&queried.order_status, &queried.reject_reason, &queried.cancel_type),
+        cum_exec_qty: queried.cum_exec_qty,
+        cum_exec_value: queried.cum_exec_value,
+        cum_exec_fee: queried.cum_exec_fee,
+        leaves_qty: queried.leaves_qty,
+        position,
+    })
+}
+
+/// Send a signed POST request to private API, then parse its response as `T`.
+/// `api_key`, `timestamp` and `sign` are added into `params` before sending.
+/// It is handled by paper exchange instead if `TradingContext::paper` is set.
+///
+/// # Arguments
+/// * `context` - `TradingContext` for context information used in trading
+/// * `end_point` - end-point URL
+/// * `params` - request's parameters
+pub fn api_private_post<T: serde::de::DeserializeOwned>(context: &TradingContext, end_point: &str, params: BTreeMap<&str, serde_json::Value>) -> Result<T, StatusCode> {
+    if let Some(paper) = &context.paper {
+        return paper_private_request(context, paper, end_point, &params);
+    }
+    let raw_url_str = get_full_uri(context, end_point);
+    let url = Url::parse(&raw_url_str).map_err(|_| StatusCode::InternalErrorParsingRawUrl)?;
+
+    // signed again on each retry, so timestamp stays within receive window
+    send_and_parse_json(&get_rate_limit_key(context, end_point, true), || {
+        let mut params = params.clone();
+        let sign = sign_private_params(context, &mut params)?;
+        params.insert("sign", serde_json::Value::String(sign));
+
+        let body = serde_json::to_value(&params).map_err(|_| StatusCode::InternalErrorParsingJsonObject)?;
+        tracing::debug!(method = "POST", url = %url, body = %redact_json(&body), "request");
+        let body = serde_json::to_vec(&body).map_err(|_| StatusCode::InternalErrorParsingJsonObject)?;
+
+        new_request_builder(context, "POST", &url)
+            .body(body)
+            .map_err(|_| StatusCode::InternalErrorCreatingHttpRequest)
+    })
+}
+
+/// Send a signed GET request to private API, then parse its response as `T`.
+/// `api_key`, `timestamp` and `sign` are added into query string.
+/// It is handled by paper exchange instead if `TradingContext::paper` is set.
+///
+/// # Arguments
+/// * `context` - `TradingContext` for context information used in trading
+/// * `end_point` - end-point URL without query string
+/// * `params` - request's parameters
+pub fn api_private_get<T: serde::de::DeserializeOwned>(context: &TradingContext, end_point: &str, params: BTreeMap<&str, serde_json::Value>) -> Result<T, StatusCode> {
+    if let Some(paper) = &context.paper {
+        return paper_private_request(context, paper, end_point, &params);
+    }
+    send_and_parse_json(&get_rate_limit_key(context, end_point, true), || {
+        let mut params = params.clone();
+        let sign = sign_private_params(context, &mut params)?;
+        let query_str = format!("{}&sign={}", form_param_str(&params), sign);
+
+        let raw_url_str = get_full_uri(context, &format!("{}?{}", end_point, query_str));
+        let url = Url::parse(&raw_url_str).map_err(|_| StatusCode::InternalErrorParsingRawUrl)?;
+        tracing::debug!(method = "GET", url = %redact_url(url.as_str()), "request");
+
+        new_request_builder(context, "GET", &url)
+            .body(())
+            .map_err(|_| StatusCode::InternalErrorCreatingHttpRequest)
+    })
+}
+
+/// Send a GET request to public API, then parse its response as `T`.
+///
+/// # Arguments
+/// * `context` - `TradingContext` for context information used in trading
+/// * `end_point` - end-point URL along with its query string
+pub fn api_public_get<T: serde::de::DeserializeOwned>(context: &TradingContext, end_point: &str) -> Result<T, StatusCode> {
+    let raw_url_str = get_full_uri(context, end_point);
+    let url = Url::parse(&raw_url_str).map_err(|_| StatusCode::InternalErrorParsingRawUrl)?;
+
+    send_and_parse_json(&get_rate_limit_key(context, end_point, false), || {
+        tracing::debug!(method = "GET", url = %url, "request");
+        new_request_builder(context, "GET", &url)
+            .body(())
+            .map_err(|_| StatusCode::InternalErrorCreatingHttpRequest)
+    })
+}
+
+/// Send request built by `build_request` then parse its response body as json
+/// into `T`. A token of rate limit of `rate_limit_key` is taken before each
+/// attempt. If the request is rejected by rate limit, it is built, and sent
+/// again after backoff up to `RATE_LIMIT_RETRIES` times. Each response is
+/// logged at debug level along with its timing, and Bybit's `ret_code`, and
+/// its body at trace level.
+///
+/// # Arguments
+/// * `rate_limit_key` - key of token bucket, see `get_rate_limit_key`
+/// * `build_request` - build the request to send, called on each attempt
+fn send_and_parse_json<T, B, F>(rate_limit_key: &str, build_request: F) -> Result<T, StatusCode>
+where
+    T: serde::de::DeserializeOwned,
+    B: Into<isahc::Body>,
+    F: Fn() -> Result<isahc::Request<B>, StatusCode>,
+{
+    let mut attempt = 0;
+    loop {
+        acquire_rate_limit_token(rate_limit_key)?;
+        let start = std::time::Instant::now();
+        let mut res = isahc::send(build_request()?).map_err(|e| {
+            tracing::warn!(key = rate_limit_key, elapsed_ms = start.elapsed().as_millis() as u64, error = %e, "request failed");
+            StatusCode::ErrorNetwork
+        })?;
+        let body = res.text().map_err(|_| StatusCode::ErrorNetwork)?;
+        let json = serde_json::from_str::<serde_json::Value>(&body).ok();
+
+        let ret_code = json.as_ref().and_then(|j| j.get("ret_code")).and_then(|c| c.as_i64());
+        let ret_msg = json.as_ref().and_then(|j| j.get("ret_msg")).and_then(|m| m.as_str()).unwrap_or_default();
+        tracing::debug!(key = rate_limit_key, status = res.status().as_u16(), elapsed_ms = start.elapsed().as_millis() as u64, ret_code, ret_msg, attempt, "response");
+        match &json {
+            Some(json) => tracing::trace!(key = rate_limit_key, body = %redact_json(json), "response body"),
+            None => tracing::trace!(key = rate_limit_key, body = %body, "response body"),
+        }
+
+        let status = parse_rate_limit_status(res.headers(), json.as_ref());
+        update_rate_limit(rate_limit_key, &status);
+
+        if is_rate_limited(res.status(), json.as_ref()) {
+            let backoff = if attempt < RATE_LIMIT_RETRIES { get_rate_limit_backoff(attempt, &status) } else { None };
+            match backoff {
+                Some(backoff) => {
+                    print_info(&format!("rate limited on {}, retrying in {:.3} secs", rate_limit_key, backoff.as_secs_f64()));
+                    std::thread::sleep(backoff);
+                    attempt += 1;
+                    continue;
                 },
-                Err(e) => {
-                    eprintln!("Error: {:?}", e);
-                    Err(StatusCode::ErrorJsonParsing)
+                None => {
+                    eprintln!("Error: rate limited on {}, giving up after {} retries", rate_limit_key, attempt);
+                    return Err(StatusCode::ErrorRateLimited);
                 }
             }
+        }
+
+        return match json {
+            Some(json) => serde_json::from_value::<T>(json).map_err(|e| {
+                eprintln!("Error: unexpected response: {}", e);
+                StatusCode::ErrorJsonParsing
+            }),
+            None => {
+                eprintln!("Error: response is not json, HTTP status {}", res.status());
+                Err(StatusCode::ErrorJsonParsing)
+            }
+        };
+    }
+}
+
+/// Add `api_key`, and `timestamp` into `params` then sign them.
+/// Return the signature, or `StatusCode::ErrorMissingCredentials` if credentials
+/// cannot be loaded. Parameters are signed in alphabetical order of their
+/// keys as required by Bybit, which `BTreeMap` already maintains.
+///
+/// # Arguments
+/// * `context` - `TradingContext` for api key and secret
+/// * `params` - request's parameters
+pub fn sign_private_params(context: &TradingContext, params: &mut BTreeMap<&str, serde_json::Value>) -> Result<String, StatusCode> {
+    let credentials = get_api_credentials(context)?;
+    params.insert("api_key", serde_json::Value::String(credentials.api_key.clone()));
+    params.insert("timestamp", serde_json::Value::String(get_unix_timestamp_as_millis().to_string()));
+
+    Ok(sign_private_request_params(&form_param_str(params), &credentials.api_secret))
+}
+
+/// Form parameter string in form of `key1=value1&key2=value2` from `params`.
+///
+/// # Arguments
+/// * `params` - request's parameters
+pub fn form_param_str(params: &BTreeMap<&str, serde_json::Value>) -> String {
+    params.iter()
+        .map(|(k, v)| match v {
+            serde_json::Value::String(s) => format!("{}={}", k, s),
+            _ => format!("{}={}", k, v),
+        })
+        .collect::<Vec<String>>()
+        .join("&")
+}
+
+/// Deserialize `f64` from either json number or json string.
+/// Bybit returns numeric values in both forms depending on end-point.
+pub fn deserialize_f64<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    use serde::Deserialize;
+
+    #[derive(serde::Deserialize)]
+    #[serde(untagged)]
+    enum NumOrStr {
+        Num(f64),
+        Str(String),
+    }
+
+    // null e.g. unset stop-loss of paper exchange's order is also 0
+    match Option::<NumOrStr>::deserialize(deserializer)? {
+        None => Ok(0.0),
+        Some(NumOrStr::Num(v)) => Ok(v),
+        Some(NumOrStr::Str(s)) if s.is_empty() => Ok(0.0),
+        Some(NumOrStr::Str(s)) => s.parse::<f64>().map_err(serde::de::Error::custom),
+    }
+}
+
+/// Map order status, and its cancel information as returned from Bybit into
+/// `OrderState`.
+///
+/// # Arguments
+/// * `order_status` - order status string e.g. `New`, `Filled`, etc
+/// * `reject_reason` - reject reason as returned along with the order
+/// * `cancel_type` - cancel type as returned along with the order
+pub fn to_order_state(order_status: &str, reject_reason: &str, cancel_type: &str) -> OrderState {
+    match order_status {
+        "PartiallyFilled" => OrderState::PartiallyFilled,
+        "Filled" => OrderState::Filled,
+        "Rejected" => OrderState::Rejected,
+        "Cancelled" => {
+            if reject_reason.contains("PostOnly") || cancel_type.contains("PostOnly") {
+                OrderState::CancelledPostOnly
+            } else {
+                OrderState::Cancelled
+            }
         },
-        Err(_) => Err(StatusCode::ErrorApiResponse)
+        // Created, New, PendingCancel are still live on the order book
+        _ => OrderState::New,
+    }
+}
+
+/// Whether or not `state` is final, thus no more update is expected.
+pub fn is_order_state_final(state: OrderState) -> bool {
+    !matches!(state, OrderState::New | OrderState::PartiallyFilled)
+}
+
+/// Get average fill price of `confirmation`, or `None` if nothing is filled.
+/// For inverse contract, executed value is in coin thus average price is
+/// executed qty over executed value.
+pub fn get_avg_fill_price(confirmation: &OrderConfirmation) -> Option<f64> {
+    if confirmation.cum_exec_qty > 0.0 && confirmation.cum_exec_value > 0.0 {
+        Some(confirmation.cum_exec_qty / confirmation.cum_exec_value)
+    } else {
+        None
+    }
+}
+
+/// Print the result of `confirmation` to stdout.
+///
+/// # Arguments
+/// * `confirmation` - `OrderConfirmation`
+pub fn print_order_confirmation(confirmation: &OrderConfirmation) {
+    for line in format_order_confirmation(confirmation) {
+        println!("{}", line);
+    }
+}
+
+/// Format the result of `confirmation` into lines of order's final state, then
+/// position if known.
+///
+/// # Arguments
+/// * `confirmation` - `OrderConfirmation`
+pub fn format_order_confirmation(confirmation: &OrderConfirmation) -> Vec<String> {
+    let c = confirmation;
+    let coin = get_settle_coin(&c.symbol);
+    let fill = match get_avg_fill_price(c) {
+        Some(avg) => format!(", filled {} @ {:.6} (fee {:.8} {})", c.cum_exec_qty, avg, c.cum_exec_fee, coin),
+        None => String::new(),
+    };
+    let mut lines = vec![format!("order {} {} {} {} @ {}: {}{}", c.order_id, c.side, c.qty, c.symbol, c.price, get_order_state_label(c.state), fill)];
+    if let Some(p) = &c.position {
+        lines.push(format!("position {} {} {} @ {}", p.symbol, p.side, p.size, p.entry_price));
+    }
+    lines
+}
+
+/// Describe `state` of an order to the user.
+pub fn get_order_state_label(state: OrderState) -> &'static str {
+    match state {
+        OrderState::New => "new",
+        OrderState::PartiallyFilled => "partially filled",
+        OrderState::Filled => "filled",
+        OrderState::Cancelled => "cancelled",
+        OrderState::CancelledPostOnly => "cancelled (post-only would take liquidity)",
+        OrderState::Rejected => "rejected",
+    }
+}
+
+/// Convert position data from private stream into `BybitPositionResult`.
+pub fn to_position_result(data: BybitWsPositionData) -> BybitPositionResult {
+    BybitPositionResult {
+        symbol: data.symbol,
+        side: data.side,
+        size: data.size,
+        entry_price: data.entry_price,
+        unrealised_pnl: data.unrealised_pnl,
+    }
+}
+
+/// Get the settlement coin of inverse contract `symbol`. Ex. `XRPUSD` is
+/// settled in `XRP`.
+pub fn get_settle_coin(symbol: &str) -> &str {
+    symbol.strip_suffix("USD").unwrap_or(symbol)
+}
+
+/// Get current price of the specified `symbol`.
+///
+/// # Arguments
+/// * `context` - `TradingContext` for context information used in trading
+/// * `symbol` - symbol to get the current price (current price is **last traded price**)
+pub fn api_get_current_price(context: &TradingContext, symbol: &str) -> Result<f64, StatusCode> {
+    let ticker = api_get_ticker(context, symbol)?;
+    ticker.last_price.parse::<f64>().map_err(|_| StatusCode::ErrorNumericJsonParsing)
+}
+
+/// Get reference price of the specified `symbol` as of `TradingContext::price_ref`.
+/// For `PriceReference::BidAsk`, it is best bid for buy side, and best ask for
+/// sell side.
+///
+/// # Arguments
+/// * `context` - `TradingContext` for context information used in trading
+/// * `symbol` - symbol to get the reference price
+/// * `is_buy_side` - whether or not it is buy side
+pub fn api_get_reference_price(context: &TradingContext, symbol: &str, is_buy_side: bool) -> Result<f64, StatusCode> {
+    if context.price_ref == PriceReference::Last {
+        return api_get_current_price(context, symbol);
     }
+
+    let ticker = api_get_ticker(context, symbol)?;
+    let price_str = match context.price_ref {
+        PriceReference::Mark => &ticker.mark_price,
+        PriceReference::Index => &ticker.index_price,
+        PriceReference::BidAsk => if is_buy_side { &ticker.bid_price } else { &ticker.ask_price },
+        PriceReference::Last => &ticker.last_price,
+    };
+    price_str.parse::<f64>().map_err(|_| StatusCode::ErrorNumericJsonParsing)
+}
+
+/// Get latest information of the specified `symbol`.
+/// It comes from recorded market data if paper exchange replays one.
+///
+/// # Arguments
+/// * `context` - `TradingContext` for context information used in trading
+/// * `symbol` - symbol to get its latest information
+pub fn api_get_ticker(context: &TradingContext, symbol: &str) -> Result<BybitLatestInformationSymbolResult, StatusCode> {
+    if let Some(paper) = context.paper.as_ref().filter(|p| p.replay_path.is_some()) {
+        return paper_get_ticker(context, paper, symbol);
+    }
+
+    let json = api_public_get::<BybitLatestInformationSymbolResponse>(context, &("/v2/public/tickers?symbol=".to_owned() + symbol))?;
+
+    // early return if error
+    if json.ret_code != 0 {
+        eprintln!("Error: {}", json.ret_msg);
+        return Err(StatusCode::ErrorApiResponse);
+    }
+
+    // guarantee to have result for success case, safe to unwrap
+    let mut result = json.result.unwrap();
+
+    if result.is_empty() {
+        return Err(StatusCode::ApiEmptyResult);
+    }
+
+    Ok(result.swap_remove(0))
 }
 
 /// Get server time from Bybit server through api
@@ -179,37 +1205,25 @@ pub fn api_get_current_price(context: &TradingContext, symbol: &str) -> Result<f
 /// Currently we don't use this to reduce time in making an additional HTTP request
 /// to just get a server's timestamp to satisfy Bybit side. But we can just get
 /// our local timestamp and use it just fine if our local one has time synced
-/// properly.
+/// properly. See `print_clock_skew`.
 ///
 /// # Arguments
 /// * `context` - `TradingContext` for context information used in trading
 pub fn api_get_bybit_timestamp(context: &TradingContext) -> Result<u64, StatusCode> {
-    let raw_url_str = get_full_uri(context.use_testnet, "/v2/public/time");
-    let url = Url::parse(&raw_url_str);
-    if let Err(_) = url {
-        return Err(StatusCode::InternalErrorParsingRawUrl);
-    }
-
-    let request = isahc::Request::builder()
-        .method("GET")
-        .uri(url.unwrap().as_str())
-        .header("content-type", "application/json")
-        .version_negotiation(isahc::config::VersionNegotiation::http2())
-        .body(());
-    if let Err(_) = request {
-        return Err(StatusCode::InternalErrorCreatingHttpRequest);
-    }
+    let json = api_public_get::<BybitServerTimeResponse>(context, "/v2/public/time")?;
+    parse_time_now(&json.time_now)
+}
 
-    match isahc::send(request.unwrap()) {
-        Ok(mut res) => {
-            match res.json::<BybitServerTimeResponse>() {
-                Ok(json) => {
-                    parse_time_now(&json.time_now)
-                },
-                Err(_) => Err(StatusCode::ErrorJsonParsing)
-            }
-        },
-        Err(_) => Err(StatusCode::ErrorApiResponse),
+/// Print how far local clock is off from Bybit server's, as a request signed
+/// with local timestamp is rejected once it is off by more than `recv_window`.
+/// Nothing is printed if server time cannot be fetched.
+///
+/// # Arguments
+/// * `context` - `TradingContext` for context information used in trading
+fn print_clock_skew(context: &TradingContext) {
+    if let Ok(server_time) = api_get_bybit_timestamp(context) {
+        let skew = get_unix_timestamp_as_millis() as i64 - server_time as i64;
+        eprintln!("Error: local clock is {} ms {} Bybit server, sync it then try again", skew.unsigned_abs(), if skew > 0 { "ahead of" } else { "behind" });
     }
 }
 
@@ -259,17 +1273,42 @@ pub fn sign_private_request_params(str: &str, secret: &str) -> String {
 /// # Arguments
 /// * `code` - `StatusCode`
 pub fn print_error_if_necessary(code: StatusCode) {
+    if !matches!(code, StatusCode::Success) {
+        eprintln!("Error: {}", get_status_code_message(&code));
+    }
+}
+
+/// Get human readable message of `code`.
+///
+/// # Arguments
+/// * `code` - `StatusCode`
+pub fn get_status_code_message(code: &StatusCode) -> &'static str {
     match code {
-        StatusCode::InternalErrorCreatingHttpRequest => eprintln!("Error: internal error creating http request"),
-        StatusCode::InternalErrorParsingRawUrl => eprintln!("Error: internal error parsing a raw url"),
-        StatusCode::ErrorJsonParsing => eprintln!("Error: parsing json"),
-        StatusCode::ErrorApiResponse => eprintln!("Error: received error in api response"),
-        StatusCode::InternalErrorGeneric => eprintln!("Error: internal generic error"),
-        StatusCode::MalformedAPIResponseFormat => eprintln!("Error: malformed result from API response"),
-        StatusCode::ApiEmptyResult => eprintln!("Error: API has empty result"),
-        StatusCode::ErrorNumericJsonParsing => eprintln!("Error: numeric Json parsing error"),
-        StatusCode::InternalErrorNoTickStepAvailable => eprintln!("Error: no tick steps available for specified symbol"),
-        _ => {}
+        StatusCode::Success => "success",
+        StatusCode::InternalErrorCreatingHttpRequest => "internal error creating http request",
+        StatusCode::InternalErrorParsingRawUrl => "internal error parsing a raw url",
+        StatusCode::ErrorJsonParsing => "parsing json",
+        StatusCode::ErrorApiResponse => "received error in api response",
+        StatusCode::InternalErrorGeneric => "internal generic error",
+        StatusCode::MalformedAPIResponseFormat => "malformed result from API response",
+        StatusCode::ApiEmptyResult => "API has empty result",
+        StatusCode::ErrorNumericJsonParsing => "numeric Json parsing error",
+        StatusCode::InternalErrorNoTickStepAvailable => "no tick steps available for specified symbol",
+        StatusCode::InternalErrorParsingJsonObject => "internal error parsing json object",
+        StatusCode::ErrorIncorrectParameterValue => "incorrect parameter value",
+        StatusCode::ErrorWebSocket => "websocket connection error",
+        StatusCode::ErrorWebSocketAuth => "websocket authentication failed",
+        StatusCode::ErrorOrderBookOutOfSync => "order book is out of sync",
+        StatusCode::ErrorConfig => "invalid configuration",
+        StatusCode::ErrorMissingCredentials => "missing API credentials",
+        StatusCode::ErrorCredentialStore => "cannot access credential store",
+        StatusCode::ErrorPaper => "paper exchange error",
+        StatusCode::ErrorLimitExceeded => "order exceeds safety limits",
+        StatusCode::ErrorNotConfirmed => "order is not confirmed",
+        StatusCode::ErrorNetwork => "network error, no response from API",
+        StatusCode::ErrorDuplicateOrder => "duplicate of an order just sent",
+        StatusCode::ErrorRateLimited => "rate limited by API, try again later",
+        StatusCode::ErrorJournal => "cannot access trade journal",
     }
 }
 
@@ -310,6 +1349,83 @@ pub fn get_unix_timestamp_as_millis() -> u128 {
     }
 }
 
+/// Format unix timestamp in milliseconds as UTC time e.g. `2022-05-01 08:00:00`.
+///
+/// # Arguments
+/// * `millis` - unix timestamp in milliseconds
+pub fn format_unix_millis(millis: u64) -> String {
+    let secs = millis / 1000;
+    let (year, month, day) = civil_from_days((secs / 86400) as i64);
+    let secs_of_day = secs % 86400;
+    format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}", year, month, day, secs_of_day / 3600, secs_of_day / 60 % 60, secs_of_day % 60)
+}
+
+/// Parse time given at command line into unix timestamp in milliseconds.
+/// It is either UTC date, and optionally time e.g. `2022-05-01`,
+/// `2022-05-01T08:00`, `2022-05-01 08:00:00`, or time ago e.g. `30m`, `24h`, `7d`.
+///
+/// # Arguments
+/// * `s` - time to parse
+pub fn parse_time_arg(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let unit_millis = |unit: &str| match unit {
+        "s" => Some(1000),
+        "m" => Some(60 * 1000),
+        "h" => Some(60 * 60 * 1000),
+        "d" => Some(24 * 60 * 60 * 1000),
+        _ => None,
+    };
+    let ago = s.len().checked_sub(1)
+        .and_then(|n| s.get(..n)?.parse::<u64>().ok().zip(s.get(n..).and_then(unit_millis)));
+    if let Some((n, unit_millis)) = ago {
+        let ago_millis = n.checked_mul(unit_millis).ok_or_else(|| format!("invalid time '{}', too far ago", s))?;
+        return Ok((get_unix_timestamp_as_millis() as u64).saturating_sub(ago_millis));
+    }
+
+    let invalid = || format!("invalid time '{}', expect e.g. 2022-05-01, 2022-05-01T08:00:00, or 24h", s);
+    let (date, time) = s.split_once(['T', ' ']).unwrap_or((s, "00:00:00"));
+    let date: Vec<u32> = date.split('-').map(|p| p.parse::<u32>()).collect::<Result<_, _>>().map_err(|_| invalid())?;
+    let mut time: Vec<u32> = time.trim_end_matches('Z').split(':').map(|p| p.parse::<u32>()).collect::<Result<_, _>>().map_err(|_| invalid())?;
+    time.resize(3, 0);
+    match (date.as_slice(), time.as_slice()) {
+        (&[year, month, day], &[hour, minute, second]) if (1..=12).contains(&month) && (1..=31).contains(&day) && hour < 24 && minute < 60 && second < 60 => {
+            // dates before unix epoch, or too far in future to fit are invalid
+            let days = u64::try_from(days_from_civil(year as i64, month, day)).map_err(|_| invalid())?;
+            days.checked_mul(86400)
+                .and_then(|secs| secs.checked_add((hour * 3600 + minute * 60 + second) as u64))
+                .and_then(|secs| secs.checked_mul(1000))
+                .ok_or_else(invalid)
+        },
+        _ => Err(invalid()),
+    }
+}
+
+/// Number of days since unix epoch of the date in proleptic Gregorian calendar.
+/// Ref http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// Date of number of days since unix epoch, inverse of `days_from_civil`.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
 /// Internal function to count the steps of the specified value.
 /// Ex. 0.0001 has 4 steps.
 ///
@@ -323,6 +1439,7 @@ pub fn count_tick_steps(value: f64) -> i32 {
     let mut count = 0;
     let mut value_copy = value;
 
+    #[allow(clippy::assign_op_pattern)]
     while value_copy < 1.0 {
         value_copy = value_copy * 10.0;
         count = count + 1;
@@ -331,28 +1448,213 @@ pub fn count_tick_steps(value: f64) -> i32 {
     count
 }
 
-/// Get API key from `TradingContext`.
+/// Round `value` to the nearest multiple of `tick_step`.
+///
+/// # Arguments
+/// * `value` - value to round
+/// * `tick_step` - tick step of the symbol
+pub fn round_to_tick(value: f64, tick_step: f64) -> f64 {
+    ticks_to_price(price_to_ticks(value, tick_step), tick_step)
+}
+
+/// Convert `price` into number of ticks.
+///
+/// # Arguments
+/// * `price` - price
+/// * `tick_step` - tick step of the symbol
+pub fn price_to_ticks(price: f64, tick_step: f64) -> i64 {
+    (price / tick_step).round() as i64
+}
+
+/// Convert number of `ticks` back into price. Result is rounded to decimal
+/// places of `tick_step` to get rid of floating-point error.
+///
+/// # Arguments
+/// * `ticks` - number of ticks
+/// * `tick_step` - tick step of the symbol
+pub fn ticks_to_price(ticks: i64, tick_step: f64) -> f64 {
+    let tick_step_value_roundup = 10.0_f64.powi(count_tick_steps(tick_step));
+    ((ticks as f64 * tick_step) * tick_step_value_roundup).round() / tick_step_value_roundup
+}
+
+/// Load API credentials of the selected network from `TradingContext::credentials`.
+/// Print which one is missing, and return `StatusCode::ErrorMissingCredentials`
+/// if they cannot be loaded.
+/// Credentials from the encrypted store prompt for passphrase only once, then
+/// are kept in memory for the rest of the session.
 ///
 /// # Arguments
 /// * `context` - `TradingContext`
-pub fn get_api_key(context: &TradingContext) -> &str {
-    if context.use_testnet { &context.testnet_api_key } else { &context.api_key }
+pub fn get_api_credentials(context: &TradingContext) -> Result<ApiCredentials, StatusCode> {
+    match &context.credentials {
+        CredentialSource::Env { key_env, secret_env } => {
+            let read_env = |name: &str| match std::env::var(name) {
+                Ok(value) if !value.is_empty() => Ok(value),
+                _ => {
+                    eprintln!("Error: required env variable {} to be set for {}", name, if context.use_testnet { "testnet" } else { "mainnet" });
+                    Err(StatusCode::ErrorMissingCredentials)
+                }
+            };
+            Ok(ApiCredentials {
+                api_key: read_env(key_env)?,
+                api_secret: read_env(secret_env)?,
+            })
+        },
+        CredentialSource::Store { name, unlocked } => {
+            // serialize unlocking so concurrent callers prompt only once
+            static UNLOCK_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+            if let Some(credentials) = unlocked.get() {
+                return Ok(credentials.clone());
+            }
+            let _guard = UNLOCK_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(credentials) = unlocked.get() {
+                return Ok(credentials.clone());
+            }
+            let credentials = unlock_store_credentials(name)?;
+            let _ = unlocked.set(credentials.clone());
+            Ok(credentials)
+        },
+    }
+}
+
+/// Encode `bytes` as lowercase hex string.
+///
+/// # Arguments
+/// * `bytes` - bytes to encode
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Encode `bytes` as standard base64 string with padding.
+///
+/// # Arguments
+/// * `bytes` - bytes to encode
+pub fn to_base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, b)| n | (*b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// Decode hex string `s` into bytes. Return `None` if `s` is not valid hex.
+///
+/// # Arguments
+/// * `s` - hex string
+pub fn from_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.is_ascii() || !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len()).step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).ok())
+        .collect()
 }
 
-/// Get API secret from `TradingContext`.
+/// Get HTTP version negotiation for `url`.
+/// HTTP/2 is negotiated over TLS, so plain `http://` URL e.g. of a local mock
+/// server uses HTTP/1.1 instead.
+///
+/// # Arguments
+/// * `url` - URL to send request to
+pub fn get_version_negotiation(url: &Url) -> isahc::config::VersionNegotiation {
+    if url.scheme() == "http" {
+        isahc::config::VersionNegotiation::http11()
+    } else {
+        isahc::config::VersionNegotiation::http2()
+    }
+}
+
+/// Start building a request to `url` with HTTP version, and proxy as of
+/// `context`. Proxy from standard environment variables is used if
+/// `TradingContext::proxy` is not set.
 ///
 /// # Arguments
 /// * `context` - `TradingContext`
-pub fn get_api_secret(context: &TradingContext) -> &str {
-    if context.use_testnet { &context.testnet_api_secret } else { &context.api_secret }
+/// * `method` - HTTP method
+/// * `url` - URL to send request to
+pub fn new_request_builder(context: &TradingContext, method: &str, url: &Url) -> isahc::http::request::Builder {
+    let builder = isahc::Request::builder()
+        .method(method)
+        .uri(url.as_str())
+        .header("content-type", "application/json")
+        .version_negotiation(get_version_negotiation(url))
+        .timeout(std::time::Duration::from_secs(HTTP_TIMEOUT_SECS));
+    match context.proxy.as_ref().and_then(|proxy| proxy.parse::<isahc::http::Uri>().ok()) {
+        Some(proxy) => builder.proxy(Some(proxy)),
+        None => builder,
+    }
 }
 
 /// Form the full URI from specified `end_point` and whether or not it is meant
-/// to be using on testnet as specified by `use_testnet`.
+/// to be using on testnet as specified by `TradingContext::use_testnet`, at
+/// `TradingContext::domain`. `TradingContext::base_url` takes precedence if set.
+///
+/// # Arguments
+/// * `context` - `TradingContext`
+/// * `end_point` - end-point URL
+pub fn get_full_uri(context: &TradingContext, end_point: &str) -> String {
+    match &context.base_url {
+        Some(base_url) => format!("{}{}", base_url.trim_end_matches('/'), end_point),
+        None => format!("{}{}", get_preset_base_url(&context.domain, context.use_testnet), end_point),
+    }
+}
+
+/// Form the full websocket URI from specified `end_point` the same way as
+/// `get_full_uri`. `TradingContext::ws_url` takes precedence if set.
 ///
 /// # Arguments
-/// * `use_testnet` - whether or not to use testnet
+/// * `context` - `TradingContext`
 /// * `end_point` - end-point URL
-pub fn get_full_uri(use_testnet: bool, end_point: &str) -> String {
-    format!("{prefix}{end_point}", prefix=if use_testnet { TESTNET_URI_PREFIX } else { URI_PREFIX }, end_point=end_point)
+pub fn get_full_ws_uri(context: &TradingContext, end_point: &str) -> String {
+    match &context.ws_url {
+        Some(ws_url) => format!("{}{}", ws_url.trim_end_matches('/'), end_point),
+        None => format!("{}{}", get_preset_ws_url(&context.domain, context.use_testnet), end_point),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_time_arg_reads_dates_and_times() {
+        assert_eq!(parse_time_arg("2022-05-01"), Ok(1651363200000));
+        assert_eq!(parse_time_arg("2022-05-01T08:00"), Ok(1651392000000));
+        assert_eq!(parse_time_arg(" 2022-05-01 08:00:30Z "), Ok(1651392030000));
+        assert_eq!(parse_time_arg("1970-01-01"), Ok(0));
+    }
+
+    #[test]
+    fn parse_time_arg_reads_time_ago() {
+        let now = get_unix_timestamp_as_millis() as u64;
+        let ago = now - parse_time_arg("24h").unwrap();
+        assert!((24 * 60 * 60 * 1000..24 * 60 * 60 * 1000 + 5000).contains(&ago), "{}", ago);
+        let ago = now - parse_time_arg("30m").unwrap();
+        assert!((30 * 60 * 1000..30 * 60 * 1000 + 5000).contains(&ago), "{}", ago);
+        // far beyond epoch is clamped to it
+        assert_eq!(parse_time_arg("100000d"), Ok(0));
+    }
+
+    #[test]
+    fn parse_time_arg_rejects_overflow() {
+        assert!(parse_time_arg("99999999999999d").is_err());
+        assert!(parse_time_arg("18446744073709551615s").is_err());
+        assert!(parse_time_arg("4000000000-01-01").is_err());
+    }
+
+    #[test]
+    fn parse_time_arg_rejects_malformed() {
+        for s in ["", "d", "7w", "-7d", "7é", "2022-13-01", "2022-05-32", "2022-05", "1969-12-31", "2022-05-01T24:00", "yesterday"] {
+            assert!(parse_time_arg(s).is_err(), "{}", s);
+        }
+    }
 }
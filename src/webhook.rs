@@ -0,0 +1,176 @@
+use crate::types::*;
+use crate::util::*;
+use crate::output::*;
+
+use std::collections::HashMap;
+use std::io::Read;
+
+use ring::{constant_time, hmac};
+
+/// Max size of a request body we accept.
+const MAX_BODY_BYTES: u64 = 16 * 1024;
+
+/// Run local HTTP listener accepting authenticated order payloads.
+/// Valid order is routed through `api_send_quick_limit_order` with stop-loss,
+/// and take-profit of the payload if specified.
+///
+/// # Arguments
+/// * `context` - `TradingContext` for context information used in trading
+/// * `listen_addr` - address to listen on e.g. `127.0.0.1:8080`
+/// * `secret` - shared secret
+/// * `max_age_secs` - max age of payload's timestamp before it is rejected
+pub fn run_webhook(context: &TradingContext, listen_addr: &str, secret: &str, max_age_secs: u64) -> Result<(), StatusCode> {
+    if secret.is_empty() {
+        return Err(StatusCode::ErrorIncorrectParameterValue);
+    }
+
+    let server = tiny_http::Server::http(listen_addr).map_err(|e| {
+        eprintln!("Error: cannot listen on {}: {}", listen_addr, e);
+        StatusCode::InternalErrorGeneric
+    })?;
+    print_info(&format!("listening on {}", listen_addr));
+
+    // nonces seen within max age, along with their timestamp
+    let mut seen_nonces: HashMap<String, u64> = HashMap::new();
+
+    for mut request in server.incoming_requests() {
+        let (status, body) = if request.method() != &tiny_http::Method::Post {
+            (405, serde_json::json!({"ok": false, "error": "method not allowed"}))
+        } else {
+            let signature = request.headers().iter()
+                .find(|h| h.field.equiv("X-Quicky-Signature"))
+                .map(|h| h.value.as_str().to_owned());
+
+            let mut raw = Vec::new();
+            match request.as_reader().take(MAX_BODY_BYTES).read_to_end(&mut raw) {
+                Ok(_) => handle_webhook_order(context, secret, max_age_secs, &mut seen_nonces, &raw, signature.as_deref()),
+                Err(_) => (400, serde_json::json!({"ok": false, "error": "cannot read body"})),
+            }
+        };
+
+        let response = tiny_http::Response::from_string(body.to_string())
+            .with_status_code(status)
+            .with_header(tiny_http::Header::from_bytes("Content-Type", "application/json").unwrap());
+        let _ = request.respond(response);
+    }
+    Ok(())
+}
+
+/// Authenticate, validate, then place order of a webhook request.
+/// Return HTTP status code, and json body of the response.
+fn handle_webhook_order(context: &TradingContext, secret: &str, max_age_secs: u64, seen_nonces: &mut HashMap<String, u64>, raw: &[u8], signature: Option<&str>) -> (u16, serde_json::Value) {
+    let payload: WebhookOrderPayload = match serde_json::from_slice(raw) {
+        Ok(payload) => payload,
+        Err(_) => return (400, serde_json::json!({"ok": false, "error": "malformed payload"})),
+    };
+
+    let authenticated = match (signature, &payload.secret) {
+        (Some(signature), _) => verify_webhook_signature(raw, secret, signature),
+        (None, Some(payload_secret)) => constant_time::verify_slices_are_equal(payload_secret.as_bytes(), secret.as_bytes()).is_ok(),
+        (None, None) => false,
+    };
+    if !authenticated {
+        return (401, serde_json::json!({"ok": false, "error": "unauthorized"}));
+    }
+
+    // replay protection: payload must be fresh, and its nonce never seen within max age
+    // only authenticated requests record their nonce, so forged ones can't burn nonces of legitimate ones
+    let now = get_unix_timestamp_as_millis() as u64;
+    let max_age_millis = max_age_secs * 1000;
+    seen_nonces.retain(|_, ts| now.saturating_sub(*ts) <= max_age_millis);
+    if now.abs_diff(payload.timestamp) > max_age_millis {
+        return (409, serde_json::json!({"ok": false, "error": "stale timestamp"}));
+    }
+    if seen_nonces.contains_key(&payload.nonce) {
+        return (409, serde_json::json!({"ok": false, "error": "replayed nonce"}));
+    }
+    seen_nonces.insert(payload.nonce.clone(), payload.timestamp);
+
+    let qty = match validate_webhook_order(context, &payload) {
+        Ok(qty) => qty,
+        Err(e) => return (400, serde_json::json!({"ok": false, "error": get_status_code_message(&e)})),
+    };
+
+    let order_context = get_order_context(context, payload.sl_pcnt, payload.tp_pcnt);
+
+    let start = std::time::Instant::now();
+    match api_send_quick_limit_order(&order_context, &payload.symbol, qty) {
+        Ok(order) => {
+            match get_output_mode() {
+                OutputMode::Json => {
+                    let outcome = QuickOrderOutcome { order: order.clone(), elapsed: measure_end(&start, false), confirm_elapsed: None, confirmation: None, expired: false, fallback: None };
+                    if let Err(e) = print_json_line(&to_order_report(None, &outcome)) {
+                        print_error_if_necessary(e);
+                    }
+                },
+                _ if is_quiet() => {},
+                _ => println!("placed {} {} {} @ {} ({})", order.side, order.qty, order.symbol, order.price, order.order_id),
+            }
+            (200, serde_json::json!({"ok": true, "order": order}))
+        },
+        Err(e) => {
+            let message = get_status_code_message(&e);
+            eprintln!("Error: {}", message);
+            (502, serde_json::json!({"ok": false, "error": message}))
+        },
+    }
+}
+
+/// Validate `payload`, then return signed quantity for the order.
+///
+/// # Arguments
+/// * `context` - `TradingContext` for symbols we know of
+/// * `payload` - webhook payload
+pub fn validate_webhook_order(context: &TradingContext, payload: &WebhookOrderPayload) -> Result<i64, StatusCode> {
+    validate_order_fields(context, &payload.symbol, &payload.side, payload.qty, payload.sl_pcnt, payload.tp_pcnt)
+}
+
+/// Verify hex `signature` of HMAC-SHA256 of `body` with `secret`.
+/// Comparison is done in constant time.
+///
+/// # Arguments
+/// * `body` - raw request body
+/// * `secret` - shared secret
+/// * `signature` - hex string of signature
+pub fn verify_webhook_signature(body: &[u8], secret: &str, signature: &str) -> bool {
+    match from_hex(signature.trim()) {
+        Some(tag) => {
+            let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+            hmac::verify(&key, body, &tag).is_ok()
+        },
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 4231 test case 2
+    const BODY: &[u8] = b"what do ya want for nothing?";
+    const SECRET: &str = "Jefe";
+    const SIGNATURE: &str = "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843";
+
+    #[test]
+    fn valid_signature_is_accepted() {
+        assert!(verify_webhook_signature(BODY, SECRET, SIGNATURE));
+        assert!(verify_webhook_signature(BODY, SECRET, &SIGNATURE.to_uppercase()));
+        assert!(verify_webhook_signature(BODY, SECRET, &format!(" {}\n", SIGNATURE)));
+    }
+
+    #[test]
+    fn bad_signature_is_rejected() {
+        let flipped = format!("6{}", &SIGNATURE[1..]);
+        assert!(!verify_webhook_signature(BODY, SECRET, &flipped));
+        assert!(!verify_webhook_signature(BODY, "jefe", SIGNATURE));
+        assert!(!verify_webhook_signature(b"what do ya want for nothing!", SECRET, SIGNATURE));
+        assert!(!verify_webhook_signature(BODY, SECRET, &SIGNATURE[..62]));
+    }
+
+    #[test]
+    fn malformed_signature_is_rejected() {
+        for signature in ["", "5bd", "zz", &format!("{}0", SIGNATURE), "é5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec384"] {
+            assert!(!verify_webhook_signature(BODY, SECRET, signature), "{}", signature);
+        }
+    }
+}
@@ -0,0 +1,319 @@
+use crate::types::*;
+use crate::defines::*;
+use crate::util::*;
+use crate::paper::*;
+use crate::logging::*;
+
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use tungstenite::{Message, WebSocket};
+use tungstenite::stream::MaybeTlsStream;
+use url::Url;
+
+/// Websocket connection to Bybit.
+pub type BybitWebSocket = WebSocket<MaybeTlsStream<TcpStream>>;
+
+/// Read timeout of underlying socket. It bounds how long a single read will
+/// block, so callers can check their own deadline in between.
+const WS_READ_TIMEOUT_MILLIS: u64 = 200;
+
+/// Connect to Bybit's websocket at the specified `uri`, tunnelled through HTTP
+/// `proxy` if set. Underlying socket has its read timeout set, see `ws_read_json`.
+///
+/// # Arguments
+/// * `uri` - full websocket URI e.g. `wss://stream.bybit.com/realtime`
+/// * `proxy` - URL of HTTP proxy e.g. `http://127.0.0.1:3128`
+pub fn ws_connect(uri: &str, proxy: Option<&str>) -> Result<BybitWebSocket, StatusCode> {
+    let url = Url::parse(uri).map_err(|_| StatusCode::InternalErrorParsingRawUrl)?;
+    let start = Instant::now();
+    let host = url.host_str().ok_or(StatusCode::InternalErrorParsingRawUrl)?;
+    let port = url.port_or_known_default().ok_or(StatusCode::InternalErrorParsingRawUrl)?;
+
+    let stream = match proxy {
+        Some(proxy) => connect_through_proxy(proxy, host, port)?,
+        None => TcpStream::connect((host, port)).map_err(|_| StatusCode::ErrorWebSocket)?,
+    };
+    let _ = stream.set_nodelay(true);
+    if stream.set_read_timeout(Some(Duration::from_millis(WS_READ_TIMEOUT_MILLIS))).is_err() {
+        return Err(StatusCode::ErrorWebSocket);
+    }
+
+    match tungstenite::client_tls(uri, stream) {
+        Ok((ws, _)) => {
+            tracing::debug!(uri, elapsed_ms = start.elapsed().as_millis() as u64, "websocket connected");
+            Ok(ws)
+        },
+        Err(e) => {
+            tracing::warn!(uri, error = %e, "websocket connection failed");
+            eprintln!("Error: {}", e);
+            Err(StatusCode::ErrorWebSocket)
+        }
+    }
+}
+
+/// Open a tunnel to `host`:`port` through HTTP `proxy` with `CONNECT` method.
+/// Credentials in `proxy` URL are sent as basic authentication.
+fn connect_through_proxy(proxy: &str, host: &str, port: u16) -> Result<TcpStream, StatusCode> {
+    let proxy_url = Url::parse(proxy).map_err(|_| StatusCode::InternalErrorParsingRawUrl)?;
+    if proxy_url.scheme() != "http" {
+        eprintln!("Error: websocket only supports http:// proxy, not {}", proxy_url.scheme());
+        return Err(StatusCode::ErrorWebSocket);
+    }
+    let proxy_host = proxy_url.host_str().ok_or(StatusCode::InternalErrorParsingRawUrl)?;
+    let proxy_port = proxy_url.port_or_known_default().ok_or(StatusCode::InternalErrorParsingRawUrl)?;
+    let mut stream = TcpStream::connect((proxy_host, proxy_port)).map_err(|_| StatusCode::ErrorWebSocket)?;
+
+    let mut request = format!("CONNECT {host}:{port} HTTP/1.1\r\nHost: {host}:{port}\r\n", host=host, port=port);
+    if !proxy_url.username().is_empty() {
+        let user_pass = format!("{}:{}", proxy_url.username(), proxy_url.password().unwrap_or(""));
+        request += &format!("Proxy-Authorization: Basic {}\r\n", to_base64(user_pass.as_bytes()));
+    }
+    request += "\r\n";
+    stream.write_all(request.as_bytes()).map_err(|_| StatusCode::ErrorWebSocket)?;
+
+    // read response's header byte by byte, so nothing after it is consumed
+    let mut response = Vec::new();
+    let mut byte = [0u8; 1];
+    while !response.ends_with(b"\r\n\r\n") {
+        if response.len() > 8192 || stream.read_exact(&mut byte).is_err() {
+            return Err(StatusCode::ErrorWebSocket);
+        }
+        response.push(byte[0]);
+    }
+    let status_line = String::from_utf8_lossy(&response);
+    if status_line.split_whitespace().nth(1) != Some("200") {
+        eprintln!("Error: proxy refused to connect: {}", status_line.lines().next().unwrap_or(""));
+        return Err(StatusCode::ErrorWebSocket);
+    }
+    Ok(stream)
+}
+
+/// Send `value` as a text message.
+///
+/// # Arguments
+/// * `ws` - websocket
+/// * `value` - json value to send
+pub fn ws_send_json(ws: &mut BybitWebSocket, value: &serde_json::Value) -> Result<(), StatusCode> {
+    tracing::trace!(message = %redact_json(value), "websocket send");
+    ws.send(Message::Text(value.to_string())).map_err(|_| StatusCode::ErrorWebSocket)
+}
+
+/// Read a json message.
+/// Return `None` if nothing arrives within read timeout of the underlying socket.
+/// Ping/pong control frames are handled internally.
+///
+/// # Arguments
+/// * `ws` - websocket
+pub fn ws_read_json(ws: &mut BybitWebSocket) -> Result<Option<serde_json::Value>, StatusCode> {
+    match ws.read() {
+        Ok(Message::Text(text)) => {
+            let json: serde_json::Value = serde_json::from_str(&text).map_err(|_| StatusCode::ErrorJsonParsing)?;
+            tracing::trace!(message = %redact_json(&json), "websocket receive");
+            Ok(Some(json))
+        },
+        Ok(Message::Close(_)) => Err(StatusCode::ErrorWebSocket),
+        Ok(_) => Ok(None),
+        Err(tungstenite::Error::Io(e)) if e.kind() == std::io::ErrorKind::WouldBlock || e.kind() == std::io::ErrorKind::TimedOut => {
+            // flush pending pong if any
+            let _ = ws.flush();
+            Ok(None)
+        },
+        Err(_) => Err(StatusCode::ErrorWebSocket),
+    }
+}
+
+/// Send application level ping as required by Bybit to keep connection alive.
+/// It should be sent every 30 seconds.
+///
+/// # Arguments
+/// * `ws` - websocket
+pub fn ws_ping(ws: &mut BybitWebSocket) -> Result<(), StatusCode> {
+    ws_send_json(ws, &serde_json::json!({"op": "ping"}))
+}
+
+/// Send an operation request then wait for its response.
+/// Messages of other topics arrived in between are discarded.
+///
+/// # Arguments
+/// * `ws` - websocket
+/// * `op` - operation e.g. `auth`, or `subscribe`
+/// * `args` - arguments of the operation
+/// * `timeout` - how long to wait for the response
+pub fn ws_request(ws: &mut BybitWebSocket, op: &str, args: serde_json::Value, timeout: Duration) -> Result<(), StatusCode> {
+    ws_send_json(ws, &serde_json::json!({"op": op, "args": args}))?;
+
+    let start = Instant::now();
+    while start.elapsed() < timeout {
+        if let Some(json) = ws_read_json(ws)? {
+            if json["request"]["op"].as_str() != Some(op) {
+                continue;
+            }
+            let success = json["success"].as_bool() == Some(true);
+            tracing::debug!(op, success, ret_msg = json["ret_msg"].as_str().unwrap_or_default(), elapsed_ms = start.elapsed().as_millis() as u64, "websocket response");
+            if success {
+                return Ok(());
+            }
+            eprintln!("Error: {}", json["ret_msg"]);
+            return Err(if op == "auth" { StatusCode::ErrorWebSocketAuth } else { StatusCode::ErrorWebSocket });
+        }
+    }
+    Err(StatusCode::ErrorWebSocket)
+}
+
+/// Connect to private stream, authenticate then subscribe to `order`,
+/// `execution`, and `position` topics.
+///
+/// # Arguments
+/// * `context` - `TradingContext` for api key and secret
+pub fn ws_connect_private_stream(context: &TradingContext) -> Result<BybitWebSocket, StatusCode> {
+    let credentials = get_api_credentials(context)?;
+    let mut ws = ws_connect(&get_full_ws_uri(context, "/realtime"), context.proxy.as_deref())?;
+
+    // https://bybit-exchange.github.io/docs/inverse/#t-websocketauthentication
+    let expires = get_unix_timestamp_as_millis() + 10000;
+    let signature = sign_private_request_params(&format!("GET/realtime{}", expires), &credentials.api_secret);
+    let timeout = Duration::from_secs(5);
+
+    ws_request(&mut ws, "auth", serde_json::json!([credentials.api_key, expires, signature]), timeout)?;
+    ws_request(&mut ws, "subscribe", serde_json::json!(["order", "execution", "position"]), timeout)?;
+
+    Ok(ws)
+}
+
+/// Read an event from private stream.
+/// Return `None` if nothing arrives within read timeout of the underlying socket.
+///
+/// # Arguments
+/// * `ws` - websocket connected via `ws_connect_private_stream`
+pub fn ws_read_private_event(ws: &mut BybitWebSocket) -> Result<Option<BybitPrivateStreamEvent>, StatusCode> {
+    let json = match ws_read_json(ws)? {
+        Some(json) => json,
+        None => return Ok(None),
+    };
+
+    let data = json["data"].clone();
+    let event = match json["topic"].as_str() {
+        Some("order") => BybitPrivateStreamEvent::Order(serde_json::from_value(data).map_err(|_| StatusCode::ErrorJsonParsing)?),
+        Some("execution") => BybitPrivateStreamEvent::Execution(serde_json::from_value(data).map_err(|_| StatusCode::ErrorJsonParsing)?),
+        Some("position") => BybitPrivateStreamEvent::Position(serde_json::from_value(data).map_err(|_| StatusCode::ErrorJsonParsing)?),
+        _ => BybitPrivateStreamEvent::Other,
+    };
+    Ok(Some(event))
+}
+
+/// Keep private stream connected, and pass every event to `on_event` until
+/// `running` is false. Reconnect if connection drops. `connected` reflects
+/// whether or not the stream is currently connected.
+/// Paper exchange emulates the stream, see `run_paper_private_stream`.
+///
+/// # Arguments
+/// * `context` - `TradingContext` for api key and secret
+/// * `running` - flag to keep running
+/// * `connected` - flag set while connected
+/// * `on_event` - callback for every event
+pub fn run_private_stream<F: FnMut(BybitPrivateStreamEvent)>(context: &TradingContext, running: &AtomicBool, connected: &AtomicBool, mut on_event: F) {
+    if let Some(paper) = &context.paper {
+        return run_paper_private_stream(context, paper, running, connected, on_event);
+    }
+    while running.load(Ordering::SeqCst) {
+        let mut ws = match ws_connect_private_stream(context) {
+            Ok(ws) => ws,
+            Err(_) => {
+                std::thread::sleep(Duration::from_millis(WS_RECONNECT_DELAY_MILLIS));
+                continue;
+            }
+        };
+        connected.store(true, Ordering::SeqCst);
+
+        let mut last_ping = Instant::now();
+        while running.load(Ordering::SeqCst) {
+            if last_ping.elapsed().as_secs() >= WS_PING_INTERVAL_SECS {
+                last_ping = Instant::now();
+                if ws_ping(&mut ws).is_err() {
+                    break;
+                }
+            }
+            match ws_read_private_event(&mut ws) {
+                Ok(Some(event)) => on_event(event),
+                Ok(None) => {},
+                Err(_) => break,
+            }
+        }
+
+        connected.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Wait for `order` to reach its final state, or until `timeout`.
+/// Return the latest known state of the order.
+///
+/// Stream might be connected only after the order was placed, so the order is
+/// queried once to catch up before relying on the stream. Stream is pinged
+/// while waiting so long waits e.g. of `--ttl` keep it alive.
+///
+/// # Arguments
+/// * `context` - `TradingContext` for context information used in trading
+/// * `ws` - websocket connected via `ws_connect_private_stream`
+/// * `order` - order as returned when it was created
+/// * `timeout` - how long to wait for the final state
+pub fn wait_for_order_confirmation(context: &TradingContext, ws: &mut BybitWebSocket, order: &BybitOrderResult, timeout: Duration) -> Result<OrderConfirmation, StatusCode> {
+    let start = Instant::now();
+    let mut confirmation = api_get_order_confirmation(context, order, false)?;
+
+    // executions are tracked on their own in case order topic lags behind,
+    // on top of those already counted by the query
+    let mut exec_ids = std::collections::HashSet::new();
+    let (mut exec_qty, mut exec_value, mut exec_fee) = (confirmation.cum_exec_qty, confirmation.cum_exec_value, confirmation.cum_exec_fee);
+    let queried_leaves_qty = confirmation.leaves_qty;
+
+    let mut last_ping = Instant::now();
+    while !is_order_state_final(confirmation.state) && start.elapsed() < timeout {
+        if last_ping.elapsed().as_secs() >= WS_PING_INTERVAL_SECS {
+            last_ping = Instant::now();
+            ws_ping(ws)?;
+        }
+        match ws_read_private_event(ws)? {
+            Some(BybitPrivateStreamEvent::Order(data)) => {
+                for d in data.iter().filter(|d| d.order_id == order.order_id) {
+                    // cumulative values only grow, ignore stale update
+                    if d.cum_exec_qty < confirmation.cum_exec_qty {
+                        continue;
+                    }
+                    confirmation.state = to_order_state(&d.order_status, &d.reject_reason, &d.cancel_type);
+                    confirmation.cum_exec_qty = d.cum_exec_qty;
+                    confirmation.cum_exec_value = d.cum_exec_value;
+                    confirmation.cum_exec_fee = d.cum_exec_fee;
+                    confirmation.leaves_qty = d.leaves_qty;
+                }
+            },
+            Some(BybitPrivateStreamEvent::Execution(data)) => {
+                for d in data.iter().filter(|d| d.order_id == order.order_id && d.price > 0.0) {
+                    // leaves only shrink, execution leaving as much as queried is already counted
+                    if !exec_ids.insert(d.exec_id.clone()) || d.leaves_qty >= queried_leaves_qty {
+                        continue;
+                    }
+                    exec_qty += d.exec_qty;
+                    exec_value += d.exec_qty / d.price;
+                    exec_fee += d.exec_fee;
+                    if exec_qty > confirmation.cum_exec_qty {
+                        confirmation.state = if d.leaves_qty == 0.0 { OrderState::Filled } else { OrderState::PartiallyFilled };
+                        confirmation.cum_exec_qty = exec_qty;
+                        confirmation.cum_exec_value = exec_value;
+                        confirmation.cum_exec_fee = exec_fee;
+                        confirmation.leaves_qty = d.leaves_qty;
+                    }
+                }
+            },
+            Some(BybitPrivateStreamEvent::Position(data)) => {
+                if let Some(d) = data.into_iter().find(|d| d.symbol == order.symbol) {
+                    confirmation.position = Some(d);
+                }
+            },
+            _ => {}
+        }
+    }
+
+    Ok(confirmation)
+}
@@ -0,0 +1,1195 @@
+//! Integration tests running quicky against the mock Bybit server.
+
+mod support;
+
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Output, Stdio};
+use std::time::{Duration, Instant};
+
+use serde_json::json;
+use support::{error_response, MockBybit};
+
+const API_KEY: &str = "mock-key";
+const API_SECRET: &str = "mock-secret";
+
+/// Start mock with XRPUSD ticker, and the default api key.
+fn start_mock() -> MockBybit {
+    let mock = MockBybit::start();
+    mock.add_key(API_KEY, API_SECRET);
+    mock.set_ticker("XRPUSD", json!({"last_price": "0.5000", "bid_price": "0.4998", "ask_price": "0.5002", "mark_price": "0.5010"}));
+    mock
+}
+
+/// Create an empty directory for test `name` to act as home of quicky.
+fn test_dir(name: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Command of quicky pointed to `mock`, isolated from environment of the user
+/// under `dir`.
+fn quicky(mock: &MockBybit, dir: &Path, envs: &[(&str, &str)], args: &[&str]) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_quicky"));
+    command.env_clear()
+        .env("HOME", dir)
+        .env("XDG_CONFIG_HOME", dir.join("config"))
+        .env("XDG_DATA_HOME", dir.join("data"))
+        .env("XDG_RUNTIME_DIR", dir)
+        .env("QUICKY_BASE_URL", mock.url())
+        .envs(envs.iter().copied())
+        .args(args);
+    command
+}
+
+/// Run quicky to completion with the default testnet credentials.
+fn run_quicky(mock: &MockBybit, dir: &Path, args: &[&str]) -> Output {
+    let envs = [("QUICKY_BYBIT_TESTNET_API_KEY", API_KEY), ("QUICKY_BYBIT_TESTNET_API_SECRET", API_SECRET)];
+    quicky(mock, dir, &envs, args).output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn buy_is_signed_and_placed_a_tick_below_last_price() {
+    let mock = start_mock();
+    let dir = test_dir("buy");
+
+    let output = run_quicky(&mock, &dir, &["--testnet", "--no-confirm", "-s", "XRPUSD", "-q", "10"]);
+    assert!(stdout(&output).contains("accepted Buy 10 XRPUSD @ 0.4999"), "{}{}", stdout(&output), stderr(&output));
+
+    let requests = mock.requests("/v2/private/order/create");
+    assert_eq!(requests.len(), 1);
+    let params = &requests[0].params;
+    assert_eq!(params["api_key"], API_KEY);
+    assert_eq!(params["side"], "Buy");
+    assert_eq!(params["order_type"], "Limit");
+    assert_eq!(params["time_in_force"], "PostOnly");
+    assert_eq!(params["qty"], "10");
+    assert_eq!(params["price"], "0.4999");
+    assert_eq!(params["stop_loss"], "0.4975");
+    assert!(!params.contains_key("take_profit"));
+    assert_eq!(mock.orders(API_KEY)[0]["order_status"], "New");
+}
+
+#[test]
+fn sell_is_placed_a_tick_above_last_price_with_take_profit() {
+    let mock = start_mock();
+    let dir = test_dir("sell");
+
+    let output = run_quicky(&mock, &dir, &["--testnet", "--no-confirm", "-s", "XRPUSD", "-q", "-10", "--tp-pcnt", "1"]);
+    assert!(stdout(&output).contains("accepted Sell 10 XRPUSD @ 0.5001"), "{}{}", stdout(&output), stderr(&output));
+
+    let params = &mock.requests("/v2/private/order/create")[0].params;
+    assert_eq!(params["side"], "Sell");
+    assert_eq!(params["price"], "0.5001");
+    assert_eq!(params["stop_loss"], "0.5025");
+    assert_eq!(params["take_profit"], "0.495");
+}
+
+#[test]
+fn price_reference_is_respected() {
+    let mock = start_mock();
+    let dir = test_dir("price_ref");
+
+    run_quicky(&mock, &dir, &["--testnet", "--no-confirm", "-s", "XRPUSD", "-q", "10", "--price-ref", "bidask"]);
+    run_quicky(&mock, &dir, &["--testnet", "--no-confirm", "-s", "XRPUSD", "-q", "11", "--price-ref", "mark"]);
+
+    let requests = mock.requests("/v2/private/order/create");
+    assert_eq!(requests.len(), 2);
+    // join best bid
+    assert_eq!(requests[0].params["price"], "0.4998");
+    // a tick below mark price
+    assert_eq!(requests[1].params["price"], "0.5009");
+}
+
+#[test]
+fn dry_run_sends_no_order() {
+    let mock = start_mock();
+    let dir = test_dir("dry_run");
+
+    let output = run_quicky(&mock, &dir, &["--testnet", "--dry-run", "-s", "XRPUSD", "-q", "10"]);
+    let out = stdout(&output);
+    assert!(out.contains("dry run, order is not sent"), "{}{}", out, stderr(&output));
+    assert!(out.contains("Buy 10 XRPUSD PostOnly limit @ 0.4999"));
+    assert!(out.contains("sign=<redacted>"));
+    assert!(!out.contains(API_SECRET));
+    assert_eq!(mock.requests("/v2/public/tickers").len(), 1);
+    assert!(mock.requests("/v2/private/order/create").is_empty());
+}
+
+#[test]
+fn wrong_secret_fails_signature_check() {
+    let mock = start_mock();
+    let dir = test_dir("wrong_secret");
+
+    let envs = [("QUICKY_BYBIT_TESTNET_API_KEY", API_KEY), ("QUICKY_BYBIT_TESTNET_API_SECRET", "not-the-secret")];
+    let output = quicky(&mock, &dir, &envs, &["--testnet", "--no-confirm", "-s", "XRPUSD", "-q", "10"]).output().unwrap();
+    let err = stderr(&output);
+    assert!(err.contains("error sign!"), "{}", err);
+    assert!(err.contains("Error: received error in api response"), "{}", err);
+    assert!(mock.orders(API_KEY).is_empty());
+}
+
+#[test]
+fn scripted_error_is_reported() {
+    let mock = start_mock();
+    let dir = test_dir("scripted_error");
+    mock.script("/v2/private/order/create", error_response(30031, "insufficient available balance"));
+
+    let output = run_quicky(&mock, &dir, &["--testnet", "--no-confirm", "-s", "XRPUSD", "-q", "10"]);
+    let err = stderr(&output);
+    assert!(!output.status.success());
+    assert!(err.contains("insufficient available balance"), "{}", err);
+    assert!(!stdout(&output).contains("accepted"));
+
+    // script is consumed, the next order goes through
+    let output = run_quicky(&mock, &dir, &["--testnet", "--no-confirm", "-s", "XRPUSD", "-q", "10"]);
+    assert!(output.status.success());
+    assert!(stdout(&output).contains("accepted Buy 10 XRPUSD"), "{}{}", stdout(&output), stderr(&output));
+}
+
+#[test]
+fn clock_skew_is_reported_when_timestamp_is_rejected() {
+    let mock = start_mock();
+    let dir = test_dir("clock_skew");
+    mock.script("/v2/private/order/create", error_response(10002, "invalid request, please check your timestamp and recv_window param"));
+    let server_secs = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs() + 3600;
+    mock.script("/v2/public/time", json!({"ret_code": 0, "ret_msg": "OK", "ext_code": "", "ext_info": "", "result": {}, "time_now": format!("{}.000000", server_secs)}));
+
+    let output = run_quicky(&mock, &dir, &["--testnet", "--no-confirm", "-s", "XRPUSD", "-q", "10"]);
+    let err = stderr(&output);
+    assert!(!output.status.success());
+    assert!(err.contains("local clock is") && err.contains("behind Bybit server"), "{}", err);
+    assert_eq!(mock.requests("/v2/public/time").len(), 1);
+}
+
+#[test]
+fn missing_credentials_are_reported() {
+    let mock = start_mock();
+    let dir = test_dir("missing_credentials");
+
+    let output = quicky(&mock, &dir, &[], &["--testnet", "--no-confirm", "-s", "XRPUSD", "-q", "10"]).output().unwrap();
+    assert!(stderr(&output).contains("QUICKY_BYBIT_TESTNET_API_KEY"), "{}", stderr(&output));
+    assert!(mock.requests("/v2/private/order/create").is_empty());
+}
+
+#[test]
+fn malformed_environment_override_fails_instead_of_falling_back() {
+    let mock = start_mock();
+    let dir = test_dir("env_override");
+    let credentials = [("QUICKY_BYBIT_TESTNET_API_KEY", API_KEY), ("QUICKY_BYBIT_TESTNET_API_SECRET", API_SECRET)];
+
+    // testnet credentials only work if the flag selects testnet
+    for value in ["1", "yes", "TRUE"] {
+        let envs = [credentials[0], credentials[1], ("QUICKY_TESTNET", value)];
+        let output = quicky(&mock, &dir, &envs, &["--no-confirm", "--dedupe-window", "0", "-s", "XRPUSD", "-q", "10"]).output().unwrap();
+        assert!(stdout(&output).contains("accepted Buy 10 XRPUSD"), "{}: {}{}", value, stdout(&output), stderr(&output));
+    }
+
+    for (name, value) in [("QUICKY_TESTNET", "maybe"), ("QUICKY_SL_PCNT", "half")] {
+        let envs = [credentials[0], credentials[1], ("QUICKY_TESTNET", "1"), (name, value)];
+        let output = quicky(&mock, &dir, &envs, &["--no-confirm", "-s", "XRPUSD", "-q", "10"]).output().unwrap();
+        assert!(!output.status.success());
+        assert!(stderr(&output).contains(&format!("environment variable {} has malformed value '{}'", name, value)), "{}", stderr(&output));
+    }
+    assert_eq!(mock.requests("/v2/private/order/create").len(), 3);
+}
+
+#[test]
+fn group_fans_out_with_credentials_of_each_account() {
+    let mock = start_mock();
+    mock.add_key("key-a", "secret-a");
+    mock.add_key("key-b", "secret-b");
+    let dir = test_dir("group");
+    let config = write_config(&dir, r#"
+[accounts.a]
+api_key_env = "KEY_A"
+api_secret_env = "SECRET_A"
+
+[accounts.b]
+api_key_env = "KEY_B"
+api_secret_env = "SECRET_B"
+
+[groups.both]
+a = 1.0
+b = 0.5
+"#);
+
+    let envs = [("KEY_A", "key-a"), ("SECRET_A", "secret-a"), ("KEY_B", "key-b"), ("SECRET_B", "secret-b")];
+    let args = ["--config", &config, "--testnet", "--no-confirm", "-a", "both", "-s", "XRPUSD", "-q", "10"];
+    let output = quicky(&mock, &dir, &envs, &args).output().unwrap();
+    let out = stdout(&output);
+    assert!(out.contains("[a] accepted Buy 10 XRPUSD"), "{}{}", out, stderr(&output));
+    assert!(out.contains("[b] accepted Buy 5 XRPUSD"), "{}", out);
+    assert!(out.contains("group both: placed on 2/2 accounts, qty 15"), "{}", out);
+    assert!(output.status.success());
+
+    assert_eq!(mock.orders("key-a")[0]["qty"], 10);
+    assert_eq!(mock.orders("key-b")[0]["qty"], 5);
+
+    // failure on one of the accounts fails the command
+    mock.script("/v2/private/order/create", error_response(30031, "insufficient available balance"));
+    let output = quicky(&mock, &dir, &envs, &[&args[..], &["--dedupe-window", "0"]].concat()).output().unwrap();
+    assert!(stdout(&output).contains("group both: placed on 1/2 accounts"), "{}{}", stdout(&output), stderr(&output));
+    assert!(!output.status.success());
+}
+
+#[test]
+fn paper_exchange_uses_public_market_data_only() {
+    let mock = start_mock();
+    let dir = test_dir("paper");
+    let state = dir.join("paper.json");
+
+    let output = quicky(&mock, &dir, &[], &["--testnet", "--paper", "--paper-state", state.to_str().unwrap(), "-s", "XRPUSD", "-q", "10"]).output().unwrap();
+    let out = stdout(&output);
+    assert!(out.contains("Buy 10 XRPUSD @ 0.4999: new"), "{}{}", out, stderr(&output));
+    assert!(state.exists());
+
+    assert!(!mock.requests("/v2/public/tickers").is_empty());
+    assert!(!mock.requests("/v2/public/orderBook/L2").is_empty());
+    assert!(mock.requests("/v2/private/order/create").is_empty());
+}
+
+#[test]
+fn paper_reduce_only_order_fills_no_more_than_position_left() {
+    let mock = start_mock();
+    let dir = test_dir("paper_reduce_only");
+    let state_path = dir.join("paper.json");
+    let paper = ["--testnet", "--paper", "--paper-state", state_path.to_str().unwrap()];
+    run_quicky(&mock, &dir, &[&paper[..], &["-s", "XRPUSD", "-q", "10"]].concat());
+
+    // position shrank to 4 after reduce-only order of 10 was placed
+    let mut state: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&state_path).unwrap()).unwrap();
+    let mut order = state["orders"][0].clone();
+    state["orders"][0]["order_status"] = json!("Cancelled");
+    state["orders"][0]["leaves_qty"] = json!(0.0);
+    for (field, value) in [("order_id", json!("paper-99")), ("order_link_id", json!("")), ("side", json!("Sell")), ("price", json!(0.5005)), ("time_in_force", json!("GoodTillCancel")), ("reduce_only", json!(true))] {
+        order[field] = value;
+    }
+    state["orders"].as_array_mut().unwrap().push(order);
+    state["positions"]["XRPUSD"] = json!({"symbol": "XRPUSD", "side": "Buy", "size": 4.0, "entry_price": 0.4999, "stop_loss": 0.0, "take_profit": 0.0, "realised_pnl": 0.0, "funding_paid": 0.0});
+    std::fs::write(&state_path, state.to_string()).unwrap();
+
+    mock.set_ticker("XRPUSD", json!({"last_price": "0.501"}));
+    let output = run_quicky(&mock, &dir, &[&paper[..], &["--output", "json", "paper", "status"]].concat());
+    let status: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap_or_else(|_| panic!("{}{}", stdout(&output), stderr(&output)));
+    assert_eq!(status["positions"].as_array().map(|p| p.len()), Some(0), "{}", status);
+
+    let state: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&state_path).unwrap()).unwrap();
+    let order = state["orders"].as_array().unwrap().iter().find(|o| o["order_id"] == "paper-99").unwrap();
+    assert_eq!((order["order_status"].as_str(), order["cum_exec_qty"].as_f64(), order["leaves_qty"].as_f64()), (Some("Cancelled"), Some(4.0), Some(0.0)), "{}", order);
+}
+
+/// Daemon process killed once dropped.
+struct Daemon(Child);
+
+impl Drop for Daemon {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+#[test]
+fn daemon_places_cancels_and_closes() {
+    let mock = start_mock();
+    let dir = test_dir("daemon");
+    let socket = dir.join("quicky.sock");
+    let socket = socket.to_str().unwrap();
+    let envs = [("QUICKY_BYBIT_TESTNET_API_KEY", API_KEY), ("QUICKY_BYBIT_TESTNET_API_SECRET", API_SECRET)];
+
+    let _daemon = Daemon(quicky(&mock, &dir, &envs, &["--testnet", "daemon", "--socket", socket])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap());
+    let start = Instant::now();
+    while !Path::new(socket).exists() {
+        assert!(start.elapsed() < Duration::from_secs(10), "daemon didn't listen");
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    assert_eq!(std::fs::metadata(socket).unwrap().permissions().mode() & 0o777, 0o600);
+    let output = quicky(&mock, &dir, &envs, &["--testnet", "daemon", "--socket", socket]).output().unwrap();
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("already listening"), "{}", stderr(&output));
+
+    let send = |words: &[&str]| {
+        let args: Vec<&str> = ["send", "--socket", socket].iter().chain(words.iter()).copied().collect();
+        quicky(&mock, &dir, &[], &args).output().unwrap()
+    };
+
+    let output = send(&["place", "XRPUSD", "10"]);
+    assert!(output.status.success(), "{}", stdout(&output));
+    let output = send(&["cancel", "XRPUSD"]);
+    assert!(output.status.success(), "{}", stdout(&output));
+    assert_eq!(mock.orders(API_KEY)[0]["order_status"], "Cancelled");
+
+    mock.set_position(API_KEY, "XRPUSD", "Buy", 30, 0.5);
+    let output = send(&["close", "XRPUSD"]);
+    assert!(output.status.success(), "{}", stdout(&output));
+    let params = &mock.requests("/v2/private/order/create")[1].params;
+    assert_eq!(params["order_type"], "Market");
+    assert_eq!(params["side"], "Sell");
+    assert_eq!(params["qty"], "30");
+    assert_eq!(params["reduce_only"], "true");
+    assert_eq!(mock.requests("/v2/private/position/list")[0].method, "GET");
+    assert_eq!(mock.position(API_KEY, "XRPUSD")["side"], "None");
+
+    let output = send(&["status"]);
+    let status: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!((status["ok"].as_bool(), status["testnet"].as_bool()), (Some(true), Some(true)), "{}", status);
+    let output = send(&["{\"cmd\": \"place\", \"symbol\": \"XRPUSD\", \"qty\": -5}"]);
+    assert!(output.status.success(), "{}", stdout(&output));
+    assert_eq!(mock.requests("/v2/private/order/create")[2].params["side"], "Sell");
+    let output = send(&["hold", "XRPUSD"]);
+    assert!(!output.status.success(), "{}", stdout(&output));
+}
+
+/// Webhook process killed once dropped, along with address it listens on.
+struct Webhook(Child, String);
+
+impl Drop for Webhook {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+impl Webhook {
+    /// Post `body` with `signature` header if any, and return HTTP status of the response.
+    fn post(&self, body: &str, signature: Option<&str>) -> u16 {
+        use std::io::{Read, Write};
+
+        let mut stream = std::net::TcpStream::connect(&self.1).unwrap();
+        let signature = signature.map(|s| format!("X-Quicky-Signature: {}\r\n", s)).unwrap_or_default();
+        write!(stream, "POST / HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n{}", self.1, body.len(), signature, body).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response.split_whitespace().nth(1).and_then(|s| s.parse().ok()).unwrap_or_else(|| panic!("{}", response))
+    }
+}
+
+/// Hex of HMAC-SHA256 of `body` with `secret`.
+fn sign_webhook(body: &str, secret: &str) -> String {
+    let key = ring::hmac::Key::new(ring::hmac::HMAC_SHA256, secret.as_bytes());
+    ring::hmac::sign(&key, body.as_bytes()).as_ref().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[test]
+fn webhook_places_authenticated_orders_and_refuses_replays() {
+    let mock = start_mock();
+    let dir = test_dir("webhook");
+    let addr = {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap().to_string()
+    };
+    let envs = [("QUICKY_BYBIT_TESTNET_API_KEY", API_KEY), ("QUICKY_BYBIT_TESTNET_API_SECRET", API_SECRET), ("QUICKY_WEBHOOK_SECRET", "hook-secret")];
+    let webhook = Webhook(quicky(&mock, &dir, &envs, &["--testnet", "--dedupe-window", "0", "webhook", "--listen", &addr])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap(), addr.clone());
+    let start = Instant::now();
+    while std::net::TcpStream::connect(&addr).is_err() {
+        assert!(start.elapsed() < Duration::from_secs(10), "webhook didn't listen");
+        std::thread::sleep(Duration::from_millis(50));
+    }
+
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_millis() as u64;
+    let payload = |nonce: &str, timestamp: u64| json!({"symbol": "XRPUSD", "side": "Buy", "qty": 10, "timestamp": timestamp, "nonce": nonce}).to_string();
+
+    let body = payload("n-1", now);
+    assert_eq!(webhook.post(&body, Some(&sign_webhook(&body, "hook-secret"))), 200);
+    // the same payload again is a replay
+    assert_eq!(webhook.post(&body, Some(&sign_webhook(&body, "hook-secret"))), 409);
+
+    let body = payload("n-2", now - 60 * 1000);
+    assert_eq!(webhook.post(&body, Some(&sign_webhook(&body, "hook-secret"))), 409);
+    let body = payload("n-3", now);
+    assert_eq!(webhook.post(&body, Some(&sign_webhook(&body, "wrong-secret"))), 401);
+    assert_eq!(webhook.post(&body, None), 401);
+    // rejected requests don't use up the nonce
+    assert_eq!(webhook.post(&body, Some(&sign_webhook(&body, "hook-secret"))), 200);
+
+    // secret in payload works in place of signature
+    let body = json!({"symbol": "XRPUSD", "side": "Sell", "qty": 5, "timestamp": now, "nonce": "n-4", "secret": "hook-secret"}).to_string();
+    assert_eq!(webhook.post(&body, None), 200);
+
+    let creates = mock.requests("/v2/private/order/create");
+    assert_eq!(creates.len(), 3);
+    assert_eq!((creates[0].params["side"].as_str(), creates[2].params["side"].as_str()), ("Buy", "Sell"));
+}
+
+#[test]
+fn profile_settings_are_overridden_by_environment_then_command_line() {
+    let mock = start_mock();
+    let dir = test_dir("profiles");
+    let config = write_config(&dir, r#"
+default_profile = "test"
+
+[profiles.test]
+testnet = true
+symbol = "XRPUSD"
+qty = 10
+sl_pcnt = 1.0
+dedupe_window_secs = 0
+
+[profiles.big]
+testnet = true
+symbol = "XRPUSD"
+qty = 30
+dedupe_window_secs = 0
+"#);
+    let credentials = [("QUICKY_BYBIT_TESTNET_API_KEY", API_KEY), ("QUICKY_BYBIT_TESTNET_API_SECRET", API_SECRET), ("QUICKY_CONFIG", config.as_str())];
+    let order = |envs: &[(&str, &str)], args: &[&str]| {
+        let envs: Vec<(&str, &str)> = credentials.iter().chain(envs.iter()).copied().collect();
+        let output = quicky(&mock, &dir, &envs, &[&["--no-confirm"], args].concat()).output().unwrap();
+        assert!(output.status.success(), "{}{}", stdout(&output), stderr(&output));
+        let params = mock.requests("/v2/private/order/create").last().unwrap().params.clone();
+        (params["qty"].clone(), params["stop_loss"].clone())
+    };
+
+    // symbol, quantity, stop-loss, and testnet all come from the default profile
+    assert_eq!(order(&[], &[]), (String::from("10"), String::from("0.495")));
+    assert_eq!(order(&[("QUICKY_PROFILE", "big")], &[]).0, "30");
+    assert_eq!(order(&[], &["--profile", "big"]).0, "30");
+    assert_eq!(order(&[("QUICKY_QTY", "7"), ("QUICKY_SL_PCNT", "2")], &[]), (String::from("7"), String::from("0.49")));
+    assert_eq!(order(&[("QUICKY_QTY", "7")], &["-q", "5"]).0, "5");
+
+    let output = quicky(&mock, &dir, &credentials, &["--no-confirm", "--profile", "missing"]).output().unwrap();
+    assert!(!output.status.success());
+    assert_eq!(mock.requests("/v2/private/order/create").len(), 5);
+}
+
+#[test]
+fn base_url_at_command_line_takes_precedence() {
+    let mock = start_mock();
+    let dir = test_dir("base_url");
+    let envs = [("QUICKY_BYBIT_TESTNET_API_KEY", API_KEY), ("QUICKY_BYBIT_TESTNET_API_SECRET", API_SECRET), ("QUICKY_BASE_URL", "http://127.0.0.1:1")];
+
+    let output = quicky(&mock, &dir, &envs, &["--testnet", "--no-confirm", "--base-url", mock.url(), "-s", "XRPUSD", "-q", "10"]).output().unwrap();
+    assert!(stdout(&output).contains("accepted Buy 10 XRPUSD"), "{}{}", stdout(&output), stderr(&output));
+
+    let output = quicky(&mock, &dir, &envs, &["--testnet", "--no-confirm", "--base-url", "127.0.0.1:1", "-s", "XRPUSD", "-q", "10"]).output().unwrap();
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("base URL '127.0.0.1:1' must be an absolute URL"), "{}", stderr(&output));
+    assert_eq!(mock.requests("/v2/private/order/create").len(), 1);
+}
+
+/// Write config file of test `dir` with `content`, and return its path.
+fn write_config(dir: &Path, content: &str) -> String {
+    let path = dir.join("config.toml");
+    std::fs::write(&path, content).unwrap();
+    path.to_str().unwrap().to_owned()
+}
+
+#[test]
+fn safety_limits_reject_order_before_sending() {
+    let mock = start_mock();
+    let dir = test_dir("limits");
+    let config = write_config(&dir, r#"
+[limits.symbols.XRPUSD]
+max_qty = 50
+max_notional = 80
+max_position = 100
+"#);
+
+    // 60 contracts is 120 XRP at 0.4999
+    let output = run_quicky(&mock, &dir, &["--config", &config, "--testnet", "--no-confirm", "-s", "XRPUSD", "-q", "60"]);
+    let err = stderr(&output);
+    assert!(err.contains("quantity 60 is above max_qty 50 of XRPUSD"), "{}", err);
+    assert!(err.contains("above max_notional 80 of XRPUSD"), "{}", err);
+    assert!(err.contains("Error: order exceeds safety limits"), "{}", err);
+
+    mock.set_position(API_KEY, "XRPUSD", "Buy", 95, 0.5);
+    let output = run_quicky(&mock, &dir, &["--config", &config, "--testnet", "--no-confirm", "-s", "XRPUSD", "-q", "10"]);
+    assert!(stderr(&output).contains("position of XRPUSD would be 105 once filled, above max_position 100"), "{}", stderr(&output));
+
+    // reducing the position is fine
+    let output = run_quicky(&mock, &dir, &["--config", &config, "--testnet", "--no-confirm", "-s", "XRPUSD", "-q", "-10"]);
+    assert!(stdout(&output).contains("accepted Sell 10 XRPUSD"), "{}{}", stdout(&output), stderr(&output));
+    assert_eq!(mock.requests("/v2/private/order/create").len(), 1);
+}
+
+#[test]
+fn safety_limits_on_open_orders_and_price_deviation() {
+    let mock = start_mock();
+    let dir = test_dir("limits_stateful");
+    let config = write_config(&dir, r#"
+[limits]
+max_open_orders = 1
+max_price_deviation_pcnt = 0.5
+"#);
+    let args = ["--config", &config, "--testnet", "--no-confirm", "-s", "XRPUSD", "-q", "10"];
+
+    let output = run_quicky(&mock, &dir, &args);
+    assert!(stdout(&output).contains("accepted Buy 10 XRPUSD"), "{}{}", stdout(&output), stderr(&output));
+    let output = run_quicky(&mock, &dir, &args);
+    assert!(stderr(&output).contains("1 open orders of XRPUSD already, max_open_orders is 1"), "{}", stderr(&output));
+    assert_eq!(mock.requests("/v2/private/order/list")[0].params["order_status"], "New,PartiallyFilled");
+
+    // limit price is 0.4999, over 0.5% away from mark price
+    mock.set_ticker("XRPUSD", json!({"last_price": "0.5000", "mark_price": "0.5030"}));
+    let output = run_quicky(&mock, &dir, &["--config", &config, "--testnet", "--dry-run", "-s", "XRPUSD", "-q", "10"]);
+    assert!(stderr(&output).contains("from mark price 0.503, above max_price_deviation_pcnt 0.5"), "{}", stderr(&output));
+    assert!(!stdout(&output).contains("within safety limits"));
+    assert_eq!(mock.requests("/v2/private/order/create").len(), 1);
+}
+
+#[test]
+fn large_mainnet_order_needs_confirmation() {
+    let mock = start_mock();
+    let dir = test_dir("confirm_mainnet");
+    let config = write_config(&dir, r#"
+[limits]
+confirm_mainnet_above = 20
+"#);
+    let envs = [("QUICKY_BYBIT_API_KEY", API_KEY), ("QUICKY_BYBIT_API_SECRET", API_SECRET)];
+
+    // stdin is not a terminal, so it cannot be confirmed
+    let output = quicky(&mock, &dir, &envs, &["--config", &config, "--mainnet", "--no-confirm", "-s", "XRPUSD", "-q", "30"])
+        .stdin(Stdio::null()).output().unwrap();
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("pass --yes"), "{}", stderr(&output));
+    assert!(mock.requests("/v2/public/tickers").is_empty());
+
+    let output = quicky(&mock, &dir, &envs, &["--config", &config, "--mainnet", "--no-confirm", "-s", "XRPUSD", "-q", "20"])
+        .stdin(Stdio::null()).output().unwrap();
+    assert!(stdout(&output).contains("accepted Buy 20 XRPUSD"), "{}{}", stdout(&output), stderr(&output));
+
+    let output = quicky(&mock, &dir, &envs, &["--config", &config, "--mainnet", "--no-confirm", "--yes", "-s", "XRPUSD", "-q", "30"])
+        .stdin(Stdio::null()).output().unwrap();
+    assert!(stdout(&output).contains("accepted Buy 30 XRPUSD"), "{}{}", stdout(&output), stderr(&output));
+    assert_eq!(mock.requests("/v2/private/order/create").len(), 2);
+}
+
+#[test]
+fn every_order_has_unique_link_id_and_duplicates_are_refused() {
+    let mock = start_mock();
+    let dir = test_dir("dedupe");
+    let args = ["--testnet", "--no-confirm", "-s", "XRPUSD", "-q", "10"];
+
+    let output = run_quicky(&mock, &dir, &args);
+    assert!(stdout(&output).contains("accepted Buy 10 XRPUSD"), "{}{}", stdout(&output), stderr(&output));
+    let output = run_quicky(&mock, &dir, &args);
+    assert!(stderr(&output).contains("the same order Buy 10 XRPUSD was sent"), "{}", stderr(&output));
+    assert!(stderr(&output).contains("Error: duplicate of an order just sent"), "{}", stderr(&output));
+
+    // different quantity, or turned off
+    run_quicky(&mock, &dir, &["--testnet", "--no-confirm", "-s", "XRPUSD", "-q", "20"]);
+    run_quicky(&mock, &dir, &["--testnet", "--no-confirm", "--dedupe-window", "0", "-s", "XRPUSD", "-q", "10"]);
+
+    let link_ids: Vec<String> = mock.requests("/v2/private/order/create").iter().map(|r| r.params["order_link_id"].clone()).collect();
+    assert_eq!(link_ids.len(), 3);
+    assert!(link_ids.iter().all(|id| id.starts_with("quicky-") && id.len() <= 36), "{:?}", link_ids);
+    assert!(link_ids[0] != link_ids[1] && link_ids[1] != link_ids[2] && link_ids[0] != link_ids[2]);
+}
+
+#[test]
+fn order_is_queried_by_link_id_before_resending_on_network_error() {
+    let mock = start_mock();
+    let dir = test_dir("network_error");
+    let args = ["--testnet", "--no-confirm", "--dedupe-window", "0", "-s", "XRPUSD", "-q", "10"];
+
+    // order landed, but its response is lost
+    mock.drop_connection("/v2/private/order/create", true);
+    let output = run_quicky(&mock, &dir, &args);
+    assert!(stdout(&output).contains("accepted Buy 10 XRPUSD"), "{}{}", stdout(&output), stderr(&output));
+    let creates = mock.requests("/v2/private/order/create");
+    assert_eq!(creates.len(), 1);
+    let queries = mock.requests("/v2/private/order");
+    assert_eq!(queries.len(), 1);
+    assert_eq!(queries[0].params["order_link_id"], creates[0].params["order_link_id"]);
+    assert_eq!(mock.orders(API_KEY).len(), 1);
+
+    // order didn't land, so it is sent again with the same link id
+    mock.drop_connection("/v2/private/order/create", false);
+    let output = run_quicky(&mock, &dir, &args);
+    assert!(stderr(&output).contains("didn't land due to network error, sending it again"), "{}", stderr(&output));
+    assert!(stdout(&output).contains("accepted Buy 10 XRPUSD"), "{}{}", stdout(&output), stderr(&output));
+    let creates = mock.requests("/v2/private/order/create");
+    assert_eq!(creates.len(), 3);
+    assert_eq!(creates[1].params["order_link_id"], creates[2].params["order_link_id"]);
+    assert_eq!(mock.orders(API_KEY).len(), 2);
+
+    // it never gets through, so it is reported along with its link id
+    for _ in 0..3 {
+        mock.drop_connection("/v2/private/order/create", false);
+    }
+    let output = run_quicky(&mock, &dir, &[&["--output", "json"], &args[..]].concat());
+    assert!(!output.status.success());
+    let report: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap_or_else(|_| panic!("{}{}", stdout(&output), stderr(&output)));
+    let creates = mock.requests("/v2/private/order/create");
+    assert_eq!(creates.len(), 6);
+    assert_eq!(report["status"], "failed", "{}", report);
+    assert_eq!(report["order_link_id"].as_str(), Some(creates[5].params["order_link_id"].as_str()), "{}", report);
+}
+
+#[test]
+fn rate_limited_order_is_signed_again_and_retried_after_backoff() {
+    let mock = start_mock();
+    let dir = test_dir("rate_limited");
+
+    mock.script("/v2/private/order/create", error_response(10006, "too many visits!"));
+    mock.script("/v2/private/order/create", error_response(10006, "too many visits!"));
+    let output = run_quicky(&mock, &dir, &["--testnet", "--no-confirm", "-v", "-s", "XRPUSD", "-q", "10"]);
+    assert!(stdout(&output).contains("accepted Buy 10 XRPUSD"), "{}{}", stdout(&output), stderr(&output));
+    assert_eq!(stderr(&output).matches("rate limited on env:QUICKY_BYBIT_TESTNET_API_KEY /v2/private/order/create, retrying").count(), 2, "{}", stderr(&output));
+    assert!(stderr(&output).contains("rate limit budget key=\"env:QUICKY_BYBIT_TESTNET_API_KEY /v2/private/order/create\" remaining=97 limit=100"), "{}", stderr(&output));
+
+    let creates = mock.requests("/v2/private/order/create");
+    assert_eq!(creates.len(), 3);
+    assert!(creates[0].params["timestamp"] != creates[2].params["timestamp"]);
+    assert_eq!(creates[0].params["order_link_id"], creates[2].params["order_link_id"]);
+    assert_eq!(mock.orders(API_KEY).len(), 1);
+}
+
+#[test]
+fn used_up_rate_limit_is_not_retried_until_it_resets() {
+    let mock = start_mock();
+    let dir = test_dir("rate_limit_used_up");
+    mock.set_rate_limit(1);
+
+    let output = run_quicky(&mock, &dir, &["--testnet", "--no-confirm", "-s", "XRPUSD", "-q", "10"]);
+    assert!(stdout(&output).contains("accepted Buy 10 XRPUSD"), "{}{}", stdout(&output), stderr(&output));
+
+    let output = run_quicky(&mock, &dir, &["--testnet", "--no-confirm", "-s", "XRPUSD", "-q", "20"]);
+    assert!(stderr(&output).contains("giving up after 0 retries"), "{}", stderr(&output));
+    assert!(stderr(&output).contains("Error: rate limited by API"), "{}", stderr(&output));
+    assert_eq!(mock.requests("/v2/private/order/create").len(), 2);
+    assert_eq!(mock.orders(API_KEY).len(), 1);
+}
+
+#[test]
+fn journal_records_orders_and_later_fills() {
+    let mock = start_mock();
+    let dir = test_dir("journal");
+    let args = ["--testnet", "--no-confirm", "--dedupe-window", "0", "-s", "XRPUSD", "-q", "10"];
+
+    run_quicky(&mock, &dir, &args);
+    mock.script("/v2/private/order/create", error_response(30024, "insufficient balance"));
+    run_quicky(&mock, &dir, &args);
+
+    let journal = std::fs::read_to_string(dir.join("data/quicky/journal.jsonl")).unwrap();
+    let entries: Vec<serde_json::Value> = journal.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0]["event"], "order");
+    assert_eq!(entries[0]["account"], "env:QUICKY_BYBIT_TESTNET_API_KEY");
+    assert_eq!(entries[0]["network"], "testnet");
+    assert_eq!(entries[0]["order_id"], "mock-1");
+    assert_eq!(entries[0]["price"], 0.4999);
+    assert_eq!(entries[0]["reference_price"], 0.5);
+    assert_eq!(entries[0]["stop_loss"], 0.4975);
+    assert_eq!(entries[0]["response"]["result"]["order_status"], "New");
+    assert!(entries[0]["latency_ms"].as_f64().unwrap() > 0.0);
+    assert_eq!(entries[1]["order_id"], serde_json::Value::Null);
+    assert_eq!(entries[1]["response"]["ret_msg"], "insufficient balance");
+
+    let output = run_quicky(&mock, &dir, &["--testnet", "journal"]);
+    let out = stdout(&output);
+    assert_eq!(out.lines().count(), 2, "{}", out);
+    assert!(out.lines().next().unwrap().contains("testnet env:QUICKY_BYBIT_TESTNET_API_KEY Buy 10 XRPUSD @ 0.4999 (ref 0.5)"), "{}", out);
+    assert!(out.lines().next().unwrap().ends_with("accepted"), "{}", out);
+    assert!(out.lines().nth(1).unwrap().ends_with("failed: insufficient balance"), "{}", out);
+
+    // filled after quicky exited
+    mock.fill_orders(API_KEY, "XRPUSD");
+    let output = run_quicky(&mock, &dir, &["--testnet", "journal", "--sync", "--limit", "1", "--since", "1h"]);
+    assert!(stderr(&output).contains("recorded latest state of 1 orders"), "{}", stderr(&output));
+    let output = run_quicky(&mock, &dir, &["--testnet", "journal", "--since", "2000-01-01", "--until", "1h", "--symbol", "XRPUSD"]);
+    assert_eq!(stdout(&output), "");
+    let output = run_quicky(&mock, &dir, &["--testnet", "journal", "--limit", "2"]);
+    assert!(stdout(&output).lines().next().unwrap().contains("filled 10 @ 0.499900 (fee -0.00500100 XRP)"), "{}", stdout(&output));
+}
+
+#[test]
+fn report_measures_fill_rate_price_improvement_and_fees() {
+    let mock = start_mock();
+    let dir = test_dir("report");
+    let order = |qty: &str| run_quicky(&mock, &dir, &["--testnet", "--no-confirm", "--dedupe-window", "0", "-s", "XRPUSD", "-q", qty]);
+
+    order("10");
+    mock.fill_orders(API_KEY, "XRPUSD");
+    order("20");
+    mock.cancel_post_only(API_KEY, "XRPUSD");
+    order("30");
+    mock.script("/v2/private/order/create", error_response(30024, "insufficient balance"));
+    order("40");
+
+    let output = run_quicky(&mock, &dir, &["--testnet", "report"]);
+    let out = stdout(&output);
+    assert!(out.contains("XRPUSD: 4 orders, 1 failed, 3 pending"), "{}", out);
+    assert!(stderr(&output).contains("quicky journal --sync"), "{}", stderr(&output));
+
+    run_quicky(&mock, &dir, &["--testnet", "journal", "--sync"]);
+    let output = run_quicky(&mock, &dir, &["--testnet", "--output", "json", "report"]);
+    let reports: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    let report = &reports[0];
+    assert_eq!(report["symbol"], "XRPUSD");
+    assert_eq!((report["orders"].as_u64(), report["failed"].as_u64(), report["pending"].as_u64()), (Some(4), Some(1), Some(1)));
+    assert_eq!((report["filled"].as_u64(), report["post_only_rejected"].as_u64()), (Some(1), Some(1)));
+    assert_eq!(report["post_only_rejection_pcnt"], 50.0);
+    assert!((report["fill_rate_pcnt"].as_f64().unwrap() - 100.0 / 3.0).abs() < 1e-9);
+    // bought at 0.4999 against last price of 0.5
+    assert!((report["price_improvement_bps"].as_f64().unwrap() - 2.0).abs() < 1e-6);
+    assert!(report["avg_time_to_fill_secs"].as_f64().unwrap() >= 0.0);
+    assert!((report["fees_paid"].as_f64().unwrap() + 10.0 / 0.4999 * 0.00025).abs() < 1e-9);
+    assert!((report["market_order_fees"].as_f64().unwrap() - 10.0 / 0.4999 * 0.0006).abs() < 1e-9);
+
+    let output = run_quicky(&mock, &dir, &["--testnet", "report", "-s", "XRPUSD", "--since", "1h"]);
+    let out = stdout(&output);
+    assert!(out.contains("price improvement vs reference +2.0 bps"), "{}", out);
+    assert!(out.contains("fees paid -0.00500100 XRP vs 0.01200240 XRP as market orders, saved 0.01700340 XRP"), "{}", out);
+    let output = run_quicky(&mock, &dir, &["--testnet", "report", "-s", "BTCUSD"]);
+    assert!(stdout(&output).starts_with("no orders"), "{}", stdout(&output));
+}
+
+#[test]
+fn history_is_exported_page_by_page_within_time_range() {
+    let mock = start_mock();
+    let dir = test_dir("export");
+    // 2022-05-01 00:00:00 UTC
+    let start_millis: u64 = 1651363200000;
+    for i in 0..250u64 {
+        let funding = i % 100 == 0;
+        mock.add_execution(API_KEY, json!({
+            "order_id": format!("order-{}", i), "order_link_id": "", "symbol": "XRPUSD", "side": "Buy",
+            "exec_id": format!("exec-{}", i), "exec_type": if funding { "Funding" } else { "Trade" },
+            "exec_price": "0.5", "exec_qty": 10, "exec_value": "20", "exec_fee": if funding { "0.002" } else { "-0.005" },
+            "fee_rate": if funding { "0.0001" } else { "-0.00025" }, "last_liquidity_ind": "AddedLiquidity",
+            "trade_time_ms": start_millis + i * 60 * 1000,
+        }));
+    }
+    for (i, created_at) in [start_millis / 1000, start_millis / 1000 + 86400].into_iter().enumerate() {
+        mock.add_closed_pnl(API_KEY, json!({
+            "id": i, "symbol": "XRPUSD", "order_id": format!("close-{}", i), "side": "Sell", "exec_type": "Trade",
+            "closed_size": 10, "avg_entry_price": "0.5", "avg_exit_price": "0.55", "closed_pnl": 0.01, "fill_count": 1,
+            "created_at": created_at,
+        }));
+    }
+
+    let output = run_quicky(&mock, &dir, &["--testnet", "export", "executions", "-s", "XRPUSD"]);
+    let out = stdout(&output);
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(lines.len(), 1 + 247, "{}", stderr(&output));
+    assert_eq!(lines[0], "time,symbol,order_id,order_link_id,exec_id,exec_type,side,price,qty,value,fee,fee_rate,liquidity,coin,fee_usd");
+    assert_eq!(lines[1], "2022-05-01 00:01:00,XRPUSD,order-1,,exec-1,Trade,Buy,0.5,10.0,20.0,-0.005,-0.00025,maker,XRP,-0.0025");
+    assert_eq!(mock.requests("/v2/private/execution/list").len(), 2);
+
+    let output = run_quicky(&mock, &dir, &["--testnet", "export", "funding", "-s", "XRPUSD", "--format", "json"]);
+    let funding: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(funding.as_array().unwrap().len(), 3);
+    assert_eq!(funding[1]["time"], "2022-05-01 01:40:00");
+    assert_eq!(funding[1]["funding_rate"], 0.0001);
+    assert_eq!(funding[1]["funding_fee_usd"], 0.001);
+
+    let output = run_quicky(&mock, &dir, &["--testnet", "export", "executions", "-s", "XRPUSD", "--since", "2022-05-01T01:00", "--until", "2022-05-01T02:00"]);
+    assert_eq!(stdout(&output).lines().count(), 1 + 59);
+
+    // pages after until are not requested
+    let requests = mock.requests("/v2/private/execution/list").len();
+    let output = run_quicky(&mock, &dir, &["--testnet", "export", "executions", "-s", "XRPUSD", "--until", "2022-05-01T01:00"]);
+    assert_eq!(stdout(&output).lines().count(), 1 + 59, "{}", stderr(&output));
+    let pages = &mock.requests("/v2/private/execution/list")[requests..];
+    assert_eq!(pages.len(), 1);
+    assert_eq!(pages[0].params["order"], "asc");
+
+    let file = dir.join("closed-pnl.csv");
+    let output = run_quicky(&mock, &dir, &["--testnet", "export", "closed-pnl", "-s", "XRPUSD", "--since", "2022-05-02", "-o", file.to_str().unwrap()]);
+    assert!(stderr(&output).contains("exported 1 records"), "{}", stderr(&output));
+    let csv = std::fs::read_to_string(&file).unwrap();
+    assert_eq!(csv.lines().nth(1).unwrap(), "2022-05-02 00:00:00,XRPUSD,close-1,Sell,Trade,10.0,0.5,0.55,0.01,XRP,0.0055,1");
+    assert_eq!(mock.requests("/v2/private/trade/closed-pnl/list")[0].params["start_time"], "1651449600");
+}
+
+#[test]
+fn json_output_has_order_ids_prices_and_timings() {
+    let mock = start_mock();
+    let dir = test_dir("output_json");
+
+    let output = run_quicky(&mock, &dir, &["--testnet", "--no-confirm", "--output", "json", "-s", "XRPUSD", "-q", "10"]);
+    let report: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    let order = &mock.orders(API_KEY)[0];
+    assert_eq!(report["order_id"], order["order_id"]);
+    assert!(report["order_link_id"].as_str().unwrap().starts_with("quicky-"), "{}", report);
+    assert_eq!((report["side"].as_str(), report["qty"].as_u64(), report["symbol"].as_str()), (Some("Buy"), Some(10), Some("XRPUSD")));
+    assert_eq!((report["price"].as_f64(), report["stop_loss"].as_f64()), (Some(0.4999), Some(0.4975)));
+    assert!(report["take_profit"].is_null());
+    assert_eq!(report["status"], "accepted");
+    assert!(report["elapsed_secs"].as_f64().unwrap() >= 0.0);
+
+    mock.script("/v2/private/order/create", error_response(30024, "insufficient balance"));
+    let output = run_quicky(&mock, &dir, &["--testnet", "--no-confirm", "--dedupe-window", "0", "--output", "json", "-s", "XRPUSD", "-q", "-20"]);
+    let report: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!((report["status"].as_str(), report["side"].as_str(), report["qty"].as_u64()), (Some("failed"), Some("Sell"), Some(20)));
+    assert!(report["order_id"].is_null());
+    assert!(stderr(&output).contains("Error: insufficient balance (ret_code 30024)"), "{}", stderr(&output));
+
+    let output = run_quicky(&mock, &dir, &["--testnet", "--output", "json", "journal"]);
+    let orders: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(orders.as_array().unwrap().len(), 2);
+    assert_eq!(orders[0]["order"]["order_id"], order["order_id"]);
+    assert_eq!(orders[1]["order"]["error"], "insufficient balance");
+}
+
+#[test]
+fn table_and_quiet_output() {
+    let mock = start_mock();
+    let dir = test_dir("output_table");
+
+    let output = run_quicky(&mock, &dir, &["--testnet", "--no-confirm", "--quiet", "-s", "XRPUSD", "-q", "10"]);
+    assert_eq!(stdout(&output), "");
+    assert_eq!(stderr(&output), "");
+    assert_eq!(mock.orders(API_KEY).len(), 1);
+
+    let output = run_quicky(&mock, &dir, &["--testnet", "--no-confirm", "--dedupe-window", "0", "--output", "table", "-s", "XRPUSD", "-q", "-10"]);
+    let out = stdout(&output);
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(lines.len(), 2, "{}", out);
+    assert!(lines[0].starts_with("ACCOUNT  ORDER_ID"), "{}", out);
+    assert!(lines[1].contains("Sell") && lines[1].contains("0.5001") && lines[1].contains("0.5025"), "{}", out);
+    // columns are aligned under the header
+    assert_eq!(lines[0].find("SIDE"), lines[1].find("Sell"), "{}", out);
+
+    let output = run_quicky(&mock, &dir, &["--testnet", "--output", "table", "journal"]);
+    let out = stdout(&output);
+    assert_eq!(out.lines().count(), 3, "{}", out);
+    assert!(out.lines().next().unwrap().contains("ORDER_LINK_ID"), "{}", out);
+}
+
+#[test]
+fn requests_are_logged_with_credentials_redacted() {
+    let mock = start_mock();
+    let dir = test_dir("logging");
+    let log_path = dir.join("logs").join("quicky.log");
+
+    run_quicky(&mock, &dir, &["--testnet", "--no-confirm", "-vv", "--log-file", log_path.to_str().unwrap(), "--log-format", "json", "-s", "XRPUSD", "-q", "10"]);
+    let log = std::fs::read_to_string(&log_path).unwrap();
+    let sign = mock.requests("/v2/private/order/create")[0].params["sign"].clone();
+    assert!(!log.contains(API_KEY) && !log.contains(&sign), "{}", log);
+
+    let lines: Vec<serde_json::Value> = log.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+    let request = lines.iter()
+        .find(|l| l["fields"]["message"] == "request" && l["fields"]["url"].as_str().unwrap().ends_with("/v2/private/order/create"))
+        .unwrap();
+    assert_eq!(request["level"], "DEBUG");
+    let body: serde_json::Value = serde_json::from_str(request["fields"]["body"].as_str().unwrap()).unwrap();
+    assert_eq!((body["api_key"].as_str(), body["sign"].as_str(), body["symbol"].as_str()), (Some("<redacted>"), Some("<redacted>"), Some("XRPUSD")));
+    let response = lines.iter()
+        .find(|l| l["fields"]["message"] == "response" && l["fields"]["key"].as_str().unwrap().ends_with("/v2/private/order/create"))
+        .unwrap();
+    assert_eq!((response["fields"]["status"].as_u64(), response["fields"]["ret_code"].as_i64()), (Some(200), Some(0)));
+    assert!(response["fields"]["elapsed_ms"].is_u64());
+    assert!(lines.iter().any(|l| l["level"] == "TRACE" && l["fields"]["message"] == "response body"));
+
+    // signed query string of GET is redacted too, logs are filtered by RUST_LOG without -v
+    let envs = [("QUICKY_BYBIT_TESTNET_API_KEY", API_KEY), ("QUICKY_BYBIT_TESTNET_API_SECRET", API_SECRET), ("RUST_LOG", "quicky::util=debug")];
+    let output = quicky(&mock, &dir, &envs, &["--testnet", "journal", "--sync"]).output().unwrap();
+    let err = stderr(&output);
+    assert!(err.contains("/v2/private/order?") && err.contains("api_key=<redacted>") && err.contains("sign=<redacted>"), "{}", err);
+    assert!(!err.contains(API_KEY), "{}", err);
+    assert!(!err.contains("rate limit budget"), "{}", err);
+}
+
+#[test]
+fn ladder_splits_order_across_price_levels_with_the_same_stop_loss() {
+    let mock = start_mock();
+    let dir = test_dir("ladder");
+
+    let output = run_quicky(&mock, &dir, &["--testnet", "ladder", "-s", "XRPUSD", "-q", "100", "--levels", "5", "--from-ticks", "1", "--to-ticks", "10"]);
+    let out = stdout(&output);
+    assert!(out.contains("accepted Buy 7 XRPUSD @ 0.4999"), "{}{}", out, stderr(&output));
+    assert!(out.contains("ladder Buy 100 XRPUSD: 5/5 levels from 0.4999 to 0.499, stop-loss 0.4975"), "{}", out);
+
+    let requests = mock.requests("/v2/private/order/create");
+    assert_eq!(requests.len(), 5);
+    let mut levels: Vec<(String, String)> = requests.iter().map(|r| (r.params["price"].clone(), r.params["qty"].clone())).collect();
+    levels.sort_by(|a, b| b.0.cmp(&a.0));
+    let expected = [("0.4999", "7"), ("0.4997", "13"), ("0.4994", "20"), ("0.4992", "27"), ("0.499", "33")];
+    assert_eq!(levels, expected.map(|(p, q)| (p.to_owned(), q.to_owned())));
+    assert!(requests.iter().all(|r| r.params["stop_loss"] == "0.4975" && r.params["side"] == "Buy"));
+    // as many tickers as a single order
+    assert_eq!(mock.requests("/v2/public/tickers").len(), 1);
+
+    let output = run_quicky(&mock, &dir, &["--testnet", "--output", "json", "ladder", "-s", "XRPUSD", "-q", "-10", "--levels", "2", "--weights", "1,4"]);
+    let report: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!((report["side"].as_str(), report["qty"].as_u64(), report["placed"].as_u64()), (Some("Sell"), Some(10), Some(2)), "{}", report);
+    assert_eq!((report["orders"][0]["price"].as_f64(), report["orders"][0]["qty"].as_u64()), (Some(0.5001), Some(2)));
+    assert_eq!((report["orders"][1]["price"].as_f64(), report["orders"][1]["qty"].as_u64()), (Some(0.501), Some(8)));
+
+    let output = run_quicky(&mock, &dir, &["--testnet", "ladder", "-s", "XRPUSD", "-q", "30", "--levels", "3", "--weighting", "geometric", "--ratio", "2", "--dry-run"]);
+    let out = stdout(&output);
+    assert!(out.contains("Buy 4 XRPUSD PostOnly limit @ 0.4999, 1 ticks from reference 0.5"), "{}{}", out, stderr(&output));
+    assert!(out.contains("Buy 17 XRPUSD PostOnly limit @ 0.499, 10 ticks from reference 0.5"), "{}", out);
+    assert!(out.contains("within safety limits"), "{}", out);
+
+    let output = run_quicky(&mock, &dir, &["--testnet", "ladder", "-s", "XRPUSD", "-q", "3", "--levels", "5"]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("Error:"), "{}", stderr(&output));
+    assert_eq!(mock.requests("/v2/private/order/create").len(), 7);
+}
+
+#[test]
+fn batch_places_orders_from_stdin_and_file_with_results_per_line() {
+    use std::io::Write;
+
+    let mock = start_mock();
+    let dir = test_dir("batch");
+    let envs = [("QUICKY_BYBIT_TESTNET_API_KEY", API_KEY), ("QUICKY_BYBIT_TESTNET_API_SECRET", API_SECRET)];
+
+    let mut child = quicky(&mock, &dir, &envs, &["--testnet", "batch"])
+        .stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn().unwrap();
+    child.stdin.take().unwrap().write_all(b"XRPUSD buy 10\n\n# hedge\nXRPUSD Sell 20 1 2\nDOGEUSD Buy 5\n").unwrap();
+    let output = child.wait_with_output().unwrap();
+    let out = stdout(&output);
+    assert!(!output.status.success());
+    assert!(out.contains("line 1: accepted Buy 10 XRPUSD @ 0.4999"), "{}{}", out, stderr(&output));
+    assert!(out.contains("line 4: accepted Sell 20 XRPUSD @ 0.5001"), "{}", out);
+    assert!(out.contains("line 5: failed Buy 5 DOGEUSD: "), "{}", out);
+    assert!(out.contains("batch: placed 2/3 orders, 1 failed"), "{}", out);
+
+    let requests = mock.requests("/v2/private/order/create");
+    assert_eq!(requests.len(), 2);
+    let sell = requests.iter().find(|r| r.params["side"] == "Sell").unwrap();
+    assert_eq!((sell.params["qty"].as_str(), sell.params["stop_loss"].as_str(), sell.params["take_profit"].as_str()), ("20", "0.505", "0.49"));
+
+    // header names columns in any order
+    let file = dir.join("orders.csv");
+    std::fs::write(&file, "symbol,qty,side,tp_pcnt\nXRPUSD,30,Buy,\nXRPUSD,40,Buy,1\n").unwrap();
+    let output = run_quicky(&mock, &dir, &["--testnet", "--output", "json", "batch", file.to_str().unwrap()]);
+    let report: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!((report["placed"].as_u64(), report["failed"].as_u64()), (Some(2), Some(0)), "{}", report);
+    assert_eq!((report["orders"][0]["line"].as_u64(), report["orders"][0]["qty"].as_u64()), (Some(2), Some(30)));
+    assert_eq!((report["orders"][1]["line"].as_u64(), report["orders"][1]["take_profit"].as_f64()), (Some(3), Some(0.505)));
+    assert!(output.status.success());
+
+    let output = run_quicky(&mock, &dir, &["--testnet", "--dedupe-window", "0", "batch", "--dry-run", file.to_str().unwrap()]);
+    let out = stdout(&output);
+    assert!(out.contains("line 3: Buy 40 XRPUSD PostOnly limit @ 0.4999, stop-loss 0.4975, take-profit 0.505"), "{}{}", out, stderr(&output));
+
+    // nothing is placed if any order is malformed
+    let file = dir.join("orders.json");
+    std::fs::write(&file, r#"[{"symbol": "XRPUSD", "side": "Buy", "qty": 50}, {"symbol": "XRPUSD", "side": "Hold", "qty": 60}]"#).unwrap();
+    let output = run_quicky(&mock, &dir, &["--testnet", "batch", file.to_str().unwrap()]);
+    assert!(stderr(&output).contains("line 2: unknown side 'Hold'"), "{}", stderr(&output));
+    assert_eq!(mock.requests("/v2/private/order/create").len(), 4);
+}
+
+#[test]
+fn batch_of_identical_lines_is_placed_while_the_same_batch_sent_again_is_refused() {
+    let mock = start_mock();
+    let dir = test_dir("batch_dedupe");
+    let file = dir.join("orders.txt");
+    std::fs::write(&file, "XRPUSD Buy 10\nXRPUSD Buy 10\n").unwrap();
+
+    let output = run_quicky(&mock, &dir, &["--testnet", "batch", file.to_str().unwrap()]);
+    assert!(output.status.success(), "{}{}", stdout(&output), stderr(&output));
+    assert!(stdout(&output).contains("batch: placed 2/2 orders, 0 failed"), "{}", stdout(&output));
+
+    let output = run_quicky(&mock, &dir, &["--testnet", "batch", file.to_str().unwrap()]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("the same order Buy 10 XRPUSD was sent"), "{}", stderr(&output));
+    assert!(stdout(&output).contains("batch: placed 0/2 orders, 2 failed"), "{}", stdout(&output));
+    assert_eq!(mock.requests("/v2/private/order/create").len(), 2);
+}
+
+#[test]
+fn batch_lines_of_the_same_symbol_and_side_are_limited_and_confirmed_as_a_whole() {
+    let mock = start_mock();
+    let dir = test_dir("batch_limits");
+    let config = write_config(&dir, r#"
+[limits]
+max_open_orders = 3
+confirm_mainnet_above = 20
+
+[limits.symbols.XRPUSD]
+max_qty = 50
+"#);
+    let file = dir.join("orders.txt");
+    std::fs::write(&file, "XRPUSD Buy 20
+XRPUSD Buy 20
+XRPUSD Buy 20
+XRPUSD Sell 10
+").unwrap();
+
+    // each buy line is within max_qty, yet they add up above it
+    let output = run_quicky(&mock, &dir, &["--config", &config, "--testnet", "batch", file.to_str().unwrap()]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("quantity 60 is above max_qty 50 of XRPUSD"), "{}", stderr(&output));
+    assert!(stdout(&output).contains("batch: placed 1/4 orders, 3 failed"), "{}", stdout(&output));
+
+    // 3 buy lines, and the sell placed before are above max_open_orders
+    std::fs::write(&file, "XRPUSD Buy 10
+XRPUSD Buy 10
+XRPUSD Buy 10
+").unwrap();
+    let output = run_quicky(&mock, &dir, &["--config", &config, "--testnet", "batch", "--dry-run", file.to_str().unwrap()]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("1 open orders of XRPUSD already, max_open_orders is 3"), "{}", stderr(&output));
+    assert_eq!(mock.requests("/v2/private/order/create").len(), 1);
+
+    // lines each under the threshold add up to a total that needs confirmation,
+    // asked once for the whole batch
+    let envs = [("QUICKY_BYBIT_API_KEY", API_KEY), ("QUICKY_BYBIT_API_SECRET", API_SECRET)];
+    let output = quicky(&mock, &dir, &envs, &["--config", &config, "--mainnet", "batch", file.to_str().unwrap()])
+        .stdin(Stdio::null()).output().unwrap();
+    assert!(!output.status.success());
+    let err = stderr(&output);
+    assert_eq!(err.matches("needs confirmation").count(), 1, "{}", err);
+    assert!(err.contains("Buy 30 XRPUSD on mainnet needs confirmation"), "{}", err);
+    assert_eq!(mock.requests("/v2/private/order/create").len(), 1);
+}
+
+#[test]
+fn order_is_cancelled_after_ttl_even_if_watching_it_fails() {
+    let mock = start_mock();
+    let dir = test_dir("ttl_watch_failure");
+
+    // a failed query is retried on the next poll
+    mock.script("/v2/private/order", error_response(10016, "service error"));
+    let output = run_quicky(&mock, &dir, &["--testnet", "--no-confirm", "--ttl", "1.2", "-s", "XRPUSD", "-q", "10"]);
+    assert!(output.status.success(), "{}{}", stdout(&output), stderr(&output));
+    assert!(stdout(&output).contains("cancelled after ttl of 1.2 secs"), "{}", stdout(&output));
+    assert_eq!(mock.requests("/v2/private/order/cancel").len(), 1);
+
+    // the order is cancelled once ttl passes even if it cannot be queried at all
+    for _ in 0..4 {
+        mock.script("/v2/private/order", error_response(10016, "service error"));
+    }
+    let output = run_quicky(&mock, &dir, &["--testnet", "--no-confirm", "--dedupe-window", "0", "--ttl", "0.5", "-s", "XRPUSD", "-q", "10"]);
+    assert!(stdout(&output).contains("(final state unknown)"), "{}{}", stdout(&output), stderr(&output));
+    assert_eq!(mock.requests("/v2/private/order/cancel").len(), 2);
+    assert!(mock.orders(API_KEY).iter().all(|o| o["order_status"] == "Cancelled"));
+}
+
+#[test]
+fn order_is_cancelled_after_ttl_and_rest_is_sent_at_market() {
+    let mock = start_mock();
+    let dir = test_dir("ttl");
+    let envs = [("QUICKY_BYBIT_TESTNET_API_KEY", API_KEY), ("QUICKY_BYBIT_TESTNET_API_SECRET", API_SECRET)];
+
+    let output = run_quicky(&mock, &dir, &["--testnet", "--no-confirm", "--ttl", "0.2", "-s", "XRPUSD", "-q", "10"]);
+    let out = stdout(&output);
+    assert!(out.contains("cancelled after ttl of 0.2 secs, 10 of 10 left unfilled"), "{}{}", out, stderr(&output));
+    assert!(out.contains("filled 0 by limit, 10 unfilled"), "{}", out);
+    assert_eq!(mock.orders(API_KEY)[0]["order_status"], "Cancelled");
+    assert_eq!(mock.requests("/v2/private/order/cancel").len(), 1);
+
+    let child = quicky(&mock, &dir, &envs, &["--testnet", "--no-confirm", "--dedupe-window", "0", "--output", "json", "--ttl", "1.5", "--fallback", "market", "-s", "XRPUSD", "-q", "10"])
+        .stdout(Stdio::piped()).stderr(Stdio::piped()).spawn().unwrap();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while mock.orders(API_KEY).len() < 2 && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    mock.fill_orders_partially(API_KEY, "XRPUSD", 4);
+    let output = child.wait_with_output().unwrap();
+    let report: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!((report["status"].as_str(), report["filled_qty"].as_f64(), report["expired"].as_bool()), (Some("cancelled"), Some(4.0), Some(true)), "{}", report);
+    let fallback = &report["fallback"];
+    assert_eq!((fallback["side"].as_str(), fallback["qty"].as_u64(), fallback["status"].as_str()), (Some("Buy"), Some(6), Some("filled")), "{}", report);
+    assert_eq!((fallback["filled_qty"].as_f64(), fallback["avg_fill_price"].as_f64()), (Some(6.0), Some(0.5)));
+
+    let market = &mock.requests("/v2/private/order/create")[2].params;
+    assert_eq!((market["order_type"].as_str(), market["time_in_force"].as_str()), ("Market", "ImmediateOrCancel"));
+    assert_eq!((market["qty"].as_str(), market["stop_loss"].as_str()), ("6", "0.4975"));
+}
+
+#[test]
+fn bracket_places_targets_once_entry_fills_and_resizes_stop_loss_as_they_hit() {
+    let mock = start_mock();
+    let dir = test_dir("bracket");
+    let envs = [("QUICKY_BYBIT_TESTNET_API_KEY", API_KEY), ("QUICKY_BYBIT_TESTNET_API_SECRET", API_SECRET)];
+    let wait_for = |done: &dyn Fn() -> bool| {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !done() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(20));
+        }
+    };
+
+    let output = run_quicky(&mock, &dir, &["--testnet", "bracket", "-s", "XRPUSD", "-q", "100", "--dry-run"]);
+    let out = stdout(&output);
+    assert!(out.contains("entry Buy 100 XRPUSD PostOnly limit @ 0.4999, stop-loss 0.4975, risk 0.0024"), "{}{}", out, stderr(&output));
+    assert!(out.contains("target 3: Sell 20 XRPUSD reduce-only limit @ 0.5071, 20% at 3R"), "{}", out);
+    assert!(mock.requests("/v2/private/order/create").is_empty());
+
+    let child = quicky(&mock, &dir, &envs, &["--testnet", "--output", "json", "bracket", "-s", "XRPUSD", "-q", "100"])
+        .stdout(Stdio::piped()).stderr(Stdio::piped()).spawn().unwrap();
+    wait_for(&|| !mock.orders(API_KEY).is_empty());
+    mock.set_position(API_KEY, "XRPUSD", "Buy", 100, 0.4999);
+    mock.fill_orders(API_KEY, "XRPUSD");
+
+    // targets are placed reduce-only at 1R, 2R, and 3R for 50%, 30%, and 20% of the fill
+    wait_for(&|| mock.orders(API_KEY).len() == 4);
+    let targets: Vec<(String, String, String, String)> = mock.requests("/v2/private/order/create")[1..].iter()
+        .map(|r| (r.params["side"].clone(), r.params["qty"].clone(), r.params["price"].clone(), r.params["reduce_only"].clone()))
+        .collect();
+    for target in [("Sell", "50", "0.5023"), ("Sell", "30", "0.5047"), ("Sell", "20", "0.5071")] {
+        assert!(targets.contains(&(target.0.to_owned(), target.1.to_owned(), target.2.to_owned(), String::from("true"))), "{:?}", targets);
+    }
+
+    // the first target hits, so stop-loss covers what is left of the position
+    let first = mock.orders(API_KEY).into_iter().find(|o| o["price"] == 0.5023).unwrap();
+    mock.fill_order(API_KEY, first["order_id"].as_str().unwrap());
+    mock.set_position(API_KEY, "XRPUSD", "Buy", 50, 0.4999);
+    wait_for(&|| !mock.requests("/v2/private/position/trading-stop").is_empty());
+    let stop = &mock.requests("/v2/private/position/trading-stop")[0].params;
+    assert_eq!((stop["stop_loss"].as_str(), stop["sl_size"].as_str()), ("0.4975", "50"));
+
+    // stop-loss closes the rest, so targets left are cancelled
+    mock.set_position(API_KEY, "XRPUSD", "None", 0, 0.0);
+    let output = child.wait_with_output().unwrap();
+    let report: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap_or_else(|_| panic!("{}{}", stdout(&output), stderr(&output)));
+    assert_eq!((report["outcome"].as_str(), report["entry"]["filled_qty"].as_f64(), report["risk"].as_f64()), (Some("stopped"), Some(100.0), Some(0.0024)), "{}", report);
+    let statuses: Vec<&str> = report["targets"].as_array().unwrap().iter().map(|t| t["status"].as_str().unwrap()).collect();
+    assert_eq!(statuses, ["filled", "cancelled", "cancelled"]);
+    assert_eq!(mock.requests("/v2/private/order/cancel").len(), 2);
+}
+
+#[test]
+fn bracket_refuses_open_position_and_cancels_targets_when_management_times_out() {
+    let mock = start_mock();
+    let dir = test_dir("bracket_timeout");
+    let envs = [("QUICKY_BYBIT_TESTNET_API_KEY", API_KEY), ("QUICKY_BYBIT_TESTNET_API_SECRET", API_SECRET)];
+    let wait_for = |done: &dyn Fn() -> bool| {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !done() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(20));
+        }
+    };
+
+    // position that is already open would be taken as of the bracket
+    mock.set_position(API_KEY, "XRPUSD", "Buy", 10, 0.49);
+    let output = quicky(&mock, &dir, &envs, &["--testnet", "bracket", "-s", "XRPUSD", "-q", "100"]).output().unwrap();
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("is already open"), "{}", stderr(&output));
+    assert!(mock.requests("/v2/private/order/create").is_empty());
+
+    mock.set_position(API_KEY, "XRPUSD", "None", 0, 0.0);
+    let child = quicky(&mock, &dir, &envs, &["--testnet", "--output", "json", "bracket", "-s", "XRPUSD", "-q", "100", "--manage-timeout", "3"])
+        .stdout(Stdio::piped()).stderr(Stdio::piped()).spawn().unwrap();
+    wait_for(&|| !mock.orders(API_KEY).is_empty());
+    mock.set_position(API_KEY, "XRPUSD", "Buy", 100, 0.4999);
+    mock.fill_orders(API_KEY, "XRPUSD");
+    wait_for(&|| mock.orders(API_KEY).len() == 4);
+
+    // failed checks are retried, then targets left are cancelled once it times out
+    for _ in 0..2 {
+        mock.script("/v2/private/position/list", error_response(10002, "request expired"));
+    }
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stderr(&output).contains("retrying"), "{}", stderr(&output));
+    let report: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap_or_else(|_| panic!("{}{}", stdout(&output), stderr(&output)));
+    assert_eq!((report["outcome"].as_str(), report["remaining_qty"].as_u64()), (Some("timed_out"), Some(100)), "{}", report);
+    let statuses: Vec<&str> = report["targets"].as_array().unwrap().iter().map(|t| t["status"].as_str().unwrap()).collect();
+    assert_eq!(statuses, ["cancelled", "cancelled", "cancelled"]);
+}
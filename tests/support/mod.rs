@@ -0,0 +1,615 @@
+//! Mock of Bybit's REST API for integration tests.
+//!
+//! It serves end-points quicky makes use of on a local port, verifies signature
+//! of private requests the same way Bybit does, and keeps orders and positions
+//! of each api key in memory. Responses of an end-point can be scripted ahead
+//! to test how quicky handles errors.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use ring::hmac;
+use serde_json::{json, Value};
+
+/// Window in milliseconds that timestamp of a private request must be within,
+/// if the request doesn't specify `recv_window`.
+const DEFAULT_RECV_WINDOW_MILLIS: i64 = 5000;
+
+/// Pseudo HTTP status of a request whose connection is to be dropped.
+const DROP_CONNECTION: u16 = 0;
+
+/// Requests per minute allowed on each private end-point, unless set otherwise.
+const DEFAULT_RATE_LIMIT: u64 = 100;
+
+/// Rebate rate of maker fills.
+const MAKER_REBATE_RATE: f64 = 0.00025;
+
+/// Window of rate limits in milliseconds.
+const RATE_LIMIT_WINDOW_MILLIS: u128 = 60 * 1000;
+
+/// Request as received by the mock.
+#[derive(Debug, Clone)]
+pub struct MockRequest {
+    pub method: String,
+    pub path: String,
+    /// Parameters from either query string, or json body. Values are in the
+    /// same form as they are signed.
+    pub params: BTreeMap<String, String>,
+}
+
+/// State of the mock shared with its serving thread.
+#[derive(Default)]
+struct MockState {
+    /// Api secret by api key
+    secrets: HashMap<String, String>,
+    /// Ticker by symbol
+    tickers: HashMap<String, Value>,
+    /// Scripted responses by path, returned in order instead of handling requests
+    scripts: HashMap<String, VecDeque<Value>>,
+    /// Connections to drop by path, whether or not each request is handled first
+    drops: HashMap<String, VecDeque<bool>>,
+    requests: Vec<MockRequest>,
+    /// Orders along with api key of their owner
+    orders: Vec<(String, Value)>,
+    /// Positions by api key, and symbol
+    positions: HashMap<(String, String), Value>,
+    next_order_id: u64,
+    /// Closed PnL records along with api key of their owner
+    closed_pnl: Vec<(String, Value)>,
+    /// Execution records along with api key of their owner
+    executions: Vec<(String, Value)>,
+    /// Requests per minute allowed on each private end-point, default if `None`
+    rate_limit: Option<u64>,
+    /// Start of current rate-limit window, and requests made within it by api key, and path
+    rate_usage: HashMap<(String, String), (u128, u64)>,
+}
+
+/// Rate-limit budget reported in headers of a private response.
+struct RateLimit {
+    limit: u64,
+    remaining: u64,
+    reset_at: u128,
+    /// Whether the request is beyond the limit, so rejected
+    exceeded: bool,
+}
+
+/// Mock Bybit server listening on a local port until dropped.
+pub struct MockBybit {
+    server: Arc<tiny_http::Server>,
+    state: Arc<Mutex<MockState>>,
+    url: String,
+    handle: Option<std::thread::JoinHandle<()>>,
+}
+
+impl MockBybit {
+    /// Start serving on a free local port.
+    pub fn start() -> Self {
+        let server = Arc::new(tiny_http::Server::http("127.0.0.1:0").expect("bind mock server"));
+        let url = format!("http://{}", server.server_addr().to_ip().expect("ip address of mock server"));
+        let state = Arc::new(Mutex::new(MockState::default()));
+
+        let handle = {
+            let (server, state) = (server.clone(), state.clone());
+            std::thread::spawn(move || {
+                for mut request in server.incoming_requests() {
+                    let (status, response, rate_limit) = handle_request(&state, &mut request);
+                    if status == DROP_CONNECTION {
+                        // malformed reply, so client fails as of a network error
+                        let mut writer = request.into_writer();
+                        let _ = writer.write_all(b"garbage\r\n\r\n").and_then(|_| writer.flush());
+                        continue;
+                    }
+                    let mut response = tiny_http::Response::from_string(response.to_string())
+                        .with_status_code(status)
+                        .with_header("content-type: application/json".parse::<tiny_http::Header>().unwrap());
+                    if let Some(rate_limit) = rate_limit {
+                        for (name, value) in [("X-Bapi-Limit", rate_limit.limit as u128), ("X-Bapi-Limit-Status", rate_limit.remaining as u128), ("X-Bapi-Limit-Reset-Timestamp", rate_limit.reset_at)] {
+                            response.add_header(format!("{}: {}", name, value).parse::<tiny_http::Header>().unwrap());
+                        }
+                    }
+                    let _ = request.respond(response);
+                }
+            })
+        };
+
+        MockBybit { server, state, url, handle: Some(handle) }
+    }
+
+    /// Base URL to point quicky to.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Accept private requests signed with `api_key`, and `api_secret`.
+    pub fn add_key(&self, api_key: &str, api_secret: &str) {
+        self.state.lock().unwrap().secrets.insert(api_key.to_owned(), api_secret.to_owned());
+    }
+
+    /// Set ticker of `symbol`. `fields` e.g. `last_price` are merged into a
+    /// complete ticker whose prices all default to `last_price`.
+    pub fn set_ticker(&self, symbol: &str, fields: Value) {
+        let last_price = fields["last_price"].as_str().unwrap_or("1").to_owned();
+        let mut ticker = json!({
+            "symbol": symbol,
+            "bid_price": last_price,
+            "ask_price": last_price,
+            "last_price": last_price,
+            "last_tick_direction": "ZeroPlusTick",
+            "prev_price_24h": last_price,
+            "price_24h_pcnt": "0",
+            "high_price_24h": last_price,
+            "low_price_24h": last_price,
+            "prev_price_1h": last_price,
+            "price_1h_pcnt": "0",
+            "mark_price": last_price,
+            "index_price": last_price,
+            "open_interest": 0,
+            "open_value": "0",
+            "total_turnover": "0",
+            "turnover_24h": "0",
+            "total_volume": 0,
+            "volume_24h": 0,
+            "funding_rate": "0.0001",
+            "predicted_funding_rate": "0.0001",
+            "next_funding_time": "",
+            "countdown_hour": 0,
+            "delivery_fee_rate": "",
+            "predicted_delivery_price": "",
+            "delivery_time": "",
+        });
+        if let Value::Object(fields) = fields {
+            for (k, v) in fields.into_iter() {
+                ticker[k] = v;
+            }
+        }
+        self.state.lock().unwrap().tickers.insert(symbol.to_owned(), ticker);
+    }
+
+    /// Set position of `symbol` held by account of `api_key`.
+    pub fn set_position(&self, api_key: &str, symbol: &str, side: &str, size: u64, entry_price: f64) {
+        self.state.lock().unwrap().positions.insert((api_key.to_owned(), symbol.to_owned()), position_json(symbol, side, size, entry_price));
+    }
+
+    /// Allow `limit` requests per minute on each private end-point of each api
+    /// key. Requests beyond it are rejected with ret_code 10006.
+    pub fn set_rate_limit(&self, limit: u64) {
+        self.state.lock().unwrap().rate_limit = Some(limit);
+    }
+
+    /// Respond to the next request of `path` with `response`, after its
+    /// signature is verified if it is private. Scripted responses of the same
+    /// path are used in order they are added.
+    pub fn script(&self, path: &str, response: Value) {
+        self.state.lock().unwrap().scripts.entry(path.to_owned()).or_default().push_back(response);
+    }
+
+    /// Drop connection of the next request of `path` without a valid response,
+    /// as of a network error. The request is handled first if `after_handling`
+    /// e.g. the order lands, yet its response is lost.
+    pub fn drop_connection(&self, path: &str, after_handling: bool) {
+        self.state.lock().unwrap().drops.entry(path.to_owned()).or_default().push_back(after_handling);
+    }
+
+    /// Requests received on `path` so far.
+    pub fn requests(&self, path: &str) -> Vec<MockRequest> {
+        self.state.lock().unwrap().requests.iter().filter(|r| r.path == path).cloned().collect()
+    }
+
+    /// Fill all active orders of `symbol` of account of `api_key` at their
+    /// price as maker, earning rebate. Position is left as it is.
+    pub fn fill_orders(&self, api_key: &str, symbol: &str) {
+        let mut state = self.state.lock().unwrap();
+        for (_, order) in state.orders.iter_mut().filter(|(k, o)| k == api_key && o["symbol"] == symbol) {
+            if order["order_status"] != "New" && order["order_status"] != "PartiallyFilled" {
+                continue;
+            }
+            let (qty, price) = (order["qty"].as_u64().unwrap_or(0), order["price"].as_f64().unwrap_or(0.0));
+            let value = qty as f64 / price;
+            order["order_status"] = json!("Filled");
+            order["leaves_qty"] = json!(0);
+            order["cum_exec_qty"] = json!(qty);
+            order["cum_exec_value"] = json!(value);
+            order["cum_exec_fee"] = json!(-value * MAKER_REBATE_RATE);
+        }
+    }
+
+    /// Fill active order of `order_id` of account of `api_key` at its price as
+    /// maker. Position is left as it is.
+    pub fn fill_order(&self, api_key: &str, order_id: &str) {
+        let mut state = self.state.lock().unwrap();
+        if let Some((_, order)) = state.orders.iter_mut().find(|(k, o)| k == api_key && o["order_id"] == order_id) {
+            let (qty, price) = (order["qty"].as_u64().unwrap_or(0), order["price"].as_f64().unwrap_or(0.0));
+            let value = qty as f64 / price;
+            order["order_status"] = json!("Filled");
+            order["leaves_qty"] = json!(0);
+            order["cum_exec_qty"] = json!(qty);
+            order["cum_exec_value"] = json!(value);
+            order["cum_exec_fee"] = json!(-value * MAKER_REBATE_RATE);
+        }
+    }
+
+    /// Fill `qty` of all active orders of `symbol` of account of `api_key` at
+    /// their price as maker, leaving the rest of them active.
+    pub fn fill_orders_partially(&self, api_key: &str, symbol: &str, qty: u64) {
+        let mut state = self.state.lock().unwrap();
+        for (_, order) in state.orders.iter_mut().filter(|(k, o)| k == api_key && o["symbol"] == symbol) {
+            let leaves_qty = order["leaves_qty"].as_u64().unwrap_or(0);
+            if (order["order_status"] != "New" && order["order_status"] != "PartiallyFilled") || leaves_qty <= qty {
+                continue;
+            }
+            let (cum_exec_qty, price) = (order["cum_exec_qty"].as_u64().unwrap_or(0) + qty, order["price"].as_f64().unwrap_or(0.0));
+            let value = cum_exec_qty as f64 / price;
+            order["order_status"] = json!("PartiallyFilled");
+            order["leaves_qty"] = json!(leaves_qty - qty);
+            order["cum_exec_qty"] = json!(cum_exec_qty);
+            order["cum_exec_value"] = json!(value);
+            order["cum_exec_fee"] = json!(-value * MAKER_REBATE_RATE);
+        }
+    }
+
+    /// Cancel all active orders of `symbol` of account of `api_key` as post-only
+    /// orders which would take liquidity.
+    pub fn cancel_post_only(&self, api_key: &str, symbol: &str) {
+        let mut state = self.state.lock().unwrap();
+        for (_, order) in state.orders.iter_mut().filter(|(k, o)| k == api_key && o["symbol"] == symbol) {
+            if order["order_status"] == "New" {
+                order["order_status"] = json!("Cancelled");
+                order["reject_reason"] = json!("EC_PostOnlyWillTakeLiquidity");
+            }
+        }
+    }
+
+    /// Add closed PnL `record` of Bybit's form to history of account of `api_key`.
+    pub fn add_closed_pnl(&self, api_key: &str, record: Value) {
+        self.state.lock().unwrap().closed_pnl.push((api_key.to_owned(), record));
+    }
+
+    /// Add execution `record` of Bybit's form to history of account of `api_key`.
+    pub fn add_execution(&self, api_key: &str, record: Value) {
+        self.state.lock().unwrap().executions.push((api_key.to_owned(), record));
+    }
+
+    /// Orders of account of `api_key`.
+    pub fn orders(&self, api_key: &str) -> Vec<Value> {
+        self.state.lock().unwrap().orders.iter().filter(|(k, _)| k == api_key).map(|(_, o)| o.clone()).collect()
+    }
+
+    /// Position of `symbol` held by account of `api_key`.
+    pub fn position(&self, api_key: &str, symbol: &str) -> Value {
+        let state = self.state.lock().unwrap();
+        state.positions.get(&(api_key.to_owned(), symbol.to_owned())).cloned().unwrap_or_else(|| position_json(symbol, "None", 0, 0.0))
+    }
+}
+
+impl Drop for MockBybit {
+    fn drop(&mut self) {
+        self.server.unblock();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Bybit's response with `result`.
+pub fn ok_response(result: Value) -> Value {
+    json!({
+        "ret_code": 0,
+        "ret_msg": "OK",
+        "ext_code": "",
+        "ext_info": "",
+        "result": result,
+        "time_now": format!("{:.6}", now_millis() as f64 / 1000.0),
+    })
+}
+
+/// Bybit's response of failure.
+pub fn error_response(ret_code: u32, ret_msg: &str) -> Value {
+    json!({
+        "ret_code": ret_code,
+        "ret_msg": ret_msg,
+        "ext_code": "",
+        "ext_info": "",
+        "result": null,
+        "time_now": format!("{:.6}", now_millis() as f64 / 1000.0),
+    })
+}
+
+/// Record `request`, then return HTTP status, json of its response, and
+/// rate-limit budget if it is private.
+fn handle_request(state: &Mutex<MockState>, request: &mut tiny_http::Request) -> (u16, Value, Option<RateLimit>) {
+    let method = request.method().to_string();
+    let (path, query) = match request.url().split_once('?') {
+        Some((path, query)) => (path.to_owned(), query.to_owned()),
+        None => (request.url().to_owned(), String::new()),
+    };
+
+    let mut params = BTreeMap::new();
+    for pair in query.split('&').filter(|p| !p.is_empty()) {
+        let (k, v) = pair.split_once('=').unwrap_or((pair, ""));
+        params.insert(k.to_owned(), v.to_owned());
+    }
+    let mut body = String::new();
+    if request.as_reader().read_to_string(&mut body).is_err() {
+        return (400, error_response(10001, "cannot read body"), None);
+    }
+    if !body.is_empty() {
+        match serde_json::from_str::<Value>(&body) {
+            // signed in the same form as quicky does; strings as they are, others as json
+            Ok(Value::Object(fields)) => for (k, v) in fields.into_iter() {
+                params.insert(k, match v {
+                    Value::String(s) => s,
+                    v => v.to_string(),
+                });
+            },
+            _ => return (400, error_response(10001, "invalid json body"), None),
+        }
+    }
+
+    let mut state = state.lock().unwrap();
+    state.requests.push(MockRequest { method: method.clone(), path: path.clone(), params: params.clone() });
+
+    let (api_key, rate_limit) = if path.starts_with("/v2/private/") {
+        match verify_signature(&state, &params) {
+            Ok(api_key) => {
+                let rate_limit = use_rate_limit(&mut state, &api_key, &path);
+                (api_key, Some(rate_limit))
+            },
+            Err(response) => return (200, response, None),
+        }
+    } else {
+        (String::new(), None)
+    };
+    if rate_limit.as_ref().is_some_and(|r| r.exceeded) {
+        return (200, error_response(10006, "too many visits!"), rate_limit);
+    }
+    let drop_after_handling = state.drops.get_mut(&path).and_then(|q| q.pop_front());
+    if drop_after_handling == Some(false) {
+        return (DROP_CONNECTION, Value::Null, None);
+    }
+    if let Some(response) = state.scripts.get_mut(&path).and_then(|q| q.pop_front()) {
+        return (200, response, rate_limit);
+    }
+
+    let symbol = params.get("symbol").cloned().unwrap_or_default();
+    let response = match (method.as_str(), path.as_str()) {
+        ("GET", "/v2/public/time") => ok_response(json!({})),
+        ("GET", "/v2/public/tickers") => match state.tickers.get(&symbol) {
+            Some(ticker) => ok_response(json!([ticker])),
+            None => error_response(10001, "Symbol is invalid"),
+        },
+        ("GET", "/v2/public/orderBook/L2") => match state.tickers.get(&symbol) {
+            Some(ticker) => ok_response(json!([
+                {"symbol": symbol, "price": ticker["bid_price"], "size": 1000, "side": "Buy"},
+                {"symbol": symbol, "price": ticker["ask_price"], "size": 1000, "side": "Sell"},
+            ])),
+            None => error_response(10001, "Symbol is invalid"),
+        },
+        ("GET", "/v2/public/trading-records") => ok_response(json!([])),
+        ("POST", "/v2/private/order/create") => create_order(&mut state, &api_key, &params),
+        ("POST", "/v2/private/order/cancelAll") => {
+            let mut cancelled = Vec::new();
+            for (_, order) in state.orders.iter_mut().filter(|(k, o)| *k == api_key && o["symbol"] == symbol.as_str()) {
+                if order["order_status"] == "New" || order["order_status"] == "PartiallyFilled" {
+                    order["order_status"] = json!("Cancelled");
+                    order["cancel_type"] = json!("CancelByUser");
+                    cancelled.push(order.clone());
+                }
+            }
+            ok_response(json!(cancelled))
+        },
+        ("POST", "/v2/private/order/cancel") => {
+            let order_id = params.get("order_id").cloned().unwrap_or_default();
+            let active = |o: &Value| o["order_status"] == "New" || o["order_status"] == "PartiallyFilled";
+            match state.orders.iter_mut().find(|(k, o)| *k == api_key && o["order_id"] == order_id.as_str() && active(o)) {
+                Some((_, order)) => {
+                    order["order_status"] = json!("Cancelled");
+                    order["cancel_type"] = json!("CancelByUser");
+                    order["leaves_qty"] = json!(0);
+                    ok_response(order.clone())
+                },
+                None => error_response(20001, "order not exists or too late to cancel"),
+            }
+        },
+        ("GET", "/v2/private/order/list") => {
+            let statuses: Option<Vec<&str>> = params.get("order_status").map(|s| s.split(',').collect());
+            let data: Vec<Value> = state.orders.iter().rev()
+                .filter(|(k, o)| *k == api_key && o["symbol"] == symbol.as_str())
+                .filter(|(_, o)| statuses.as_ref().is_none_or(|s| s.iter().any(|s| o["order_status"] == *s)))
+                .map(|(_, o)| o.clone())
+                .collect();
+            ok_response(json!({"data": data, "cursor": ""}))
+        },
+        ("GET", "/v2/private/order") => {
+            let (field, id) = match params.get("order_id") {
+                Some(order_id) => ("order_id", order_id.clone()),
+                None => ("order_link_id", params.get("order_link_id").cloned().unwrap_or_default()),
+            };
+            match state.orders.iter().find(|(k, o)| *k == api_key && o[field] == id.as_str()) {
+                Some((_, order)) => ok_response(order.clone()),
+                None => error_response(20001, "order not exists or too late to cancel"),
+            }
+        },
+        ("GET", "/v2/private/trade/closed-pnl/list") => {
+            // in seconds
+            let records = history_page(&state.closed_pnl, &api_key, &symbol, &params, "created_at", 1000);
+            ok_response(json!({"current_page": params.get("page").and_then(|p| p.parse::<u64>().ok()).unwrap_or(1), "data": records}))
+        },
+        ("GET", "/v2/private/execution/list") => {
+            let records = history_page(&state.executions, &api_key, &symbol, &params, "trade_time_ms", 1);
+            ok_response(json!({"order_id": "", "trade_list": records}))
+        },
+        ("GET", "/v2/private/position/list") => {
+            let position = state.positions.get(&(api_key, symbol.clone())).cloned().unwrap_or_else(|| position_json(&symbol, "None", 0, 0.0));
+            ok_response(position)
+        },
+        ("POST", "/v2/private/position/trading-stop") => match state.positions.get_mut(&(api_key, symbol)) {
+            Some(position) if position["size"].as_u64().unwrap_or(0) > 0 => {
+                for field in ["stop_loss", "sl_size"] {
+                    if let Some(value) = params.get(field) {
+                        position[field] = json!(value);
+                    }
+                }
+                ok_response(json!({}))
+            },
+            _ => error_response(30024, "position is not exists"),
+        },
+        _ => return (404, error_response(10001, "not found"), rate_limit),
+    };
+    (if drop_after_handling.is_some() { DROP_CONNECTION } else { 200 }, response, rate_limit)
+}
+
+/// Count a request of `path` by `api_key` against rate limit, then return the
+/// budget left within current window.
+fn use_rate_limit(state: &mut MockState, api_key: &str, path: &str) -> RateLimit {
+    let limit = state.rate_limit.unwrap_or(DEFAULT_RATE_LIMIT);
+    let now = now_millis();
+    let (window_start, used) = state.rate_usage.entry((api_key.to_owned(), path.to_owned())).or_insert((now, 0));
+    if now - *window_start >= RATE_LIMIT_WINDOW_MILLIS {
+        *window_start = now;
+        *used = 0;
+    }
+    *used += 1;
+    RateLimit {
+        limit,
+        remaining: limit.saturating_sub(*used),
+        reset_at: *window_start + RATE_LIMIT_WINDOW_MILLIS,
+        exceeded: *used > limit,
+    }
+}
+
+/// Verify signature of private request's `params`.
+/// Return api key of the request, or Bybit's response of failure.
+fn verify_signature(state: &MockState, params: &BTreeMap<String, String>) -> Result<String, Value> {
+    let api_key = params.get("api_key").ok_or_else(|| error_response(10003, "invalid api_key"))?;
+    let secret = state.secrets.get(api_key).ok_or_else(|| error_response(10003, "invalid api_key"))?;
+
+    let timestamp: i64 = params.get("timestamp").and_then(|t| t.parse().ok()).ok_or_else(|| error_response(10002, "invalid timestamp"))?;
+    let recv_window: i64 = params.get("recv_window").and_then(|w| w.parse().ok()).unwrap_or(DEFAULT_RECV_WINDOW_MILLIS);
+    if (now_millis() as i64 - timestamp).abs() > recv_window {
+        return Err(error_response(10002, "invalid request, please check your server timestamp or recv_window param"));
+    }
+
+    let payload = params.iter()
+        .filter(|(k, _)| k.as_str() != "sign")
+        .map(|(k, v)| format!("{}={}", k, v))
+        .collect::<Vec<String>>()
+        .join("&");
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    let expected: String = hmac::sign(&key, payload.as_bytes()).as_ref().iter().map(|b| format!("{:02x}", b)).collect();
+    if params.get("sign") != Some(&expected) {
+        return Err(error_response(10004, "error sign!"));
+    }
+    Ok(api_key.clone())
+}
+
+/// Create an order. Limit order rests as `New`, market order fills right
+/// away at last price, and changes the position.
+fn create_order(state: &mut MockState, api_key: &str, params: &BTreeMap<String, String>) -> Value {
+    for field in ["symbol", "side", "order_type", "qty", "time_in_force"] {
+        if !params.contains_key(field) {
+            return error_response(10001, &format!("missing required parameter '{}'", field));
+        }
+    }
+    if let Some(order_link_id) = params.get("order_link_id") {
+        if state.orders.iter().any(|(k, o)| k == api_key && o["order_link_id"] == order_link_id.as_str()) {
+            return error_response(10001, "order_link_id is repeated");
+        }
+    }
+    let symbol = &params["symbol"];
+    let last_price: f64 = match state.tickers.get(symbol) {
+        Some(ticker) => ticker["last_price"].as_str().and_then(|p| p.parse().ok()).unwrap_or(0.0),
+        None => return error_response(10001, "Symbol is invalid"),
+    };
+    let qty: u64 = match params["qty"].parse() {
+        Ok(qty) if qty > 0 => qty,
+        _ => return error_response(10001, "invalid qty"),
+    };
+    let is_market = params["order_type"] == "Market";
+    let price = if is_market { last_price } else {
+        match params.get("price").and_then(|p| p.parse::<f64>().ok()) {
+            Some(price) => price,
+            None => return error_response(10001, "missing required parameter 'price'"),
+        }
+    };
+
+    state.next_order_id += 1;
+    let order = json!({
+        "order_id": format!("mock-{}", state.next_order_id),
+        "order_link_id": params.get("order_link_id").cloned().unwrap_or_default(),
+        "symbol": symbol,
+        "side": params["side"],
+        "order_type": params["order_type"],
+        "price": price,
+        "qty": qty,
+        "time_in_force": params["time_in_force"],
+        "order_status": if is_market { "Filled" } else { "New" },
+        "leaves_qty": if is_market { 0 } else { qty },
+        "cum_exec_qty": if is_market { qty } else { 0 },
+        "cum_exec_value": if is_market { qty as f64 / price } else { 0.0 },
+        "cum_exec_fee": 0,
+        "reject_reason": "EC_NoError",
+        "cancel_type": "",
+        "stop_loss": params.get("stop_loss").cloned().unwrap_or_default(),
+        "take_profit": params.get("take_profit").cloned().unwrap_or_default(),
+        "reduce_only": params.get("reduce_only").is_some_and(|r| r == "true"),
+    });
+
+    if is_market {
+        let key = (api_key.to_owned(), symbol.clone());
+        let position = state.positions.get(&key).cloned().unwrap_or_else(|| position_json(symbol, "None", 0, 0.0));
+        let size = position["size"].as_u64().unwrap_or(0);
+        let signed_size = if position["side"] == "Sell" { -(size as i64) } else { size as i64 };
+        let signed_size = signed_size + if params["side"] == "Buy" { qty as i64 } else { -(qty as i64) };
+        let side = if signed_size > 0 { "Buy" } else if signed_size < 0 { "Sell" } else { "None" };
+        let entry_price = if signed_size == 0 { 0.0 } else { position["entry_price"].as_str().and_then(|p| p.parse().ok()).unwrap_or(price) };
+        state.positions.insert(key, position_json(symbol, side, signed_size.unsigned_abs(), entry_price));
+    }
+
+    state.orders.push((api_key.to_owned(), order.clone()));
+    ok_response(order)
+}
+
+/// Page of history `records` of `symbol` of account of `api_key`, newest first
+/// as Bybit returns, or oldest first if `order` of `params` is `asc`. It is
+/// bounded by `start_time`, and `end_time` of `params` compared against
+/// `time_field` in units of `time_unit_millis`. `null` if the page is empty.
+fn history_page(records: &[(String, Value)], api_key: &str, symbol: &str, params: &BTreeMap<String, String>, time_field: &str, time_unit_millis: u64) -> Value {
+    let param = |name: &str| params.get(name).and_then(|v| v.parse::<u64>().ok());
+    let (start_time, end_time) = (param("start_time").map(|t| t * time_unit_millis), param("end_time").map(|t| t * time_unit_millis));
+    let (page, limit) = (param("page").unwrap_or(1).max(1), param("limit").unwrap_or(20));
+    let ordered: Box<dyn Iterator<Item = &(String, Value)>> = if params.get("order").is_some_and(|o| o == "asc") {
+        Box::new(records.iter())
+    } else {
+        Box::new(records.iter().rev())
+    };
+
+    let page_records: Vec<Value> = ordered
+        .filter(|(k, r)| k == api_key && r["symbol"] == symbol)
+        .filter(|(_, r)| {
+            let time = r[time_field].as_u64().unwrap_or(0) * time_unit_millis;
+            start_time.is_none_or(|t| time >= t) && end_time.is_none_or(|t| time <= t)
+        })
+        .skip(((page - 1) * limit) as usize)
+        .take(limit as usize)
+        .map(|(_, r)| r.clone())
+        .collect();
+    if page_records.is_empty() { Value::Null } else { json!(page_records) }
+}
+
+/// Position as of Bybit's position list result.
+fn position_json(symbol: &str, side: &str, size: u64, entry_price: f64) -> Value {
+    json!({
+        "symbol": symbol,
+        "side": side,
+        "size": size,
+        "entry_price": entry_price.to_string(),
+        "unrealised_pnl": 0,
+    })
+}
+
+/// Current unix timestamp in milliseconds.
+fn now_millis() -> u128 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis()
+}